@@ -0,0 +1,21 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(location = 0) in vec3 vPosition;
+layout(location = 1) in vec3 vColor;
+
+layout(binding = 0) uniform CameraUBO {
+    mat4 view;
+    mat4 proj;
+    mat4 invertedProj;
+    vec4 eye;
+    float zNear;
+    float zFar;
+} cameraUBO;
+
+layout(location = 0) out vec3 oColor;
+
+void main() {
+    oColor = vColor;
+    gl_Position = cameraUBO.proj * cameraUBO.view * vec4(vPosition, 1.0);
+}
@@ -47,4 +47,6 @@ void main() {
     oAlpha = vColors.a;
 
     gl_Position = cameraUBO.proj * cameraUBO.view * world * vec4(vPositions, 1.0);
+    // Only used when rendering point clouds
+    gl_PointSize = 1.0;
 }
@@ -5,6 +5,8 @@
 layout(constant_id = 0) const uint LIGHT_COUNT = 1;
 layout(constant_id = 1) const uint OUTPUT_MODE = 0;
 layout(constant_id = 2) const float EMISSIVE_INTENSITY = 1.0;
+layout(constant_id = 3) const uint OIT = 0;
+layout(constant_id = 4) const uint SHADOW_LAYER_COUNT = 1;
 
 const uint OUTPUT_MODE_FINAL = 0;
 const uint OUTPUT_MODE_COLOR = 1;
@@ -35,6 +37,9 @@ const uint DIRECTIONAL_LIGHT_TYPE = 0;
 const uint POINT_LIGHT_TYPE = 1;
 const uint SPOT_LIGHT_TYPE = 2;
 
+const uint CASCADE_COUNT = 4;
+const int NO_SHADOW = -1;
+
 const uint UNLIT_FLAG_UNLIT = 1;
 
 const uint METALLIC_ROUGHNESS_WORKFLOW = 0;
@@ -57,6 +62,7 @@ struct Light {
     float angleScale;
     float angleOffset;
     uint type;
+    int shadowLayer;
 };
 
 struct PbrInfo {
@@ -127,9 +133,18 @@ layout(binding = 9, set = 2) uniform sampler2D materialSampler;
 layout(binding = 10, set = 2) uniform sampler2D occlusionSampler;
 layout(binding = 11, set = 2) uniform sampler2D emissiveSampler;
 layout(binding = 12, set = 3) uniform sampler2D aoMapSampler;
+layout(binding = 13, set = 3) uniform sampler2DArrayShadow shadowMapsSampler;
+layout(binding = 14, set = 0) uniform Shadows {
+    // View space depth at which each cascade of directional lights ends
+    vec4 cascadeSplits;
+    float bias;
+    mat4 viewProjs[SHADOW_LAYER_COUNT];
+} shadows;
 
 // Output
 layout(location = 0) out vec4 outColor;
+// Only written when OIT is enabled
+layout(location = 1) out float outRevealage;
 
 TextureChannels getTextureChannels() {
     return TextureChannels(
@@ -238,6 +253,73 @@ float sampleAOMap() {
     return texture(aoMapSampler, coords).r;
 }
 
+// Turn the output color into the weighted contribution of the fragment when
+// rendering weighted blended order-independent transparency.
+// See McGuire & Bavoil, Weighted Blended Order-Independent Transparency, JCGT 2013.
+void weightOutputColor() {
+    if (OIT == 0) {
+        return;
+    }
+    float alpha = outColor.a;
+    float weight = clamp(pow(min(1.0, alpha * 10.0) + 0.01, 3.0) * 1e8 * pow(1.0 - gl_FragCoord.z * 0.9, 3.0), 1e-2, 3e3);
+    outColor = vec4(outColor.rgb * alpha, alpha) * weight;
+    outRevealage = alpha;
+}
+
+uint getShadowLayer(Light light) {
+    uint layer = uint(light.shadowLayer);
+    if (light.type == DIRECTIONAL_LIGHT_TYPE) {
+        float depth = -(cameraUBO.view * vec4(oPositions, 1.0)).z;
+        for (uint i = 0u; i < CASCADE_COUNT - 1u; i++) {
+            if (depth <= shadows.cascadeSplits[i]) {
+                return layer + i;
+            }
+        }
+        return layer + CASCADE_COUNT - 1u;
+    }
+    if (light.type == POINT_LIGHT_TYPE) {
+        // Faces are ordered +X, -X, +Y, -Y, +Z, -Z
+        vec3 fromLight = oPositions - light.position.xyz;
+        vec3 absFromLight = abs(fromLight);
+        if (absFromLight.x >= absFromLight.y && absFromLight.x >= absFromLight.z) {
+            return layer + (fromLight.x > 0.0 ? 0u : 1u);
+        }
+        if (absFromLight.y >= absFromLight.z) {
+            return layer + (fromLight.y > 0.0 ? 2u : 3u);
+        }
+        return layer + (fromLight.z > 0.0 ? 4u : 5u);
+    }
+    return layer;
+}
+
+// Get the fraction of the light reaching the fragment with 3x3 percentage closer filtering.
+float computeShadow(Light light, vec3 n, vec3 l) {
+    if (light.shadowLayer == NO_SHADOW) {
+        return 1.0;
+    }
+
+    uint layer = getShadowLayer(light);
+    vec4 lightSpacePosition = shadows.viewProjs[layer] * vec4(oPositions, 1.0);
+    vec3 coords = lightSpacePosition.xyz / lightSpacePosition.w;
+    if (coords.z <= 0.0 || coords.z >= 1.0) {
+        return 1.0;
+    }
+
+    vec2 uv = coords.xy * 0.5 + 0.5;
+    float bias = max(shadows.bias * (1.0 - dot(n, l)), shadows.bias * 0.1);
+    float depth = coords.z - bias;
+
+    vec2 texelSize = 1.0 / vec2(textureSize(shadowMapsSampler, 0).xy);
+    float shadow = 0.0;
+    for (int x = -1; x <= 1; x++) {
+        for (int y = -1; y <= 1; y++) {
+            vec2 offset = vec2(x, y) * texelSize;
+            shadow += texture(shadowMapsSampler, vec4(uv + offset, float(layer), depth));
+        }
+    }
+    return shadow / 9.0;
+}
+
 vec3 occludeAmbientColor(vec3 ambientColor, TextureChannels textureChannels) {
     float aoMapSample = sampleAOMap();
     float sampledOcclusion = 0.0;
@@ -352,7 +434,8 @@ vec3 computeColor(
 vec3 computeDirectionalLight(Light light, PbrInfo pbrInfo, vec3 n, vec3 v) {
     vec3 l = -normalize(light.direction.xyz);
     vec3 h = normalize(l + v);
-    return computeColor(pbrInfo, n, l, v, h, light.color.rgb, light.intensity);
+    float shadow = computeShadow(light, n, l);
+    return computeColor(pbrInfo, n, l, v, h, light.color.rgb, light.intensity * shadow);
 }
 
 vec3 computePointLight(Light light, PbrInfo pbrInfo, vec3 n, vec3 v) {
@@ -362,8 +445,9 @@ vec3 computePointLight(Light light, PbrInfo pbrInfo, vec3 n, vec3 v) {
     vec3 h = normalize(l + v);
 
     float attenuation = computeAttenuation(distance, light.range);
+    float shadow = computeShadow(light, n, l);
 
-    return computeColor(pbrInfo, n, l, v, h, light.color.rgb, light.intensity * attenuation);
+    return computeColor(pbrInfo, n, l, v, h, light.color.rgb, light.intensity * attenuation * shadow);
 }
 
 vec3 computeSpotLight(Light light, PbrInfo pbrInfo, vec3 n, vec3 v) {
@@ -379,8 +463,9 @@ vec3 computeSpotLight(Light light, PbrInfo pbrInfo, vec3 n, vec3 v) {
     float cd = dot(invLightDir, l);
     float angularAttenuation = max(0.0, cd * light.angleScale + light.angleOffset);
     angularAttenuation *= angularAttenuation;
+    float shadow = computeShadow(light, n, l);
 
-    return computeColor(pbrInfo, n, l, v, h, light.color.rgb, light.intensity * attenuation * angularAttenuation);
+    return computeColor(pbrInfo, n, l, v, h, light.color.rgb, light.intensity * attenuation * angularAttenuation * shadow);
 }
 
 vec3 prefilteredReflectionLinear(vec3 R, float roughness) {
@@ -430,6 +515,7 @@ void main() {
 
     if (isUnlit()) {
         outColor = vec4(baseColor.rgb, alpha);
+        weightOutputColor();
         return;
     }
 
@@ -498,4 +584,6 @@ void main() {
         float ao = sampleAOMap();
         outColor = vec4(vec3(ao), 1.0);
     }
+
+    weightOutputColor();
 }
@@ -55,4 +55,6 @@ void main() {
     oTBN = mat3(tangent, bitangent, normal);
     oColors = vColors;
     gl_Position = cameraUBO.proj * cameraUBO.view * world * vec4(vPositions, 1.0);
+    // Only used when rendering point clouds
+    gl_PointSize = 1.0;
 }
@@ -0,0 +1,23 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(location = 0) in vec2 oCoords;
+
+layout(binding = 0) uniform sampler2D colorSampler;
+layout(binding = 1) uniform sampler2D accumulationSampler;
+layout(binding = 2) uniform sampler2D revealageSampler;
+
+layout(location = 0) out vec4 finalColor;
+
+const float EPSILON = 0.00001;
+
+void main() {
+    vec4 color = texture(colorSampler, oCoords);
+    vec4 accumulation = texture(accumulationSampler, oCoords);
+    float revealage = texture(revealageSampler, oCoords).r;
+
+    // Weighted average of the colors of the blended fragments
+    vec3 averageColor = accumulation.rgb / max(accumulation.a, EPSILON);
+
+    finalColor = vec4(mix(averageColor, color.rgb, revealage), color.a);
+}
@@ -0,0 +1,44 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(location = 0) in vec3 vPositions;
+layout(location = 1) in vec3 vNormals;
+layout(location = 2) in vec2 vTexcoords0;
+layout(location = 3) in vec2 vTexcoords1;
+layout(location = 4) in vec4 vTangents;
+layout(location = 5) in vec4 vWeights;
+layout(location = 6) in uvec4 vJoints;
+layout(location = 7) in vec4 vColors;
+
+// -- Push constants
+layout(push_constant) uniform LightUniform {
+    mat4 viewProj;
+} light;
+
+layout(binding = 0, set = 0) uniform TransformUBO {
+    mat4 matrix;
+} transform;
+
+layout(binding = 1, set = 0) uniform SkinUBO {
+    mat4 jointMatrices[512];
+} skin;
+
+layout(location = 0) out vec2 oTexcoords0;
+layout(location = 1) out vec2 oTexcoords1;
+layout(location = 2) out float oAlpha;
+
+void main() {
+    mat4 world = transform.matrix;
+    if (vWeights != vec4(0.0)) {
+        world *= vWeights.x * skin.jointMatrices[vJoints.x]
+            + vWeights.y * skin.jointMatrices[vJoints.y]
+            + vWeights.z * skin.jointMatrices[vJoints.z]
+            + vWeights.w * skin.jointMatrices[vJoints.w];
+    }
+
+    oTexcoords0 = vTexcoords0;
+    oTexcoords1 = vTexcoords1;
+    oAlpha = vColors.a;
+
+    gl_Position = light.viewProj * world * vec4(vPositions, 1.0);
+}
@@ -0,0 +1,55 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(location = 0) in vec2 oCoords;
+
+layout(binding = 0) uniform sampler2D inputImage;
+
+layout(push_constant) uniform PreviewUniform {
+    // 1.0 for the displayed channels, 0.0 for the others
+    vec4 channels;
+    // Region of the texture to display
+    vec2 uvOffset;
+    vec2 uvScale;
+    float mipLevel;
+    // 1 if the texture stores linear values, 0 if it stores sRGB values
+    uint isLinear;
+} preview;
+
+layout(location = 0) out vec4 finalColor;
+
+const float GAMMA = 2.2;
+const float INV_GAMMA = 1.0 / GAMMA;
+const float CHECKER_SIZE = 32.0;
+
+vec3 LINEARtoSRGB(vec3 color) {
+    return pow(color, vec3(INV_GAMMA));
+}
+
+vec3 checker() {
+    vec2 cell = floor(gl_FragCoord.xy / CHECKER_SIZE);
+    float value = mod(cell.x + cell.y, 2.0) == 0.0 ? 0.8 : 0.6;
+    return vec3(value);
+}
+
+void main() {
+    vec4 texel = textureLod(inputImage, preview.uvOffset + oCoords * preview.uvScale, preview.mipLevel);
+    if (preview.isLinear == 1) {
+        texel.rgb = LINEARtoSRGB(texel.rgb);
+    }
+
+    vec4 channels = preview.channels;
+    float channelCount = dot(channels, vec4(1.0));
+
+    vec3 color;
+    if (channelCount == 1.0) {
+        // A single channel is shown in grayscale
+        color = vec3(dot(texel, channels));
+    } else if (channels.a == 1.0) {
+        color = mix(checker(), texel.rgb * channels.rgb, texel.a);
+    } else {
+        color = texel.rgb * channels.rgb;
+    }
+
+    finalColor = vec4(color, 1.0);
+}
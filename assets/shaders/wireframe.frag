@@ -0,0 +1,30 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+// 1 when triangles are filled and edges are found from barycentric coordinates,
+// 0 when they are rasterized as lines
+layout(constant_id = 0) const uint BARYCENTRIC = 0;
+
+layout(location = 0) in vec3 oBarycentric;
+
+layout(push_constant) uniform WireframeUniform {
+    vec4 color;
+} wireframe;
+
+layout(location = 0) out vec4 outColor;
+
+const float LINE_WIDTH = 1.0;
+
+void main() {
+    float alpha = 1.0;
+    if (BARYCENTRIC == 1) {
+        // Distance to the closest edge in pixels
+        vec3 distances = oBarycentric / fwidth(oBarycentric);
+        float distance = min(min(distances.x, distances.y), distances.z);
+        alpha = 1.0 - smoothstep(0.0, LINE_WIDTH, distance - LINE_WIDTH * 0.5);
+        if (alpha == 0.0) {
+            discard;
+        }
+    }
+    outColor = vec4(wireframe.color.rgb, wireframe.color.a * alpha);
+}
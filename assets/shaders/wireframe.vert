@@ -0,0 +1,40 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(location = 0) in vec3 vPositions;
+layout(location = 5) in vec4 vWeights;
+layout(location = 6) in uvec4 vJoints;
+
+layout(binding = 0, set = 0) uniform CameraUBO {
+    mat4 view;
+    mat4 proj;
+    mat4 invertedProj;
+    vec4 eye;
+    float zNear;
+    float zFar;
+} cameraUBO;
+
+layout(binding = 2, set = 0) uniform TransformUBO {
+    mat4 matrix;
+} transform;
+
+layout(binding = 3, set = 0) uniform SkinUBO {
+    mat4 jointMatrices[512];
+} skin;
+
+// Only used by the barycentric fallback which draws unindexed triangles
+layout(location = 0) out vec3 oBarycentric;
+
+void main() {
+    mat4 world = transform.matrix;
+    if (vWeights != vec4(0.0)) {
+        world *= vWeights.x * skin.jointMatrices[vJoints.x]
+            + vWeights.y * skin.jointMatrices[vJoints.y]
+            + vWeights.z * skin.jointMatrices[vJoints.z]
+            + vWeights.w * skin.jointMatrices[vJoints.w];
+    }
+
+    oBarycentric = vec3(0.0);
+    oBarycentric[gl_VertexIndex % 3] = 1.0;
+    gl_Position = cameraUBO.proj * cameraUBO.view * world * vec4(vPositions, 1.0);
+}
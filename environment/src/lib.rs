@@ -315,6 +315,7 @@ fn create_env_pipeline<V: Vertex>(
             fragment_shader_params: ShaderParameters::new(params.fragment_shader_name),
             multisampling_info: &multisampling_info,
             viewport_info: params.viewport_info,
+            topology: vk::PrimitiveTopology::TRIANGLE_LIST,
             rasterizer_info: params.rasterizer_info,
             dynamic_state_info: params.dynamic_state_info,
             depth_stencil_info: None,
@@ -14,9 +14,38 @@ impl<S> AABB<S> {
     pub fn new(min: Vector3<S>, max: Vector3<S>) -> Self {
         AABB { min, max }
     }
+
+    /// Get the corner of the AABB with the smallest coordinates.
+    pub fn min(&self) -> Vector3<S>
+    where
+        S: Copy,
+    {
+        self.min
+    }
+
+    /// Get the corner of the AABB with the largest coordinates.
+    pub fn max(&self) -> Vector3<S>
+    where
+        S: Copy,
+    {
+        self.max
+    }
 }
 
 impl<S: BaseFloat> AABB<S> {
+    /// Compute the smallest AABB containing all `points`.
+    ///
+    /// Returns `None` if there is no point.
+    pub fn from_points<I: IntoIterator<Item = Vector3<S>>>(points: I) -> Option<Self> {
+        points.into_iter().fold(None, |aabb, point| match aabb {
+            None => Some(AABB::new(point, point)),
+            Some(AABB { min, max }) => Some(AABB::new(
+                Vector3::new(min.x.min(point.x), min.y.min(point.y), min.z.min(point.z)),
+                Vector3::new(max.x.max(point.x), max.y.max(point.y), max.z.max(point.z)),
+            )),
+        })
+    }
+
     /// Compute the union of several AABBs.
     pub fn union(aabbs: &[AABB<S>]) -> Option<Self> {
         if aabbs.is_empty() {
@@ -38,6 +67,12 @@ impl<S: BaseFloat> AABB<S> {
         }
     }
 
+    /// Get the size of the AABB along each axis.
+    pub fn get_size(&self) -> Vector3<S> {
+        let size = self.max - self.min;
+        Vector3::new(size.x.abs(), size.y.abs(), size.z.abs())
+    }
+
     /// Get the size of the larger side of the AABB.
     pub fn get_larger_side_size(&self) -> S {
         let size = self.max - self.min;
@@ -54,6 +89,21 @@ impl<S: BaseFloat> AABB<S> {
         }
     }
 
+    /// Compute the smallest AABB containing this one transformed by `matrix`.
+    ///
+    /// Unlike multiplying by the matrix, all the corners are transformed so
+    /// the result also contains the box when `matrix` has a rotation.
+    pub fn transform(&self, matrix: Matrix4<S>) -> Self {
+        let (min, max) = (self.min, self.max);
+        let corners = (0..8).map(|i| {
+            let x = if i & 1 == 0 { min.x } else { max.x };
+            let y = if i & 2 == 0 { min.y } else { max.y };
+            let z = if i & 4 == 0 { min.z } else { max.z };
+            (matrix * Vector4::new(x, y, z, S::one())).truncate()
+        });
+        AABB::from_points(corners).unwrap()
+    }
+
     /// Get the center of the AABB.
     pub fn get_center(&self) -> Vector3<S> {
         let two = S::one() + S::one();
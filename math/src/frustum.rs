@@ -0,0 +1,85 @@
+use super::AABB;
+use cgmath::{BaseFloat, InnerSpace, Matrix, Matrix4, Vector3, Vector4};
+
+/// View frustum defined by six planes pointing inwards.
+///
+/// Each plane is stored as `(a, b, c, d)` so that a point `p` is on
+/// its inner side when `a * p.x + b * p.y + c * p.z + d >= 0`.
+#[derive(Copy, Clone, Debug)]
+pub struct Frustum<S> {
+    planes: [Vector4<S>; 6],
+}
+
+impl<S: BaseFloat> Frustum<S> {
+    /// Extract the frustum from a projection matrix multiplied by a view matrix.
+    ///
+    /// The clip space depth is expected to range from 0 to 1 as with `perspective`.
+    pub fn from_matrix(view_proj: Matrix4<S>) -> Self {
+        let (x, y, z, w) = (
+            view_proj.row(0),
+            view_proj.row(1),
+            view_proj.row(2),
+            view_proj.row(3),
+        );
+        let normalize = |plane: Vector4<S>| plane / plane.truncate().magnitude();
+        Frustum {
+            planes: [
+                normalize(w + x),
+                normalize(w - x),
+                normalize(w + y),
+                normalize(w - y),
+                normalize(z),
+                normalize(w - z),
+            ],
+        }
+    }
+
+    /// Check whether `aabb` is at least partially inside the frustum.
+    ///
+    /// The test is conservative: some boxes outside of the frustum near
+    /// its edges are reported as intersecting it.
+    pub fn intersects_aabb(&self, aabb: &AABB<S>) -> bool {
+        let (min, max) = (aabb.min(), aabb.max());
+        self.planes.iter().all(|plane| {
+            // Corner of the box the furthest along the plane normal
+            let corner = Vector3::new(
+                if plane.x >= S::zero() { max.x } else { min.x },
+                if plane.y >= S::zero() { max.y } else { min.y },
+                if plane.z >= S::zero() { max.z } else { min.z },
+            );
+            plane.truncate().dot(corner) + plane.w >= S::zero()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::perspective;
+    use cgmath::{Deg, Point3};
+
+    fn unit_square_at(x: f32, y: f32, z: f32) -> AABB<f32> {
+        AABB::new(Vector3::new(x, y, z), Vector3::new(x + 1.0, y + 1.0, z))
+    }
+
+    #[test]
+    fn rejects_bounds_out_of_view() {
+        let view = Matrix4::look_at(
+            Point3::new(0.5, 0.5, 5.0),
+            Point3::new(0.5, 0.5, 0.0),
+            Vector3::unit_y(),
+        );
+        let frustum = Frustum::from_matrix(perspective(Deg(45.0), 1.0, 0.1, 100.0) * view);
+
+        assert!(frustum.intersects_aabb(&unit_square_at(0.0, 0.0, 0.0)));
+
+        // Behind the camera
+        assert!(!frustum.intersects_aabb(&unit_square_at(0.0, 0.0, 10.0)));
+
+        // Beside the view
+        assert!(!frustum.intersects_aabb(&unit_square_at(10.0, 0.0, 0.0)));
+
+        // Beyond the far plane
+        assert!(!frustum.intersects_aabb(&unit_square_at(0.0, 0.0, -200.0)));
+    }
+}
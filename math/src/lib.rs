@@ -1,9 +1,13 @@
 mod aabb;
+mod frustum;
+mod ray;
 
 pub use aabb::*;
 pub use cgmath;
+pub use frustum::*;
 pub use lerp;
 pub use rand;
+pub use ray::*;
 
 use cgmath::prelude::*;
 use cgmath::{BaseFloat, Matrix4, Quaternion, Rad};
@@ -50,6 +54,42 @@ where
     )
 }
 
+/// Orthographic matrix that is suitable for Vulkan.
+///
+/// Like `perspective` it inverts the projected y-axis and
+/// sets the depth range to 0..1 instead of -1..1.
+#[rustfmt::skip]
+pub fn orthographic<S: BaseFloat>(left: S, right: S, bottom: S, top: S, near: S, far: S) -> Matrix4<S> {
+    let two = S::one() + S::one();
+
+    let c0r0 = two / (right - left);
+    let c0r1 = S::zero();
+    let c0r2 = S::zero();
+    let c0r3 = S::zero();
+
+    let c1r0 = S::zero();
+    let c1r1 = -two / (top - bottom);
+    let c1r2 = S::zero();
+    let c1r3 = S::zero();
+
+    let c2r0 = S::zero();
+    let c2r1 = S::zero();
+    let c2r2 = -S::one() / (far - near);
+    let c2r3 = S::zero();
+
+    let c3r0 = -(right + left) / (right - left);
+    let c3r1 = (top + bottom) / (top - bottom);
+    let c3r2 = -near / (far - near);
+    let c3r3 = S::one();
+
+    Matrix4::new(
+        c0r0, c0r1, c0r2, c0r3,
+        c1r0, c1r1, c1r2, c1r3,
+        c2r0, c2r1, c2r2, c2r3,
+        c3r0, c3r1, c3r2, c3r3,
+    )
+}
+
 /// Clamp `value` between `min` and `max`.
 pub fn clamp<T: PartialOrd>(value: T, min: T, max: T) -> T {
     let value = if value > max { max } else { value };
@@ -129,3 +169,38 @@ pub fn slerp(left: Quaternion<f32>, right: Quaternion<f32>, amount: f32) -> Quat
         (num3 * left.v.z) + (num2 * right.v.z),
     )
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::{Point3, Vector3};
+
+    fn assert_approx_eq(a: f32, b: f32) {
+        assert!((a - b).abs() < 1e-4, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn orthographic_maps_the_box_to_the_clip_volume() {
+        let proj = orthographic(-1.0, 3.0, -2.0, 2.0, 0.5, 10.5);
+        let project = |x, y, z| {
+            let clip = proj * Point3::new(x, y, z).to_homogeneous();
+            Vector3::new(clip.x, clip.y, clip.z) / clip.w
+        };
+
+        // Projected y is inverted and depth is in 0..1
+        let near = project(-1.0, -2.0, -0.5);
+        assert_approx_eq(near.x, -1.0);
+        assert_approx_eq(near.y, 1.0);
+        assert_approx_eq(near.z, 0.0);
+
+        let far = project(3.0, 2.0, -10.5);
+        assert_approx_eq(far.x, 1.0);
+        assert_approx_eq(far.y, -1.0);
+        assert_approx_eq(far.z, 1.0);
+
+        let center = project(1.0, 0.0, -5.5);
+        assert_approx_eq(center.x, 0.0);
+        assert_approx_eq(center.y, 0.0);
+        assert_approx_eq(center.z, 0.5);
+    }
+}
@@ -0,0 +1,194 @@
+use super::AABB;
+use cgmath::{BaseFloat, InnerSpace, Matrix4, Point3, Transform, Vector3, Vector4};
+
+/// Half-line starting at `origin` and going along `direction`.
+///
+/// The direction is not normalized so the distances returned by the
+/// intersection tests are expressed in multiples of its length.
+#[derive(Copy, Clone, Debug)]
+pub struct Ray<S> {
+    origin: Point3<S>,
+    direction: Vector3<S>,
+}
+
+impl<S> Ray<S> {
+    /// Create a new ray.
+    pub fn new(origin: Point3<S>, direction: Vector3<S>) -> Self {
+        Ray { origin, direction }
+    }
+}
+
+impl<S: BaseFloat> Ray<S> {
+    /// Create the ray going through the point at `x` and `y` in normalized device coordinates.
+    ///
+    /// `inverse_view_proj` is the inverse of the projection matrix multiplied by
+    /// the view matrix. The ray starts on the near plane and its direction is normalized.
+    pub fn from_ndc(x: S, y: S, inverse_view_proj: Matrix4<S>) -> Self {
+        let unproject = |z: S| {
+            let point = inverse_view_proj * Vector4::new(x, y, z, S::one());
+            Point3::from_homogeneous(point)
+        };
+        let near = unproject(S::zero());
+        let far = unproject(S::one());
+        Ray::new(near, (far - near).normalize())
+    }
+
+    pub fn origin(&self) -> Point3<S> {
+        self.origin
+    }
+
+    pub fn direction(&self) -> Vector3<S> {
+        self.direction
+    }
+
+    /// Get the point at `distance` along the ray.
+    pub fn at(&self, distance: S) -> Point3<S> {
+        self.origin + self.direction * distance
+    }
+
+    /// Transform the ray by `matrix`.
+    ///
+    /// Distances are preserved, a point at a given distance along the
+    /// transformed ray is the transformed point at the same distance.
+    pub fn transform(&self, matrix: Matrix4<S>) -> Self {
+        Ray::new(
+            matrix.transform_point(self.origin),
+            matrix.transform_vector(self.direction),
+        )
+    }
+
+    /// Get the distance at which the ray enters `aabb`.
+    ///
+    /// Returns zero if the origin is inside the box and `None` if the ray misses it.
+    pub fn intersect_aabb(&self, aabb: &AABB<S>) -> Option<S> {
+        let (min, max) = (aabb.min(), aabb.max());
+        let mut near = S::zero();
+        let mut far = S::infinity();
+
+        for axis in 0..3 {
+            let origin = self.origin[axis];
+            let direction = self.direction[axis];
+            if direction == S::zero() {
+                if origin < min[axis] || origin > max[axis] {
+                    return None;
+                }
+                continue;
+            }
+
+            let t0 = (min[axis] - origin) / direction;
+            let t1 = (max[axis] - origin) / direction;
+            let (t0, t1) = if t0 < t1 { (t0, t1) } else { (t1, t0) };
+            near = super::max(near, t0);
+            far = super::min(far, t1);
+            if near > far {
+                return None;
+            }
+        }
+
+        Some(near)
+    }
+
+    /// Get the distance at which the ray hits the triangle `p0`, `p1`, `p2`.
+    ///
+    /// Both faces of the triangle are tested. Returns `None` if the ray
+    /// misses it or if the triangle is degenerate.
+    pub fn intersect_triangle(&self, p0: Point3<S>, p1: Point3<S>, p2: Point3<S>) -> Option<S> {
+        // Möller–Trumbore intersection
+        let edge1 = p1 - p0;
+        let edge2 = p2 - p0;
+        let p = self.direction.cross(edge2);
+        let determinant = edge1.dot(p);
+        if determinant == S::zero() {
+            return None;
+        }
+
+        let inverse_determinant = S::one() / determinant;
+        let s = self.origin - p0;
+        let u = s.dot(p) * inverse_determinant;
+        if u < S::zero() || u > S::one() {
+            return None;
+        }
+
+        let q = s.cross(edge1);
+        let v = self.direction.dot(q) * inverse_determinant;
+        if v < S::zero() || u + v > S::one() {
+            return None;
+        }
+
+        let distance = edge2.dot(q) * inverse_determinant;
+        if distance >= S::zero() {
+            Some(distance)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_approx_eq(a: f32, b: f32) {
+        assert!((a - b).abs() < 1e-4, "{} != {}", a, b);
+    }
+
+    fn triangle() -> [Point3<f32>; 3] {
+        [
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(1.0, 0.0, 0.0),
+            Point3::new(0.0, 1.0, 0.0),
+        ]
+    }
+
+    #[test]
+    fn intersects_both_faces_of_triangles() {
+        let [p0, p1, p2] = triangle();
+
+        let front = Ray::new(Point3::new(0.25, 0.25, 3.0), -Vector3::unit_z());
+        assert_approx_eq(front.intersect_triangle(p0, p1, p2).unwrap(), 3.0);
+        let back = Ray::new(Point3::new(0.25, 0.25, -2.0), Vector3::unit_z());
+        assert_approx_eq(back.intersect_triangle(p0, p1, p2).unwrap(), 2.0);
+
+        // Behind the origin and outside of the triangle
+        let behind = Ray::new(Point3::new(0.25, 0.25, -3.0), -Vector3::unit_z());
+        assert!(behind.intersect_triangle(p0, p1, p2).is_none());
+        let outside = Ray::new(Point3::new(0.75, 0.75, 3.0), -Vector3::unit_z());
+        assert!(outside.intersect_triangle(p0, p1, p2).is_none());
+
+        // Degenerate
+        assert!(front.intersect_triangle(p0, p1, p1).is_none());
+    }
+
+    #[test]
+    fn enters_aabb_at_its_nearest_face() {
+        let aabb = AABB::new(Vector3::new(-1.0, -1.0, -1.0), Vector3::new(1.0, 1.0, 1.0));
+
+        let ray = Ray::new(Point3::new(0.0, 0.0, 5.0), -Vector3::unit_z());
+        assert_approx_eq(ray.intersect_aabb(&aabb).unwrap(), 4.0);
+
+        let inside = Ray::new(Point3::new(0.5, 0.0, 0.0), Vector3::unit_x());
+        assert_approx_eq(inside.intersect_aabb(&aabb).unwrap(), 0.0);
+
+        let parallel = Ray::new(Point3::new(2.0, 0.0, 5.0), -Vector3::unit_z());
+        assert!(parallel.intersect_aabb(&aabb).is_none());
+        let away = Ray::new(Point3::new(0.0, 0.0, 5.0), Vector3::unit_z());
+        assert!(away.intersect_aabb(&aabb).is_none());
+    }
+
+    #[test]
+    fn transform_preserves_distances() {
+        let ray = Ray::new(Point3::new(1.0, 0.0, 0.0), Vector3::unit_x());
+        let matrix =
+            Matrix4::from_translation(Vector3::new(0.0, 0.0, 2.0)) * Matrix4::from_scale(2.0);
+
+        let transformed = ray.transform(matrix);
+        assert_eq!(transformed.at(3.0), matrix.transform_point(ray.at(3.0)));
+    }
+
+    #[test]
+    fn from_ndc_starts_on_the_near_plane() {
+        let ray = Ray::from_ndc(0.0, 0.0, Matrix4::from_scale(2.0));
+        assert_eq!(ray.origin(), Point3::new(0.0, 0.0, 0.0));
+        assert_eq!(ray.direction(), Vector3::unit_z());
+    }
+}
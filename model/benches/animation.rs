@@ -0,0 +1,200 @@
+//! Keyframe lookup benchmarks on synthetic large clips.
+//!
+//! Run with `cargo bench -p model`.
+
+mod common;
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use model::{load_animations, Animations, Nodes, PlaybackState};
+
+const NODE_COUNT: usize = 64;
+const KEYFRAME_COUNTS: [usize; 3] = [100, 1_000, 10_000];
+const FRAME_DELTA: f32 = 1.0 / 60.0;
+
+/// Build a binary glTF with `node_count` nodes each animated by a
+/// translation and a rotation channel of `keyframe_count` linear keyframes.
+fn build_glb(node_count: usize, keyframe_count: usize) -> Vec<u8> {
+    let mut bin = Vec::new();
+    for i in 0..keyframe_count {
+        bin.extend_from_slice(&(i as f32 * FRAME_DELTA).to_le_bytes());
+    }
+    let times_length = bin.len();
+    for i in 0..keyframe_count {
+        for v in &[i as f32, 0.0, 0.0] {
+            bin.extend_from_slice(&v.to_le_bytes());
+        }
+    }
+    let translations_length = bin.len() - times_length;
+    for i in 0..keyframe_count {
+        let angle = i as f32 * 0.01;
+        for v in &[0.0, (angle * 0.5).sin(), 0.0, (angle * 0.5).cos()] {
+            bin.extend_from_slice(&v.to_le_bytes());
+        }
+    }
+    let rotations_length = bin.len() - times_length - translations_length;
+
+    let nodes = (0..node_count).map(|_| "{}").collect::<Vec<_>>().join(",");
+    let scene_nodes = (0..node_count)
+        .map(|i| i.to_string())
+        .collect::<Vec<_>>()
+        .join(",");
+    let channels = (0..node_count)
+        .map(|i| {
+            format!(
+                r#"{{"sampler":0,"target":{{"node":{0},"path":"translation"}}}},
+                   {{"sampler":1,"target":{{"node":{0},"path":"rotation"}}}}"#,
+                i
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(",");
+
+    let json = format!(
+        r#"{{
+            "asset": {{ "version": "2.0" }},
+            "scene": 0,
+            "scenes": [{{ "nodes": [{scene_nodes}] }}],
+            "nodes": [{nodes}],
+            "buffers": [{{ "byteLength": {bin_length} }}],
+            "bufferViews": [
+                {{ "buffer": 0, "byteOffset": 0, "byteLength": {times_length} }},
+                {{ "buffer": 0, "byteOffset": {times_length}, "byteLength": {translations_length} }},
+                {{ "buffer": 0, "byteOffset": {rotations_offset}, "byteLength": {rotations_length} }}
+            ],
+            "accessors": [
+                {{ "bufferView": 0, "componentType": 5126, "count": {count}, "type": "SCALAR", "min": [0.0], "max": [{max_time}] }},
+                {{ "bufferView": 1, "componentType": 5126, "count": {count}, "type": "VEC3" }},
+                {{ "bufferView": 2, "componentType": 5126, "count": {count}, "type": "VEC4" }}
+            ],
+            "animations": [{{
+                "samplers": [
+                    {{ "input": 0, "output": 1, "interpolation": "LINEAR" }},
+                    {{ "input": 0, "output": 2, "interpolation": "LINEAR" }}
+                ],
+                "channels": [{channels}]
+            }}]
+        }}"#,
+        scene_nodes = scene_nodes,
+        nodes = nodes,
+        bin_length = bin.len(),
+        times_length = times_length,
+        translations_length = translations_length,
+        rotations_offset = times_length + translations_length,
+        rotations_length = rotations_length,
+        count = keyframe_count,
+        max_time = (keyframe_count - 1) as f32 * FRAME_DELTA,
+        channels = channels,
+    );
+
+    common::build_glb(json, bin)
+}
+
+fn load_clip(node_count: usize, keyframe_count: usize) -> (Animations, Nodes) {
+    let (document, buffers, _) =
+        gltf::import_slice(build_glb(node_count, keyframe_count)).expect("Invalid synthetic glTF");
+    let scene = document.default_scene().unwrap();
+    let nodes = Nodes::from_gltf_nodes(document.nodes(), &scene);
+    let animations =
+        load_animations(document.animations(), &buffers).expect("Missing synthetic animation");
+    (animations, nodes)
+}
+
+/// Keyframe lookup as it was done before the cursor/binary search.
+///
+/// Kept as the baseline the real sampler is compared to. The sampler cases
+/// also interpolate and write the sampled values so they give an upper bound
+/// of the lookup cost.
+fn legacy_linear_scan(times: &[f32], t: f32) -> Option<usize> {
+    (0..(times.len() - 1)).find(|&i| t >= times[i] && t < times[i + 1])
+}
+
+/// Times spread over the clip in a pseudo random order so each lookup
+/// misses the cached cursor.
+fn seek_times(keyframe_count: usize) -> Vec<f32> {
+    let max_time = (keyframe_count - 1) as f32 * FRAME_DELTA;
+    let mut state = 0x2545_f491u32;
+    (0..1024)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            (state as f32 / std::u32::MAX as f32) * max_time
+        })
+        .collect()
+}
+
+/// Sample the clip at each of `times` in turn.
+///
+/// Each iteration samples the translation and rotation channels of every node of the clip.
+fn bench_sampler(
+    b: &mut criterion::Bencher,
+    (animations, nodes): &mut (Animations, Nodes),
+    times: &[f32],
+) {
+    let playback_state = *animations.get_playback_state();
+    let mut index = 0;
+    b.iter(|| {
+        index = (index + 1) % times.len();
+        animations.set_playback_state(
+            nodes,
+            PlaybackState {
+                time: black_box(times[index]),
+                ..playback_state
+            },
+        )
+    })
+}
+
+fn bench_lookup(c: &mut Criterion) {
+    let mut group = c.benchmark_group("keyframe_lookup");
+    for &keyframe_count in KEYFRAME_COUNTS.iter() {
+        let times = (0..keyframe_count)
+            .map(|i| i as f32 * FRAME_DELTA)
+            .collect::<Vec<_>>();
+        let seek = seek_times(keyframe_count);
+        let mut index = 0;
+        group.bench_function(
+            BenchmarkId::new("linear_scan_baseline", keyframe_count),
+            |b| {
+                b.iter(|| {
+                    index = (index + 1) % seek.len();
+                    legacy_linear_scan(black_box(&times), black_box(seek[index]))
+                })
+            },
+        );
+
+        let mut clip = load_clip(1, keyframe_count);
+        group.bench_function(BenchmarkId::new("sampler_seek", keyframe_count), |b| {
+            bench_sampler(b, &mut clip, &seek)
+        });
+        let sequential = (0..keyframe_count - 1)
+            .map(|i| (i as f32 + 0.5) * FRAME_DELTA)
+            .collect::<Vec<_>>();
+        group.bench_function(
+            BenchmarkId::new("sampler_sequential", keyframe_count),
+            |b| bench_sampler(b, &mut clip, &sequential),
+        );
+    }
+    group.finish();
+}
+
+fn bench_playback(c: &mut Criterion) {
+    let mut group = c.benchmark_group("animation_playback");
+    for &keyframe_count in KEYFRAME_COUNTS.iter() {
+        let mut clip = load_clip(NODE_COUNT, keyframe_count);
+        let (animations, nodes) = &mut clip;
+        group.bench_function(BenchmarkId::new("update", keyframe_count), |b| {
+            b.iter(|| animations.update(nodes, black_box(FRAME_DELTA)))
+        });
+
+        // Random access to every channel, the worst case of the cursor
+        let seek = seek_times(keyframe_count);
+        group.bench_function(BenchmarkId::new("seek", keyframe_count), |b| {
+            bench_sampler(b, &mut clip, &seek)
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_lookup, bench_playback);
+criterion_main!(benches);
@@ -0,0 +1,190 @@
+//! CPU-side loading benchmarks on a synthetic texture-heavy model.
+//!
+//! The model is loaded with a single thread and with the whole thread pool
+//! to measure the speedup of decoding images, converting them and generating
+//! tangents in parallel.
+//!
+//! Run with `cargo bench -p model --bench loading`.
+
+mod common;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use image::{DynamicImage, ImageOutputFormat, RgbImage};
+use model::Scene;
+
+const IMAGE_COUNT: usize = 8;
+const IMAGE_SIZE: u32 = 2048;
+const MESH_COUNT: usize = 16;
+/// Number of quads per side of the grid of each mesh.
+const GRID_SIZE: usize = 128;
+
+fn build_png(seed: usize) -> Vec<u8> {
+    let image = RgbImage::from_fn(IMAGE_SIZE, IMAGE_SIZE, |x, y| {
+        let noise = (x.wrapping_mul(7919) ^ y.wrapping_mul(104_729)).wrapping_add(seed as u32);
+        image::Rgb([x as u8, y as u8, noise as u8])
+    });
+    let mut png = Vec::new();
+    DynamicImage::ImageRgb8(image)
+        .write_to(&mut png, ImageOutputFormat::Png)
+        .expect("Failed to encode synthetic image");
+    png
+}
+
+/// Vertices and indices of a flat grid with positions, normals and texture
+/// coordinates but no tangents.
+fn build_grid() -> Vec<u8> {
+    let vertex_per_side = GRID_SIZE + 1;
+    let mut bin = Vec::new();
+    for y in 0..vertex_per_side {
+        for x in 0..vertex_per_side {
+            let (u, v) = (x as f32 / GRID_SIZE as f32, y as f32 / GRID_SIZE as f32);
+            for value in &[u, v, 0.0, 0.0, 0.0, 1.0, u, v] {
+                bin.extend_from_slice(&value.to_le_bytes());
+            }
+        }
+    }
+    for y in 0..GRID_SIZE {
+        for x in 0..GRID_SIZE {
+            let i = (y * vertex_per_side + x) as u32;
+            let j = i + vertex_per_side as u32;
+            for index in &[i, i + 1, j, j, i + 1, j + 1] {
+                bin.extend_from_slice(&index.to_le_bytes());
+            }
+        }
+    }
+    bin
+}
+
+/// Build a binary glTF with `MESH_COUNT` grids and `IMAGE_COUNT` png images.
+fn build_glb() -> Vec<u8> {
+    let vertex_count = (GRID_SIZE + 1) * (GRID_SIZE + 1);
+    let index_count = GRID_SIZE * GRID_SIZE * 6;
+    let vertices_length = vertex_count * 8 * 4;
+
+    let mut bin = build_grid();
+    let grid_length = bin.len();
+    let mut image_views = Vec::new();
+    for seed in 0..IMAGE_COUNT {
+        let png = build_png(seed);
+        image_views.push((bin.len(), png.len()));
+        bin.extend_from_slice(&png);
+        while bin.len() % 4 != 0 {
+            bin.push(0);
+        }
+    }
+
+    let nodes = (0..MESH_COUNT)
+        .map(|i| format!(r#"{{ "mesh": {} }}"#, i))
+        .collect::<Vec<_>>()
+        .join(",");
+    let scene_nodes = (0..MESH_COUNT)
+        .map(|i| i.to_string())
+        .collect::<Vec<_>>()
+        .join(",");
+    let meshes = (0..MESH_COUNT)
+        .map(|i| {
+            format!(
+                r#"{{ "primitives": [{{
+                    "attributes": {{ "POSITION": 0, "NORMAL": 1, "TEXCOORD_0": 2 }},
+                    "indices": 3,
+                    "material": {}
+                }}] }}"#,
+                i % IMAGE_COUNT
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(",");
+    let materials = (0..IMAGE_COUNT)
+        .map(|i| {
+            format!(
+                r#"{{ "pbrMetallicRoughness": {{ "baseColorTexture": {{ "index": {0} }} }},
+                     "normalTexture": {{ "index": {0} }} }}"#,
+                i
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(",");
+    let textures = (0..IMAGE_COUNT)
+        .map(|i| format!(r#"{{ "source": {} }}"#, i))
+        .collect::<Vec<_>>()
+        .join(",");
+    let images = (0..IMAGE_COUNT)
+        .map(|i| format!(r#"{{ "bufferView": {}, "mimeType": "image/png" }}"#, i + 2))
+        .collect::<Vec<_>>()
+        .join(",");
+    let image_buffer_views = image_views
+        .iter()
+        .map(|(offset, length)| {
+            format!(
+                r#"{{ "buffer": 0, "byteOffset": {}, "byteLength": {} }}"#,
+                offset, length
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(",");
+
+    let json = format!(
+        r#"{{
+            "asset": {{ "version": "2.0" }},
+            "scene": 0,
+            "scenes": [{{ "nodes": [{scene_nodes}] }}],
+            "nodes": [{nodes}],
+            "meshes": [{meshes}],
+            "materials": [{materials}],
+            "textures": [{textures}],
+            "images": [{images}],
+            "buffers": [{{ "byteLength": {bin_length} }}],
+            "bufferViews": [
+                {{ "buffer": 0, "byteOffset": 0, "byteLength": {vertices_length}, "byteStride": 32 }},
+                {{ "buffer": 0, "byteOffset": {vertices_length}, "byteLength": {indices_length} }},
+                {image_buffer_views}
+            ],
+            "accessors": [
+                {{ "bufferView": 0, "byteOffset": 0, "componentType": 5126, "count": {vertex_count},
+                   "type": "VEC3", "min": [0.0, 0.0, 0.0], "max": [1.0, 1.0, 0.0] }},
+                {{ "bufferView": 0, "byteOffset": 12, "componentType": 5126, "count": {vertex_count}, "type": "VEC3" }},
+                {{ "bufferView": 0, "byteOffset": 24, "componentType": 5126, "count": {vertex_count}, "type": "VEC2" }},
+                {{ "bufferView": 1, "componentType": 5125, "count": {index_count}, "type": "SCALAR" }}
+            ]
+        }}"#,
+        scene_nodes = scene_nodes,
+        nodes = nodes,
+        meshes = meshes,
+        materials = materials,
+        textures = textures,
+        images = images,
+        bin_length = bin.len(),
+        vertices_length = vertices_length,
+        indices_length = grid_length - vertices_length,
+        image_buffer_views = image_buffer_views,
+        vertex_count = vertex_count,
+        index_count = index_count,
+    );
+
+    common::build_glb(json, bin)
+}
+
+fn bench_loading(c: &mut Criterion) {
+    let glb = build_glb();
+    let max_threads = rayon::current_num_threads();
+
+    let mut group = c.benchmark_group("texture_heavy_loading");
+    group.sample_size(10);
+    let mut thread_counts = vec![1, max_threads];
+    thread_counts.dedup();
+    for thread_count in thread_counts {
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(thread_count)
+            .build()
+            .expect("Failed to create thread pool");
+        group.bench_with_input(BenchmarkId::new("threads", thread_count), &glb, |b, glb| {
+            b.iter(|| {
+                pool.install(|| Scene::from_slice("bench", glb).expect("Invalid synthetic glTF"))
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_loading);
+criterion_main!(benches);
@@ -13,7 +13,7 @@ use math::cgmath::{InnerSpace, Quaternion, Vector3, VectorSpace};
 use math::slerp;
 use std::cmp::Ordering;
 
-trait Interpolate: Copy {
+pub(crate) trait Interpolate: Copy {
     fn linear(self, other: Self, amount: f32) -> Self;
 
     fn cubic_spline(
@@ -78,93 +78,198 @@ impl Interpolate for Quaternion<f32> {
 }
 
 #[derive(Copy, Clone, Debug)]
-enum Interpolation {
+pub(crate) enum Interpolation {
     Linear,
     Step,
     CubicSpline,
 }
 
 #[derive(Debug)]
-struct Sampler<T> {
+pub(crate) struct Sampler<T> {
     interpolation: Interpolation,
     times: Vec<f32>,
     values: Vec<T>,
+    /// Index of the last keyframe used for sampling.
+    ///
+    /// Animations are mostly played forward so the next requested time
+    /// is very likely to be in the same or the next keyframe interval.
+    cursor: usize,
 }
 
 impl<T> Sampler<T> {
+    fn new(interpolation: Interpolation, times: Vec<f32>, values: Vec<T>) -> Self {
+        Self {
+            interpolation,
+            times,
+            values,
+            cursor: 0,
+        }
+    }
+
     fn get_max_time(&self) -> f32 {
         self.times.last().copied().unwrap_or(0.0)
     }
+
+    pub(crate) fn interpolation(&self) -> Interpolation {
+        self.interpolation
+    }
+
+    pub(crate) fn times(&self) -> &[f32] {
+        &self.times
+    }
+
+    /// Keyframe values. Cubic spline samplers store an in-tangent, a value
+    /// and an out-tangent per keyframe.
+    pub(crate) fn values(&self) -> &[T] {
+        &self.values
+    }
+
+    /// Find the index `i` of the keyframe interval containing `t`
+    /// so that `times[i] <= t < times[i + 1]`.
+    ///
+    /// The cached cursor and its successor are checked first then
+    /// we fall back to a binary search. `t` must be in the time range
+    /// of the sampler and the sampler must have at least two keyframes.
+    fn find_keyframe(&mut self, t: f32) -> usize {
+        let last_interval = self.times.len() - 2;
+
+        let cursor = self.cursor.min(last_interval);
+        for index in cursor..=(cursor + 1).min(last_interval) {
+            if self.times[index] <= t && t < self.times[index + 1] {
+                self.cursor = index;
+                return index;
+            }
+        }
+
+        let index = match self
+            .times
+            .binary_search_by(|time| time.partial_cmp(&t).unwrap_or(Ordering::Less))
+        {
+            Ok(index) => index,
+            Err(index) => index.saturating_sub(1),
+        };
+        let index = index.min(last_interval);
+        self.cursor = index;
+        index
+    }
+
+    /// Get the value of the keyframe at `index`.
+    fn get_keyframe_value(&self, index: usize) -> T
+    where
+        T: Copy,
+    {
+        match self.interpolation {
+            Interpolation::CubicSpline => self.values[index * 3 + 1],
+            _ => self.values[index],
+        }
+    }
 }
 
 impl<T: Interpolate> Sampler<T> {
-    fn sample(&self, t: f32) -> Option<T> {
-        let index = {
-            let mut index = None;
-            for i in 0..(self.times.len() - 1) {
-                let previous = self.times[i];
-                let next = self.times[i + 1];
-                if t >= previous && t < next {
-                    index = Some(i);
-                    break;
-                }
+    /// Sample the value at `t`.
+    ///
+    /// Before the first keyframe, the first value is returned. After the last
+    /// keyframe, the last value is returned.
+    fn sample(&mut self, t: f32) -> Option<T> {
+        let keyframe_count = self.times.len();
+        if keyframe_count == 0 {
+            return None;
+        }
+
+        if keyframe_count == 1 || t <= self.times[0] {
+            return Some(self.get_keyframe_value(0));
+        }
+
+        if t >= self.times[keyframe_count - 1] {
+            return Some(self.get_keyframe_value(keyframe_count - 1));
+        }
+
+        let i = self.find_keyframe(t);
+
+        let previous_time = self.times[i];
+        let next_time = self.times[i + 1];
+        let delta = next_time - previous_time;
+        let from_start = t - previous_time;
+        let factor = from_start / delta;
+
+        let value = match self.interpolation {
+            Interpolation::Step => self.values[i],
+            Interpolation::Linear => {
+                let previous_value = self.values[i];
+                let next_value = self.values[i + 1];
+
+                previous_value.linear(next_value, factor)
+            }
+            Interpolation::CubicSpline => {
+                let previous_values = [
+                    self.values[i * 3],
+                    self.values[i * 3 + 1],
+                    self.values[i * 3 + 2],
+                ];
+                let next_values = [
+                    self.values[i * 3 + 3],
+                    self.values[i * 3 + 4],
+                    self.values[i * 3 + 5],
+                ];
+                Interpolate::cubic_spline(
+                    previous_values,
+                    previous_time,
+                    next_values,
+                    next_time,
+                    factor,
+                )
             }
-            index
         };
 
-        index.map(|i| {
-            let previous_time = self.times[i];
-            let next_time = self.times[i + 1];
-            let delta = next_time - previous_time;
-            let from_start = t - previous_time;
-            let factor = from_start / delta;
-
-            match self.interpolation {
-                Interpolation::Step => self.values[i],
-                Interpolation::Linear => {
-                    let previous_value = self.values[i];
-                    let next_value = self.values[i + 1];
-
-                    previous_value.linear(next_value, factor)
-                }
-                Interpolation::CubicSpline => {
-                    let previous_values = [
-                        self.values[i * 3],
-                        self.values[i * 3 + 1],
-                        self.values[i * 3 + 2],
-                    ];
-                    let next_values = [
-                        self.values[i * 3 + 3],
-                        self.values[i * 3 + 4],
-                        self.values[i * 3 + 5],
-                    ];
-                    Interpolate::cubic_spline(
-                        previous_values,
-                        previous_time,
-                        next_values,
-                        next_time,
-                        factor,
-                    )
-                }
-            }
-        })
+        Some(value)
     }
 }
 
 #[derive(Debug)]
-struct Channel<T> {
+pub(crate) struct Channel<T> {
     sampler: Sampler<T>,
     node_index: usize,
 }
 
 impl<T> Channel<T> {
+    /// Create a new channel.
+    ///
+    /// Returns `None` if the sampler's keyframe times and values don't match.
+    fn new(sampler: Sampler<T>, node_index: usize) -> Option<Self> {
+        let expected_value_count = match sampler.interpolation {
+            Interpolation::CubicSpline => sampler.times.len() * 3,
+            _ => sampler.times.len(),
+        };
+        if sampler.values.len() != expected_value_count {
+            log::warn!(
+                "Ignoring animation channel of node {}. Expected {} values but found {}",
+                node_index,
+                expected_value_count,
+                sampler.values.len()
+            );
+            return None;
+        }
+        Some(Self {
+            sampler,
+            node_index,
+        })
+    }
+
     fn get_max_time(&self) -> f32 {
         self.sampler.get_max_time()
     }
+
+    pub(crate) fn sampler(&self) -> &Sampler<T> {
+        &self.sampler
+    }
+
+    pub(crate) fn node_index(&self) -> usize {
+        self.node_index
+    }
 }
 
 impl<T: Interpolate> Channel<T> {
-    fn sample(&self, t: f32) -> Option<(usize, T)> {
+    fn sample(&mut self, t: f32) -> Option<(usize, T)> {
         self.sampler.sample(t).map(|s| (self.node_index, s))
     }
 }
@@ -257,6 +362,24 @@ impl Animations {
         self.playback_state.time = 0.0;
     }
 
+    /// Restore a playback state, for example one saved before reloading the model.
+    ///
+    /// The time is clamped to the duration of the animation. Nodes are animated
+    /// even if the playback is paused so they match the restored time.
+    pub fn set_playback_state(&mut self, nodes: &mut Nodes, playback_state: PlaybackState) -> bool {
+        match self.animations.get_mut(playback_state.current) {
+            Some(animation) => {
+                self.playback_state = PlaybackState {
+                    time: playback_state.time.min(animation.total_time),
+                    total_time: animation.total_time,
+                    ..playback_state
+                };
+                animation.animate(nodes, self.playback_state.time)
+            }
+            None => false,
+        }
+    }
+
     pub fn animations(&self) -> &[Animation] {
         &self.animations
     }
@@ -289,18 +412,30 @@ impl Animation {
         !translations.is_empty() || !rotations.is_empty() || !scale.is_empty()
     }
 
-    fn sample(&self, t: f32) -> NodesKeyFrame {
+    pub(crate) fn translation_channels(&self) -> &[Channel<Vector3<f32>>] {
+        &self.translation_channels
+    }
+
+    pub(crate) fn rotation_channels(&self) -> &[Channel<Quaternion<f32>>] {
+        &self.rotation_channels
+    }
+
+    pub(crate) fn scale_channels(&self) -> &[Channel<Vector3<f32>>] {
+        &self.scale_channels
+    }
+
+    fn sample(&mut self, t: f32) -> NodesKeyFrame {
         NodesKeyFrame(
             self.translation_channels
-                .iter()
+                .iter_mut()
                 .filter_map(|tc| tc.sample(t))
                 .collect::<Vec<_>>(),
             self.rotation_channels
-                .iter()
+                .iter_mut()
                 .filter_map(|tc| tc.sample(t))
                 .collect::<Vec<_>>(),
             self.scale_channels
-                .iter()
+                .iter_mut()
                 .filter_map(|tc| tc.sample(t))
                 .collect::<Vec<_>>(),
         )
@@ -376,18 +511,14 @@ fn map_translation_channel(
 ) -> Option<Channel<Vector3<f32>>> {
     let gltf_sampler = gltf_channel.sampler();
     if let Property::Translation = gltf_channel.target().property() {
-        map_interpolation(gltf_sampler.interpolation()).map(|i| {
+        map_interpolation(gltf_sampler.interpolation()).and_then(|i| {
             let reader = gltf_channel.reader(|buffer| Some(&data[buffer.index()]));
             let times = read_times(&reader);
             let output = read_translations(&reader);
-            Channel {
-                sampler: Sampler {
-                    interpolation: i,
-                    times,
-                    values: output,
-                },
-                node_index: gltf_channel.target().node().index(),
-            }
+            Channel::new(
+                Sampler::new(i, times, output),
+                gltf_channel.target().node().index(),
+            )
         })
     } else {
         None
@@ -407,18 +538,14 @@ fn map_rotation_channel(
 ) -> Option<Channel<Quaternion<f32>>> {
     let gltf_sampler = gltf_channel.sampler();
     if let Property::Rotation = gltf_channel.target().property() {
-        map_interpolation(gltf_sampler.interpolation()).map(|interpolation| {
+        map_interpolation(gltf_sampler.interpolation()).and_then(|interpolation| {
             let reader = gltf_channel.reader(|buffer| Some(&data[buffer.index()]));
             let times = read_times(&reader);
             let output = read_rotations(&reader);
-            Channel {
-                sampler: Sampler {
-                    interpolation,
-                    times,
-                    values: output,
-                },
-                node_index: gltf_channel.target().node().index(),
-            }
+            Channel::new(
+                Sampler::new(interpolation, times, output),
+                gltf_channel.target().node().index(),
+            )
         })
     } else {
         None
@@ -435,18 +562,14 @@ fn map_scale_channels(gltf_channels: Channels, data: &[Data]) -> Vec<Channel<Vec
 fn map_scale_channel(gltf_channel: &GltfChannel, data: &[Data]) -> Option<Channel<Vector3<f32>>> {
     let gltf_sampler = gltf_channel.sampler();
     if let Property::Scale = gltf_channel.target().property() {
-        map_interpolation(gltf_sampler.interpolation()).map(|i| {
+        map_interpolation(gltf_sampler.interpolation()).and_then(|i| {
             let reader = gltf_channel.reader(|buffer| Some(&data[buffer.index()]));
             let times = read_times(&reader);
             let output = read_scales(&reader);
-            Channel {
-                sampler: Sampler {
-                    interpolation: i,
-                    times,
-                    values: output,
-                },
-                node_index: gltf_channel.target().node().index(),
-            }
+            Channel::new(
+                Sampler::new(i, times, output),
+                gltf_channel.target().node().index(),
+            )
         })
     } else {
         None
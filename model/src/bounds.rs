@@ -0,0 +1,58 @@
+//! World space bounds of the nodes and primitives of a scene.
+//!
+//! Bounds follow the current pose of the scene. A skinned vertex is a weighted
+//! average of its position transformed by each of its joints so skinned
+//! primitives are bounded by the union of their box transformed by each joint.
+
+use super::{Scene, MAX_JOINTS_PER_MESH};
+use math::AABB;
+
+/// Bounds of a mesh node in world space.
+#[derive(Clone, Debug)]
+pub struct NodeBounds {
+    /// Union of the bounds of the primitives.
+    pub aabb: AABB<f32>,
+    /// Bounds of each primitive, indexed like the primitives of the mesh.
+    pub primitives: Vec<AABB<f32>>,
+}
+
+/// Compute the bounds of all the nodes of `scene`.
+///
+/// The result is indexed like the nodes. Nodes without mesh or with a
+/// mesh without primitive have no bounds.
+pub(crate) fn compute_world_bounds(scene: &Scene) -> Vec<Option<NodeBounds>> {
+    let meshes = scene.geometry().meshes();
+
+    scene
+        .nodes()
+        .nodes()
+        .iter()
+        .map(|node| {
+            let mesh = &meshes[node.mesh_index()?];
+            let transform = node.transform();
+            let joints = node
+                .skin_index()
+                .map_or(&[][..], |index| scene.skins()[index].joints());
+            let joints = &joints[..joints.len().min(MAX_JOINTS_PER_MESH)];
+
+            let primitives = mesh
+                .primitives()
+                .iter()
+                .map(|primitive| {
+                    let aabb = primitive.aabb();
+                    // Vertices without weights are not skinned
+                    let mut posed = vec![aabb.transform(transform)];
+                    posed.extend(
+                        joints
+                            .iter()
+                            .map(|joint| aabb.transform(transform * joint.matrix())),
+                    );
+                    AABB::union(&posed).unwrap()
+                })
+                .collect::<Vec<_>>();
+
+            let aabb = AABB::union(&primitives)?;
+            Some(NodeBounds { aabb, primitives })
+        })
+        .collect()
+}
@@ -1,22 +1,151 @@
-use std::{error::Error, fmt};
+use crate::metadata::Issue;
+use gltf::image::Format;
+use std::{error::Error, fmt, io};
 
 #[derive(Debug)]
-pub struct ModelLoadingError {
-    message: String,
+pub enum ModelLoadingError {
+    /// The model file or one of its resources could not be read.
+    Io(io::Error),
+    /// The document or one of its embedded resources is malformed.
+    Parse(String),
+    /// The document requires an extension the viewer does not support.
+    ///
+    /// `issues` are the errors found when validating the document.
+    UnsupportedExtension {
+        extension: String,
+        issues: Vec<Issue>,
+    },
+    /// An image has a pixel format that can't be used as a texture.
+    UnsupportedTextureFormat { image: usize, format: Format },
+    /// An accessor is missing or its content is not usable.
+    InvalidAccessor(String),
+    /// An external resource could not be resolved.
+    MissingResource(String),
+    /// The document does not contain any scene.
+    NoScene,
+    /// The document does not contain anything to render.
+    EmptyScene,
+    /// The loading was cancelled before completion.
+    Cancelled,
 }
 
-impl ModelLoadingError {
-    pub fn new<S: Into<String>>(message: S) -> Self {
-        Self {
-            message: message.into(),
+impl fmt::Display for ModelLoadingError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Failed to load model. Cause: ")?;
+        match self {
+            ModelLoadingError::Io(error) => write!(f, "I/O error: {}", error),
+            ModelLoadingError::Parse(message) => write!(f, "Parsing error: {}", message),
+            ModelLoadingError::UnsupportedExtension { extension, issues } => {
+                write!(f, "Extension {} is required but not supported", extension)?;
+                for issue in issues {
+                    write!(f, "\n{}: {}", issue.subject, issue.message)?;
+                }
+                Ok(())
+            }
+            ModelLoadingError::UnsupportedTextureFormat { image, format } => write!(
+                f,
+                "Image {} has an unsupported texture format ({:?})",
+                image, format
+            ),
+            ModelLoadingError::InvalidAccessor(message) => {
+                write!(f, "Invalid accessor: {}", message)
+            }
+            ModelLoadingError::MissingResource(message) => {
+                write!(f, "Missing resource: {}", message)
+            }
+            ModelLoadingError::NoScene => write!(f, "There is no scene"),
+            ModelLoadingError::EmptyScene => write!(f, "Could not find any renderable primitives"),
+            ModelLoadingError::Cancelled => write!(f, "Loading was cancelled"),
         }
     }
 }
 
-impl fmt::Display for ModelLoadingError {
+impl Error for ModelLoadingError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ModelLoadingError::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for ModelLoadingError {
+    fn from(error: io::Error) -> Self {
+        ModelLoadingError::Io(error)
+    }
+}
+
+impl From<gltf::Error> for ModelLoadingError {
+    fn from(error: gltf::Error) -> Self {
+        match error {
+            gltf::Error::Io(error) => ModelLoadingError::Io(error),
+            error => ModelLoadingError::Parse(error.to_string()),
+        }
+    }
+}
+
+impl From<image::ImageError> for ModelLoadingError {
+    fn from(error: image::ImageError) -> Self {
+        ModelLoadingError::Parse(error.to_string())
+    }
+}
+
+impl From<base64::DecodeError> for ModelLoadingError {
+    fn from(error: base64::DecodeError) -> Self {
+        ModelLoadingError::Parse(error.to_string())
+    }
+}
+
+#[derive(Debug)]
+pub enum ModelExportError {
+    /// The exported files could not be written.
+    Io(io::Error),
+    /// The document or one of its images could not be encoded.
+    Encoding(String),
+}
+
+impl fmt::Display for ModelExportError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "Failed to load model. Cause: {}", self.message)
+        write!(f, "Failed to export model. Cause: ")?;
+        match self {
+            ModelExportError::Io(error) => write!(f, "I/O error: {}", error),
+            ModelExportError::Encoding(message) => write!(f, "Encoding error: {}", message),
+        }
+    }
+}
+
+impl Error for ModelExportError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ModelExportError::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for ModelExportError {
+    fn from(error: io::Error) -> Self {
+        ModelExportError::Io(error)
+    }
+}
+
+impl From<gltf::Error> for ModelExportError {
+    fn from(error: gltf::Error) -> Self {
+        match error {
+            gltf::Error::Io(error) => ModelExportError::Io(error),
+            error => ModelExportError::Encoding(error.to_string()),
+        }
     }
 }
 
-impl Error for ModelLoadingError {}
+impl From<gltf::json::Error> for ModelExportError {
+    fn from(error: gltf::json::Error) -> Self {
+        ModelExportError::Encoding(error.to_string())
+    }
+}
+
+impl From<image::ImageError> for ModelExportError {
+    fn from(error: image::ImageError) -> Self {
+        ModelExportError::Encoding(error.to_string())
+    }
+}
@@ -0,0 +1,854 @@
+//! Export of a scene to glTF or binary glTF.
+//!
+//! The scene is written as it is in memory so nodes are exported in their
+//! current pose. The geometry, the animations and the images, encoded as png,
+//! are stored in a single buffer. The placement and transform of the scene
+//! in the viewer are not exported.
+//!
+//! Nodes hidden in the viewer are exported without their mesh so the
+//! hierarchy, the skins and the animations referencing them stay valid.
+
+use super::{
+    animation::{Channel, Interpolation},
+    material::{ALPHA_MODE_BLEND, ALPHA_MODE_MASK},
+    Light, Material, MeshData, ModelExportError, MorphTarget, RgbaImage, Scene, TextureInfo,
+    Type as LightType, Workflow,
+};
+use gltf::{
+    binary::{Glb, Header},
+    json::{
+        self,
+        accessor::{ComponentType, GenericComponentType, Type},
+        animation::Property,
+        buffer::Target,
+        extensions::{
+            material::{PbrDiffuseFactor, PbrSpecularFactor, PbrSpecularGlossiness, Unlit},
+            scene::khr_lights_punctual,
+        },
+        material::{
+            AlphaCutoff, AlphaMode, EmissiveFactor, NormalTexture, OcclusionTexture,
+            PbrBaseColorFactor, PbrMetallicRoughness, StrengthFactor,
+        },
+        mesh::Semantic,
+        validation::Checked::Valid,
+        Index,
+    },
+    scene::Transform,
+};
+use image::{DynamicImage, ImageBuffer, ImageOutputFormat};
+use math::cgmath::{Quaternion, Vector3};
+use rayon::prelude::*;
+use std::{borrow::Cow, collections::BTreeMap, fs, path::Path};
+
+const GENERATOR: &str = "gltf-viewer-rs";
+
+/// File format of an exported scene.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// A `.gltf` document and its `.bin` buffer.
+    Gltf,
+    /// A single `.glb` file.
+    Glb,
+}
+
+impl ExportFormat {
+    /// Get the format matching the extension of `path`.
+    ///
+    /// Anything but a `glb` extension is exported as glTF.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Self {
+        match path.as_ref().extension().and_then(|e| e.to_str()) {
+            Some(extension) if extension.eq_ignore_ascii_case("glb") => ExportFormat::Glb,
+            _ => ExportFormat::Gltf,
+        }
+    }
+}
+
+/// Write `scene` to `path`.
+///
+/// In glTF format, the buffer is written next to the document with the same
+/// name and the `bin` extension. `node_visibility` is indexed like the nodes
+/// of the scene, missing nodes are visible.
+pub(crate) fn write_scene(
+    scene: &Scene,
+    path: &Path,
+    format: ExportFormat,
+    node_visibility: &[bool],
+) -> Result<(), ModelExportError> {
+    match format {
+        ExportFormat::Glb => fs::write(path, to_glb(scene, node_visibility)?)?,
+        ExportFormat::Gltf => {
+            let buffer_path = path.with_extension("bin");
+            let buffer_uri = buffer_path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .map(String::from)
+                .ok_or_else(|| {
+                    ModelExportError::Encoding(format!("Invalid file name {}", path.display()))
+                })?;
+            let (root, buffer) = build_document(scene, Some(buffer_uri), node_visibility)?;
+            fs::write(path, root.to_vec_pretty()?)?;
+            fs::write(buffer_path, buffer)?;
+        }
+    }
+    Ok(())
+}
+
+/// Encode `scene` as binary glTF.
+pub(crate) fn to_glb(scene: &Scene, node_visibility: &[bool]) -> Result<Vec<u8>, ModelExportError> {
+    let (root, buffer) = build_document(scene, None, node_visibility)?;
+    let glb = Glb {
+        header: Header {
+            magic: *b"glTF",
+            version: 2,
+            length: 0,
+        },
+        json: Cow::Owned(root.to_vec()?),
+        bin: Some(Cow::Owned(buffer)),
+    };
+    Ok(glb.to_vec()?)
+}
+
+/// Build the glTF document of `scene` and its binary buffer.
+///
+/// `buffer_uri` is the uri of the buffer or `None` if it is stored in a glb.
+fn build_document(
+    scene: &Scene,
+    buffer_uri: Option<String>,
+    node_visibility: &[bool],
+) -> Result<(json::Root, Vec<u8>), ModelExportError> {
+    let mut builder = DocumentBuilder::default();
+    let names = scene.metadata().names();
+
+    builder.push_images(scene.images(), &names.images)?;
+    builder.push_textures(scene);
+    let materials = builder.push_materials(scene);
+    let meshes = scene
+        .geometry()
+        .meshes()
+        .iter()
+        .enumerate()
+        .map(|(index, mesh)| builder.push_mesh(scene, index, mesh, &materials))
+        .collect::<Vec<_>>();
+    builder.push_skins(scene);
+    builder.push_nodes(scene, &meshes, node_visibility);
+    builder.push_lights(scene.lights(), &names.lights);
+    builder.push_animations(scene);
+
+    let roots = scene
+        .nodes()
+        .roots_indices()
+        .iter()
+        .map(|index| Index::new(*index as _))
+        .collect();
+    Ok(builder.finish(scene.metadata().name(), roots, buffer_uri))
+}
+
+/// Incremental construction of a glTF document and its single buffer.
+#[derive(Default)]
+pub(crate) struct DocumentBuilder {
+    pub root: json::Root,
+    buffer: Vec<u8>,
+}
+
+impl DocumentBuilder {
+    /// Add the asset, the buffer and a scene made of `roots` to the document.
+    ///
+    /// `buffer_uri` is the uri of the buffer or `None` if it is stored in a glb.
+    pub fn finish(
+        self,
+        scene_name: &str,
+        roots: Vec<Index<json::Node>>,
+        buffer_uri: Option<String>,
+    ) -> (json::Root, Vec<u8>) {
+        let DocumentBuilder { mut root, buffer } = self;
+        root.asset = json::Asset {
+            copyright: None,
+            extensions: None,
+            extras: Default::default(),
+            generator: Some(String::from(GENERATOR)),
+            min_version: None,
+            version: String::from("2.0"),
+        };
+        root.buffers.push(json::Buffer {
+            byte_length: buffer.len() as _,
+            name: None,
+            uri: buffer_uri,
+            extensions: None,
+            extras: Default::default(),
+        });
+        root.scene = Some(Index::new(0));
+        root.scenes.push(json::Scene {
+            extensions: None,
+            extras: Default::default(),
+            name: Some(scene_name.to_owned()),
+            nodes: roots,
+        });
+        (root, buffer)
+    }
+
+    pub fn use_extension(&mut self, extension: &str) {
+        if !self.root.extensions_used.iter().any(|e| e == extension) {
+            self.root.extensions_used.push(String::from(extension));
+        }
+    }
+
+    fn push_view(&mut self, data: &[u8], target: Option<Target>) -> Index<json::buffer::View> {
+        while self.buffer.len() % 4 != 0 {
+            self.buffer.push(0);
+        }
+        let view = json::buffer::View {
+            buffer: Index::new(0),
+            byte_length: data.len() as _,
+            byte_offset: Some(self.buffer.len() as _),
+            byte_stride: None,
+            name: None,
+            target: target.map(Valid),
+            extensions: None,
+            extras: Default::default(),
+        };
+        self.buffer.extend_from_slice(data);
+        push(&mut self.root.buffer_views, view)
+    }
+
+    /// Push `elements` in a new view and create an accessor for them.
+    ///
+    /// `bounds` are the min and max values of the accessor.
+    pub fn push_accessor<E: Element>(
+        &mut self,
+        elements: &[E],
+        target: Option<Target>,
+        bounds: Option<(json::Value, json::Value)>,
+    ) -> Index<json::Accessor> {
+        let mut data = Vec::new();
+        elements.iter().for_each(|e| e.write(&mut data));
+        let view = self.push_view(&data, target);
+
+        let (min, max) = bounds.map_or((None, None), |(min, max)| (Some(min), Some(max)));
+        let accessor = json::Accessor {
+            buffer_view: Some(view),
+            byte_offset: 0,
+            count: elements.len() as _,
+            component_type: Valid(GenericComponentType(E::COMPONENT_TYPE)),
+            extensions: None,
+            extras: Default::default(),
+            type_: Valid(E::TYPE),
+            min,
+            max,
+            name: None,
+            normalized: false,
+            sparse: None,
+        };
+        push(&mut self.root.accessors, accessor)
+    }
+
+    fn push_images(
+        &mut self,
+        images: &[RgbaImage],
+        names: &[Option<String>],
+    ) -> Result<(), ModelExportError> {
+        let encoded_images = images
+            .par_iter()
+            .map(encode_png)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        for (index, encoded_image) in encoded_images.into_iter().enumerate() {
+            let view = self.push_view(&encoded_image, None);
+            self.root.images.push(json::Image {
+                buffer_view: Some(view),
+                mime_type: Some(json::image::MimeType(String::from("image/png"))),
+                name: name(names, index),
+                uri: None,
+                extensions: None,
+                extras: Default::default(),
+            });
+        }
+        Ok(())
+    }
+
+    fn push_textures(&mut self, scene: &Scene) {
+        let names = &scene.metadata().names().textures;
+        for (index, texture) in scene.textures().iter().enumerate() {
+            let sampler = push(
+                &mut self.root.samplers,
+                json::texture::Sampler {
+                    mag_filter: Some(Valid(texture.mag_filter)),
+                    min_filter: Some(Valid(texture.min_filter)),
+                    name: None,
+                    wrap_s: Valid(texture.wrap_s),
+                    wrap_t: Valid(texture.wrap_t),
+                    extensions: None,
+                    extras: Default::default(),
+                },
+            );
+            self.root.textures.push(json::Texture {
+                name: name(names, index),
+                sampler: Some(sampler),
+                source: Index::new(texture.image_index as _),
+                extensions: None,
+                extras: Default::default(),
+            });
+        }
+    }
+
+    /// Push the materials used by the primitives of the scene.
+    ///
+    /// Returns the index of the exported material of each source material.
+    /// The default material is not exported.
+    fn push_materials(&mut self, scene: &Scene) -> BTreeMap<usize, Index<json::Material>> {
+        let materials = scene
+            .geometry()
+            .meshes()
+            .iter()
+            .flat_map(|mesh| mesh.primitives())
+            .map(|primitive| primitive.material())
+            .filter_map(|material| material.get_index().map(|index| (index, material)))
+            .collect::<BTreeMap<_, _>>();
+
+        let names = &scene.metadata().names().materials;
+        materials
+            .into_iter()
+            .map(|(index, material)| {
+                let material = json::Material {
+                    name: name(names, index),
+                    ..self.map_material(&material)
+                };
+                (index, push(&mut self.root.materials, material))
+            })
+            .collect()
+    }
+
+    fn map_material(&mut self, material: &Material) -> json::Material {
+        let alpha_mode = match material.get_alpha_mode() {
+            ALPHA_MODE_MASK => AlphaMode::Mask,
+            ALPHA_MODE_BLEND => AlphaMode::Blend,
+            _ => AlphaMode::Opaque,
+        };
+
+        let mut json_material = json::Material {
+            alpha_cutoff: AlphaCutoff(material.get_alpha_cutoff()),
+            alpha_mode: Valid(alpha_mode),
+            double_sided: material.is_double_sided(),
+            normal_texture: material.get_normals_texture().map(|info| NormalTexture {
+                index: Index::new(info.get_index() as _),
+                scale: material.get_normal_scale(),
+                tex_coord: info.get_channel(),
+                extensions: None,
+                extras: Default::default(),
+            }),
+            occlusion_texture: material
+                .get_occlusion_texture()
+                .map(|info| OcclusionTexture {
+                    index: Index::new(info.get_index() as _),
+                    strength: StrengthFactor(material.get_occlusion()),
+                    tex_coord: info.get_channel(),
+                    extensions: None,
+                    extras: Default::default(),
+                }),
+            emissive_texture: map_texture_info(material.get_emissive_texture()),
+            emissive_factor: EmissiveFactor(material.get_emissive()),
+            ..Default::default()
+        };
+
+        let mut extensions = json::extensions::material::Material::default();
+        match material.get_workflow() {
+            Workflow::MetallicRoughness(workflow) => {
+                json_material.pbr_metallic_roughness = PbrMetallicRoughness {
+                    base_color_factor: PbrBaseColorFactor(material.get_color()),
+                    base_color_texture: map_texture_info(material.get_color_texture()),
+                    metallic_factor: StrengthFactor(workflow.get_metallic()),
+                    roughness_factor: StrengthFactor(workflow.get_roughness()),
+                    metallic_roughness_texture: map_texture_info(
+                        workflow.get_metallic_roughness_texture(),
+                    ),
+                    extensions: None,
+                    extras: Default::default(),
+                };
+            }
+            Workflow::SpecularGlossiness(workflow) => {
+                self.use_extension("KHR_materials_pbrSpecularGlossiness");
+                extensions.pbr_specular_glossiness = Some(PbrSpecularGlossiness {
+                    diffuse_factor: PbrDiffuseFactor(material.get_color()),
+                    diffuse_texture: map_texture_info(material.get_color_texture()),
+                    specular_factor: PbrSpecularFactor(workflow.get_specular()),
+                    glossiness_factor: StrengthFactor(workflow.get_glossiness()),
+                    specular_glossiness_texture: map_texture_info(
+                        workflow.get_specular_glossiness_texture(),
+                    ),
+                    extras: Default::default(),
+                });
+            }
+        }
+        if material.is_unlit() {
+            self.use_extension("KHR_materials_unlit");
+            extensions.unlit = Some(Unlit {});
+        }
+        if extensions.pbr_specular_glossiness.is_some() || extensions.unlit.is_some() {
+            json_material.extensions = Some(extensions);
+        }
+
+        json_material
+    }
+
+    /// Push a mesh and its primitives.
+    ///
+    /// Returns `None` if the mesh has no primitive since it is not valid glTF.
+    fn push_mesh(
+        &mut self,
+        scene: &Scene,
+        index: usize,
+        mesh: &MeshData,
+        materials: &BTreeMap<usize, Index<json::Material>>,
+    ) -> Option<Index<json::Mesh>> {
+        if mesh.primitives().is_empty() {
+            return None;
+        }
+
+        let geometry = scene.geometry();
+        let primitives = mesh
+            .primitives()
+            .iter()
+            .map(|primitive| {
+                let vertices = primitive.vertices(geometry);
+                let attributes = primitive.attributes();
+                let mut json_attributes = std::collections::HashMap::new();
+
+                let positions = vertices.iter().map(|v| v.position).collect::<Vec<_>>();
+                let bounds = position_bounds(&positions);
+                let accessor = self.push_accessor(&positions, Some(Target::ArrayBuffer), bounds);
+                json_attributes.insert(Valid(Semantic::Positions), accessor);
+
+                if attributes.normals {
+                    let normals = vertices.iter().map(|v| v.normal).collect::<Vec<_>>();
+                    let accessor = self.push_accessor(&normals, Some(Target::ArrayBuffer), None);
+                    json_attributes.insert(Valid(Semantic::Normals), accessor);
+                }
+                if attributes.tangents {
+                    let tangents = vertices.iter().map(|v| v.tangent).collect::<Vec<_>>();
+                    let accessor = self.push_accessor(&tangents, Some(Target::ArrayBuffer), None);
+                    json_attributes.insert(Valid(Semantic::Tangents), accessor);
+                }
+                if attributes.tex_coords_0 {
+                    let coords = vertices.iter().map(|v| v.tex_coords_0).collect::<Vec<_>>();
+                    let accessor = self.push_accessor(&coords, Some(Target::ArrayBuffer), None);
+                    json_attributes.insert(Valid(Semantic::TexCoords(0)), accessor);
+                }
+                if attributes.tex_coords_1 {
+                    let coords = vertices.iter().map(|v| v.tex_coords_1).collect::<Vec<_>>();
+                    let accessor = self.push_accessor(&coords, Some(Target::ArrayBuffer), None);
+                    json_attributes.insert(Valid(Semantic::TexCoords(1)), accessor);
+                }
+                if attributes.colors {
+                    let colors = vertices.iter().map(|v| v.colors).collect::<Vec<_>>();
+                    let accessor = self.push_accessor(&colors, Some(Target::ArrayBuffer), None);
+                    json_attributes.insert(Valid(Semantic::Colors(0)), accessor);
+                }
+                if attributes.skinning {
+                    let joints = vertices
+                        .iter()
+                        .map(|v| {
+                            let [x, y, z, w] = v.joints;
+                            [x as u16, y as u16, z as u16, w as u16]
+                        })
+                        .collect::<Vec<_>>();
+                    let accessor = self.push_accessor(&joints, Some(Target::ArrayBuffer), None);
+                    json_attributes.insert(Valid(Semantic::Joints(0)), accessor);
+
+                    let weights = vertices.iter().map(|v| v.weights).collect::<Vec<_>>();
+                    let accessor = self.push_accessor(&weights, Some(Target::ArrayBuffer), None);
+                    json_attributes.insert(Valid(Semantic::Weights(0)), accessor);
+                }
+
+                let indices = primitive.indices(geometry).map(|indices| {
+                    self.push_accessor(indices, Some(Target::ElementArrayBuffer), None)
+                });
+
+                let targets = primitive
+                    .targets()
+                    .iter()
+                    .map(|target| self.push_morph_target(target))
+                    .collect::<Vec<_>>();
+
+                json::mesh::Primitive {
+                    attributes: json_attributes,
+                    extensions: None,
+                    extras: Default::default(),
+                    indices,
+                    material: primitive
+                        .material()
+                        .get_index()
+                        .and_then(|index| materials.get(&index).copied()),
+                    mode: Valid(primitive.mode().into()),
+                    targets: if targets.is_empty() {
+                        None
+                    } else {
+                        Some(targets)
+                    },
+                }
+            })
+            .collect();
+
+        let mesh = json::Mesh {
+            extensions: None,
+            extras: Default::default(),
+            name: name(&scene.metadata().names().meshes, index),
+            primitives,
+            weights: mesh.weights().map(<[f32]>::to_vec),
+        };
+        Some(push(&mut self.root.meshes, mesh))
+    }
+
+    fn push_morph_target(&mut self, target: &MorphTarget) -> json::mesh::MorphTarget {
+        let positions = Some(&target.positions)
+            .filter(|positions| !positions.is_empty())
+            .map(|positions| {
+                let bounds = position_bounds(positions);
+                self.push_accessor(positions, Some(Target::ArrayBuffer), bounds)
+            });
+        let normals = Some(&target.normals)
+            .filter(|normals| !normals.is_empty())
+            .map(|normals| self.push_accessor(normals, Some(Target::ArrayBuffer), None));
+        let tangents = Some(&target.tangents)
+            .filter(|tangents| !tangents.is_empty())
+            .map(|tangents| self.push_accessor(tangents, Some(Target::ArrayBuffer), None));
+        json::mesh::MorphTarget {
+            positions,
+            normals,
+            tangents,
+        }
+    }
+
+    fn push_skins(&mut self, scene: &Scene) {
+        let names = &scene.metadata().names().skins;
+        for (index, skin) in scene.skins().iter().enumerate() {
+            let inverse_bind_matrices = skin
+                .joints()
+                .iter()
+                .map(|joint| *joint.inverse_bind_matrix().as_ref())
+                .collect::<Vec<[f32; 16]>>();
+            let inverse_bind_matrices = self.push_accessor(&inverse_bind_matrices, None, None);
+
+            self.root.skins.push(json::Skin {
+                extensions: None,
+                extras: Default::default(),
+                inverse_bind_matrices: Some(inverse_bind_matrices),
+                joints: skin
+                    .joints()
+                    .iter()
+                    .map(|joint| Index::new(joint.node_id() as _))
+                    .collect(),
+                name: name(names, index),
+                skeleton: None,
+            });
+        }
+    }
+
+    /// Push the nodes of the scene.
+    ///
+    /// The transforms are pushed without the animations, which are exported separately.
+    /// Nodes that are not visible according to `node_visibility` are pushed without their mesh.
+    fn push_nodes(
+        &mut self,
+        scene: &Scene,
+        meshes: &[Option<Index<json::Mesh>>],
+        node_visibility: &[bool],
+    ) {
+        let names = &scene.metadata().names().nodes;
+        for (index, node) in scene.nodes().nodes().iter().enumerate() {
+            let visible = node_visibility.get(index).copied().unwrap_or(true);
+            let (matrix, translation, rotation, scale) = match node.rest_transform() {
+                Transform::Matrix { matrix } => {
+                    let mut elements = [0.0; 16];
+                    elements.copy_from_slice(&matrix.concat());
+                    (Some(elements), None, None, None)
+                }
+                Transform::Decomposed {
+                    translation,
+                    rotation,
+                    scale,
+                } => (
+                    None,
+                    Some(*translation),
+                    Some(json::scene::UnitQuaternion(*rotation)),
+                    Some(*scale),
+                ),
+            };
+
+            let children = node
+                .children_indices()
+                .iter()
+                .map(|index| Index::new(*index as _))
+                .collect::<Vec<_>>();
+
+            let extensions = node
+                .light_index()
+                .map(|index| json::extensions::scene::Node {
+                    khr_lights_punctual: Some(khr_lights_punctual::KhrLightsPunctual {
+                        light: Index::new(index as _),
+                    }),
+                });
+
+            self.root.nodes.push(json::Node {
+                camera: None,
+                children: if children.is_empty() {
+                    None
+                } else {
+                    Some(children)
+                },
+                extensions,
+                extras: Default::default(),
+                matrix,
+                mesh: node
+                    .mesh_index()
+                    .filter(|_| visible)
+                    .and_then(|index| meshes.get(index).copied().flatten()),
+                name: name(names, index),
+                rotation,
+                scale,
+                translation,
+                skin: node.skin_index().map(|index| Index::new(index as _)),
+                weights: None,
+            });
+        }
+    }
+
+    fn push_lights(&mut self, lights: &[Light], names: &[Option<String>]) {
+        if lights.is_empty() {
+            return;
+        }
+        self.use_extension("KHR_lights_punctual");
+
+        let lights = lights
+            .iter()
+            .enumerate()
+            .map(|(index, light)| {
+                let (type_, spot) = match light.light_type() {
+                    LightType::Directional => (khr_lights_punctual::Type::Directional, None),
+                    LightType::Point => (khr_lights_punctual::Type::Point, None),
+                    LightType::Spot {
+                        inner_cone_angle,
+                        outer_cone_angle,
+                    } => (
+                        khr_lights_punctual::Type::Spot,
+                        Some(khr_lights_punctual::Spot {
+                            inner_cone_angle,
+                            outer_cone_angle,
+                        }),
+                    ),
+                };
+                khr_lights_punctual::Light {
+                    color: light.color(),
+                    extensions: None,
+                    extras: Default::default(),
+                    intensity: light.intensity(),
+                    name: name(names, index),
+                    range: light.range(),
+                    spot,
+                    type_: Valid(type_),
+                }
+            })
+            .collect();
+
+        self.root.extensions = Some(json::extensions::root::Root {
+            khr_lights_punctual: Some(json::extensions::root::KhrLightsPunctual { lights }),
+        });
+    }
+
+    fn push_animations(&mut self, scene: &Scene) {
+        let animations = match scene.animations() {
+            Some(animations) => animations.animations(),
+            None => return,
+        };
+
+        let names = &scene.metadata().names().animations;
+        for (index, animation) in animations.iter().enumerate() {
+            let mut samplers = Vec::new();
+            let mut channels = Vec::new();
+            for channel in animation.translation_channels() {
+                let channel = self.push_channel(&mut samplers, channel, Property::Translation);
+                channels.push(channel);
+            }
+            for channel in animation.rotation_channels() {
+                let channel = self.push_channel(&mut samplers, channel, Property::Rotation);
+                channels.push(channel);
+            }
+            for channel in animation.scale_channels() {
+                let channel = self.push_channel(&mut samplers, channel, Property::Scale);
+                channels.push(channel);
+            }
+
+            self.root.animations.push(json::Animation {
+                extensions: None,
+                extras: Default::default(),
+                channels,
+                name: name(names, index),
+                samplers,
+            });
+        }
+    }
+
+    fn push_channel<T: ToElement>(
+        &mut self,
+        samplers: &mut Vec<json::animation::Sampler>,
+        channel: &Channel<T>,
+        property: Property,
+    ) -> json::animation::Channel {
+        let sampler = channel.sampler();
+        let times = sampler.times();
+        let bounds = match (times.first(), times.last()) {
+            (Some(min), Some(max)) => {
+                Some((json::Value::from(vec![*min]), json::Value::from(vec![*max])))
+            }
+            _ => None,
+        };
+        let input = self.push_accessor(times, None, bounds);
+
+        let values = sampler
+            .values()
+            .iter()
+            .map(ToElement::to_element)
+            .collect::<Vec<_>>();
+        let output = self.push_accessor(&values, None, None);
+
+        let interpolation = match sampler.interpolation() {
+            Interpolation::Linear => json::animation::Interpolation::Linear,
+            Interpolation::Step => json::animation::Interpolation::Step,
+            Interpolation::CubicSpline => json::animation::Interpolation::CubicSpline,
+        };
+        let sampler = push(
+            samplers,
+            json::animation::Sampler {
+                extensions: None,
+                extras: Default::default(),
+                input,
+                interpolation: Valid(interpolation),
+                output,
+            },
+        );
+
+        json::animation::Channel {
+            sampler,
+            target: json::animation::Target {
+                extensions: None,
+                extras: Default::default(),
+                node: Index::new(channel.node_index() as _),
+                path: Valid(property),
+            },
+            extensions: None,
+            extras: Default::default(),
+        }
+    }
+}
+
+/// Get the name at `index` in `names` if any.
+fn name(names: &[Option<String>], index: usize) -> Option<String> {
+    names.get(index).cloned().flatten()
+}
+
+pub(crate) fn push<T>(items: &mut Vec<T>, item: T) -> Index<T> {
+    items.push(item);
+    Index::new((items.len() - 1) as _)
+}
+
+fn map_texture_info(info: Option<TextureInfo>) -> Option<json::texture::Info> {
+    info.map(|info| json::texture::Info {
+        index: Index::new(info.get_index() as _),
+        tex_coord: info.get_channel(),
+        extensions: None,
+        extras: Default::default(),
+    })
+}
+
+pub(crate) fn position_bounds(positions: &[[f32; 3]]) -> Option<(json::Value, json::Value)> {
+    let first = *positions.first()?;
+    let (min, max) = positions
+        .iter()
+        .fold((first, first), |(mut min, mut max), p| {
+            for i in 0..3 {
+                min[i] = min[i].min(p[i]);
+                max[i] = max[i].max(p[i]);
+            }
+            (min, max)
+        });
+    Some((
+        json::Value::from(min.to_vec()),
+        json::Value::from(max.to_vec()),
+    ))
+}
+
+fn encode_png(image: &RgbaImage) -> Result<Vec<u8>, ModelExportError> {
+    let buffer = ImageBuffer::from_raw(image.width, image.height, image.pixels.clone())
+        .ok_or_else(|| ModelExportError::Encoding(String::from("Invalid image size")))?;
+    let mut encoded_image = Vec::new();
+    DynamicImage::ImageRgba8(buffer).write_to(&mut encoded_image, ImageOutputFormat::Png)?;
+    Ok(encoded_image)
+}
+
+/// Element of an accessor.
+pub(crate) trait Element {
+    const COMPONENT_TYPE: ComponentType;
+    const TYPE: Type;
+
+    /// Write the element in little endian.
+    fn write(&self, buffer: &mut Vec<u8>);
+}
+
+impl Element for f32 {
+    const COMPONENT_TYPE: ComponentType = ComponentType::F32;
+    const TYPE: Type = Type::Scalar;
+
+    fn write(&self, buffer: &mut Vec<u8>) {
+        buffer.extend_from_slice(&self.to_le_bytes());
+    }
+}
+
+impl Element for u32 {
+    const COMPONENT_TYPE: ComponentType = ComponentType::U32;
+    const TYPE: Type = Type::Scalar;
+
+    fn write(&self, buffer: &mut Vec<u8>) {
+        buffer.extend_from_slice(&self.to_le_bytes());
+    }
+}
+
+macro_rules! impl_array_element {
+    ($component:ty, $size:expr, $component_type:expr, $type:expr) => {
+        impl Element for [$component; $size] {
+            const COMPONENT_TYPE: ComponentType = $component_type;
+            const TYPE: Type = $type;
+
+            fn write(&self, buffer: &mut Vec<u8>) {
+                self.iter()
+                    .for_each(|c| buffer.extend_from_slice(&c.to_le_bytes()));
+            }
+        }
+    };
+}
+
+impl_array_element!(f32, 2, ComponentType::F32, Type::Vec2);
+impl_array_element!(f32, 3, ComponentType::F32, Type::Vec3);
+impl_array_element!(f32, 4, ComponentType::F32, Type::Vec4);
+impl_array_element!(u16, 4, ComponentType::U16, Type::Vec4);
+impl_array_element!(f32, 16, ComponentType::F32, Type::Mat4);
+
+/// Animation values that can be written in an accessor.
+trait ToElement {
+    type Element: Element;
+
+    fn to_element(&self) -> Self::Element;
+}
+
+impl ToElement for Vector3<f32> {
+    type Element = [f32; 3];
+
+    fn to_element(&self) -> Self::Element {
+        [self.x, self.y, self.z]
+    }
+}
+
+impl ToElement for Quaternion<f32> {
+    type Element = [f32; 4];
+
+    fn to_element(&self) -> Self::Element {
+        [self.v.x, self.v.y, self.v.z, self.s]
+    }
+}
@@ -0,0 +1,201 @@
+//! In-memory glTF import.
+//!
+//! `gltf::import` only works with files on disk. This module allows
+//! importing a glTF/glb from a byte slice or a reader. Embedded data URIs
+//! are decoded and other URIs are delegated to a user-provided resolver.
+
+use super::error::ModelLoadingError;
+use gltf::{
+    buffer::{Data as BufferData, Source as BufferSource},
+    image::{Data as ImageData, Format, Source as ImageSource},
+    Document, Gltf,
+};
+use image::{DynamicImage, GenericImageView, ImageFormat};
+use rayon::prelude::*;
+use std::{
+    borrow::Cow,
+    error::Error,
+    fs,
+    io::Read,
+    path::{Path, PathBuf},
+};
+
+type Result<T> = std::result::Result<T, ModelLoadingError>;
+
+pub(crate) type Import = (Document, Vec<BufferData>, Vec<ImageData>);
+
+/// Resolve the content of an external resource from its uri.
+///
+/// The uri is passed as it appears in the glTF document.
+pub type ResourceResolver<'a> =
+    dyn FnMut(&str) -> std::result::Result<Vec<u8>, Box<dyn Error>> + 'a;
+
+/// Import a glTF from a slice without support for external resources.
+pub(crate) fn import_slice(slice: &[u8]) -> Result<Import> {
+    import_slice_with_resolver(slice, &mut no_external_resource)
+}
+
+/// Import a glTF from a reader without support for external resources.
+pub(crate) fn import_reader<R: Read>(mut reader: R) -> Result<Import> {
+    let mut bytes = Vec::new();
+    reader.read_to_end(&mut bytes)?;
+    import_slice(&bytes)
+}
+
+/// Import a glTF from a slice and resolve external resources with `resolver`.
+pub(crate) fn import_slice_with_resolver(
+    slice: &[u8],
+    resolver: &mut ResourceResolver,
+) -> Result<Import> {
+    let Gltf { document, blob } = Gltf::from_slice(slice)?;
+    let buffers = import_buffers(&document, blob, resolver)?;
+    let images = import_images(&document, &buffers, resolver)?;
+    Ok((document, buffers, images))
+}
+
+/// Resolve the external resources of a glTF file relative to its `base` directory.
+pub(crate) fn file_resolver(
+    base: &Path,
+) -> impl FnMut(&str) -> std::result::Result<Vec<u8>, Box<dyn Error>> + '_ {
+    move |uri| {
+        let path = match uri.strip_prefix("file://") {
+            Some(path) => PathBuf::from(path),
+            None => base.join(uri),
+        };
+        Ok(fs::read(path)?)
+    }
+}
+
+fn no_external_resource(_uri: &str) -> std::result::Result<Vec<u8>, Box<dyn Error>> {
+    Err("no resolver for external resources".into())
+}
+
+pub(crate) fn import_buffers(
+    document: &Document,
+    mut blob: Option<Vec<u8>>,
+    resolver: &mut ResourceResolver,
+) -> Result<Vec<BufferData>> {
+    let mut buffers = Vec::new();
+    for buffer in document.buffers() {
+        let mut data = match buffer.source() {
+            BufferSource::Bin => blob
+                .take()
+                .ok_or_else(|| ModelLoadingError::MissingResource(String::from("binary chunk")))?,
+            BufferSource::Uri(uri) => read_uri(uri, resolver)?,
+        };
+        if data.len() < buffer.length() {
+            return Err(ModelLoadingError::Parse(format!(
+                "Buffer {} is {} bytes long but {} were expected",
+                buffer.index(),
+                data.len(),
+                buffer.length()
+            )));
+        }
+        while data.len() % 4 != 0 {
+            data.push(0);
+        }
+        buffers.push(BufferData(data));
+    }
+    Ok(buffers)
+}
+
+pub(crate) fn import_images(
+    document: &Document,
+    buffers: &[BufferData],
+    resolver: &mut ResourceResolver,
+) -> Result<Vec<ImageData>> {
+    // The resolver can't be shared between threads so the encoded images are read first
+    let encoded_images = document
+        .images()
+        .map(|image| match image.source() {
+            ImageSource::View { view, mime_type } => {
+                let begin = view.offset();
+                let end = begin + view.length();
+                let data = buffers
+                    .get(view.buffer().index())
+                    .and_then(|buffer| buffer.0.get(begin..end))
+                    .ok_or_else(|| {
+                        ModelLoadingError::InvalidAccessor(format!(
+                            "Buffer view {} of image {} is out of the bounds of buffer {}",
+                            view.index(),
+                            image.index(),
+                            view.buffer().index()
+                        ))
+                    })?;
+                Ok((Cow::Borrowed(data), Some(mime_type)))
+            }
+            ImageSource::Uri { uri, mime_type } => {
+                let mime_type = mime_type.or_else(|| parse_data_uri(uri).and_then(|(m, _)| m));
+                Ok((Cow::Owned(read_uri(uri, resolver)?), mime_type))
+            }
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    encoded_images
+        .par_iter()
+        .map(|(encoded_image, mime_type)| {
+            let decoded_image = match mime_type {
+                Some("image/png") => {
+                    image::load_from_memory_with_format(encoded_image, ImageFormat::Png)?
+                }
+                Some("image/jpeg") => {
+                    image::load_from_memory_with_format(encoded_image, ImageFormat::Jpeg)?
+                }
+                _ => image::load_from_memory(encoded_image)?,
+            };
+            Ok(to_image_data(decoded_image))
+        })
+        .collect()
+}
+
+/// Read the content of `uri`.
+///
+/// Data uris are decoded, any other uri is passed to `resolver`.
+fn read_uri(uri: &str, resolver: &mut ResourceResolver) -> Result<Vec<u8>> {
+    match parse_data_uri(uri) {
+        Some((_, data)) => Ok(base64::decode(data)?),
+        None => resolver(uri)
+            .map_err(|error| ModelLoadingError::MissingResource(format!("{} ({})", uri, error))),
+    }
+}
+
+/// Split a `data:[<media type>];base64,<data>` uri into its media type and data.
+fn parse_data_uri(uri: &str) -> Option<(Option<&str>, &str)> {
+    if !uri.starts_with("data:") {
+        return None;
+    }
+    let uri = &uri["data:".len()..];
+    let separator = ";base64,";
+    uri.find(separator).map(|index| {
+        let mime_type = &uri[..index];
+        let mime_type = if mime_type.is_empty() {
+            None
+        } else {
+            Some(mime_type)
+        };
+        (mime_type, &uri[index + separator.len()..])
+    })
+}
+
+pub(crate) fn to_image_data(image: DynamicImage) -> ImageData {
+    let (width, height) = image.dimensions();
+    // Take the 8 bits buffers instead of copying them
+    let (format, pixels) = match image {
+        DynamicImage::ImageLuma8(image) => (Format::R8, image.into_raw()),
+        DynamicImage::ImageLumaA8(image) => (Format::R8G8, image.into_raw()),
+        DynamicImage::ImageRgb8(image) => (Format::R8G8B8, image.into_raw()),
+        DynamicImage::ImageRgba8(image) => (Format::R8G8B8A8, image.into_raw()),
+        DynamicImage::ImageBgr8(image) => (Format::B8G8R8, image.into_raw()),
+        DynamicImage::ImageBgra8(image) => (Format::B8G8R8A8, image.into_raw()),
+        DynamicImage::ImageLuma16(_) => (Format::R16, image.to_bytes()),
+        DynamicImage::ImageLumaA16(_) => (Format::R16G16, image.to_bytes()),
+        DynamicImage::ImageRgb16(_) => (Format::R16G16B16, image.to_bytes()),
+        DynamicImage::ImageRgba16(_) => (Format::R16G16B16A16, image.to_bytes()),
+    };
+    ImageData {
+        pixels,
+        format,
+        width,
+        height,
+    }
+}
@@ -1,41 +1,70 @@
 mod animation;
+mod bounds;
 mod error;
+mod export;
+mod import;
 mod light;
 mod material;
 mod mesh;
 pub mod metadata;
 mod mikktspace;
 mod node;
+mod obj;
+mod picking;
+mod ply;
+mod progress;
+mod scan;
+mod scene;
 mod skin;
+mod statistics;
 mod texture;
+mod validation;
 mod vertex;
+mod vertex_frames;
 
 use self::mikktspace::generate_tangents;
 pub use self::{
-    animation::*, error::*, light::*, material::*, mesh::*, node::*, skin::*, texture::*, vertex::*,
+    animation::*,
+    bounds::NodeBounds,
+    error::*,
+    export::ExportFormat,
+    import::ResourceResolver,
+    light::*,
+    material::*,
+    mesh::*,
+    node::*,
+    picking::Hit,
+    progress::{LoadingStage, ProgressCallback},
+    scene::*,
+    skin::*,
+    texture::*,
+    vertex::*,
+    vertex_frames::VertexFrame,
 };
-use cgmath::Matrix4;
-use math::*;
+use math::cgmath::{Matrix4, Quaternion, Vector3};
 use metadata::Metadata;
-use std::{error::Error, path::Path, result::Result, sync::Arc};
+use std::{io::Read, path::Path, result::Result, sync::Arc};
 use vulkan::ash::vk;
-use vulkan::{Buffer, Context, PreLoadedResource};
+use vulkan::{Buffer, Context, PreLoadedResource, ThreadCommandBuffer};
+
+/// glTF extensions the viewer knows how to handle.
+pub(crate) const SUPPORTED_EXTENSIONS: [&str; 3] = [
+    "KHR_lights_punctual",
+    "KHR_materials_unlit",
+    "KHR_materials_pbrSpecularGlossiness",
+];
 
 pub struct ModelStagingResources {
     _staged_vertices: Buffer,
     _staged_indices: Option<Buffer>,
     _staged_textures: Vec<Buffer>,
+    _texture_command_buffers: Vec<ThreadCommandBuffer>,
 }
 
 pub struct Model {
-    metadata: Metadata,
+    scene: Scene,
     meshes: Vec<Mesh>,
-    nodes: Nodes,
-    global_transform: Matrix4<f32>,
-    animations: Option<Animations>,
-    skins: Vec<Skin>,
     textures: Textures,
-    lights: Vec<Light>,
 }
 
 impl Model {
@@ -43,156 +72,194 @@ impl Model {
         context: Arc<Context>,
         command_buffer: vk::CommandBuffer,
         path: P,
-    ) -> Result<PreLoadedResource<Model, ModelStagingResources>, Box<dyn Error>> {
-        log::debug!("Importing gltf file");
-        let (document, buffers, images) = gltf::import(&path)?;
+    ) -> Result<PreLoadedResource<Model, ModelStagingResources>, ModelLoadingError> {
+        let scene = Scene::from_file(path)?;
+        Ok(Self::create_from_scene(context, command_buffer, scene))
+    }
 
-        let metadata = Metadata::new(path, &document);
+    pub fn create_from_obj_file<P: AsRef<Path>>(
+        context: Arc<Context>,
+        command_buffer: vk::CommandBuffer,
+        path: P,
+    ) -> Result<PreLoadedResource<Model, ModelStagingResources>, ModelLoadingError> {
+        let scene = Scene::from_obj_file(path)?;
+        Ok(Self::create_from_scene(context, command_buffer, scene))
+    }
 
-        log::debug!("Creating the model");
-        if document.scenes().len() == 0 {
-            return Err(Box::new(ModelLoadingError::new("There is no scene")));
-        }
+    pub fn create_from_stl_file<P: AsRef<Path>>(
+        context: Arc<Context>,
+        command_buffer: vk::CommandBuffer,
+        path: P,
+    ) -> Result<PreLoadedResource<Model, ModelStagingResources>, ModelLoadingError> {
+        let scene = Scene::from_stl_file(path)?;
+        Ok(Self::create_from_scene(context, command_buffer, scene))
+    }
 
-        let meshes = create_meshes_from_gltf(&context, command_buffer, &document, &buffers);
-        if meshes.is_none() {
-            return Err(Box::new(ModelLoadingError::new(
-                "Could not find any renderable primitives",
-            )));
-        }
+    pub fn create_from_ply_file<P: AsRef<Path>>(
+        context: Arc<Context>,
+        command_buffer: vk::CommandBuffer,
+        path: P,
+    ) -> Result<PreLoadedResource<Model, ModelStagingResources>, ModelLoadingError> {
+        let scene = Scene::from_ply_file(path)?;
+        Ok(Self::create_from_scene(context, command_buffer, scene))
+    }
+
+    /// Create a model from a glb or a self-contained glTF in memory.
+    ///
+    /// Embedded data uris are supported but external resources are not.
+    /// Use `create_from_slice_with_resolver` for those.
+    pub fn create_from_slice(
+        context: Arc<Context>,
+        command_buffer: vk::CommandBuffer,
+        name: &str,
+        slice: &[u8],
+    ) -> Result<PreLoadedResource<Model, ModelStagingResources>, ModelLoadingError> {
+        let scene = Scene::from_slice(name, slice)?;
+        Ok(Self::create_from_scene(context, command_buffer, scene))
+    }
+
+    /// Create a model from a glb or a self-contained glTF read from `reader`.
+    pub fn create_from_reader<R: Read>(
+        context: Arc<Context>,
+        command_buffer: vk::CommandBuffer,
+        name: &str,
+        reader: R,
+    ) -> Result<PreLoadedResource<Model, ModelStagingResources>, ModelLoadingError> {
+        let scene = Scene::from_reader(name, reader)?;
+        Ok(Self::create_from_scene(context, command_buffer, scene))
+    }
+
+    /// Create a model from a glTF in memory.
+    ///
+    /// External buffers and images are loaded by calling `resolver` with their uri.
+    pub fn create_from_slice_with_resolver(
+        context: Arc<Context>,
+        command_buffer: vk::CommandBuffer,
+        name: &str,
+        slice: &[u8],
+        resolver: &mut ResourceResolver,
+    ) -> Result<PreLoadedResource<Model, ModelStagingResources>, ModelLoadingError> {
+        let scene = Scene::from_slice_with_resolver(name, slice, resolver)?;
+        Ok(Self::create_from_scene(context, command_buffer, scene))
+    }
 
+    /// Upload `scene` on the GPU.
+    ///
+    /// The upload commands are recorded in `command_buffer`.
+    pub fn create_from_scene(
+        context: Arc<Context>,
+        command_buffer: vk::CommandBuffer,
+        scene: Scene,
+    ) -> PreLoadedResource<Model, ModelStagingResources> {
+        log::debug!("Creating the model");
         let Meshes {
             meshes,
             vertices: staged_vertices,
             indices: staged_indices,
-        } = meshes.unwrap();
-
-        let scene = document
-            .default_scene()
-            .unwrap_or_else(|| document.scenes().next().unwrap());
-
-        let animations = load_animations(document.animations(), &buffers);
-
-        let mut skins = create_skins_from_gltf(document.skins(), &buffers);
-
-        let mut nodes = Nodes::from_gltf_nodes(document.nodes(), &scene);
-
-        let global_transform = {
-            let aabb = compute_aabb(&nodes, &meshes);
-            let transform = compute_unit_cube_at_origin_transform(aabb);
-            nodes.transform(Some(transform));
-            nodes
-                .get_skins_transform()
-                .iter()
-                .for_each(|(index, transform)| {
-                    let skin = &mut skins[*index];
-                    skin.compute_joints_matrices(*transform, &nodes.nodes());
-                });
-            transform
-        };
+        } = create_meshes(&context, command_buffer, scene.geometry());
 
-        let (textures, staged_textures) = texture::create_textures_from_gltf(
-            &context,
-            command_buffer,
-            document.textures(),
-            &images,
-        );
+        let (textures, staged_textures, texture_command_buffers) =
+            create_textures(&context, scene.images(), scene.textures());
 
-        let lights = create_lights_from_gltf(&document);
+        let thread_command_buffers = texture_command_buffers
+            .iter()
+            .map(ThreadCommandBuffer::command_buffer)
+            .collect();
 
         let model = Model {
-            metadata,
+            scene,
             meshes,
-            nodes,
-            global_transform,
-            animations,
-            skins,
             textures,
-            lights,
         };
 
         let model_staging_res = ModelStagingResources {
             _staged_vertices: staged_vertices,
             _staged_indices: staged_indices,
             _staged_textures: staged_textures,
+            _texture_command_buffers: texture_command_buffers,
         };
 
-        Ok(PreLoadedResource::new(
-            context,
-            command_buffer,
-            model,
-            model_staging_res,
-        ))
+        PreLoadedResource::new(context, command_buffer, model, model_staging_res)
+            .with_thread_command_buffers(thread_command_buffers)
     }
 }
 
 impl Model {
     pub fn update(&mut self, delta_time: f32) -> bool {
-        let updated = if let Some(animations) = self.animations.as_mut() {
-            animations.update(&mut self.nodes, delta_time)
-        } else {
-            false
-        };
+        self.scene.update(delta_time)
+    }
+
+    pub fn set_placement(&mut self, placement: Placement) {
+        self.scene.set_placement(placement);
+    }
 
-        if updated {
-            self.nodes.transform(Some(self.global_transform));
-            self.nodes
-                .get_skins_transform()
-                .iter()
-                .for_each(|(index, transform)| {
-                    let skin = &mut self.skins[*index];
-                    skin.compute_joints_matrices(*transform, &self.nodes.nodes());
-                });
-        }
+    pub fn set_node_transform(
+        &mut self,
+        index: usize,
+        translation: Vector3<f32>,
+        rotation: Quaternion<f32>,
+        scale: Vector3<f32>,
+    ) {
+        self.scene
+            .set_node_transform(index, translation, rotation, scale);
+    }
+
+    pub fn reset_node_transform(&mut self, index: usize) {
+        self.scene.reset_node_transform(index);
+    }
 
-        updated
+    pub fn set_transform(&mut self, transform: Matrix4<f32>) {
+        self.scene.set_transform(transform);
+    }
+
+    /// Replace the material of all the primitives using the material with the same index.
+    pub fn set_material(&mut self, material: Material) {
+        self.scene.set_material(material);
+        self.meshes
+            .iter_mut()
+            .for_each(|mesh| mesh.set_material(material));
     }
 }
 
 /// Animations methods
 impl Model {
     pub fn get_animation_playback_state(&self) -> Option<PlaybackState> {
-        self.animations
-            .as_ref()
-            .map(Animations::get_playback_state)
-            .copied()
+        self.scene.get_animation_playback_state()
     }
 
     pub fn set_current_animation(&mut self, animation_index: usize) {
-        if let Some(animations) = self.animations.as_mut() {
-            animations.set_current(animation_index);
-        }
+        self.scene.set_current_animation(animation_index);
     }
 
     pub fn set_animation_playback_mode(&mut self, playback_mode: PlaybackMode) {
-        if let Some(animations) = self.animations.as_mut() {
-            animations.set_playback_mode(playback_mode);
-        }
+        self.scene.set_animation_playback_mode(playback_mode);
     }
 
     pub fn toggle_animation(&mut self) {
-        if let Some(animations) = self.animations.as_mut() {
-            animations.toggle();
-        }
+        self.scene.toggle_animation();
     }
 
     pub fn stop_animation(&mut self) {
-        if let Some(animations) = self.animations.as_mut() {
-            animations.stop();
-        }
+        self.scene.stop_animation();
     }
 
     pub fn reset_animation(&mut self) {
-        if let Some(animations) = self.animations.as_mut() {
-            animations.reset();
-        }
+        self.scene.reset_animation();
+    }
+
+    pub fn set_animation_playback_state(&mut self, playback_state: PlaybackState) {
+        self.scene.set_animation_playback_state(playback_state);
     }
 }
 
 /// Getters
 impl Model {
+    pub fn scene(&self) -> &Scene {
+        &self.scene
+    }
+
     pub fn metadata(&self) -> &Metadata {
-        &self.metadata
+        self.scene.metadata()
     }
 
     pub fn meshes(&self) -> &[Mesh] {
@@ -208,11 +275,11 @@ impl Model {
     }
 
     pub fn skins(&self) -> &[Skin] {
-        &self.skins
+        self.scene.skins()
     }
 
     pub fn nodes(&self) -> &Nodes {
-        &self.nodes
+        self.scene.nodes()
     }
 
     pub fn textures(&self) -> &[Texture] {
@@ -220,31 +287,6 @@ impl Model {
     }
 
     pub fn lights(&self) -> &[Light] {
-        &self.lights
+        self.scene.lights()
     }
 }
-
-fn compute_aabb(nodes: &Nodes, meshes: &[Mesh]) -> AABB<f32> {
-    let aabbs = nodes
-        .nodes()
-        .iter()
-        .filter(|n| n.mesh_index().is_some())
-        .map(|n| {
-            let mesh = &meshes[n.mesh_index().unwrap()];
-            mesh.aabb() * n.transform()
-        })
-        .collect::<Vec<_>>();
-    AABB::union(&aabbs).unwrap()
-}
-
-fn compute_unit_cube_at_origin_transform(aabb: AABB<f32>) -> Matrix4<f32> {
-    let larger_side = aabb.get_larger_side_size();
-    let scale_factor = (1.0_f32 / larger_side) * 10.0;
-
-    let aabb = aabb * scale_factor;
-    let center = aabb.get_center();
-
-    let translation = Matrix4::from_translation(-center);
-    let scale = Matrix4::from_scale(scale_factor);
-    translation * scale
-}
@@ -3,15 +3,18 @@ use gltf::{
     texture::Info,
 };
 
-const ALPHA_MODE_OPAQUE: u32 = 0;
-const ALPHA_MODE_MASK: u32 = 1;
-const ALPHA_MODE_BLEND: u32 = 2;
+pub const ALPHA_MODE_OPAQUE: u32 = 0;
+pub const ALPHA_MODE_MASK: u32 = 1;
+pub const ALPHA_MODE_BLEND: u32 = 2;
+const TEXTURE_SLOT_COUNT: usize = 5;
 
 #[derive(Clone, Copy, Debug)]
 pub struct Material {
+    index: Option<usize>,
     color: [f32; 4],
     emissive: [f32; 3],
     occlusion: f32,
+    normal_scale: f32,
     color_texture: Option<TextureInfo>,
     emissive_texture: Option<TextureInfo>,
     normals_texture: Option<TextureInfo>,
@@ -21,6 +24,28 @@ pub struct Material {
     alpha_cutoff: f32,
     double_sided: bool,
     is_unlit: bool,
+    disabled_textures: [bool; TEXTURE_SLOT_COUNT],
+}
+
+/// Texture slots of a material.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TextureSlot {
+    Color,
+    /// Metallic/roughness or specular/glossiness texture depending on the workflow.
+    Material,
+    Emissive,
+    Normals,
+    Occlusion,
+}
+
+impl TextureSlot {
+    pub const ALL: [TextureSlot; TEXTURE_SLOT_COUNT] = [
+        TextureSlot::Color,
+        TextureSlot::Material,
+        TextureSlot::Emissive,
+        TextureSlot::Normals,
+        TextureSlot::Occlusion,
+    ];
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -58,6 +83,14 @@ impl MetallicRoughnessWorkflow {
     pub fn get_metallic_roughness_texture_index(&self) -> Option<usize> {
         self.metallic_roughness_texture.map(|info| info.index)
     }
+
+    pub fn set_metallic(&mut self, metallic: f32) {
+        self.metallic = metallic;
+    }
+
+    pub fn set_roughness(&mut self, roughness: f32) {
+        self.roughness = roughness;
+    }
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -83,9 +116,22 @@ impl SpecularGlossinessWorkflow {
     pub fn get_specular_glossiness_texture_index(&self) -> Option<usize> {
         self.specular_glossiness_texture.map(|info| info.index)
     }
+
+    pub fn set_specular(&mut self, specular: [f32; 3]) {
+        self.specular = specular;
+    }
+
+    pub fn set_glossiness(&mut self, glossiness: f32) {
+        self.glossiness = glossiness;
+    }
 }
 
 impl Material {
+    /// Index of the material in the source document or `None` for the default material.
+    pub fn get_index(&self) -> Option<usize> {
+        self.index
+    }
+
     pub fn get_color(&self) -> [f32; 4] {
         self.color
     }
@@ -98,6 +144,13 @@ impl Material {
         self.occlusion
     }
 
+    /// Scale of the normals read from the normal texture.
+    ///
+    /// It is not used for rendering but kept so it can be exported.
+    pub fn get_normal_scale(&self) -> f32 {
+        self.normal_scale
+    }
+
     pub fn get_alpha_mode(&self) -> u32 {
         self.alpha_mode
     }
@@ -153,6 +206,69 @@ impl Material {
     pub fn get_workflow(&self) -> Workflow {
         self.workflow
     }
+
+    /// Get the texture of `slot` or `None` if there is none or if it is disabled.
+    pub fn get_enabled_texture(&self, slot: TextureSlot) -> Option<TextureInfo> {
+        self.get_texture(slot)
+            .filter(|_| self.is_texture_enabled(slot))
+    }
+
+    /// Get the texture of `slot`, enabled or not.
+    pub fn get_texture(&self, slot: TextureSlot) -> Option<TextureInfo> {
+        match slot {
+            TextureSlot::Color => self.color_texture,
+            TextureSlot::Material => match self.workflow {
+                Workflow::MetallicRoughness(workflow) => workflow.metallic_roughness_texture,
+                Workflow::SpecularGlossiness(workflow) => workflow.specular_glossiness_texture,
+            },
+            TextureSlot::Emissive => self.emissive_texture,
+            TextureSlot::Normals => self.normals_texture,
+            TextureSlot::Occlusion => self.occlusion_texture,
+        }
+    }
+
+    pub fn is_texture_enabled(&self, slot: TextureSlot) -> bool {
+        !self.disabled_textures[slot as usize]
+    }
+}
+
+/// Setters
+///
+/// Textures are only referenced by the material so disabling a texture
+/// keeps it bound but ignored by the shaders.
+impl Material {
+    pub fn set_color(&mut self, color: [f32; 4]) {
+        self.color = color;
+    }
+
+    pub fn set_emissive(&mut self, emissive: [f32; 3]) {
+        self.emissive = emissive;
+    }
+
+    /// Set the alpha mode, one of `ALPHA_MODE_OPAQUE`, `ALPHA_MODE_MASK` or `ALPHA_MODE_BLEND`.
+    pub fn set_alpha_mode(&mut self, alpha_mode: u32) {
+        self.alpha_mode = alpha_mode;
+    }
+
+    pub fn set_alpha_cutoff(&mut self, alpha_cutoff: f32) {
+        self.alpha_cutoff = alpha_cutoff;
+    }
+
+    pub fn set_double_sided(&mut self, double_sided: bool) {
+        self.double_sided = double_sided;
+    }
+
+    pub fn set_unlit(&mut self, unlit: bool) {
+        self.is_unlit = unlit;
+    }
+
+    pub fn set_workflow(&mut self, workflow: Workflow) {
+        self.workflow = workflow;
+    }
+
+    pub fn set_texture_enabled(&mut self, slot: TextureSlot, enabled: bool) {
+        self.disabled_textures[slot as usize] = !enabled;
+    }
 }
 
 impl TextureInfo {
@@ -180,7 +296,7 @@ impl<'a> From<GltfMaterial<'a>> for Material {
         };
         let color_texture = get_texture(color_texture);
         let emissive_texture = get_texture(material.emissive_texture());
-        let normals_texture = get_normals_texture(material.normal_texture());
+        let (normal_scale, normals_texture) = get_normals_texture(material.normal_texture());
         let (occlusion, occlusion_texture) = get_occlusion(material.occlusion_texture());
 
         let workflow = match material.pbr_specular_glossiness() {
@@ -207,9 +323,11 @@ impl<'a> From<GltfMaterial<'a>> for Material {
         let is_unlit = material.unlit();
 
         Material {
+            index: material.index(),
             color,
             emissive,
             occlusion,
+            normal_scale,
             color_texture,
             emissive_texture,
             normals_texture,
@@ -219,6 +337,7 @@ impl<'a> From<GltfMaterial<'a>> for Material {
             alpha_cutoff,
             double_sided,
             is_unlit,
+            disabled_textures: [false; TEXTURE_SLOT_COUNT],
         }
     }
 }
@@ -230,11 +349,17 @@ fn get_texture(texture_info: Option<Info>) -> Option<TextureInfo> {
     })
 }
 
-fn get_normals_texture(texture_info: Option<NormalTexture>) -> Option<TextureInfo> {
-    texture_info.map(|tex_info| TextureInfo {
+fn get_normals_texture(texture_info: Option<NormalTexture>) -> (f32, Option<TextureInfo>) {
+    let scale = texture_info
+        .as_ref()
+        .map_or(1.0, |tex_info| tex_info.scale());
+
+    let texture = texture_info.map(|tex_info| TextureInfo {
         index: tex_info.texture().index(),
         channel: tex_info.tex_coord(),
-    })
+    });
+
+    (scale, texture)
 }
 
 fn get_occlusion(texture_info: Option<OcclusionTexture>) -> (f32, Option<TextureInfo>) {
@@ -1,4 +1,9 @@
-use super::{generate_tangents, IndexBuffer, Material, ModelVertex, VertexBuffer};
+use super::{
+    generate_tangents,
+    metadata::PrimitiveMode,
+    progress::{enter_stage, LoadingStage, ProgressCallback},
+    IndexBuffer, Material, ModelLoadingError, ModelVertex, VertexBuffer,
+};
 use ash::vk;
 use cgmath::Vector3;
 use gltf::{
@@ -7,20 +12,13 @@ use gltf::{
     Document,
 };
 use math::*;
+use rayon::prelude::*;
 use std::{mem::size_of, sync::Arc};
 use vulkan::*;
 
 pub struct Mesh {
     primitives: Vec<Primitive>,
-    aabb: AABB<f32>,
-}
-
-impl Mesh {
-    fn new(primitives: Vec<Primitive>) -> Self {
-        let aabbs = primitives.iter().map(|p| p.aabb()).collect::<Vec<_>>();
-        let aabb = AABB::union(&aabbs).unwrap();
-        Mesh { primitives, aabb }
-    }
+    aabb: Option<AABB<f32>>,
 }
 
 impl Mesh {
@@ -32,15 +30,25 @@ impl Mesh {
         self.primitives.len()
     }
 
-    pub fn aabb(&self) -> AABB<f32> {
+    /// Get the AABB of the mesh or `None` if it has no renderable primitive.
+    pub fn aabb(&self) -> Option<AABB<f32>> {
         self.aabb
     }
+
+    /// Replace the material of the primitives using a material with the same index.
+    pub(crate) fn set_material(&mut self, material: Material) {
+        self.primitives
+            .iter_mut()
+            .filter(|p| p.material.get_index() == material.get_index())
+            .for_each(|p| p.material = material);
+    }
 }
 
 pub struct Primitive {
     index: usize,
     vertices: VertexBuffer,
     indices: Option<IndexBuffer>,
+    mode: PrimitiveMode,
     material: Material,
     aabb: AABB<f32>,
 }
@@ -58,6 +66,10 @@ impl Primitive {
         &self.indices
     }
 
+    pub fn mode(&self) -> PrimitiveMode {
+        self.mode
+    }
+
     pub fn material(&self) -> Material {
         self.material
     }
@@ -73,12 +85,155 @@ type VertexBufferPart = (usize, usize);
 /// Index buffer byte offset / element count
 type IndexBufferPart = (usize, usize);
 
-struct PrimitiveData {
+/// CPU-side geometry of all the meshes of a document.
+///
+/// The vertices and indices of all primitives are packed in a single
+/// vertex and index array that are uploaded as is on the GPU.
+#[derive(Clone, Debug)]
+pub struct Geometry {
+    meshes: Vec<MeshData>,
+    vertices: Vec<ModelVertex>,
+    indices: Vec<u32>,
+}
+
+impl Geometry {
+    pub fn meshes(&self) -> &[MeshData] {
+        &self.meshes
+    }
+
+    pub fn vertices(&self) -> &[ModelVertex] {
+        &self.vertices
+    }
+
+    pub fn indices(&self) -> &[u32] {
+        &self.indices
+    }
+
+    /// Replace the material of the primitives using a material with the same index.
+    pub(crate) fn set_material(&mut self, material: Material) {
+        self.meshes
+            .iter_mut()
+            .flat_map(|mesh| mesh.primitives.iter_mut())
+            .filter(|p| p.material.get_index() == material.get_index())
+            .for_each(|p| p.material = material);
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct MeshData {
+    primitives: Vec<PrimitiveData>,
+    aabb: Option<AABB<f32>>,
+    /// Default weights of the morph targets.
+    weights: Option<Vec<f32>>,
+}
+
+impl MeshData {
+    fn new(primitives: Vec<PrimitiveData>, weights: Option<Vec<f32>>) -> Self {
+        let aabbs = primitives.iter().map(|p| p.aabb).collect::<Vec<_>>();
+        let aabb = AABB::union(&aabbs);
+        MeshData {
+            primitives,
+            aabb,
+            weights,
+        }
+    }
+}
+
+impl MeshData {
+    pub fn primitives(&self) -> &[PrimitiveData] {
+        &self.primitives
+    }
+
+    /// Get the AABB of the mesh or `None` if it has no renderable primitive.
+    pub fn aabb(&self) -> Option<AABB<f32>> {
+        self.aabb
+    }
+
+    /// Get the default weights of the morph targets of the primitives.
+    pub fn weights(&self) -> Option<&[f32]> {
+        self.weights.as_deref()
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct PrimitiveData {
     index: usize,
     indices: Option<IndexBufferPart>,
     vertices: VertexBufferPart,
+    mode: PrimitiveMode,
+    attributes: Attributes,
     material: Material,
     aabb: AABB<f32>,
+    targets: Vec<MorphTarget>,
+}
+
+/// Displacements of the vertices of a primitive for one morph target.
+///
+/// Morph targets are not rendered but kept so they can be exported.
+/// Attributes that are not displaced by the target are empty.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MorphTarget {
+    pub positions: Vec<[f32; 3]>,
+    pub normals: Vec<[f32; 3]>,
+    pub tangents: Vec<[f32; 3]>,
+}
+
+/// Optional vertex attributes of a primitive.
+///
+/// Missing attributes are filled with default values in the vertices
+/// so this is needed to know which ones were actually provided.
+#[derive(Copy, Clone, Debug)]
+pub(crate) struct Attributes {
+    pub normals: bool,
+    pub tex_coords_0: bool,
+    pub tex_coords_1: bool,
+    /// Tangents were either provided or generated.
+    pub tangents: bool,
+    /// Joints and weights.
+    pub skinning: bool,
+    pub colors: bool,
+}
+
+impl PrimitiveData {
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Get the vertices of the primitive from the geometry they belong to.
+    pub fn vertices<'a>(&self, geometry: &'a Geometry) -> &'a [ModelVertex] {
+        let (offset, count) = self.vertices;
+        let first = offset / size_of::<ModelVertex>();
+        &geometry.vertices[first..first + count]
+    }
+
+    /// Get the indices of the primitive from the geometry they belong to.
+    pub fn indices<'a>(&self, geometry: &'a Geometry) -> Option<&'a [u32]> {
+        self.indices.map(|(offset, count)| {
+            let first = offset / size_of::<u32>();
+            &geometry.indices[first..first + count]
+        })
+    }
+
+    pub fn mode(&self) -> PrimitiveMode {
+        self.mode
+    }
+
+    pub fn material(&self) -> Material {
+        self.material
+    }
+
+    pub fn aabb(&self) -> AABB<f32> {
+        self.aabb
+    }
+
+    pub(crate) fn attributes(&self) -> Attributes {
+        self.attributes
+    }
+
+    /// Get the morph targets of the primitive.
+    pub fn targets(&self) -> &[MorphTarget] {
+        &self.targets
+    }
 }
 
 pub struct Meshes {
@@ -87,15 +242,19 @@ pub struct Meshes {
     pub indices: Option<Buffer>,
 }
 
-pub fn create_meshes_from_gltf(
-    context: &Arc<Context>,
-    command_buffer: vk::CommandBuffer,
+/// Read the geometry of all the meshes in the document.
+///
+/// Missing tangents are generated once all meshes are read.
+/// Returns `None` if the document contains no renderable primitive.
+pub(crate) fn read_geometry_from_gltf(
     document: &Document,
     buffers: &[Data],
-) -> Option<Meshes> {
-    let mut meshes_data = Vec::<Vec<PrimitiveData>>::new();
+    progress: &mut ProgressCallback,
+) -> Result<Option<Geometry>, ModelLoadingError> {
+    let mut meshes_data = Vec::<MeshData>::new();
     let mut all_vertices = Vec::<ModelVertex>::new();
     let mut all_indices = Vec::<u32>::new();
+    let mut primitives_without_tangents = Vec::<(VertexBufferPart, Option<IndexBufferPart>)>::new();
 
     let mut primitive_count = 0;
 
@@ -108,7 +267,7 @@ pub fn create_meshes_from_gltf(
 
             if let Some(accessor) = primitive.get(&Semantic::Positions) {
                 let aabb = get_aabb(&primitive.bounding_box());
-                let positions = read_positions(&reader);
+                let positions = read_positions(&reader, mesh.index(), primitive.index())?;
                 let normals = read_normals(&reader);
                 let tex_coords_0 = read_tex_coords(&reader, 0);
                 let tex_coords_1 = read_tex_coords(&reader, 1);
@@ -116,8 +275,9 @@ pub fn create_meshes_from_gltf(
                 let weights = read_weights(&reader);
                 let joints = read_joints(&reader);
                 let colors = read_colors(&reader);
+                let targets = read_morph_targets(&reader);
 
-                let mut vertices = positions
+                let vertices = positions
                     .iter()
                     .enumerate()
                     .map(|(index, position)| {
@@ -143,16 +303,11 @@ pub fn create_meshes_from_gltf(
                     })
                     .collect::<Vec<_>>();
 
-                let indices = read_indices(&reader);
-
-                if !positions.is_empty()
-                    && !normals.is_empty()
-                    && !tex_coords_0.is_empty()
-                    && tangents.is_empty()
-                {
-                    generate_tangents(indices.as_deref(), &mut vertices);
-                }
+                let mode = PrimitiveMode::from(primitive.mode());
 
+                // Out of range indices are reported by the validation and removed so they are not drawn
+                let indices = read_indices(&reader)
+                    .map(|indices| remove_out_of_range_indices(indices, mode, vertices.len()));
                 let indices = indices.map(|indices| {
                     let offset = all_indices.len() * size_of::<u32>();
                     all_indices.extend_from_slice(&indices);
@@ -162,6 +317,25 @@ pub fn create_meshes_from_gltf(
                 let offset = all_vertices.len() * size_of::<ModelVertex>();
                 all_vertices.extend_from_slice(&vertices);
 
+                // Tangents are only generated for triangle lists
+                let generate_tangents = mode == PrimitiveMode::Triangles
+                    && !positions.is_empty()
+                    && !normals.is_empty()
+                    && !tex_coords_0.is_empty()
+                    && tangents.is_empty();
+                if generate_tangents {
+                    primitives_without_tangents.push(((offset, vertices.len()), indices));
+                }
+
+                let attributes = Attributes {
+                    normals: !normals.is_empty(),
+                    tex_coords_0: !tex_coords_0.is_empty(),
+                    tex_coords_1: !tex_coords_1.is_empty(),
+                    tangents: !tangents.is_empty() || generate_tangents,
+                    skinning: !joints.is_empty() && !weights.is_empty(),
+                    colors: !colors.is_empty(),
+                };
+
                 let material = primitive.material().into();
 
                 let index = primitive_count;
@@ -171,78 +345,170 @@ pub fn create_meshes_from_gltf(
                     index,
                     indices,
                     vertices: (offset, accessor.count()),
+                    mode,
+                    attributes,
                     material,
                     aabb,
+                    targets,
                 });
             }
         }
 
-        meshes_data.push(primitives_buffers);
+        let weights = mesh.weights().map(<[f32]>::to_vec);
+        meshes_data.push(MeshData::new(primitives_buffers, weights));
     }
 
-    if !meshes_data.is_empty() {
-        let indices = if all_indices.is_empty() {
-            None
-        } else {
-            let (indices, staged_indices) = cmd_create_device_local_buffer_with_data::<u8, _>(
-                context,
-                command_buffer,
-                vk::BufferUsageFlags::INDEX_BUFFER,
-                &all_indices,
-            );
-            Some((Arc::new(indices), staged_indices))
-        };
+    enter_stage(progress, LoadingStage::Tangents)?;
+    // Primitives are stored one after the other so their vertices are split
+    // into disjoint slices and tangents are generated in parallel
+    let mut remaining_vertices = all_vertices.as_mut_slice();
+    let mut remaining_start = 0;
+    let mut tangents_jobs = Vec::with_capacity(primitives_without_tangents.len());
+    for (vertices, indices) in primitives_without_tangents {
+        let (offset, count) = vertices;
+        let first = offset / size_of::<ModelVertex>();
+        let (_, tail) =
+            std::mem::take(&mut remaining_vertices).split_at_mut(first - remaining_start);
+        let (vertices, tail) = tail.split_at_mut(count);
+        remaining_vertices = tail;
+        remaining_start = first + count;
+
+        let indices = indices.map(|(offset, count)| {
+            let first = offset / size_of::<u32>();
+            &all_indices[first..first + count]
+        });
+        tangents_jobs.push((indices, vertices));
+    }
+    tangents_jobs
+        .into_par_iter()
+        .for_each(|(indices, vertices)| generate_tangents(indices, vertices));
+
+    if meshes_data.is_empty() {
+        return Ok(None);
+    }
 
-        let (vertices, staged_vertices) = cmd_create_device_local_buffer_with_data::<u8, _>(
+    Ok(Some(Geometry {
+        meshes: meshes_data,
+        vertices: all_vertices,
+        indices: all_indices,
+    }))
+}
+
+/// Upload `geometry` on the GPU.
+pub(crate) fn create_meshes(
+    context: &Arc<Context>,
+    command_buffer: vk::CommandBuffer,
+    geometry: &Geometry,
+) -> Meshes {
+    let indices = if geometry.indices.is_empty() {
+        None
+    } else {
+        let (indices, staged_indices) = cmd_create_device_local_buffer_with_data::<u8, _>(
             context,
             command_buffer,
-            vk::BufferUsageFlags::VERTEX_BUFFER,
-            &all_vertices,
+            vk::BufferUsageFlags::INDEX_BUFFER,
+            &geometry.indices,
         );
-        let vertices = Arc::new(vertices);
+        Some((Arc::new(indices), staged_indices))
+    };
+
+    let (vertices, staged_vertices) = cmd_create_device_local_buffer_with_data::<u8, _>(
+        context,
+        command_buffer,
+        vk::BufferUsageFlags::VERTEX_BUFFER,
+        &geometry.vertices,
+    );
+    let vertices = Arc::new(vertices);
+
+    let meshes = geometry
+        .meshes
+        .iter()
+        .map(|mesh_data| {
+            let primitives = mesh_data
+                .primitives
+                .iter()
+                .map(|buffers| {
+                    // Primitives whose indices were all removed have nothing to draw
+                    let mesh_indices = buffers.indices.filter(|(_, count)| *count > 0);
+                    let mesh_vertices = buffers.vertices;
+                    let vertex_count = match buffers.indices {
+                        Some(_) if mesh_indices.is_none() => 0,
+                        _ => mesh_vertices.1,
+                    };
+                    let vertex_buffer = VertexBuffer::new(
+                        Arc::clone(&vertices),
+                        mesh_vertices.0 as _,
+                        vertex_count as _,
+                    );
+
+                    let index_buffer = mesh_indices.map(|mesh_indices| {
+                        IndexBuffer::new(
+                            Arc::clone(indices.as_ref().map(|(indices, _)| indices).unwrap()),
+                            mesh_indices.0 as _,
+                            mesh_indices.1 as _,
+                        )
+                    });
+
+                    Primitive {
+                        index: buffers.index,
+                        vertices: vertex_buffer,
+                        indices: index_buffer,
+                        mode: buffers.mode,
+                        material: buffers.material,
+                        aabb: buffers.aabb,
+                    }
+                })
+                .collect::<Vec<_>>();
+            Mesh {
+                primitives,
+                aabb: mesh_data.aabb,
+            }
+        })
+        .collect();
 
-        let meshes = meshes_data
-            .iter()
-            .map(|primitives_buffers| {
-                let primitives = primitives_buffers
-                    .iter()
-                    .map(|buffers| {
-                        let mesh_vertices = buffers.vertices;
-                        let vertex_buffer = VertexBuffer::new(
-                            Arc::clone(&vertices),
-                            mesh_vertices.0 as _,
-                            mesh_vertices.1 as _,
-                        );
-
-                        let index_buffer = buffers.indices.map(|mesh_indices| {
-                            IndexBuffer::new(
-                                Arc::clone(indices.as_ref().map(|(indices, _)| indices).unwrap()),
-                                mesh_indices.0 as _,
-                                mesh_indices.1 as _,
-                            )
-                        });
-
-                        Primitive {
-                            index: buffers.index,
-                            vertices: vertex_buffer,
-                            indices: index_buffer,
-                            material: buffers.material,
-                            aabb: buffers.aabb,
-                        }
-                    })
-                    .collect::<Vec<_>>();
-                Mesh::new(primitives)
-            })
-            .collect();
-
-        return Some(Meshes {
-            meshes,
-            vertices: staged_vertices,
-            indices: indices.map(|(_, staged_indices)| staged_indices),
-        });
+    Meshes {
+        meshes,
+        vertices: staged_vertices,
+        indices: indices.map(|(_, staged_indices)| staged_indices),
+    }
+}
+
+fn read_morph_targets<'a, 's, F>(reader: &Reader<'a, 's, F>) -> Vec<MorphTarget>
+where
+    F: Clone + Fn(GltfBuffer<'a>) -> Option<&'s [u8]>,
+{
+    reader
+        .read_morph_targets()
+        .map(|(positions, normals, tangents)| MorphTarget {
+            positions: positions.map_or(vec![], |positions| positions.collect()),
+            normals: normals.map_or(vec![], |normals| normals.collect()),
+            tangents: tangents.map_or(vec![], |tangents| tangents.collect()),
+        })
+        .collect()
+}
+
+/// Remove the indices that are not lower than `vertex_count`.
+///
+/// Whole triangles are removed from triangle lists so the others keep their vertices.
+fn remove_out_of_range_indices(
+    indices: Vec<u32>,
+    mode: PrimitiveMode,
+    vertex_count: usize,
+) -> Vec<u32> {
+    let in_range = |index: &u32| (*index as usize) < vertex_count;
+    if indices.iter().all(in_range) {
+        return indices;
     }
 
-    None
+    match mode {
+        PrimitiveMode::Triangles => indices
+            .chunks_exact(3)
+            .filter(|triangle| triangle.iter().all(in_range))
+            .flatten()
+            .copied()
+            .collect(),
+        _ => indices.into_iter().filter(in_range).collect(),
+    }
 }
 
 fn read_indices<'a, 's, F>(reader: &Reader<'a, 's, F>) -> Option<Vec<u32>>
@@ -264,14 +530,23 @@ fn get_aabb(bounds: &Bounds<[f32; 3]>) -> AABB<f32> {
     AABB::new(min, max)
 }
 
-fn read_positions<'a, 's, F>(reader: &Reader<'a, 's, F>) -> Vec<[f32; 3]>
+fn read_positions<'a, 's, F>(
+    reader: &Reader<'a, 's, F>,
+    mesh_index: usize,
+    primitive_index: usize,
+) -> Result<Vec<[f32; 3]>, ModelLoadingError>
 where
     F: Clone + Fn(GltfBuffer<'a>) -> Option<&'s [u8]>,
 {
     reader
         .read_positions()
-        .expect("Position primitives should be present")
-        .collect()
+        .map(|positions| positions.collect())
+        .ok_or_else(|| {
+            ModelLoadingError::InvalidAccessor(format!(
+                "Positions of primitive {} of mesh {} could not be read",
+                primitive_index, mesh_index
+            ))
+        })
 }
 
 fn read_normals<'a, 's, F>(reader: &Reader<'a, 's, F>) -> Vec<[f32; 3]>
@@ -1,16 +1,25 @@
+pub use crate::statistics::{
+    ImageStatistics, MeshStatistics, PrimitiveStatistics, SkinStatistics, Statistics,
+};
+pub use crate::validation::{Issue, Severity, Subject};
+
+use crate::validation::validate;
 use gltf::{
+    buffer::{Data, Source as BufferSource},
+    image::Source as ImageSource,
     khr_lights_punctual::{Kind as GltfLightKind, Light as GltfLight},
     material::AlphaMode as GltfAlphaMode,
     mesh::Mode as GltfPrimitiveMode,
     Animation as GltfAnimation, Document, Material as GltfMaterial, Mesh as GltfMesh,
     Node as GltfNode, Primitive as GltfPrimitive, Scene,
 };
+use math::AABB;
 use std::{fmt, path::Path};
 
 #[derive(Clone, Debug)]
 pub struct Metadata {
     name: String,
-    path: String,
+    path: Option<String>,
     scene_count: usize,
     node_count: usize,
     animation_count: usize,
@@ -19,15 +28,28 @@ pub struct Metadata {
     material_count: usize,
     texture_count: usize,
     light_count: usize,
+    dimensions: [f32; 3],
+    statistics: Statistics,
+    external_resources: Vec<String>,
     nodes: Vec<Node>,
     animations: Vec<Animation>,
+    names: Names,
+    issues: Vec<Issue>,
 }
 
 impl Metadata {
-    pub(crate) fn new<P: AsRef<Path>>(path: P, document: &Document) -> Self {
+    pub(crate) fn new(
+        name: &str,
+        path: Option<&Path>,
+        document: &Document,
+        buffers: &[Data],
+        aabb: AABB<f32>,
+        statistics: Statistics,
+    ) -> Self {
+        let size = aabb.get_size();
         Metadata {
-            name: String::from(path.as_ref().file_name().unwrap().to_str().unwrap()),
-            path: String::from(path.as_ref().to_str().unwrap()),
+            name: String::from(name),
+            path: path.map(|path| path.to_string_lossy().into_owned()),
             scene_count: document.scenes().len(),
             node_count: document.nodes().len(),
             animation_count: document.animations().len(),
@@ -36,12 +58,35 @@ impl Metadata {
             material_count: document.materials().len(),
             texture_count: document.textures().len(),
             light_count: document.lights().map_or(0, |lights| lights.len()),
+            dimensions: [size.x, size.y, size.z],
+            statistics,
+            external_resources: list_external_resources(document),
             nodes: build_tree(document),
             animations: document.animations().map(Animation::from).collect(),
+            names: Names::from(document),
+            issues: validate(document, buffers),
         }
     }
 }
 
+fn list_external_resources(document: &Document) -> Vec<String> {
+    let buffer_uris = document
+        .buffers()
+        .filter_map(|buffer| match buffer.source() {
+            BufferSource::Uri(uri) => Some(uri),
+            BufferSource::Bin => None,
+        });
+    let image_uris = document.images().filter_map(|image| match image.source() {
+        ImageSource::Uri { uri, .. } => Some(uri),
+        ImageSource::View { .. } => None,
+    });
+    buffer_uris
+        .chain(image_uris)
+        .filter(|uri| !uri.starts_with("data:"))
+        .map(String::from)
+        .collect()
+}
+
 fn build_tree(document: &Document) -> Vec<Node> {
     let mut uid = 0;
     document
@@ -102,8 +147,9 @@ impl Metadata {
         &self.name
     }
 
-    pub fn path(&self) -> &str {
-        &self.path
+    /// Path of the model file if it was loaded from the filesystem.
+    pub fn path(&self) -> Option<&str> {
+        self.path.as_deref()
     }
 
     pub fn scene_count(&self) -> usize {
@@ -138,6 +184,26 @@ impl Metadata {
         self.light_count
     }
 
+    /// Size of the scene along each axis in meters.
+    pub fn dimensions(&self) -> [f32; 3] {
+        self.dimensions
+    }
+
+    /// Geometry and memory statistics.
+    pub fn statistics(&self) -> &Statistics {
+        &self.statistics
+    }
+
+    /// Uris of the external buffers and images, data uris excluded.
+    pub fn external_resources(&self) -> &[String] {
+        &self.external_resources
+    }
+
+    /// Replace the external resources of models that are not glTF documents.
+    pub(crate) fn set_external_resources(&mut self, resources: Vec<String>) {
+        self.external_resources = resources;
+    }
+
     pub fn nodes(&self) -> &[Node] {
         &self.nodes
     }
@@ -145,6 +211,47 @@ impl Metadata {
     pub fn animations(&self) -> &[Animation] {
         &self.animations
     }
+
+    /// Names of the objects of the document.
+    pub fn names(&self) -> &Names {
+        &self.names
+    }
+
+    /// Issues found while validating the document.
+    pub fn issues(&self) -> &[Issue] {
+        &self.issues
+    }
+}
+
+/// Names of the objects of a document, indexed like the objects they name.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Names {
+    pub nodes: Vec<Option<String>>,
+    pub meshes: Vec<Option<String>>,
+    pub materials: Vec<Option<String>>,
+    pub skins: Vec<Option<String>>,
+    pub animations: Vec<Option<String>>,
+    pub images: Vec<Option<String>>,
+    pub textures: Vec<Option<String>>,
+    pub lights: Vec<Option<String>>,
+}
+
+impl From<&Document> for Names {
+    fn from(document: &Document) -> Names {
+        let name = |name: Option<&str>| name.map(String::from);
+        Names {
+            nodes: document.nodes().map(|n| name(n.name())).collect(),
+            meshes: document.meshes().map(|m| name(m.name())).collect(),
+            materials: document.materials().map(|m| name(m.name())).collect(),
+            skins: document.skins().map(|s| name(s.name())).collect(),
+            animations: document.animations().map(|a| name(a.name())).collect(),
+            images: document.images().map(|i| name(i.name())).collect(),
+            textures: document.textures().map(|t| name(t.name())).collect(),
+            lights: document
+                .lights()
+                .map_or_else(Vec::new, |lights| lights.map(|l| name(l.name())).collect()),
+        }
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -224,6 +331,7 @@ pub struct Primitive {
     pub index: usize,
     pub mode: PrimitiveMode,
     pub material: Material,
+    pub statistics: PrimitiveStatistics,
 }
 
 impl From<GltfPrimitive<'_>> for Primitive {
@@ -232,11 +340,12 @@ impl From<GltfPrimitive<'_>> for Primitive {
             index: primitive.index(),
             mode: PrimitiveMode::from(primitive.mode()),
             material: Material::from(primitive.material()),
+            statistics: PrimitiveStatistics::from(primitive.clone()),
         }
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum PrimitiveMode {
     Points,
     Lines,
@@ -276,6 +385,20 @@ impl From<GltfPrimitiveMode> for PrimitiveMode {
     }
 }
 
+impl From<PrimitiveMode> for GltfPrimitiveMode {
+    fn from(mode: PrimitiveMode) -> GltfPrimitiveMode {
+        match mode {
+            PrimitiveMode::Points => GltfPrimitiveMode::Points,
+            PrimitiveMode::Lines => GltfPrimitiveMode::Lines,
+            PrimitiveMode::LineLoop => GltfPrimitiveMode::LineLoop,
+            PrimitiveMode::LineStrip => GltfPrimitiveMode::LineStrip,
+            PrimitiveMode::Triangles => GltfPrimitiveMode::Triangles,
+            PrimitiveMode::TriangleStrip => GltfPrimitiveMode::TriangleStrip,
+            PrimitiveMode::TriangleFan => GltfPrimitiveMode::TriangleFan,
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct Material {
     pub index: Option<usize>,
@@ -22,6 +22,8 @@ impl Nodes {
             let light_index = node.light().map(|l| l.index());
             let children_indices = node.children().map(|c| c.index()).collect::<Vec<_>>();
             let node = Node {
+                file_transform: local_transform.clone(),
+                rest_transform: local_transform.clone(),
                 local_transform,
                 global_transform_matrix,
                 mesh_index,
@@ -65,6 +67,16 @@ impl Nodes {
         }
     }
 
+    /// Get the index of the parent of the node at `index`.
+    ///
+    /// Returns `None` for root nodes and nodes that are not part of the scene.
+    pub fn parent_index(&self, index: usize) -> Option<usize> {
+        self.depth_first_taversal_indices
+            .iter()
+            .find(|(node_index, _)| *node_index == index)
+            .and_then(|(_, parent_index)| *parent_index)
+    }
+
     pub fn get_skins_transform(&self) -> Vec<(usize, Matrix4<f32>)> {
         self.nodes
             .iter()
@@ -102,10 +114,18 @@ impl Nodes {
     pub fn nodes_mut(&mut self) -> &mut [Node] {
         &mut self.nodes
     }
+
+    pub(crate) fn roots_indices(&self) -> &[usize] {
+        &self.roots_indices
+    }
 }
 
 #[derive(Clone, Debug)]
 pub struct Node {
+    /// Local transform as read from the file.
+    file_transform: Transform,
+    /// Local transform without the animations, as edited by the user.
+    rest_transform: Transform,
     local_transform: Transform,
     global_transform_matrix: Matrix4<f32>,
     mesh_index: Option<usize>,
@@ -119,6 +139,18 @@ impl Node {
         let new_tranform = transform * compute_transform_matrix(&self.local_transform);
         self.global_transform_matrix = new_tranform;
     }
+
+    /// Replace a matrix local transform by its decomposition so it can be edited.
+    fn decompose(&mut self) {
+        if let Transform::Matrix { .. } = self.local_transform {
+            let (translation, rotation, scale) = self.local_transform.clone().decomposed();
+            self.local_transform = Transform::Decomposed {
+                translation,
+                rotation,
+                scale,
+            };
+        }
+    }
 }
 
 impl Node {
@@ -138,7 +170,53 @@ impl Node {
         self.light_index
     }
 
+    pub(crate) fn rest_transform(&self) -> &Transform {
+        &self.rest_transform
+    }
+
+    /// Get the translation of the node relative to its parent.
+    pub fn translation(&self) -> Vector3<f32> {
+        let (translation, _, _) = self.local_transform.clone().decomposed();
+        Vector3::from(translation)
+    }
+
+    /// Get the rotation of the node relative to its parent.
+    pub fn rotation(&self) -> Quaternion<f32> {
+        let (_, [x, y, z, w], _) = self.local_transform.clone().decomposed();
+        Quaternion::new(w, x, y, z)
+    }
+
+    /// Get the scale of the node relative to its parent.
+    pub fn scale(&self) -> Vector3<f32> {
+        let (_, _, scale) = self.local_transform.clone().decomposed();
+        Vector3::from(scale)
+    }
+
+    /// Replace the local transform and keep it when the animations are not applied.
+    pub fn edit_transform(
+        &mut self,
+        translation: Vector3<f32>,
+        rotation: Quaternion<f32>,
+        scale: Vector3<f32>,
+    ) {
+        self.set_translation(translation);
+        self.set_rotation(rotation);
+        self.set_scale(scale);
+        self.rest_transform = self.local_transform.clone();
+    }
+
+    /// Restore the local transform read from the file.
+    pub fn reset_transform(&mut self) {
+        self.local_transform = self.file_transform.clone();
+        self.rest_transform = self.file_transform.clone();
+    }
+
+    pub(crate) fn children_indices(&self) -> &[usize] {
+        &self.children_indices
+    }
+
     pub fn set_translation(&mut self, translation: Vector3<f32>) {
+        self.decompose();
         if let Transform::Decomposed {
             rotation, scale, ..
         } = self.local_transform
@@ -152,6 +230,7 @@ impl Node {
     }
 
     pub fn set_rotation(&mut self, rotation: Quaternion<f32>) {
+        self.decompose();
         if let Transform::Decomposed {
             translation, scale, ..
         } = self.local_transform
@@ -165,6 +244,7 @@ impl Node {
     }
 
     pub fn set_scale(&mut self, scale: Vector3<f32>) {
+        self.decompose();
         if let Transform::Decomposed {
             translation,
             rotation,
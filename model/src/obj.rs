@@ -0,0 +1,771 @@
+//! Wavefront OBJ/MTL import.
+//!
+//! The OBJ file is converted into an in-memory glTF document so the scene is
+//! built, validated and described exactly like a glTF scene. Each object or
+//! group of the file becomes a node with a mesh of a single primitive.
+//!
+//! MTL parameters are mapped to the closest metallic-roughness values:
+//!
+//! - `Kd`/`map_Kd` give the base color and `d`/`Tr`/`map_d` its alpha. Partially
+//!   dissolved materials are blended while an alpha map alone is used as a mask.
+//! - `Pr`/`map_Pr` and `Pm`/`map_Pm` give the roughness and metalness. Without
+//!   them, the roughness is derived from the specular exponent `Ns` and the
+//!   material is not metallic.
+//! - `Ke`/`map_Ke` give the emissive color.
+//! - `norm`, `map_Bump` or `bump` give the normal map.
+//! - The illumination model 0 (color without lighting) is unlit.
+//!
+//! Maps stored in separate files, like `map_Pr` and `map_Pm`, are packed into
+//! the channels of a single image. Missing texture files are replaced by white
+//! images since legacy files often reference textures with absolute paths.
+//!
+//! Polygons are triangulated by ear clipping and missing normals are computed
+//! from the faces sharing each position. Missing tangents are then generated
+//! like for glTF primitives.
+
+use super::{
+    error::ModelLoadingError,
+    export::{position_bounds, push, DocumentBuilder},
+    import::to_image_data,
+};
+use gltf::{
+    buffer::Data as BufferData,
+    image::{Data as ImageData, Format},
+    json::{
+        self,
+        buffer::Target,
+        extensions::material::Unlit,
+        material::{
+            AlphaCutoff, AlphaMode, EmissiveFactor, NormalTexture, PbrBaseColorFactor,
+            PbrMetallicRoughness, StrengthFactor,
+        },
+        mesh::{Mode, Semantic},
+        validation::Checked::Valid,
+        Index,
+    },
+    Document,
+};
+use image::{
+    imageops::{self, FilterType},
+    DynamicImage, GenericImageView, GrayImage, Luma, Rgba, RgbaImage,
+};
+use math::{
+    cgmath::{InnerSpace, Vector3, Zero},
+    clamp,
+};
+use rayon::prelude::*;
+use std::{
+    cell::RefCell,
+    cmp::Ordering,
+    collections::HashMap,
+    fs::{self, File},
+    io::BufReader,
+    path::Path,
+};
+use tobj::{LoadOptions, Material as ObjMaterial, Mesh as ObjMesh, Model as ObjModel};
+
+type Result<T> = std::result::Result<T, ModelLoadingError>;
+
+const ALPHA_MASK_CUTOFF: f32 = 0.5;
+
+/// An OBJ file converted to glTF.
+pub(crate) struct ObjImport {
+    pub document: Document,
+    pub buffers: Vec<BufferData>,
+    /// Texture files of each image of the document.
+    pub images: Vec<ObjImage>,
+    /// Material libraries and texture files relative to the directory of the OBJ file.
+    pub resources: Vec<String>,
+}
+
+/// Image of the converted document made of one or several texture files.
+///
+/// Paths are relative to the directory of the OBJ file.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub(crate) struct ObjImage {
+    /// Image providing all the channels or `None` for a white image.
+    base: Option<String>,
+    /// Images replacing each channel of the base image.
+    channels: [Option<String>; 4],
+}
+
+impl ObjImage {
+    fn file(file: String) -> Self {
+        ObjImage {
+            base: Some(file),
+            ..Default::default()
+        }
+    }
+
+    fn files(&self) -> impl Iterator<Item = &String> {
+        self.base.iter().chain(self.channels.iter().flatten())
+    }
+
+    fn load(&self, base_dir: &Path) -> ImageData {
+        let base = self
+            .base
+            .as_ref()
+            .and_then(|file| open_image(base_dir, file));
+        let base = match (base, self.channels.iter().all(Option::is_none)) {
+            (Some(base), true) => return to_image_data(base),
+            (base, _) => base,
+        };
+
+        let channels = self
+            .channels
+            .iter()
+            .map(|file| file.as_ref().and_then(|file| open_image(base_dir, file)))
+            .collect::<Vec<_>>();
+
+        let (width, height) = base
+            .iter()
+            .chain(channels.iter().flatten())
+            .next()
+            .map_or((1, 1), DynamicImage::dimensions);
+        let mut packed = base.map_or_else(
+            || RgbaImage::from_pixel(width, height, Rgba([255; 4])),
+            |base| base.to_rgba8(),
+        );
+
+        for (channel, image) in channels.iter().enumerate() {
+            if let Some(image) = image {
+                let values = channel_values(image);
+                let values = if values.dimensions() == (width, height) {
+                    values
+                } else {
+                    imageops::resize(&values, width, height, FilterType::Triangle)
+                };
+                packed
+                    .pixels_mut()
+                    .zip(values.pixels())
+                    .for_each(|(pixel, value)| pixel[channel] = value[0]);
+            }
+        }
+
+        ImageData {
+            pixels: packed.into_raw(),
+            format: Format::R8G8B8A8,
+            width,
+            height,
+        }
+    }
+}
+
+/// Convert the OBJ file at `path` and its material libraries to glTF.
+pub(crate) fn import_obj(path: &Path) -> Result<ObjImport> {
+    let base_dir = path.parent().unwrap_or_else(|| Path::new("./"));
+    let mut reader = BufReader::new(File::open(path)?);
+
+    // Material libraries and their material count, in loading order
+    let libraries = RefCell::new(Vec::<(String, usize)>::new());
+    let load_options = LoadOptions {
+        single_index: true,
+        triangulate: false,
+        ignore_points: true,
+        ignore_lines: true,
+    };
+    let (models, materials) = tobj::load_obj_buf(&mut reader, &load_options, |library| {
+        let result = tobj::load_mtl(base_dir.join(library));
+        if let Ok((materials, _)) = result.as_ref() {
+            let library = to_uri(library);
+            libraries.borrow_mut().push((library, materials.len()));
+        }
+        result
+    })
+    .map_err(|error| ModelLoadingError::Parse(format!("Invalid OBJ file: {}", error)))?;
+
+    let materials = materials.unwrap_or_else(|error| {
+        log::warn!(
+            "Materials of {} could not be loaded. Cause: {}",
+            path.display(),
+            error
+        );
+        Vec::new()
+    });
+
+    // Textures are relative to the library of their material
+    let libraries = libraries.into_inner();
+    let material_dirs = libraries
+        .iter()
+        .flat_map(|(library, count)| {
+            let dir = Path::new(library).parent().unwrap_or_else(|| Path::new(""));
+            (0..*count).map(move |_| dir)
+        })
+        .collect::<Vec<_>>();
+
+    let mut converter = Converter {
+        builder: DocumentBuilder::default(),
+        materials: &materials,
+        material_dirs: &material_dirs,
+        converted_materials: HashMap::new(),
+        images: Vec::new(),
+        textures: HashMap::new(),
+    };
+
+    let roots = models
+        .iter()
+        .filter_map(|model| {
+            let mesh = converter.push_mesh(model)?;
+            let node = json::Node {
+                camera: None,
+                children: None,
+                extensions: None,
+                extras: Default::default(),
+                matrix: None,
+                mesh: Some(mesh),
+                name: Some(model.name.clone()),
+                rotation: None,
+                scale: None,
+                translation: None,
+                skin: None,
+                weights: None,
+            };
+            Some(push(&mut converter.builder.root.nodes, node))
+        })
+        .collect();
+
+    let Converter {
+        builder, images, ..
+    } = converter;
+    let name = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or_default();
+    let (root, buffer) = builder.finish(name, roots, None);
+    let document = Document::from_json(root)?;
+
+    let mut resources = libraries
+        .into_iter()
+        .map(|(library, _)| library)
+        .collect::<Vec<_>>();
+    for file in images.iter().flat_map(ObjImage::files) {
+        if !resources.contains(file) {
+            resources.push(file.clone());
+        }
+    }
+
+    Ok(ObjImport {
+        document,
+        buffers: vec![BufferData(buffer)],
+        images,
+        resources,
+    })
+}
+
+/// Decode the images of a converted OBJ file.
+///
+/// `base_dir` is the directory of the OBJ file.
+pub(crate) fn load_images(images: &[ObjImage], base_dir: &Path) -> Vec<ImageData> {
+    images
+        .par_iter()
+        .map(|image| image.load(base_dir))
+        .collect()
+}
+
+struct Converter<'a> {
+    builder: DocumentBuilder,
+    materials: &'a [ObjMaterial],
+    /// Directory of the library of each material relative to the OBJ file.
+    material_dirs: &'a [&'a Path],
+    converted_materials: HashMap<usize, Index<json::Material>>,
+    images: Vec<ObjImage>,
+    textures: HashMap<ObjImage, Index<json::Texture>>,
+}
+
+impl Converter<'_> {
+    /// Push the mesh of `model`.
+    ///
+    /// Returns `None` if it has no face.
+    fn push_mesh(&mut self, model: &ObjModel) -> Option<Index<json::Mesh>> {
+        let mesh = &model.mesh;
+        let positions = mesh
+            .positions
+            .chunks_exact(3)
+            .map(|p| [p[0], p[1], p[2]])
+            .collect::<Vec<_>>();
+        let indices = triangulate_faces(mesh, &positions);
+        if indices.is_empty() {
+            return None;
+        }
+
+        let mut attributes = HashMap::new();
+        let bounds = position_bounds(&positions);
+        let accessor = self
+            .builder
+            .push_accessor(&positions, Some(Target::ArrayBuffer), bounds);
+        attributes.insert(Valid(Semantic::Positions), accessor);
+
+        let normals = if mesh.normals.len() == mesh.positions.len() {
+            mesh.normals
+                .chunks_exact(3)
+                .map(|n| [n[0], n[1], n[2]])
+                .collect()
+        } else {
+            generate_normals(&positions, &indices)
+        };
+        let accessor = self
+            .builder
+            .push_accessor(&normals, Some(Target::ArrayBuffer), None);
+        attributes.insert(Valid(Semantic::Normals), accessor);
+
+        if mesh.texcoords.len() / 2 == positions.len() {
+            // The origin of OBJ texture coordinates is the bottom left corner of the image
+            let coords = mesh
+                .texcoords
+                .chunks_exact(2)
+                .map(|c| [c[0], 1.0 - c[1]])
+                .collect::<Vec<_>>();
+            let accessor = self
+                .builder
+                .push_accessor(&coords, Some(Target::ArrayBuffer), None);
+            attributes.insert(Valid(Semantic::TexCoords(0)), accessor);
+        }
+
+        if mesh.vertex_color.len() == mesh.positions.len() {
+            let colors = mesh
+                .vertex_color
+                .chunks_exact(3)
+                .map(|c| [c[0], c[1], c[2]])
+                .collect::<Vec<_>>();
+            let accessor = self
+                .builder
+                .push_accessor(&colors, Some(Target::ArrayBuffer), None);
+            attributes.insert(Valid(Semantic::Colors(0)), accessor);
+        }
+
+        let indices = self
+            .builder
+            .push_accessor(&indices, Some(Target::ElementArrayBuffer), None);
+
+        let primitive = json::mesh::Primitive {
+            attributes,
+            extensions: None,
+            extras: Default::default(),
+            indices: Some(indices),
+            material: mesh.material_id.and_then(|id| self.push_material(id)),
+            mode: Valid(Mode::Triangles),
+            targets: None,
+        };
+        let mesh = json::Mesh {
+            extensions: None,
+            extras: Default::default(),
+            name: Some(model.name.clone()),
+            primitives: vec![primitive],
+            weights: None,
+        };
+        Some(push(&mut self.builder.root.meshes, mesh))
+    }
+
+    /// Push the material `id` the first time it is used.
+    ///
+    /// Unused materials are not converted so they are not reported as unused.
+    fn push_material(&mut self, id: usize) -> Option<Index<json::Material>> {
+        if let Some(index) = self.converted_materials.get(&id) {
+            return Some(*index);
+        }
+        let material = self.materials.get(id)?;
+        let dir = self
+            .material_dirs
+            .get(id)
+            .copied()
+            .unwrap_or_else(|| Path::new(""));
+        let material = self.map_material(material, dir);
+        let index = push(&mut self.builder.root.materials, material);
+        self.converted_materials.insert(id, index);
+        Some(index)
+    }
+
+    fn map_material(&mut self, material: &ObjMaterial, dir: &Path) -> json::Material {
+        let map = |statement: &str| texture_path(statement, dir);
+        let param_map = |key: &str| material.unknown_param.get(key).and_then(|s| map(s));
+
+        let alpha = opacity(material);
+        let color_map = map(&material.diffuse_texture);
+        let alpha_map =
+            map(&material.dissolve_texture).filter(|file| Some(file) != color_map.as_ref());
+        let alpha_mode = if alpha < 1.0 {
+            AlphaMode::Blend
+        } else if alpha_map.is_some() {
+            AlphaMode::Mask
+        } else {
+            AlphaMode::Opaque
+        };
+        let base_color_texture = if color_map.is_some() || alpha_map.is_some() {
+            Some(self.push_texture(ObjImage {
+                base: color_map,
+                channels: [None, None, None, alpha_map],
+            }))
+        } else {
+            None
+        };
+
+        let roughness_map = param_map("map_Pr");
+        let metallic_map = param_map("map_Pm");
+        // Factors multiply the maps so they default to 1 when a map is present
+        let roughness = parse_float(material, "Pr").unwrap_or_else(|| {
+            if roughness_map.is_some() {
+                1.0
+            } else {
+                shininess_to_roughness(material.shininess)
+            }
+        });
+        let metallic = parse_float(material, "Pm").unwrap_or_else(|| {
+            if metallic_map.is_some() {
+                1.0
+            } else {
+                0.0
+            }
+        });
+        let metallic_roughness_texture = if roughness_map.is_some() || metallic_map.is_some() {
+            Some(self.push_texture(ObjImage {
+                base: None,
+                channels: [None, roughness_map, metallic_map, None],
+            }))
+        } else {
+            None
+        };
+
+        let emissive_map = param_map("map_Ke");
+        let emissive = parse_color(material, "Ke").unwrap_or_else(|| {
+            if emissive_map.is_some() {
+                [1.0; 3]
+            } else {
+                [0.0; 3]
+            }
+        });
+        let emissive_texture = emissive_map.map(|file| self.push_texture(ObjImage::file(file)));
+
+        let normal_map = param_map("norm").or_else(|| map(&material.normal_texture));
+        let normal_texture = normal_map.map(|file| {
+            let info = self.push_texture(ObjImage::file(file));
+            NormalTexture {
+                index: info.index,
+                scale: 1.0,
+                tex_coord: info.tex_coord,
+                extensions: None,
+                extras: Default::default(),
+            }
+        });
+
+        let extensions = if material.illumination_model == Some(0) {
+            self.builder.use_extension("KHR_materials_unlit");
+            Some(json::extensions::material::Material {
+                unlit: Some(Unlit {}),
+                ..Default::default()
+            })
+        } else {
+            None
+        };
+
+        let [r, g, b] = material.diffuse;
+        json::Material {
+            alpha_cutoff: AlphaCutoff(ALPHA_MASK_CUTOFF),
+            alpha_mode: Valid(alpha_mode),
+            pbr_metallic_roughness: PbrMetallicRoughness {
+                base_color_factor: PbrBaseColorFactor([r, g, b, clamp(alpha, 0.0, 1.0)]),
+                base_color_texture,
+                metallic_factor: StrengthFactor(clamp(metallic, 0.0, 1.0)),
+                roughness_factor: StrengthFactor(clamp(roughness, 0.0, 1.0)),
+                metallic_roughness_texture,
+                extensions: None,
+                extras: Default::default(),
+            },
+            normal_texture,
+            emissive_texture,
+            emissive_factor: EmissiveFactor(emissive),
+            name: Some(material.name.clone()),
+            extensions,
+            ..Default::default()
+        }
+    }
+
+    /// Push the texture of `image`, or reuse it if another material uses it.
+    fn push_texture(&mut self, image: ObjImage) -> json::texture::Info {
+        let index = match self.textures.get(&image) {
+            Some(index) => *index,
+            None => {
+                let source = push(
+                    &mut self.builder.root.images,
+                    json::Image {
+                        buffer_view: None,
+                        mime_type: None,
+                        name: None,
+                        uri: image.files().next().cloned(),
+                        extensions: None,
+                        extras: Default::default(),
+                    },
+                );
+                let texture = push(
+                    &mut self.builder.root.textures,
+                    json::Texture {
+                        name: None,
+                        sampler: None,
+                        source,
+                        extensions: None,
+                        extras: Default::default(),
+                    },
+                );
+                self.images.push(image.clone());
+                self.textures.insert(image, texture);
+                texture
+            }
+        };
+        json::texture::Info {
+            index,
+            tex_coord: 0,
+            extensions: None,
+            extras: Default::default(),
+        }
+    }
+}
+
+/// Approximate the roughness matching a Phong specular exponent.
+fn shininess_to_roughness(shininess: f32) -> f32 {
+    (2.0 / (shininess.max(0.0) + 2.0)).sqrt()
+}
+
+/// Get the opacity of `material` from its `d` statement, or from `Tr` which is
+/// the inverse of `d` used by some exporters instead.
+///
+/// Materials with neither statement are opaque. tobj stores `d` in `dissolve`
+/// and sets it to 1 when the statement is missing so `Tr` is only used then.
+fn opacity(material: &ObjMaterial) -> f32 {
+    const DEFAULT_OPACITY: f32 = 1.0;
+    let alpha = match parse_float(material, "Tr") {
+        Some(transparency) if material.dissolve >= DEFAULT_OPACITY => 1.0 - transparency,
+        _ => material.dissolve,
+    };
+    if alpha.is_finite() {
+        clamp(alpha, 0.0, 1.0)
+    } else {
+        DEFAULT_OPACITY
+    }
+}
+
+fn parse_float(material: &ObjMaterial, key: &str) -> Option<f32> {
+    material
+        .unknown_param
+        .get(key)?
+        .split_whitespace()
+        .next()?
+        .parse()
+        .ok()
+}
+
+/// Parse a color with either one value for all components or three values.
+fn parse_color(material: &ObjMaterial, key: &str) -> Option<[f32; 3]> {
+    let values = material
+        .unknown_param
+        .get(key)?
+        .split_whitespace()
+        .map(str::parse)
+        .collect::<std::result::Result<Vec<f32>, _>>()
+        .ok()?;
+    match values.as_slice() {
+        [value] => Some([*value; 3]),
+        [r, g, b, ..] => Some([*r, *g, *b]),
+        _ => None,
+    }
+}
+
+/// Get the file of a texture statement relative to the OBJ file.
+///
+/// Options preceding the file name, such as `-bm 0.5`, are skipped.
+fn texture_path(statement: &str, dir: &Path) -> Option<String> {
+    let words = statement.split_whitespace().collect::<Vec<_>>();
+    let mut index = 0;
+    while index + 1 < words.len() && words[index].starts_with('-') {
+        // Vector options take up to 3 numbers, others take one argument
+        let max_arguments = match words[index] {
+            "-o" | "-s" | "-t" => 3,
+            "-mm" => 2,
+            _ => 1,
+        };
+        index += 1;
+        let mut arguments = 0;
+        while arguments < max_arguments
+            && index + 1 < words.len()
+            && (arguments == 0 || words[index].parse::<f32>().is_ok())
+        {
+            index += 1;
+            arguments += 1;
+        }
+    }
+
+    let file = words.get(index..)?.join(" ");
+    if file.is_empty() {
+        return None;
+    }
+    Some(to_uri(&dir.join(file.replace('\\', "/"))))
+}
+
+fn to_uri(path: &Path) -> String {
+    path.to_string_lossy().replace('\\', "/")
+}
+
+fn open_image(base_dir: &Path, file: &str) -> Option<DynamicImage> {
+    let result = fs::read(base_dir.join(file))
+        .map_err(|error| error.to_string())
+        .and_then(|bytes| image::load_from_memory(&bytes).map_err(|error| error.to_string()));
+    match result {
+        Ok(image) => Some(image),
+        Err(error) => {
+            log::warn!(
+                "Texture {} is replaced by a white image. Cause: {}",
+                file,
+                error
+            );
+            None
+        }
+    }
+}
+
+/// Get the values to store in a channel of a packed image.
+///
+/// They are the alpha of images with transparency and the luminance of the others.
+fn channel_values(image: &DynamicImage) -> GrayImage {
+    if image.color().has_alpha() {
+        let rgba = image.to_rgba8();
+        GrayImage::from_fn(rgba.width(), rgba.height(), |x, y| {
+            Luma([rgba.get_pixel(x, y)[3]])
+        })
+    } else {
+        image.to_luma8()
+    }
+}
+
+/// Triangulate the faces of `mesh`.
+fn triangulate_faces(mesh: &ObjMesh, positions: &[[f32; 3]]) -> Vec<u32> {
+    // Arities are only provided when some faces are not triangles
+    if mesh.face_arities.is_empty() {
+        return mesh.indices.clone();
+    }
+
+    let mut triangles = Vec::with_capacity(mesh.indices.len() * 2);
+    let mut start = 0;
+    for arity in &mesh.face_arities {
+        let end = start + *arity as usize;
+        triangulate(positions, &mesh.indices[start..end], &mut triangles);
+        start = end;
+    }
+    triangles
+}
+
+/// Triangulate a polygon by ear clipping and push its triangles in `triangles`.
+///
+/// The polygon is projected on the axis plane that is the most parallel to it.
+/// The remaining vertices are triangulated as a fan if no ear can be found,
+/// which happens with degenerate or self-intersecting polygons and with
+/// non finite positions.
+pub(crate) fn triangulate(positions: &[[f32; 3]], polygon: &[u32], triangles: &mut Vec<u32>) {
+    if polygon.len() < 3 {
+        return;
+    }
+    if polygon.len() == 3 {
+        triangles.extend_from_slice(polygon);
+        return;
+    }
+
+    // Newell's method gives the normal of non planar polygons too
+    let mut normal = [0.0f32; 3];
+    for (i, index) in polygon.iter().enumerate() {
+        let current = positions[*index as usize];
+        let next = positions[polygon[(i + 1) % polygon.len()] as usize];
+        normal[0] += (current[1] - next[1]) * (current[2] + next[2]);
+        normal[1] += (current[2] - next[2]) * (current[0] + next[0]);
+        normal[2] += (current[0] - next[0]) * (current[1] + next[1]);
+    }
+    let axis = (0..3)
+        .max_by(|a, b| {
+            let (a, b) = (normal[*a].abs(), normal[*b].abs());
+            a.partial_cmp(&b).unwrap_or(Ordering::Equal)
+        })
+        .unwrap();
+    // Polygons are counter-clockwise in the (u, v) plane when the normal points along the axis
+    let (u, v) = match axis {
+        0 => (1, 2),
+        1 => (2, 0),
+        _ => (0, 1),
+    };
+    let orientation = if normal[axis] < 0.0 { -1.0 } else { 1.0 };
+    let points = polygon
+        .iter()
+        .map(|index| {
+            let position = positions[*index as usize];
+            [position[u], position[v]]
+        })
+        .collect::<Vec<_>>();
+    let area =
+        |a: usize, b: usize, c: usize| orientation * signed_area(points[a], points[b], points[c]);
+
+    let mut remaining = (0..polygon.len()).collect::<Vec<_>>();
+    while remaining.len() > 3 {
+        let count = remaining.len();
+        let ear = (0..count).find(|i| {
+            let a = remaining[(i + count - 1) % count];
+            let b = remaining[*i];
+            let c = remaining[(i + 1) % count];
+            area(a, b, c) > 0.0
+                && remaining.iter().all(|p| {
+                    *p == a
+                        || *p == b
+                        || *p == c
+                        || area(a, b, *p) <= 0.0
+                        || area(b, c, *p) <= 0.0
+                        || area(c, a, *p) <= 0.0
+                })
+        });
+        match ear {
+            Some(i) => {
+                let a = remaining[(i + count - 1) % count];
+                let c = remaining[(i + 1) % count];
+                triangles.extend_from_slice(&[polygon[a], polygon[remaining[i]], polygon[c]]);
+                remaining.remove(i);
+            }
+            None => break,
+        }
+    }
+
+    for i in 1..remaining.len() - 1 {
+        triangles.extend_from_slice(&[
+            polygon[remaining[0]],
+            polygon[remaining[i]],
+            polygon[remaining[i + 1]],
+        ]);
+    }
+}
+
+/// Twice the signed area of the triangle abc, positive if it is counter-clockwise.
+fn signed_area(a: [f32; 2], b: [f32; 2], c: [f32; 2]) -> f32 {
+    (b[0] - a[0]) * (c[1] - a[1]) - (b[1] - a[1]) * (c[0] - a[0])
+}
+
+/// Compute smooth normals by summing the normals of the triangles around each position.
+///
+/// Vertices sharing a position but not their texture coordinates are distinct
+/// so positions are compared by value to avoid seams.
+pub(crate) fn generate_normals(positions: &[[f32; 3]], indices: &[u32]) -> Vec<[f32; 3]> {
+    let key = |p: [f32; 3]| [p[0].to_bits(), p[1].to_bits(), p[2].to_bits()];
+
+    let mut normals = HashMap::<[u32; 3], Vector3<f32>>::new();
+    for triangle in indices.chunks_exact(3) {
+        let p0 = Vector3::from(positions[triangle[0] as usize]);
+        let p1 = Vector3::from(positions[triangle[1] as usize]);
+        let p2 = Vector3::from(positions[triangle[2] as usize]);
+        // Not normalized so larger triangles weigh more
+        let normal = (p1 - p0).cross(p2 - p0);
+        for index in triangle {
+            *normals
+                .entry(key(positions[*index as usize]))
+                .or_insert_with(Vector3::zero) += normal;
+        }
+    }
+
+    positions
+        .iter()
+        .map(|position| match normals.get(&key(*position)) {
+            Some(normal) if normal.magnitude2() > 0.0 => normal.normalize().into(),
+            _ => [0.0, 0.0, 1.0],
+        })
+        .collect()
+}
@@ -0,0 +1,209 @@
+//! Ray casting against the triangles of a scene.
+//!
+//! Each primitive is first tested against its bounding box in world space
+//! and only then against its triangles. Skinned primitives are tested in
+//! their current pose, the same way `gbuffer.vert` skins them on the GPU.
+//! Only triangle lists are tested since other primitives are not drawn as
+//! surfaces.
+
+use super::{metadata::PrimitiveMode, Geometry, ModelVertex, Node, PrimitiveData, Scene, Skin};
+use math::{
+    cgmath::{Matrix4, Point3, SquareMatrix, Vector3, Zero},
+    Ray, AABB,
+};
+
+/// The closest primitive hit by a ray.
+#[derive(Copy, Clone, Debug)]
+pub struct Hit {
+    /// Index of the node in the document.
+    pub node_index: usize,
+    pub mesh_index: usize,
+    /// Index of the primitive in its mesh.
+    pub primitive_index: usize,
+    /// Index of the material in the document or `None` for the default one.
+    pub material_index: Option<usize>,
+    /// World space position of the hit.
+    pub position: Point3<f32>,
+    /// Distance from the ray origin in multiples of the ray direction's length.
+    pub distance: f32,
+}
+
+/// Find the closest primitive of `scene` hit by `ray`.
+///
+/// `ray` is in world space. Nodes rejected by `node_filter` are skipped.
+pub(crate) fn pick<F>(scene: &Scene, ray: Ray<f32>, node_filter: F) -> Option<Hit>
+where
+    F: Fn(usize) -> bool,
+{
+    let geometry = scene.geometry();
+    let mut closest: Option<Hit> = None;
+
+    for (node_index, node) in scene.nodes().nodes().iter().enumerate() {
+        let mesh_index = match node.mesh_index() {
+            Some(index) if node_filter(node_index) => index,
+            _ => continue,
+        };
+        let skin = node.skin_index().map(|index| &scene.skins()[index]);
+
+        let primitives = geometry.meshes()[mesh_index].primitives();
+        for (primitive_index, primitive) in primitives.iter().enumerate() {
+            if primitive.mode() != PrimitiveMode::Triangles {
+                continue;
+            }
+
+            let max_distance = closest.map_or(f32::INFINITY, |hit| hit.distance);
+            let distance = match skin {
+                Some(skin) => pick_skinned(geometry, primitive, node, skin, ray, max_distance),
+                None => pick_rigid(geometry, primitive, node, ray, max_distance),
+            };
+
+            if let Some(distance) = distance {
+                closest = Some(Hit {
+                    node_index,
+                    mesh_index,
+                    primitive_index,
+                    material_index: primitive.material().get_index(),
+                    position: ray.at(distance),
+                    distance,
+                });
+            }
+        }
+    }
+
+    closest
+}
+
+/// Test a primitive that is only transformed by its node.
+///
+/// The ray is brought into the space of the primitive instead of
+/// transforming each vertex.
+fn pick_rigid(
+    geometry: &Geometry,
+    primitive: &PrimitiveData,
+    node: &Node,
+    ray: Ray<f32>,
+    max_distance: f32,
+) -> Option<f32> {
+    let inverse = node.transform().invert()?;
+    let ray = ray.transform(inverse);
+
+    match ray.intersect_aabb(&primitive.aabb()) {
+        Some(distance) if distance < max_distance => {}
+        _ => return None,
+    }
+
+    let vertices = primitive.vertices(geometry);
+    let positions = vertices.iter().map(|v| Point3::from(v.position));
+    closest_triangle(
+        ray,
+        &positions.collect::<Vec<_>>(),
+        primitive,
+        geometry,
+        max_distance,
+    )
+}
+
+/// Test a primitive in the current pose of `skin`.
+fn pick_skinned(
+    geometry: &Geometry,
+    primitive: &PrimitiveData,
+    node: &Node,
+    skin: &Skin,
+    ray: Ray<f32>,
+    max_distance: f32,
+) -> Option<f32> {
+    let transform = node.transform();
+    let joints = skin.joints();
+    let positions = primitive
+        .vertices(geometry)
+        .iter()
+        .map(|vertex| {
+            skin_vertex(vertex, transform, |index| {
+                joints
+                    .get(index)
+                    .map_or_else(Matrix4::identity, |joint| joint.matrix())
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let aabb = AABB::from_points(positions.iter().map(|p| Vector3::new(p.x, p.y, p.z)))?;
+    match ray.intersect_aabb(&aabb) {
+        Some(distance) if distance < max_distance => {}
+        _ => return None,
+    }
+
+    closest_triangle(ray, &positions, primitive, geometry, max_distance)
+}
+
+/// Compute the world space position of a skinned vertex.
+fn skin_vertex<F>(vertex: &ModelVertex, transform: Matrix4<f32>, joint_matrix: F) -> Point3<f32>
+where
+    F: Fn(usize) -> Matrix4<f32>,
+{
+    let world = vertex_transform(vertex, transform, joint_matrix);
+    let position = world * Point3::from(vertex.position).to_homogeneous();
+    Point3::from_homogeneous(position)
+}
+
+/// Compute the matrix bringing a vertex into world space.
+///
+/// It is the node `transform` combined with the weighted joint matrices
+/// when the vertex is skinned.
+pub(crate) fn vertex_transform<F>(
+    vertex: &ModelVertex,
+    transform: Matrix4<f32>,
+    joint_matrix: F,
+) -> Matrix4<f32>
+where
+    F: Fn(usize) -> Matrix4<f32>,
+{
+    if vertex.weights == [0.0; 4] {
+        return transform;
+    }
+    let skin = vertex
+        .weights
+        .iter()
+        .zip(vertex.joints.iter())
+        .fold(Matrix4::zero(), |skin, (weight, joint)| {
+            skin + joint_matrix(*joint as usize) * *weight
+        });
+    transform * skin
+}
+
+/// Get the distance to the closest triangle of `primitive` hit by `ray`.
+///
+/// `positions` are the positions of the vertices of the primitive in the space of `ray`.
+fn closest_triangle(
+    ray: Ray<f32>,
+    positions: &[Point3<f32>],
+    primitive: &PrimitiveData,
+    geometry: &Geometry,
+    max_distance: f32,
+) -> Option<f32> {
+    let sequential;
+    let indices = match primitive.indices(geometry) {
+        Some(indices) => indices,
+        None => {
+            sequential = (0..positions.len() as u32).collect::<Vec<_>>();
+            &sequential
+        }
+    };
+
+    let mut closest = None;
+    let mut max_distance = max_distance;
+    for triangle in indices.chunks_exact(3) {
+        let vertex = |i: usize| positions.get(triangle[i] as usize).copied();
+        let (p0, p1, p2) = match (vertex(0), vertex(1), vertex(2)) {
+            (Some(p0), Some(p1), Some(p2)) => (p0, p1, p2),
+            _ => continue,
+        };
+
+        if let Some(distance) = ray.intersect_triangle(p0, p1, p2) {
+            if distance < max_distance {
+                max_distance = distance;
+                closest = Some(distance);
+            }
+        }
+    }
+    closest
+}
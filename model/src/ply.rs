@@ -0,0 +1,436 @@
+//! PLY parsing.
+//!
+//! The ascii, binary little endian and binary big endian encodings are
+//! supported. Vertices are read from the `vertex` element with their `x`, `y`
+//! and `z` positions and their optional `nx`, `ny` and `nz` normals and `red`,
+//! `green`, `blue` and `alpha` colors. Integer colors are normalized according
+//! to their type. Polygons are read from the `vertex_indices` (or
+//! `vertex_index`) list of the `face` element. Other elements and properties
+//! are skipped.
+
+use super::{error::ModelLoadingError, obj::triangulate, scan::ScanMesh};
+use byteorder::{BigEndian, ByteOrder, LittleEndian};
+use std::fmt::Display;
+
+type Result<T> = std::result::Result<T, ModelLoadingError>;
+
+const FACE_INDICES: [&str; 2] = ["vertex_indices", "vertex_index"];
+
+/// Parse the content of a PLY file.
+pub(crate) fn read_ply(bytes: &[u8]) -> Result<ScanMesh> {
+    let (header, body) = read_header(bytes)?;
+    let mut body = Body {
+        encoding: header.encoding,
+        bytes: body,
+        position: 0,
+    };
+
+    let mut mesh = None;
+    let mut polygons = Vec::new();
+    for element in &header.elements {
+        match element.name.as_str() {
+            "vertex" => mesh = Some(read_vertices(element, &mut body)?),
+            "face" => read_faces(element, &mut body, &mut polygons)?,
+            _ => skip_element(element, &mut body)?,
+        }
+    }
+    let mut mesh = mesh.ok_or_else(|| invalid("no vertex element"))?;
+
+    let vertex_count = mesh.positions.len();
+    let mut triangles = Vec::with_capacity(polygons.len());
+    for polygon in &polygons {
+        if let Some(index) = polygon.iter().find(|i| **i as usize >= vertex_count) {
+            return Err(invalid(format!(
+                "face index {} is out of range (vertex count is {})",
+                index, vertex_count
+            )));
+        }
+        triangulate(&mesh.positions, polygon, &mut triangles);
+    }
+
+    // Files without faces are point clouds
+    if !triangles.is_empty() {
+        mesh.indices = Some(triangles);
+    }
+    Ok(mesh)
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Encoding {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Scalar {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+impl Scalar {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "char" | "int8" => Some(Scalar::I8),
+            "uchar" | "uint8" => Some(Scalar::U8),
+            "short" | "int16" => Some(Scalar::I16),
+            "ushort" | "uint16" => Some(Scalar::U16),
+            "int" | "int32" => Some(Scalar::I32),
+            "uint" | "uint32" => Some(Scalar::U32),
+            "float" | "float32" => Some(Scalar::F32),
+            "double" | "float64" => Some(Scalar::F64),
+            _ => None,
+        }
+    }
+
+    fn size(self) -> usize {
+        match self {
+            Scalar::I8 | Scalar::U8 => 1,
+            Scalar::I16 | Scalar::U16 => 2,
+            Scalar::I32 | Scalar::U32 | Scalar::F32 => 4,
+            Scalar::F64 => 8,
+        }
+    }
+
+    /// Map a color component of this type to [0, 1].
+    fn normalize(self, value: f64) -> f32 {
+        let max = match self {
+            Scalar::I8 => f64::from(std::i8::MAX),
+            Scalar::U8 => f64::from(std::u8::MAX),
+            Scalar::I16 => f64::from(std::i16::MAX),
+            Scalar::U16 => f64::from(std::u16::MAX),
+            Scalar::I32 => f64::from(std::i32::MAX),
+            Scalar::U32 => f64::from(std::u32::MAX),
+            Scalar::F32 | Scalar::F64 => 1.0,
+        };
+        math::clamp(value / max, 0.0, 1.0) as f32
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+enum PropertyType {
+    Scalar(Scalar),
+    List { count: Scalar, item: Scalar },
+}
+
+#[derive(Clone, Debug)]
+struct Property {
+    name: String,
+    ty: PropertyType,
+}
+
+#[derive(Clone, Debug)]
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+impl Element {
+    /// Get the index and type of the first scalar property with one of `names`.
+    fn scalar(&self, names: &[&str]) -> Option<(usize, Scalar)> {
+        self.properties
+            .iter()
+            .enumerate()
+            .find_map(|(index, property)| match property.ty {
+                PropertyType::Scalar(scalar) if names.contains(&property.name.as_str()) => {
+                    Some((index, scalar))
+                }
+                _ => None,
+            })
+    }
+}
+
+struct Header {
+    encoding: Encoding,
+    elements: Vec<Element>,
+}
+
+/// Parse the header and return it with the remaining bytes.
+fn read_header(bytes: &[u8]) -> Result<(Header, &[u8])> {
+    let mut lines = Vec::new();
+    let mut position = 0;
+    loop {
+        if position >= bytes.len() {
+            return Err(invalid("missing end_header"));
+        }
+        let end = bytes[position..]
+            .iter()
+            .position(|b| *b == b'\n')
+            .map_or(bytes.len(), |offset| position + offset);
+        let line = String::from_utf8_lossy(&bytes[position..end])
+            .trim()
+            .to_owned();
+        position = end + 1;
+        if line == "end_header" {
+            break;
+        }
+        lines.push(line);
+    }
+
+    if lines.first().map(String::as_str) != Some("ply") {
+        return Err(invalid("missing ply magic number"));
+    }
+
+    let mut encoding = None;
+    let mut elements = Vec::<Element>::new();
+    for line in &lines[1..] {
+        let words = line.split_whitespace().collect::<Vec<_>>();
+        match words.as_slice() {
+            [] | ["comment", ..] | ["obj_info", ..] => {}
+            ["format", format, _] => {
+                encoding = Some(match *format {
+                    "ascii" => Encoding::Ascii,
+                    "binary_little_endian" => Encoding::BinaryLittleEndian,
+                    "binary_big_endian" => Encoding::BinaryBigEndian,
+                    _ => return Err(invalid(format!("unknown format {}", format))),
+                })
+            }
+            ["element", name, count] => elements.push(Element {
+                name: (*name).to_owned(),
+                count: count
+                    .parse()
+                    .map_err(|_| invalid(format!("invalid element count {}", count)))?,
+                properties: Vec::new(),
+            }),
+            ["property", ty @ ..] => {
+                let (ty, name) = match ty {
+                    ["list", count, item, name] => (
+                        PropertyType::List {
+                            count: parse_scalar(count)?,
+                            item: parse_scalar(item)?,
+                        },
+                        name,
+                    ),
+                    [scalar, name] => (PropertyType::Scalar(parse_scalar(scalar)?), name),
+                    _ => return Err(invalid(format!("invalid property {}", line))),
+                };
+                elements
+                    .last_mut()
+                    .ok_or_else(|| invalid("property declared before any element"))?
+                    .properties
+                    .push(Property {
+                        name: (*name).to_owned(),
+                        ty,
+                    });
+            }
+            _ => return Err(invalid(format!("unexpected header line {}", line))),
+        }
+    }
+
+    let encoding = encoding.ok_or_else(|| invalid("missing format"))?;
+    let body = bytes.get(position..).unwrap_or_default();
+    Ok((Header { encoding, elements }, body))
+}
+
+fn parse_scalar(name: &str) -> Result<Scalar> {
+    Scalar::from_name(name).ok_or_else(|| invalid(format!("unknown property type {}", name)))
+}
+
+fn read_vertices(element: &Element, body: &mut Body) -> Result<ScanMesh> {
+    let scalar = |names: &[&str]| element.scalar(names);
+    let position_properties = match [scalar(&["x"]), scalar(&["y"]), scalar(&["z"])] {
+        [Some(x), Some(y), Some(z)] => [x.0, y.0, z.0],
+        _ => return Err(invalid("vertices have no x, y and z properties")),
+    };
+    let normal_properties = match [scalar(&["nx"]), scalar(&["ny"]), scalar(&["nz"])] {
+        [Some(x), Some(y), Some(z)] => Some([x.0, y.0, z.0]),
+        _ => None,
+    };
+    let color_properties = match [
+        scalar(&["red", "diffuse_red"]),
+        scalar(&["green", "diffuse_green"]),
+        scalar(&["blue", "diffuse_blue"]),
+    ] {
+        [Some(r), Some(g), Some(b)] => Some([r, g, b]),
+        _ => None,
+    };
+    let alpha_property = scalar(&["alpha", "diffuse_alpha"]);
+
+    let mut positions = Vec::with_capacity(body.row_capacity(element));
+    let mut normals = Vec::new();
+    let mut colors = Vec::new();
+    let mut values = vec![0.0; element.properties.len()];
+    let mut lists = vec![Vec::new(); element.properties.len()];
+    for _ in 0..element.count {
+        body.read_row(element, &mut values, &mut lists)?;
+
+        let [x, y, z] = position_properties;
+        positions.push([values[x] as f32, values[y] as f32, values[z] as f32]);
+
+        if let Some([x, y, z]) = normal_properties {
+            normals.push([values[x] as f32, values[y] as f32, values[z] as f32]);
+        }
+
+        if let Some([r, g, b]) = color_properties {
+            let component = |(index, scalar): (usize, Scalar)| scalar.normalize(values[index]);
+            let alpha = alpha_property.map_or(1.0, component);
+            colors.push([component(r), component(g), component(b), alpha]);
+        }
+    }
+
+    Ok(ScanMesh {
+        positions,
+        normals,
+        colors,
+        indices: None,
+    })
+}
+
+fn read_faces(element: &Element, body: &mut Body, polygons: &mut Vec<Vec<u32>>) -> Result<()> {
+    let indices_property = element.properties.iter().position(|property| {
+        matches!(property.ty, PropertyType::List { .. })
+            && FACE_INDICES.contains(&property.name.as_str())
+    });
+    let indices_property = match indices_property {
+        Some(property) => property,
+        None => return skip_element(element, body),
+    };
+
+    polygons.reserve(body.row_capacity(element));
+    let mut values = vec![0.0; element.properties.len()];
+    let mut lists = vec![Vec::new(); element.properties.len()];
+    for _ in 0..element.count {
+        body.read_row(element, &mut values, &mut lists)?;
+        let polygon = lists[indices_property]
+            .iter()
+            .map(|index| {
+                if *index < 0.0 {
+                    Err(invalid(format!("negative face index {}", index)))
+                } else {
+                    Ok(*index as u32)
+                }
+            })
+            .collect::<Result<Vec<_>>>()?;
+        polygons.push(polygon);
+    }
+    Ok(())
+}
+
+fn skip_element(element: &Element, body: &mut Body) -> Result<()> {
+    let mut values = vec![0.0; element.properties.len()];
+    let mut lists = vec![Vec::new(); element.properties.len()];
+    for _ in 0..element.count {
+        body.read_row(element, &mut values, &mut lists)?;
+    }
+    Ok(())
+}
+
+/// Data following the header.
+struct Body<'a> {
+    encoding: Encoding,
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl Body<'_> {
+    /// Get the number of rows of `element` that can fit in the remaining bytes.
+    ///
+    /// The count of the element comes from the header so it is bounded before
+    /// allocating for its rows.
+    fn row_capacity(&self, element: &Element) -> usize {
+        // Ascii values take at least one digit and a separator
+        let row_size = element
+            .properties
+            .iter()
+            .map(|property| match (self.encoding, property.ty) {
+                (Encoding::Ascii, _) => 2,
+                (_, PropertyType::Scalar(scalar)) => scalar.size(),
+                (_, PropertyType::List { count, .. }) => count.size(),
+            })
+            .sum::<usize>()
+            .max(1);
+        let remaining = self.bytes.len().saturating_sub(self.position);
+        element.count.min(remaining / row_size)
+    }
+
+    /// Read the next row of `element`.
+    ///
+    /// The values of scalar properties are written in `values` and the
+    /// items of list properties in `lists`, both indexed by property.
+    fn read_row(
+        &mut self,
+        element: &Element,
+        values: &mut [f64],
+        lists: &mut [Vec<f64>],
+    ) -> Result<()> {
+        for (index, property) in element.properties.iter().enumerate() {
+            match property.ty {
+                PropertyType::Scalar(scalar) => values[index] = self.read(scalar)?,
+                PropertyType::List { count, item } => {
+                    let count = self.read(count)?;
+                    if count < 0.0 {
+                        return Err(invalid(format!("negative list length {}", count)));
+                    }
+                    let list = &mut lists[index];
+                    list.clear();
+                    for _ in 0..count as usize {
+                        list.push(self.read(item)?);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn read(&mut self, scalar: Scalar) -> Result<f64> {
+        match self.encoding {
+            Encoding::Ascii => self.read_ascii(),
+            Encoding::BinaryLittleEndian => self.read_binary::<LittleEndian>(scalar),
+            Encoding::BinaryBigEndian => self.read_binary::<BigEndian>(scalar),
+        }
+    }
+
+    fn read_ascii(&mut self) -> Result<f64> {
+        let remaining = &self.bytes[self.position..];
+        let start = remaining
+            .iter()
+            .position(|b| !b.is_ascii_whitespace())
+            .ok_or_else(unexpected_end)?;
+        let length = remaining[start..]
+            .iter()
+            .position(u8::is_ascii_whitespace)
+            .unwrap_or(remaining.len() - start);
+        let token = &remaining[start..start + length];
+        self.position += start + length;
+
+        std::str::from_utf8(token)
+            .ok()
+            .and_then(|token| token.parse().ok())
+            .ok_or_else(|| invalid(format!("invalid value {}", String::from_utf8_lossy(token))))
+    }
+
+    fn read_binary<B: ByteOrder>(&mut self, scalar: Scalar) -> Result<f64> {
+        let end = self.position + scalar.size();
+        let bytes = self
+            .bytes
+            .get(self.position..end)
+            .ok_or_else(unexpected_end)?;
+        self.position = end;
+
+        Ok(match scalar {
+            Scalar::I8 => f64::from(bytes[0] as i8),
+            Scalar::U8 => f64::from(bytes[0]),
+            Scalar::I16 => f64::from(B::read_i16(bytes)),
+            Scalar::U16 => f64::from(B::read_u16(bytes)),
+            Scalar::I32 => f64::from(B::read_i32(bytes)),
+            Scalar::U32 => f64::from(B::read_u32(bytes)),
+            Scalar::F32 => f64::from(B::read_f32(bytes)),
+            Scalar::F64 => B::read_f64(bytes),
+        })
+    }
+}
+
+fn invalid<M: Display>(message: M) -> ModelLoadingError {
+    ModelLoadingError::Parse(format!("Invalid PLY file: {}", message))
+}
+
+fn unexpected_end() -> ModelLoadingError {
+    invalid("unexpected end of file")
+}
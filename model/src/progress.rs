@@ -0,0 +1,80 @@
+//! Progress reporting and cancellation of model loading.
+
+use super::error::ModelLoadingError;
+use std::fmt;
+
+/// Stages of the loading of a model, in order.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum LoadingStage {
+    /// Parsing the glTF document.
+    Parse,
+    /// Reading the buffers.
+    Buffers,
+    /// Reading the vertices and indices of the meshes.
+    Meshes,
+    /// Generating the missing tangents.
+    Tangents,
+    /// Decoding the images and preparing the textures.
+    Textures,
+    /// Recording the upload of the data to the GPU.
+    Upload,
+}
+
+const STAGES: [LoadingStage; 6] = [
+    LoadingStage::Parse,
+    LoadingStage::Buffers,
+    LoadingStage::Meshes,
+    LoadingStage::Tangents,
+    LoadingStage::Textures,
+    LoadingStage::Upload,
+];
+
+impl LoadingStage {
+    pub fn all() -> &'static [LoadingStage] {
+        &STAGES
+    }
+
+    /// Fraction of the loading that is done when entering this stage.
+    pub fn progress(self) -> f32 {
+        let index = STAGES.iter().position(|stage| *stage == self).unwrap();
+        index as f32 / STAGES.len() as f32
+    }
+}
+
+impl fmt::Display for LoadingStage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            LoadingStage::Parse => "Parsing document",
+            LoadingStage::Buffers => "Reading buffers",
+            LoadingStage::Meshes => "Reading meshes",
+            LoadingStage::Tangents => "Generating tangents",
+            LoadingStage::Textures => "Decoding textures",
+            LoadingStage::Upload => "Uploading to the GPU",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Called each time the loading enters a new stage.
+///
+/// Returning `false` cancels the loading, which then fails with
+/// `ModelLoadingError::Cancelled`.
+pub type ProgressCallback<'a> = dyn FnMut(LoadingStage) -> bool + 'a;
+
+/// Report that the loading enters `stage` and check it was not cancelled.
+pub(crate) fn enter_stage(
+    progress: &mut ProgressCallback,
+    stage: LoadingStage,
+) -> Result<(), ModelLoadingError> {
+    log::debug!("{}", stage);
+    if progress(stage) {
+        Ok(())
+    } else {
+        Err(ModelLoadingError::Cancelled)
+    }
+}
+
+/// Progress callback that never cancels the loading.
+pub(crate) fn no_progress(_stage: LoadingStage) -> bool {
+    true
+}
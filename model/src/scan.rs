@@ -0,0 +1,204 @@
+//! STL and PLY import.
+//!
+//! Scans and CAD exports are converted into an in-memory glTF document made of
+//! a single node with a mesh of a single primitive, like OBJ files. They have
+//! no material so the default one is used, except for point clouds without
+//! normals which are unlit since they can't be shaded.
+//!
+//! STL facets are flat so their vertices are not shared and get the normal of
+//! their facet. PLY vertices are kept as they are: polygons are triangulated,
+//! missing normals are computed from the faces around each vertex and files
+//! without faces are rendered as points.
+
+use super::{
+    error::ModelLoadingError,
+    export::{position_bounds, push, DocumentBuilder},
+    obj::generate_normals,
+};
+use gltf::{
+    buffer::Data as BufferData,
+    json::{
+        self,
+        buffer::Target,
+        extensions::material::Unlit,
+        mesh::{Mode, Semantic},
+        validation::Checked::Valid,
+    },
+    Document,
+};
+use math::cgmath::{InnerSpace, Vector3};
+use std::{collections::HashMap, fs::File, io::BufReader, path::Path};
+
+type Result<T> = std::result::Result<T, ModelLoadingError>;
+
+/// Geometry read from a scan or CAD file.
+#[derive(Debug)]
+pub(crate) struct ScanMesh {
+    pub positions: Vec<[f32; 3]>,
+    /// Normals of each vertex or empty if the file has none.
+    pub normals: Vec<[f32; 3]>,
+    /// Colors of each vertex or empty if the file has none.
+    pub colors: Vec<[f32; 4]>,
+    /// Indices of the triangles or `None` if the file only contains points.
+    pub indices: Option<Vec<u32>>,
+}
+
+/// Convert the STL file at `path` to glTF.
+pub(crate) fn import_stl(path: &Path) -> Result<(Document, Vec<BufferData>)> {
+    let mesh = read_stl(path)?;
+    to_document(mesh, path)
+}
+
+/// Convert the PLY file at `path` to glTF.
+pub(crate) fn import_ply(path: &Path) -> Result<(Document, Vec<BufferData>)> {
+    let bytes = std::fs::read(path)?;
+    let mesh = super::ply::read_ply(&bytes)?;
+    to_document(mesh, path)
+}
+
+/// Read the facets of an ascii or binary STL file.
+fn read_stl(path: &Path) -> Result<ScanMesh> {
+    let invalid = |error| ModelLoadingError::Parse(format!("Invalid STL file: {}", error));
+
+    let mut reader = BufReader::new(File::open(path)?);
+    let triangles = stl_io::create_stl_reader(&mut reader).map_err(invalid)?;
+
+    let mut positions = Vec::new();
+    let mut normals = Vec::new();
+    for triangle in triangles {
+        let triangle = triangle.map_err(invalid)?;
+        let [p0, p1, p2] = triangle.vertices;
+        let (p0, p1, p2) = (
+            Vector3::from(p0.0),
+            Vector3::from(p1.0),
+            Vector3::from(p2.0),
+        );
+
+        // Stored normals are often missing or wrong so they are only a fallback
+        let normal = (p1 - p0).cross(p2 - p0);
+        let normal = if normal.magnitude2() > 0.0 {
+            normal.normalize()
+        } else {
+            let stored = Vector3::from(triangle.normal.0);
+            if stored.magnitude2() > 0.0 {
+                stored.normalize()
+            } else {
+                Vector3::unit_z()
+            }
+        };
+
+        positions.extend_from_slice(&[p0.into(), p1.into(), p2.into()]);
+        normals.extend_from_slice(&[normal.into(); 3]);
+    }
+
+    let indices = (0..positions.len() as u32).collect();
+    Ok(ScanMesh {
+        positions,
+        normals,
+        colors: Vec::new(),
+        indices: Some(indices),
+    })
+}
+
+fn to_document(mesh: ScanMesh, path: &Path) -> Result<(Document, Vec<BufferData>)> {
+    let name = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or_default();
+    let ScanMesh {
+        positions,
+        mut normals,
+        colors,
+        indices,
+    } = mesh;
+    if positions.is_empty() {
+        return Err(ModelLoadingError::EmptyScene);
+    }
+
+    let mut builder = DocumentBuilder::default();
+    let mut attributes = HashMap::new();
+
+    let bounds = position_bounds(&positions);
+    let accessor = builder.push_accessor(&positions, Some(Target::ArrayBuffer), bounds);
+    attributes.insert(Valid(Semantic::Positions), accessor);
+
+    if normals.len() != positions.len() {
+        normals = match indices.as_ref() {
+            Some(indices) => generate_normals(&positions, indices),
+            None => Vec::new(),
+        };
+    }
+    if !normals.is_empty() {
+        let accessor = builder.push_accessor(&normals, Some(Target::ArrayBuffer), None);
+        attributes.insert(Valid(Semantic::Normals), accessor);
+    }
+
+    if colors.len() == positions.len() {
+        let accessor = builder.push_accessor(&colors, Some(Target::ArrayBuffer), None);
+        attributes.insert(Valid(Semantic::Colors(0)), accessor);
+    }
+
+    let (mode, indices) = match indices {
+        Some(indices) => {
+            let accessor = builder.push_accessor(&indices, Some(Target::ElementArrayBuffer), None);
+            (Mode::Triangles, Some(accessor))
+        }
+        None => (Mode::Points, None),
+    };
+
+    let material = if normals.is_empty() {
+        builder.use_extension("KHR_materials_unlit");
+        let material = json::Material {
+            name: Some(String::from("Unlit points")),
+            extensions: Some(json::extensions::material::Material {
+                unlit: Some(Unlit {}),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        Some(push(&mut builder.root.materials, material))
+    } else {
+        None
+    };
+
+    let primitive = json::mesh::Primitive {
+        attributes,
+        extensions: None,
+        extras: Default::default(),
+        indices,
+        material,
+        mode: Valid(mode),
+        targets: None,
+    };
+    let mesh = push(
+        &mut builder.root.meshes,
+        json::Mesh {
+            extensions: None,
+            extras: Default::default(),
+            name: Some(name.to_owned()),
+            primitives: vec![primitive],
+            weights: None,
+        },
+    );
+    let node = push(
+        &mut builder.root.nodes,
+        json::Node {
+            camera: None,
+            children: None,
+            extensions: None,
+            extras: Default::default(),
+            matrix: None,
+            mesh: Some(mesh),
+            name: Some(name.to_owned()),
+            rotation: None,
+            scale: None,
+            translation: None,
+            skin: None,
+            weights: None,
+        },
+    );
+
+    let (root, buffer) = builder.finish(name, vec![node], None);
+    let document = Document::from_json(root)?;
+    Ok((document, vec![BufferData(buffer)]))
+}
@@ -0,0 +1,654 @@
+use super::{
+    bounds, create_lights_from_gltf, create_skins_from_gltf, export, import, load_animations,
+    mesh::read_geometry_from_gltf,
+    metadata::{Metadata, Severity},
+    obj::{self, ObjImport},
+    picking,
+    progress::{enter_stage, no_progress, LoadingStage, ProgressCallback},
+    scan,
+    statistics::Statistics,
+    texture::read_textures_from_gltf,
+    validation::validate,
+    vertex_frames, Animations, ExportFormat, Geometry, Hit, Light, Material, ModelExportError,
+    ModelLoadingError, NodeBounds, Nodes, PlaybackMode, PlaybackState, ResourceResolver, RgbaImage,
+    Skin, TextureData, TextureReference, TextureSlot, VertexFrame, SUPPORTED_EXTENSIONS,
+};
+use cgmath::{Matrix4, Quaternion, SquareMatrix, Vector3};
+use gltf::{buffer::Data as BufferData, image::Data as ImageData, Document, Gltf};
+use math::*;
+use std::{
+    fs::File,
+    io::{BufReader, Read},
+    path::Path,
+};
+
+/// How the scene is placed in the world.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Placement {
+    /// The scene is scaled so its larger side is 10 units long and centered at the origin.
+    Normalized,
+    /// The scene keeps its original units and position.
+    Original,
+}
+
+impl Default for Placement {
+    fn default() -> Self {
+        Placement::Normalized
+    }
+}
+
+/// CPU-side representation of a glTF model.
+///
+/// It can be built and queried without a Vulkan context.
+/// Use `Model::create_from_scene` to upload it on the GPU.
+pub struct Scene {
+    metadata: Metadata,
+    geometry: Geometry,
+    nodes: Nodes,
+    placement: Placement,
+    unit_cube_transform: Matrix4<f32>,
+    transform: Matrix4<f32>,
+    animations: Option<Animations>,
+    skins: Vec<Skin>,
+    images: Vec<RgbaImage>,
+    textures: Vec<TextureData>,
+    lights: Vec<Light>,
+}
+
+impl Scene {
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, ModelLoadingError> {
+        Self::from_file_with_progress(path, &mut no_progress)
+    }
+
+    /// Create a scene from a file and report each loading stage to `progress`.
+    ///
+    /// The loading is cancelled as soon as `progress` returns `false`.
+    pub fn from_file_with_progress<P: AsRef<Path>>(
+        path: P,
+        progress: &mut ProgressCallback,
+    ) -> Result<Self, ModelLoadingError> {
+        log::debug!("Importing gltf file");
+        let path = path.as_ref();
+
+        enter_stage(progress, LoadingStage::Parse)?;
+        let reader = BufReader::new(File::open(path)?);
+        let Gltf { document, blob } = Gltf::from_reader(reader)?;
+
+        enter_stage(progress, LoadingStage::Buffers)?;
+        let base = path.parent().unwrap_or_else(|| Path::new("./"));
+        let mut resolver = import::file_resolver(base);
+        let buffers = import::import_buffers(&document, blob, &mut resolver)?;
+
+        Self::create(
+            document,
+            buffers,
+            |document, buffers| import::import_images(document, buffers, &mut resolver),
+            file_name(path),
+            Some(path),
+            progress,
+        )
+    }
+
+    /// Create a scene from a Wavefront OBJ file and its MTL material libraries.
+    pub fn from_obj_file<P: AsRef<Path>>(path: P) -> Result<Self, ModelLoadingError> {
+        Self::from_obj_file_with_progress(path, &mut no_progress)
+    }
+
+    /// Create a scene from a Wavefront OBJ file and report each loading stage to `progress`.
+    ///
+    /// The loading is cancelled as soon as `progress` returns `false`.
+    pub fn from_obj_file_with_progress<P: AsRef<Path>>(
+        path: P,
+        progress: &mut ProgressCallback,
+    ) -> Result<Self, ModelLoadingError> {
+        log::debug!("Importing obj file");
+        let path = path.as_ref();
+
+        enter_stage(progress, LoadingStage::Parse)?;
+        let ObjImport {
+            document,
+            buffers,
+            images,
+            resources,
+        } = obj::import_obj(path)?;
+
+        let base = path.parent().unwrap_or_else(|| Path::new("./"));
+        let mut scene = Self::create(
+            document,
+            buffers,
+            |_, _| Ok(obj::load_images(&images, base)),
+            file_name(path),
+            Some(path),
+            progress,
+        )?;
+        scene.metadata.set_external_resources(resources);
+
+        Ok(scene)
+    }
+
+    /// Create a scene from an ascii or binary STL file.
+    pub fn from_stl_file<P: AsRef<Path>>(path: P) -> Result<Self, ModelLoadingError> {
+        Self::from_stl_file_with_progress(path, &mut no_progress)
+    }
+
+    /// Create a scene from an STL file and report each loading stage to `progress`.
+    ///
+    /// The loading is cancelled as soon as `progress` returns `false`.
+    pub fn from_stl_file_with_progress<P: AsRef<Path>>(
+        path: P,
+        progress: &mut ProgressCallback,
+    ) -> Result<Self, ModelLoadingError> {
+        log::debug!("Importing stl file");
+        let path = path.as_ref();
+
+        enter_stage(progress, LoadingStage::Parse)?;
+        let (document, buffers) = scan::import_stl(path)?;
+        Self::create(
+            document,
+            buffers,
+            |_, _| Ok(Vec::new()),
+            file_name(path),
+            Some(path),
+            progress,
+        )
+    }
+
+    /// Create a scene from an ascii or binary PLY file.
+    ///
+    /// Files without faces are rendered as points.
+    pub fn from_ply_file<P: AsRef<Path>>(path: P) -> Result<Self, ModelLoadingError> {
+        Self::from_ply_file_with_progress(path, &mut no_progress)
+    }
+
+    /// Create a scene from a PLY file and report each loading stage to `progress`.
+    ///
+    /// The loading is cancelled as soon as `progress` returns `false`.
+    pub fn from_ply_file_with_progress<P: AsRef<Path>>(
+        path: P,
+        progress: &mut ProgressCallback,
+    ) -> Result<Self, ModelLoadingError> {
+        log::debug!("Importing ply file");
+        let path = path.as_ref();
+
+        enter_stage(progress, LoadingStage::Parse)?;
+        let (document, buffers) = scan::import_ply(path)?;
+        Self::create(
+            document,
+            buffers,
+            |_, _| Ok(Vec::new()),
+            file_name(path),
+            Some(path),
+            progress,
+        )
+    }
+
+    /// Create a scene from a glb or a self-contained glTF in memory.
+    ///
+    /// Embedded data uris are supported but external resources are not.
+    /// Use `from_slice_with_resolver` for those.
+    pub fn from_slice(name: &str, slice: &[u8]) -> Result<Self, ModelLoadingError> {
+        log::debug!("Importing gltf from memory");
+        let (document, buffers, images) = import::import_slice(slice)?;
+        Self::create(
+            document,
+            buffers,
+            |_, _| Ok(images),
+            name,
+            None,
+            &mut no_progress,
+        )
+    }
+
+    /// Create a scene from a glb or a self-contained glTF read from `reader`.
+    pub fn from_reader<R: Read>(name: &str, reader: R) -> Result<Self, ModelLoadingError> {
+        log::debug!("Importing gltf from reader");
+        let (document, buffers, images) = import::import_reader(reader)?;
+        Self::create(
+            document,
+            buffers,
+            |_, _| Ok(images),
+            name,
+            None,
+            &mut no_progress,
+        )
+    }
+
+    /// Create a scene from a glTF in memory.
+    ///
+    /// External buffers and images are loaded by calling `resolver` with their uri.
+    pub fn from_slice_with_resolver(
+        name: &str,
+        slice: &[u8],
+        resolver: &mut ResourceResolver,
+    ) -> Result<Self, ModelLoadingError> {
+        log::debug!("Importing gltf from memory");
+        let (document, buffers, images) = import::import_slice_with_resolver(slice, resolver)?;
+        Self::create(
+            document,
+            buffers,
+            |_, _| Ok(images),
+            name,
+            None,
+            &mut no_progress,
+        )
+    }
+
+    /// Build the scene from an imported document.
+    ///
+    /// `import_images` is only called once the geometry is read so images
+    /// are decoded during the textures stage.
+    fn create<F>(
+        document: Document,
+        buffers: Vec<BufferData>,
+        import_images: F,
+        name: &str,
+        path: Option<&Path>,
+        progress: &mut ProgressCallback,
+    ) -> Result<Self, ModelLoadingError>
+    where
+        F: FnOnce(&Document, &[BufferData]) -> Result<Vec<ImageData>, ModelLoadingError>,
+    {
+        log::debug!("Creating the scene");
+        if let Some(extension) = document
+            .extensions_required()
+            .find(|extension| !SUPPORTED_EXTENSIONS.contains(extension))
+        {
+            // The metadata is not built so the errors are reported with the loading error
+            let issues = validate(&document, &buffers)
+                .into_iter()
+                .filter(|issue| issue.severity == Severity::Error)
+                .collect();
+            return Err(ModelLoadingError::UnsupportedExtension {
+                extension: String::from(extension),
+                issues,
+            });
+        }
+
+        let scene = document
+            .default_scene()
+            .or_else(|| document.scenes().next())
+            .ok_or(ModelLoadingError::NoScene)?;
+
+        enter_stage(progress, LoadingStage::Meshes)?;
+        let geometry = read_geometry_from_gltf(&document, &buffers, progress)?
+            .ok_or(ModelLoadingError::EmptyScene)?;
+
+        let animations = load_animations(document.animations(), &buffers);
+
+        let skins = create_skins_from_gltf(document.skins(), &buffers);
+
+        let nodes = Nodes::from_gltf_nodes(document.nodes(), &scene);
+
+        // Bounds in the model units, which are meters according to the glTF specification
+        let original_aabb = compute_aabb(&nodes, &geometry).ok_or(ModelLoadingError::EmptyScene)?;
+        let unit_cube_transform = compute_unit_cube_at_origin_transform(original_aabb);
+
+        enter_stage(progress, LoadingStage::Textures)?;
+        let images = import_images(&document, &buffers)?;
+        let (images, textures) = read_textures_from_gltf(document.textures(), images)?;
+
+        let lights = create_lights_from_gltf(&document);
+
+        let statistics = Statistics::new(&document, &geometry, &nodes, &images);
+        let metadata = Metadata::new(name, path, &document, &buffers, original_aabb, statistics);
+
+        let mut scene = Scene {
+            metadata,
+            geometry,
+            nodes,
+            placement: Placement::default(),
+            unit_cube_transform,
+            transform: Matrix4::identity(),
+            animations,
+            skins,
+            images,
+            textures,
+            lights,
+        };
+        scene.apply_global_transform();
+
+        Ok(scene)
+    }
+}
+
+impl Scene {
+    pub fn update(&mut self, delta_time: f32) -> bool {
+        let updated = if let Some(animations) = self.animations.as_mut() {
+            animations.update(&mut self.nodes, delta_time)
+        } else {
+            false
+        };
+
+        if updated {
+            self.apply_global_transform();
+        }
+
+        updated
+    }
+
+    /// Change how the scene is placed in the world.
+    pub fn set_placement(&mut self, placement: Placement) {
+        if self.placement == placement {
+            return;
+        }
+        self.placement = placement;
+        self.apply_global_transform();
+    }
+
+    /// Set the transform applied to the whole scene after its placement.
+    pub fn set_transform(&mut self, transform: Matrix4<f32>) {
+        self.transform = transform;
+        self.apply_global_transform();
+    }
+
+    /// Set the translation, rotation and scale of the node at `index` relative to its parent.
+    ///
+    /// The global transforms of the nodes and the joint matrices are updated.
+    pub fn set_node_transform(
+        &mut self,
+        index: usize,
+        translation: Vector3<f32>,
+        rotation: Quaternion<f32>,
+        scale: Vector3<f32>,
+    ) {
+        if let Some(node) = self.nodes.nodes_mut().get_mut(index) {
+            node.edit_transform(translation, rotation, scale);
+            self.apply_global_transform();
+        }
+    }
+
+    /// Restore the local transform the node at `index` has in the file.
+    pub fn reset_node_transform(&mut self, index: usize) {
+        if let Some(node) = self.nodes.nodes_mut().get_mut(index) {
+            node.reset_transform();
+            self.apply_global_transform();
+        }
+    }
+
+    /// Replace the material of all the primitives using the material with the same index.
+    pub fn set_material(&mut self, material: Material) {
+        self.geometry.set_material(material);
+    }
+
+    fn apply_global_transform(&mut self) {
+        self.nodes.transform(Some(self.global_transform()));
+        let nodes = &self.nodes;
+        let skins = &mut self.skins;
+        nodes
+            .get_skins_transform()
+            .iter()
+            .for_each(|(index, transform)| {
+                skins[*index].compute_joints_matrices(*transform, nodes.nodes());
+            });
+    }
+}
+
+/// Export methods
+impl Scene {
+    /// Export the scene to `path`.
+    ///
+    /// The scene is exported as binary glTF if `path` has the `glb` extension.
+    /// Otherwise a glTF document and its `.bin` buffer are written.
+    pub fn export<P: AsRef<Path>>(&self, path: P) -> Result<(), ModelExportError> {
+        let path = path.as_ref();
+        export::write_scene(self, path, ExportFormat::from_path(path), &[])
+    }
+
+    /// Export the scene to `path` in the given `format`.
+    ///
+    /// `node_visibility` is indexed like the nodes of the scene. The mesh of
+    /// hidden nodes is not exported and missing nodes are considered visible.
+    pub fn export_as<P: AsRef<Path>>(
+        &self,
+        path: P,
+        format: ExportFormat,
+        node_visibility: &[bool],
+    ) -> Result<(), ModelExportError> {
+        export::write_scene(self, path.as_ref(), format, node_visibility)
+    }
+
+    /// Encode the scene as binary glTF.
+    pub fn to_glb(&self) -> Result<Vec<u8>, ModelExportError> {
+        export::to_glb(self, &[])
+    }
+}
+
+/// Animations methods
+impl Scene {
+    pub fn get_animation_playback_state(&self) -> Option<PlaybackState> {
+        self.animations
+            .as_ref()
+            .map(Animations::get_playback_state)
+            .copied()
+    }
+
+    pub fn set_current_animation(&mut self, animation_index: usize) {
+        if let Some(animations) = self.animations.as_mut() {
+            animations.set_current(animation_index);
+        }
+    }
+
+    pub fn set_animation_playback_mode(&mut self, playback_mode: PlaybackMode) {
+        if let Some(animations) = self.animations.as_mut() {
+            animations.set_playback_mode(playback_mode);
+        }
+    }
+
+    pub fn toggle_animation(&mut self) {
+        if let Some(animations) = self.animations.as_mut() {
+            animations.toggle();
+        }
+    }
+
+    pub fn stop_animation(&mut self) {
+        if let Some(animations) = self.animations.as_mut() {
+            animations.stop();
+        }
+    }
+
+    pub fn reset_animation(&mut self) {
+        if let Some(animations) = self.animations.as_mut() {
+            animations.reset();
+        }
+    }
+
+    pub fn set_animation_playback_state(&mut self, playback_state: PlaybackState) {
+        let updated = match self.animations.as_mut() {
+            Some(animations) => animations.set_playback_state(&mut self.nodes, playback_state),
+            None => false,
+        };
+
+        if updated {
+            self.apply_global_transform();
+        }
+    }
+}
+
+/// Picking
+impl Scene {
+    /// Find the closest primitive hit by `ray`.
+    ///
+    /// `ray` is in world space. Skinned primitives are tested in their current pose.
+    pub fn pick(&self, ray: Ray<f32>) -> Option<Hit> {
+        picking::pick(self, ray, |_| true)
+    }
+
+    /// Find the closest primitive hit by `ray` among the nodes accepted by `node_filter`.
+    ///
+    /// `node_filter` is called with the index of each node with a mesh.
+    pub fn pick_filtered<F>(&self, ray: Ray<f32>, node_filter: F) -> Option<Hit>
+    where
+        F: Fn(usize) -> bool,
+    {
+        picking::pick(self, ray, node_filter)
+    }
+}
+
+/// Culling
+impl Scene {
+    /// Compute the world space bounds of the nodes in their current pose.
+    ///
+    /// The result is indexed like the nodes. Nodes without mesh have no bounds.
+    pub fn world_bounds(&self) -> Vec<Option<NodeBounds>> {
+        bounds::compute_world_bounds(self)
+    }
+}
+
+/// Debugging
+impl Scene {
+    /// Get the world space tangent frames of the vertices of a primitive.
+    ///
+    /// `primitive_index` is the index of the primitive in the mesh of the node
+    /// at `node_index`. Skinned vertices are in their current pose.
+    pub fn vertex_frames(&self, node_index: usize, primitive_index: usize) -> Vec<VertexFrame> {
+        vertex_frames::compute_vertex_frames(self, node_index, primitive_index)
+    }
+}
+
+/// Getters
+impl Scene {
+    pub fn metadata(&self) -> &Metadata {
+        &self.metadata
+    }
+
+    pub fn geometry(&self) -> &Geometry {
+        &self.geometry
+    }
+
+    pub fn primitive_count(&self) -> usize {
+        self.geometry
+            .meshes()
+            .iter()
+            .map(|mesh| mesh.primitives().len())
+            .sum()
+    }
+
+    pub fn skins(&self) -> &[Skin] {
+        &self.skins
+    }
+
+    pub fn nodes(&self) -> &Nodes {
+        &self.nodes
+    }
+
+    pub fn placement(&self) -> Placement {
+        self.placement
+    }
+
+    pub fn transform(&self) -> Matrix4<f32> {
+        self.transform
+    }
+
+    /// Get the transform applied to the root nodes of the scene.
+    ///
+    /// It combines the scene transform and the placement transform.
+    pub fn global_transform(&self) -> Matrix4<f32> {
+        let placement_transform = match self.placement {
+            Placement::Normalized => self.unit_cube_transform,
+            Placement::Original => Matrix4::identity(),
+        };
+        self.transform * placement_transform
+    }
+
+    /// Get the global transform of the parent of the node at `index`.
+    ///
+    /// It is the global transform of the scene for root nodes.
+    pub fn node_parent_transform(&self, index: usize) -> Matrix4<f32> {
+        self.nodes.parent_index(index).map_or_else(
+            || self.global_transform(),
+            |parent| self.nodes.nodes()[parent].transform(),
+        )
+    }
+
+    /// Get the AABB of the scene in world space.
+    pub fn aabb(&self) -> Option<AABB<f32>> {
+        compute_aabb(&self.nodes, &self.geometry)
+    }
+
+    pub fn images(&self) -> &[RgbaImage] {
+        &self.images
+    }
+
+    pub fn textures(&self) -> &[TextureData] {
+        &self.textures
+    }
+
+    /// Get the material slots referencing each texture.
+    ///
+    /// The references are indexed like `textures`. Disabled textures are still
+    /// referenced and each material is only listed once.
+    pub fn texture_references(&self) -> Vec<Vec<TextureReference>> {
+        let mut references = vec![Vec::new(); self.textures.len()];
+        let materials = self
+            .geometry
+            .meshes()
+            .iter()
+            .flat_map(|mesh| mesh.primitives())
+            .map(|primitive| primitive.material());
+        for material in materials {
+            for slot in TextureSlot::ALL.iter().copied() {
+                let texture = match material.get_texture(slot) {
+                    Some(texture) => texture,
+                    None => continue,
+                };
+                let reference = TextureReference {
+                    material_index: material.get_index(),
+                    slot,
+                };
+                if let Some(references) = references.get_mut(texture.get_index()) {
+                    if !references.contains(&reference) {
+                        references.push(reference);
+                    }
+                }
+            }
+        }
+        references
+    }
+
+    pub fn lights(&self) -> &[Light] {
+        &self.lights
+    }
+
+    pub(crate) fn animations(&self) -> Option<&Animations> {
+        self.animations.as_ref()
+    }
+}
+
+/// Compute the AABB of all the nodes with a mesh.
+///
+/// Returns `None` if no node has a renderable mesh.
+fn compute_aabb(nodes: &Nodes, geometry: &Geometry) -> Option<AABB<f32>> {
+    let aabbs = nodes
+        .nodes()
+        .iter()
+        .filter_map(|n| {
+            n.mesh_index()
+                .and_then(|index| geometry.meshes()[index].aabb())
+                .map(|aabb| aabb.transform(n.transform()))
+        })
+        .collect::<Vec<_>>();
+    AABB::union(&aabbs)
+}
+
+fn compute_unit_cube_at_origin_transform(aabb: AABB<f32>) -> Matrix4<f32> {
+    let larger_side = aabb.get_larger_side_size();
+    // Bounds of zero size, like the ones of a single point, are only centered
+    let scale_factor = if larger_side > 0.0 && larger_side.is_finite() {
+        (1.0_f32 / larger_side) * 10.0
+    } else {
+        1.0
+    };
+
+    let aabb = aabb * scale_factor;
+    let center = aabb.get_center();
+
+    let translation = Matrix4::from_translation(-center);
+    let scale = Matrix4::from_scale(scale_factor);
+    translation * scale
+}
+
+fn file_name(path: &Path) -> &str {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or_default()
+}
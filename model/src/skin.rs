@@ -42,9 +42,13 @@ impl Joint {
     }
 
     fn compute_matrix(&mut self, transform: Matrix4<f32>, nodes: &[Node]) {
-        let global_transform_inverse = transform
-            .invert()
-            .expect("Transform matrix should be invertible");
+        let global_transform_inverse = match transform.invert() {
+            Some(inverse) => inverse,
+            None => {
+                log::warn!("Skin transform is not invertible. Skipping joint update");
+                return;
+            }
+        };
         let node_transform = nodes[self.node_id].transform();
 
         self.matrix = global_transform_inverse * node_transform * self.inverse_bind_matrix;
@@ -55,6 +59,15 @@ impl Joint {
     pub fn matrix(&self) -> Matrix4<f32> {
         self.matrix
     }
+
+    pub(crate) fn inverse_bind_matrix(&self) -> Matrix4<f32> {
+        self.inverse_bind_matrix
+    }
+
+    /// Index of the node driving the joint.
+    pub fn node_id(&self) -> usize {
+        self.node_id
+    }
 }
 
 pub fn create_skins_from_gltf(gltf_skins: GltfSkins, data: &[Data]) -> Vec<Skin> {
@@ -84,12 +97,17 @@ fn map_skin(gltf_skin: &GltfSkin, data: &[Data]) -> Skin {
     Skin { joints }
 }
 
+/// Read the inverse bind matrices of the skin.
+///
+/// When the skin does not define them, identity matrices are used as per the spec.
 fn map_inverse_bind_matrices(gltf_skin: &GltfSkin, data: &[Data]) -> Vec<Matrix4<f32>> {
-    let iter = gltf_skin
+    match gltf_skin
         .reader(|buffer| Some(&data[buffer.index()]))
         .read_inverse_bind_matrices()
-        .expect("IBM reader not found for skin");
-    iter.map(Matrix4::from).collect::<Vec<_>>()
+    {
+        Some(iter) => iter.map(Matrix4::from).collect::<Vec<_>>(),
+        None => vec![Matrix4::identity(); gltf_skin.joints().count()],
+    }
 }
 
 fn map_node_ids(gltf_skin: &GltfSkin) -> Vec<usize> {
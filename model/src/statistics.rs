@@ -0,0 +1,164 @@
+//! Geometry and memory statistics used to check assets against performance budgets.
+
+use super::{Geometry, Nodes, RgbaImage};
+use gltf::{mesh::Mode, mesh::Semantic, Document, Primitive as GltfPrimitive};
+use serde::Serialize;
+use std::mem::size_of_val;
+
+/// Size in bytes of a texel of the textures uploaded on the GPU.
+const TEXEL_SIZE: u64 = 4;
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Statistics {
+    pub vertex_count: usize,
+    pub triangle_count: usize,
+    /// Number of draw calls issued by each geometry pass.
+    pub draw_call_count: usize,
+    pub vertex_buffer_size: u64,
+    pub index_buffer_size: u64,
+    pub meshes: Vec<MeshStatistics>,
+    pub images: Vec<ImageStatistics>,
+    pub skins: Vec<SkinStatistics>,
+}
+
+impl Statistics {
+    pub(crate) fn new(
+        document: &Document,
+        geometry: &Geometry,
+        nodes: &Nodes,
+        images: &[RgbaImage],
+    ) -> Self {
+        let meshes = document
+            .meshes()
+            .map(|mesh| MeshStatistics {
+                index: mesh.index(),
+                name: mesh.name().map(String::from),
+                primitives: mesh.primitives().map(PrimitiveStatistics::from).collect(),
+            })
+            .collect::<Vec<_>>();
+
+        let primitives = meshes.iter().flat_map(|mesh| mesh.primitives.iter());
+        let vertex_count = primitives.clone().map(|p| p.vertex_count).sum();
+        let triangle_count = primitives.map(|p| p.triangle_count).sum();
+
+        let draw_call_count = nodes
+            .nodes()
+            .iter()
+            .filter_map(|node| node.mesh_index())
+            .map(|index| geometry.meshes()[index].primitives().len())
+            .sum();
+
+        let images = images
+            .iter()
+            .enumerate()
+            .map(|(index, image)| ImageStatistics::new(index, image))
+            .collect();
+
+        let skins = document
+            .skins()
+            .map(|skin| SkinStatistics {
+                index: skin.index(),
+                name: skin.name().map(String::from),
+                joint_count: skin.joints().count(),
+            })
+            .collect();
+
+        Self {
+            vertex_count,
+            triangle_count,
+            draw_call_count,
+            vertex_buffer_size: size_of_val(geometry.vertices()) as _,
+            index_buffer_size: size_of_val(geometry.indices()) as _,
+            meshes,
+            images,
+            skins,
+        }
+    }
+
+    /// Total size in bytes of the textures on the GPU, mipmaps included.
+    pub fn texture_memory(&self) -> u64 {
+        self.images.iter().map(|image| image.gpu_size).sum()
+    }
+
+    /// Total size in bytes of the vertex, index and texture data on the GPU.
+    pub fn gpu_memory(&self) -> u64 {
+        self.vertex_buffer_size + self.index_buffer_size + self.texture_memory()
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct MeshStatistics {
+    pub index: usize,
+    pub name: Option<String>,
+    pub primitives: Vec<PrimitiveStatistics>,
+}
+
+#[derive(Copy, Clone, Debug, Serialize)]
+pub struct PrimitiveStatistics {
+    pub index: usize,
+    pub vertex_count: usize,
+    pub index_count: Option<usize>,
+    pub triangle_count: usize,
+}
+
+impl From<GltfPrimitive<'_>> for PrimitiveStatistics {
+    fn from(primitive: GltfPrimitive) -> Self {
+        let vertex_count = primitive
+            .get(&Semantic::Positions)
+            .map_or(0, |positions| positions.count());
+        let index_count = primitive.indices().map(|indices| indices.count());
+        let element_count = index_count.unwrap_or(vertex_count);
+        let triangle_count = match primitive.mode() {
+            Mode::Triangles => element_count / 3,
+            Mode::TriangleStrip | Mode::TriangleFan => element_count.saturating_sub(2),
+            _ => 0,
+        };
+
+        Self {
+            index: primitive.index(),
+            vertex_count,
+            index_count,
+            triangle_count,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, Serialize)]
+pub struct ImageStatistics {
+    pub index: usize,
+    pub width: u32,
+    pub height: u32,
+    pub mip_levels: u32,
+    /// Size in bytes on the GPU, mipmaps included.
+    pub gpu_size: u64,
+}
+
+impl ImageStatistics {
+    fn new(index: usize, image: &RgbaImage) -> Self {
+        let (width, height) = (image.width, image.height);
+        // Same mip chain as the one created when uploading the texture
+        let mip_levels = ((width.min(height) as f32).log2().floor() + 1.0) as u32;
+        let gpu_size = (0..mip_levels)
+            .map(|level| {
+                let w = u64::from((width >> level).max(1));
+                let h = u64::from((height >> level).max(1));
+                w * h * TEXEL_SIZE
+            })
+            .sum();
+
+        Self {
+            index,
+            width,
+            height,
+            mip_levels,
+            gpu_size,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct SkinStatistics {
+    pub index: usize,
+    pub name: Option<String>,
+    pub joint_count: usize,
+}
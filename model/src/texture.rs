@@ -1,10 +1,11 @@
+use super::{ModelLoadingError, TextureSlot};
 use gltf::image::{Data, Format};
 use gltf::iter::Textures as GltfTextures;
 use gltf::json::texture::{MagFilter, MinFilter, WrappingMode};
-use gltf::texture::Sampler;
-use std::sync::Arc;
+use rayon::prelude::*;
+use std::sync::{Arc, Mutex};
 use vulkan::ash::{version::DeviceV1_0, vk};
-use vulkan::{Buffer, Context, Image, Texture as VulkanTexture};
+use vulkan::{Buffer, Context, Image, Texture as VulkanTexture, ThreadCommandBuffer};
 
 pub(crate) struct Textures {
     _images: Vec<VulkanTexture>,
@@ -35,27 +36,113 @@ impl Drop for Texture {
     }
 }
 
-/// Create
-pub(crate) fn create_textures_from_gltf(
-    context: &Arc<Context>,
-    command_buffer: vk::CommandBuffer,
+/// CPU-side RGBA8 image.
+#[derive(Clone, Debug)]
+pub struct RgbaImage {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+/// CPU-side texture description.
+#[derive(Clone, Copy, Debug)]
+pub struct TextureData {
+    pub image_index: usize,
+    pub min_filter: MinFilter,
+    pub mag_filter: MagFilter,
+    pub wrap_s: WrappingMode,
+    pub wrap_t: WrappingMode,
+}
+
+/// Material slot referencing a texture.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TextureReference {
+    /// Index of the material or `None` for the default material.
+    pub material_index: Option<usize>,
+    pub slot: TextureSlot,
+}
+
+/// Convert the images of the document to RGBA8 and read the textures' samplers.
+///
+/// Images are converted in parallel.
+pub(crate) fn read_textures_from_gltf(
     textures: GltfTextures,
-    images: &[Data],
-) -> (Textures, Vec<Buffer>) {
-    let (images, buffers) = images
-        .iter()
-        .map(|image| (image.width, image.height, build_rgba_buffer(image)))
-        .map(|(width, height, pixels)| {
-            VulkanTexture::cmd_from_rgba(&context, command_buffer, width, height, &pixels)
+    images: Vec<Data>,
+) -> Result<(Vec<RgbaImage>, Vec<TextureData>), ModelLoadingError> {
+    let images = images
+        .into_par_iter()
+        .enumerate()
+        .map(|(index, image)| {
+            let (width, height) = (image.width, image.height);
+            build_rgba_buffer(index, image).map(|pixels| RgbaImage {
+                width,
+                height,
+                pixels,
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let textures = textures
+        .map(|t| {
+            let sampler = t.sampler();
+            TextureData {
+                image_index: t.source().index(),
+                min_filter: sampler.min_filter().unwrap_or(MinFilter::Linear),
+                mag_filter: sampler.mag_filter().unwrap_or(MagFilter::Linear),
+                wrap_s: sampler.wrap_s(),
+                wrap_t: sampler.wrap_t(),
+            }
+        })
+        .collect();
+
+    Ok((images, textures))
+}
+
+/// Upload `images` on the GPU and create the samplers of `textures`.
+///
+/// Uploads are recorded from the threads of the thread pool, each one in its own
+/// secondary command buffer. These command buffers are returned along with the
+/// staging buffers and must be kept alive until they are executed.
+pub(crate) fn create_textures(
+    context: &Arc<Context>,
+    images: &[RgbaImage],
+    textures: &[TextureData],
+) -> (Textures, Vec<Buffer>, Vec<ThreadCommandBuffer>) {
+    // One command buffer per thread of the pool, begun when the thread records its
+    // first upload. Each slot is only used by its thread so the locks are not contended.
+    let thread_command_buffers = (0..rayon::current_num_threads())
+        .map(|_| Mutex::new(None))
+        .collect::<Vec<Mutex<Option<ThreadCommandBuffer>>>>();
+    let (images, buffers): (Vec<_>, Vec<_>) = images
+        .par_iter()
+        .map(|image| {
+            let slot = rayon::current_thread_index().unwrap_or(0) % thread_command_buffers.len();
+            let mut command_buffer = thread_command_buffers[slot].lock().unwrap();
+            let command_buffer =
+                command_buffer.get_or_insert_with(|| ThreadCommandBuffer::begin(context));
+            VulkanTexture::cmd_from_rgba(
+                context,
+                command_buffer.command_buffer(),
+                image.width,
+                image.height,
+                &image.pixels,
+            )
         })
-        .unzip::<_, _, Vec<_>, _>();
+        .unzip();
+
+    let command_buffers = thread_command_buffers
+        .into_iter()
+        .filter_map(|command_buffer| command_buffer.into_inner().unwrap())
+        .collect::<Vec<_>>();
+    command_buffers.iter().for_each(ThreadCommandBuffer::end);
 
     let textures = textures
+        .iter()
         .map(|t| {
             let context = Arc::clone(&context);
-            let image = &images[t.source().index()];
+            let image = &images[t.image_index];
             let view = image.view;
-            let sampler = map_sampler(&context, &image.image, &t.sampler());
+            let sampler = map_sampler(&context, &image.image, t);
             Texture {
                 context,
                 view,
@@ -70,57 +157,57 @@ pub(crate) fn create_textures_from_gltf(
             textures,
         },
         buffers,
+        command_buffers,
     )
 }
 
-fn build_rgba_buffer(image: &Data) -> Vec<u8> {
-    let mut buffer = Vec::new();
-    let size = image.width * image.height;
-    for index in 0..size {
-        let rgba = get_next_rgba(&image.pixels, image.format, index as usize);
-        buffer.extend_from_slice(&rgba);
-    }
+fn build_rgba_buffer(index: usize, image: Data) -> Result<Vec<u8>, ModelLoadingError> {
+    use Format::*;
+    let channels = match image.format {
+        R8G8B8A8 => return Ok(image.pixels),
+        R8 => 1,
+        R8G8 => 2,
+        R8G8B8 | B8G8R8 => 3,
+        B8G8R8A8 => 4,
+        R16 | R16G16 | R16G16B16 | R16G16B16A16 => {
+            return Err(ModelLoadingError::UnsupportedTextureFormat {
+                image: index,
+                format: image.format,
+            })
+        }
+    };
+
+    let width = image.width as usize;
+    let mut buffer = vec![0; width * image.height as usize * 4];
     buffer
+        .par_chunks_mut(width * 4)
+        .zip(image.pixels.par_chunks(width * channels))
+        .for_each(|(row, pixels)| {
+            row.chunks_exact_mut(4)
+                .zip(pixels.chunks_exact(channels))
+                .for_each(|(rgba, pixel)| rgba.copy_from_slice(&to_rgba(pixel, image.format)));
+        });
+    Ok(buffer)
 }
 
-fn get_next_rgba(pixels: &[u8], format: Format, index: usize) -> [u8; 4] {
+fn to_rgba(pixel: &[u8], format: Format) -> [u8; 4] {
     use Format::*;
     match format {
-        R8 => [pixels[index], 0, 0, std::u8::MAX],
-        R8G8 => [pixels[index * 2], pixels[index * 2 + 1], 0, std::u8::MAX],
-        R8G8B8 => [
-            pixels[index * 3],
-            pixels[index * 3 + 1],
-            pixels[index * 3 + 2],
-            std::u8::MAX,
-        ],
-        B8G8R8 => [
-            pixels[index * 3 + 2],
-            pixels[index * 3 + 1],
-            pixels[index * 3],
-            std::u8::MAX,
-        ],
-        R8G8B8A8 => [
-            pixels[index * 4],
-            pixels[index * 4 + 1],
-            pixels[index * 4 + 2],
-            pixels[index * 4 + 3],
-        ],
-        B8G8R8A8 => [
-            pixels[index * 4 + 2],
-            pixels[index * 4 + 1],
-            pixels[index * 4],
-            pixels[index * 4 + 3],
-        ],
+        R8 => [pixel[0], 0, 0, std::u8::MAX],
+        R8G8 => [pixel[0], pixel[1], 0, std::u8::MAX],
+        R8G8B8 => [pixel[0], pixel[1], pixel[2], std::u8::MAX],
+        B8G8R8 => [pixel[2], pixel[1], pixel[0], std::u8::MAX],
+        R8G8B8A8 => [pixel[0], pixel[1], pixel[2], pixel[3]],
+        B8G8R8A8 => [pixel[2], pixel[1], pixel[0], pixel[3]],
         R16 | R16G16 | R16G16B16 | R16G16B16A16 => {
-            panic!("16 bits colors are not supported for model textures")
+            unreachable!("16 bits formats are rejected in build_rgba_buffer")
         }
     }
 }
 
-fn map_sampler(context: &Arc<Context>, image: &Image, sampler: &Sampler) -> vk::Sampler {
-    let min_filter = sampler.min_filter().unwrap_or(MinFilter::Linear);
-    let mag_filter = sampler.mag_filter().unwrap_or(MagFilter::Linear);
+fn map_sampler(context: &Arc<Context>, image: &Image, texture: &TextureData) -> vk::Sampler {
+    let min_filter = texture.min_filter;
+    let mag_filter = texture.mag_filter;
     let has_mipmaps = has_mipmaps(min_filter);
     let max_lod = if has_mipmaps {
         image.get_mip_levels() as f32
@@ -131,8 +218,8 @@ fn map_sampler(context: &Arc<Context>, image: &Image, sampler: &Sampler) -> vk::
     let sampler_info = vk::SamplerCreateInfo::builder()
         .mag_filter(map_mag_filter(mag_filter))
         .min_filter(map_min_filter(min_filter))
-        .address_mode_u(map_wrap_mode(sampler.wrap_s()))
-        .address_mode_v(map_wrap_mode(sampler.wrap_t()))
+        .address_mode_u(map_wrap_mode(texture.wrap_s))
+        .address_mode_v(map_wrap_mode(texture.wrap_t))
         .address_mode_w(vk::SamplerAddressMode::REPEAT)
         .anisotropy_enable(has_mipmaps)
         .max_anisotropy(16.0)
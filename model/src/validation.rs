@@ -0,0 +1,337 @@
+//! Checks for issues that don't prevent a model from loading
+//! but that asset authors should fix before publishing.
+//!
+//! Documents requiring unsupported extensions are validated too so the
+//! errors are reported with the loading error when they are rejected.
+
+use super::SUPPORTED_EXTENSIONS;
+use gltf::{
+    animation::{util::ReadOutputs, Interpolation},
+    buffer::Data,
+    mesh::Semantic,
+    Document, Node as GltfNode,
+};
+use std::{cmp::Reverse, collections::HashSet, fmt};
+
+/// Tolerance used when checking that weights sum to one and that quaternions are normalized.
+const EPSILON: f32 = 0.01;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd)]
+pub enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Severity::Info => "Info",
+            Severity::Warning => "Warning",
+            Severity::Error => "Error",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// The glTF object an issue relates to.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Subject {
+    Document,
+    Node(usize),
+    Mesh(usize),
+    Material(usize),
+    Animation(usize),
+}
+
+impl fmt::Display for Subject {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Subject::Document => write!(f, "Document"),
+            Subject::Node(index) => write!(f, "Node {}", index),
+            Subject::Mesh(index) => write!(f, "Mesh {}", index),
+            Subject::Material(index) => write!(f, "Material {}", index),
+            Subject::Animation(index) => write!(f, "Animation {}", index),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Issue {
+    pub severity: Severity,
+    pub subject: Subject,
+    pub message: String,
+}
+
+impl Issue {
+    fn new<S: Into<String>>(severity: Severity, subject: Subject, message: S) -> Self {
+        Self {
+            severity,
+            subject,
+            message: message.into(),
+        }
+    }
+}
+
+/// Run all checks on `document` and return the issues sorted by decreasing severity.
+pub(crate) fn validate(document: &Document, buffers: &[Data]) -> Vec<Issue> {
+    let mut issues = Vec::new();
+    check_extensions(document, &mut issues);
+    check_meshes(document, buffers, &mut issues);
+    check_unused_nodes(document, &mut issues);
+    check_unused_materials(document, &mut issues);
+    check_animations(document, buffers, &mut issues);
+    issues.sort_by_key(|issue| Reverse(issue.severity));
+    issues
+}
+
+fn check_extensions(document: &Document, issues: &mut Vec<Issue>) {
+    let required = document.extensions_required().collect::<Vec<_>>();
+    for extension in &required {
+        if !SUPPORTED_EXTENSIONS.contains(extension) {
+            issues.push(Issue::new(
+                Severity::Error,
+                Subject::Document,
+                format!("Required extension {} is not supported", extension),
+            ));
+        }
+    }
+    for extension in document.extensions_used() {
+        if !required.contains(&extension) && !SUPPORTED_EXTENSIONS.contains(&extension) {
+            issues.push(Issue::new(
+                Severity::Warning,
+                Subject::Document,
+                format!(
+                    "Extension {} is not supported and will be ignored",
+                    extension
+                ),
+            ));
+        }
+    }
+}
+
+fn check_meshes(document: &Document, buffers: &[Data], issues: &mut Vec<Issue>) {
+    for mesh in document.meshes() {
+        let subject = Subject::Mesh(mesh.index());
+        for primitive in mesh.primitives() {
+            let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
+            let primitive_index = primitive.index();
+
+            let vertex_count = primitive
+                .get(&Semantic::Positions)
+                .map_or(0, |positions| positions.count());
+
+            if let Some(indices) = reader.read_indices() {
+                let out_of_range = indices
+                    .into_u32()
+                    .filter(|index| *index as usize >= vertex_count)
+                    .count();
+                if out_of_range > 0 {
+                    issues.push(Issue::new(
+                        Severity::Error,
+                        subject,
+                        format!(
+                            "Primitive {} has {} indices out of range (vertex count is {})",
+                            primitive_index, out_of_range, vertex_count
+                        ),
+                    ));
+                }
+            }
+
+            if let Some(weights) = reader.read_weights(0) {
+                let not_normalized = weights
+                    .into_f32()
+                    .filter(|w| (w.iter().sum::<f32>() - 1.0).abs() > EPSILON)
+                    .count();
+                if not_normalized > 0 {
+                    issues.push(Issue::new(
+                        Severity::Warning,
+                        subject,
+                        format!(
+                            "Primitive {} has {} vertices with joint weights not summing to 1",
+                            primitive_index, not_normalized
+                        ),
+                    ));
+                }
+            }
+
+            let material = primitive.material();
+            if material.normal_texture().is_some() && primitive.get(&Semantic::Tangents).is_none() {
+                issues.push(Issue::new(
+                    Severity::Info,
+                    subject,
+                    format!(
+                        "Primitive {} uses a normal map but has no tangents. They will be generated",
+                        primitive_index
+                    ),
+                ));
+            }
+
+            let mut non_finite_attributes = Vec::new();
+            if reader
+                .read_positions()
+                .map_or(false, |mut it| it.any(|v| !all_finite(&v)))
+            {
+                non_finite_attributes.push("POSITION");
+            }
+            if reader
+                .read_normals()
+                .map_or(false, |mut it| it.any(|v| !all_finite(&v)))
+            {
+                non_finite_attributes.push("NORMAL");
+            }
+            if reader
+                .read_tangents()
+                .map_or(false, |mut it| it.any(|v| !all_finite(&v)))
+            {
+                non_finite_attributes.push("TANGENT");
+            }
+            if reader
+                .read_tex_coords(0)
+                .map_or(false, |it| it.into_f32().any(|v| !all_finite(&v)))
+            {
+                non_finite_attributes.push("TEXCOORD_0");
+            }
+            if reader
+                .read_tex_coords(1)
+                .map_or(false, |it| it.into_f32().any(|v| !all_finite(&v)))
+            {
+                non_finite_attributes.push("TEXCOORD_1");
+            }
+            if !non_finite_attributes.is_empty() {
+                issues.push(Issue::new(
+                    Severity::Error,
+                    subject,
+                    format!(
+                        "Primitive {} has NaN or infinite values in {}",
+                        primitive_index,
+                        non_finite_attributes.join(", ")
+                    ),
+                ));
+            }
+        }
+    }
+}
+
+fn check_unused_nodes(document: &Document, issues: &mut Vec<Issue>) {
+    fn visit(node: GltfNode, visited: &mut HashSet<usize>) {
+        if visited.insert(node.index()) {
+            node.children().for_each(|child| visit(child, visited));
+        }
+    }
+
+    let mut visited = HashSet::new();
+    document
+        .scenes()
+        .flat_map(|scene| scene.nodes())
+        .for_each(|node| visit(node, &mut visited));
+
+    document
+        .nodes()
+        .filter(|node| !visited.contains(&node.index()))
+        .for_each(|node| {
+            issues.push(Issue::new(
+                Severity::Warning,
+                Subject::Node(node.index()),
+                "Node is not part of any scene",
+            ))
+        });
+}
+
+fn check_unused_materials(document: &Document, issues: &mut Vec<Issue>) {
+    let used = document
+        .meshes()
+        .flat_map(|mesh| mesh.primitives().collect::<Vec<_>>())
+        .filter_map(|primitive| primitive.material().index())
+        .collect::<HashSet<_>>();
+
+    document
+        .materials()
+        .filter_map(|material| material.index())
+        .filter(|index| !used.contains(index))
+        .for_each(|index| {
+            issues.push(Issue::new(
+                Severity::Info,
+                Subject::Material(index),
+                "Material is not used by any primitive",
+            ))
+        });
+}
+
+fn check_animations(document: &Document, buffers: &[Data], issues: &mut Vec<Issue>) {
+    for animation in document.animations() {
+        let subject = Subject::Animation(animation.index());
+        for (channel_index, channel) in animation.channels().enumerate() {
+            let reader = channel.reader(|buffer| Some(&buffers[buffer.index()]));
+
+            if reader
+                .read_inputs()
+                .map_or(false, |mut it| it.any(|t| !t.is_finite()))
+            {
+                issues.push(Issue::new(
+                    Severity::Error,
+                    subject,
+                    format!(
+                        "Channel {} has NaN or infinite keyframe times",
+                        channel_index
+                    ),
+                ));
+            }
+
+            let cubic_spline = matches!(
+                channel.sampler().interpolation(),
+                Interpolation::CubicSpline
+            );
+            let (non_finite, non_unit) = match reader.read_outputs() {
+                Some(ReadOutputs::Translations(it)) | Some(ReadOutputs::Scales(it)) => {
+                    (it.filter(|v| !all_finite(v)).count(), 0)
+                }
+                Some(ReadOutputs::Rotations(it)) => {
+                    it.into_f32()
+                        .enumerate()
+                        .fold((0, 0), |(non_finite, non_unit), (index, r)| {
+                            if !all_finite(&r) {
+                                (non_finite + 1, non_unit)
+                            } else if (!cubic_spline || index % 3 == 1)
+                                && (r.iter().map(|c| c * c).sum::<f32>().sqrt() - 1.0).abs()
+                                    > EPSILON
+                            {
+                                (non_finite, non_unit + 1)
+                            } else {
+                                (non_finite, non_unit)
+                            }
+                        })
+                }
+                Some(ReadOutputs::MorphTargetWeights(it)) => {
+                    (it.into_f32().filter(|w| !w.is_finite()).count(), 0)
+                }
+                None => (0, 0),
+            };
+
+            if non_finite > 0 {
+                issues.push(Issue::new(
+                    Severity::Error,
+                    subject,
+                    format!(
+                        "Channel {} has {} NaN or infinite output values",
+                        channel_index, non_finite
+                    ),
+                ));
+            }
+            if non_unit > 0 {
+                issues.push(Issue::new(
+                    Severity::Warning,
+                    subject,
+                    format!(
+                        "Channel {} has {} non unit quaternions",
+                        channel_index, non_unit
+                    ),
+                ));
+            }
+        }
+    }
+}
+
+fn all_finite(values: &[f32]) -> bool {
+    values.iter().all(|v| v.is_finite())
+}
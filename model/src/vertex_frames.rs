@@ -0,0 +1,83 @@
+//! World space tangent frames of the vertices of a primitive.
+//!
+//! Skinned vertices are computed in their current pose, like picking does.
+
+use super::{picking::vertex_transform, Scene};
+use math::cgmath::{InnerSpace, Matrix, Matrix3, Matrix4, Point3, SquareMatrix, Vector3, Zero};
+
+/// Position and tangent space of a vertex in world space.
+///
+/// Vectors are normalized. `tangent` and `bitangent` are zero when the
+/// vertex has no tangent.
+#[derive(Copy, Clone, Debug)]
+pub struct VertexFrame {
+    pub position: Point3<f32>,
+    pub normal: Vector3<f32>,
+    pub tangent: Vector3<f32>,
+    pub bitangent: Vector3<f32>,
+}
+
+/// Compute the frames of the vertices of a primitive of the mesh of a node.
+///
+/// Returns an empty list if the node has no mesh or if the primitive does not exist.
+pub(crate) fn compute_vertex_frames(
+    scene: &Scene,
+    node_index: usize,
+    primitive_index: usize,
+) -> Vec<VertexFrame> {
+    let geometry = scene.geometry();
+    let node = match scene.nodes().nodes().get(node_index) {
+        Some(node) => node,
+        None => return Vec::new(),
+    };
+    let primitive = match node
+        .mesh_index()
+        .and_then(|index| geometry.meshes()[index].primitives().get(primitive_index))
+    {
+        Some(primitive) => primitive,
+        None => return Vec::new(),
+    };
+    let joints = node
+        .skin_index()
+        .map_or(&[][..], |index| scene.skins()[index].joints());
+
+    primitive
+        .vertices(geometry)
+        .iter()
+        .map(|vertex| {
+            let world = vertex_transform(vertex, node.transform(), |index| {
+                joints
+                    .get(index)
+                    .map_or_else(Matrix4::identity, |joint| joint.matrix())
+            });
+            let position =
+                Point3::from_homogeneous(world * Point3::from(vertex.position).to_homogeneous());
+
+            let linear =
+                Matrix3::from_cols(world.x.truncate(), world.y.truncate(), world.z.truncate());
+            let normal_matrix = linear
+                .invert()
+                .map_or(linear, |inverse| inverse.transpose());
+            let normal = normalize_or_zero(normal_matrix * Vector3::from(vertex.normal));
+
+            let [x, y, z, handedness] = vertex.tangent;
+            let tangent = normalize_or_zero(linear * Vector3::new(x, y, z));
+            let bitangent = normalize_or_zero(normal.cross(tangent) * handedness);
+
+            VertexFrame {
+                position,
+                normal,
+                tangent,
+                bitangent,
+            }
+        })
+        .collect()
+}
+
+fn normalize_or_zero(vector: Vector3<f32>) -> Vector3<f32> {
+    if vector.magnitude2() > 0.0 {
+        vector.normalize()
+    } else {
+        Vector3::zero()
+    }
+}
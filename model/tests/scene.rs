@@ -0,0 +1,1511 @@
+//! Headless tests of the CPU-side model loading.
+//!
+//! Run with `cargo test -p model`.
+
+use math::{
+    cgmath::{Deg, Matrix4, Point3, Quaternion, Rotation3, SquareMatrix, Transform, Vector3},
+    Ray,
+};
+use model::{
+    metadata::{Issue, PrimitiveMode, Severity, Subject},
+    ExportFormat, LoadingStage, ModelLoadingError, Placement, PlaybackState, Scene,
+    TextureReference, TextureSlot, Workflow, ALPHA_MODE_OPAQUE,
+};
+
+const CESIUM_MAN: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/../assets/models/cesium_man_with_light.glb"
+);
+
+/// A triangle with positions, normals and indices stored in an embedded buffer.
+fn triangle_buffer() -> Vec<u8> {
+    let mut bin = Vec::new();
+    for v in &[0.0f32, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0] {
+        bin.extend_from_slice(&v.to_le_bytes());
+    }
+    for v in &[0.0f32, 0.0, 1.0, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0] {
+        bin.extend_from_slice(&v.to_le_bytes());
+    }
+    for i in &[0u16, 1, 2, 0] {
+        bin.extend_from_slice(&i.to_le_bytes());
+    }
+    bin
+}
+
+/// Build a glTF document around the triangle buffer.
+///
+/// `buffer_uri` is used as the uri of the buffer, `extra` is inserted as is
+/// at the root of the document.
+fn triangle_gltf(buffer_uri: &str, extra: &str) -> Vec<u8> {
+    format!(
+        r#"{{
+            "asset": {{ "version": "2.0" }},
+            {extra}
+            "nodes": [{{ "mesh": 0 }}],
+            "meshes": [{{
+                "primitives": [{{
+                    "attributes": {{ "POSITION": 0, "NORMAL": 1 }},
+                    "indices": 2
+                }}]
+            }}],
+            "buffers": [{{ "uri": "{uri}", "byteLength": 78 }}],
+            "bufferViews": [
+                {{ "buffer": 0, "byteOffset": 0, "byteLength": 36 }},
+                {{ "buffer": 0, "byteOffset": 36, "byteLength": 36 }},
+                {{ "buffer": 0, "byteOffset": 72, "byteLength": 6 }}
+            ],
+            "accessors": [
+                {{ "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3",
+                   "min": [0.0, 0.0, 0.0], "max": [1.0, 1.0, 0.0] }},
+                {{ "bufferView": 1, "componentType": 5126, "count": 3, "type": "VEC3" }},
+                {{ "bufferView": 2, "componentType": 5123, "count": 3, "type": "SCALAR" }}
+            ]
+        }}"#,
+        extra = extra,
+        uri = buffer_uri,
+    )
+    .into_bytes()
+}
+
+fn embedded_triangle_gltf(extra: &str) -> Vec<u8> {
+    let uri = format!(
+        "data:application/octet-stream;base64,{}",
+        base64::encode(&triangle_buffer())
+    );
+    triangle_gltf(&uri, extra)
+}
+
+/// Build a glTF document around `buffer`, the triangle buffer possibly modified,
+/// with an extra attribute `name` of type `accessor_type` read from `data`.
+fn triangle_gltf_with_attribute(
+    mut buffer: Vec<u8>,
+    name: &str,
+    accessor_type: &str,
+    data: &[u8],
+    extra: &str,
+) -> Vec<u8> {
+    buffer.resize(80, 0);
+    buffer.extend_from_slice(data);
+    let uri = format!(
+        "data:application/octet-stream;base64,{}",
+        base64::encode(&buffer)
+    );
+    String::from_utf8(triangle_gltf(&uri, extra))
+        .unwrap()
+        .replace(
+            r#""NORMAL": 1 }"#,
+            &format!(r#""NORMAL": 1, "{}": 3 }}"#, name),
+        )
+        .replace(
+            r#""byteLength": 78 }"#,
+            &format!(r#""byteLength": {} }}"#, buffer.len()),
+        )
+        .replace(
+            r#"{ "buffer": 0, "byteOffset": 72, "byteLength": 6 }"#,
+            &format!(
+                r#"{{ "buffer": 0, "byteOffset": 72, "byteLength": 6 }},
+                   {{ "buffer": 0, "byteOffset": 80, "byteLength": {} }}"#,
+                data.len()
+            ),
+        )
+        .replace(
+            r#""count": 3, "type": "SCALAR" }"#,
+            &format!(
+                r#""count": 3, "type": "SCALAR" }},
+                   {{ "bufferView": 3, "componentType": 5126, "count": 3, "type": "{}" }}"#,
+                accessor_type
+            ),
+        )
+        .into_bytes()
+}
+
+fn f32_bytes(values: &[f32]) -> Vec<u8> {
+    values
+        .iter()
+        .flat_map(|v| v.to_le_bytes().to_vec())
+        .collect()
+}
+
+const WITH_SCENE: &str = r#""scene": 0, "scenes": [{ "nodes": [0] }],"#;
+
+fn assert_approx_eq(a: f32, b: f32) {
+    assert!((a - b).abs() < 1e-4, "{} != {}", a, b);
+}
+
+#[test]
+fn load_glb_from_file() {
+    let scene = Scene::from_file(CESIUM_MAN).unwrap();
+    let metadata = scene.metadata();
+
+    assert_eq!(metadata.name(), "cesium_man_with_light.glb");
+    assert!(metadata.path().is_some());
+    assert_eq!(metadata.mesh_count(), scene.geometry().meshes().len());
+    assert_eq!(metadata.skin_count(), scene.skins().len());
+    assert_eq!(metadata.light_count(), scene.lights().len());
+    assert_eq!(metadata.node_count(), scene.nodes().nodes().len());
+    assert!(metadata.animation_count() > 0);
+    assert!(!scene.skins().is_empty());
+    assert!(!scene.lights().is_empty());
+    assert!(scene.primitive_count() > 0);
+    assert_eq!(scene.images().len(), scene.textures().len());
+}
+
+#[test]
+fn load_glb_from_slice_and_reader() {
+    let bytes = std::fs::read(CESIUM_MAN).unwrap();
+    let from_slice = Scene::from_slice("cesium", &bytes).unwrap();
+    let from_reader = Scene::from_reader("cesium", bytes.as_slice()).unwrap();
+
+    assert!(from_slice.metadata().path().is_none());
+    assert_eq!(from_slice.metadata().name(), "cesium");
+    assert_eq!(
+        from_slice.geometry().vertices().len(),
+        from_reader.geometry().vertices().len()
+    );
+    assert_eq!(
+        from_slice.geometry().indices().len(),
+        from_reader.geometry().indices().len()
+    );
+}
+
+#[test]
+fn loading_stages_are_reported_in_order() {
+    let mut stages = Vec::new();
+    Scene::from_file_with_progress(CESIUM_MAN, &mut |stage| {
+        stages.push(stage);
+        true
+    })
+    .unwrap();
+
+    assert_eq!(stages, &LoadingStage::all()[..5]);
+}
+
+#[test]
+fn loading_can_be_cancelled() {
+    let mut stages = Vec::new();
+    let result = Scene::from_file_with_progress(CESIUM_MAN, &mut |stage| {
+        stages.push(stage);
+        stage != LoadingStage::Tangents
+    });
+
+    assert!(matches!(result, Err(ModelLoadingError::Cancelled)));
+    assert_eq!(stages.last(), Some(&LoadingStage::Tangents));
+}
+
+#[test]
+fn scene_is_normalized_to_unit_cube() {
+    let scene = Scene::from_file(CESIUM_MAN).unwrap();
+    let aabb = scene.aabb().unwrap();
+
+    assert_approx_eq(aabb.get_larger_side_size(), 10.0);
+    let center = aabb.get_center();
+    assert_approx_eq(center.x, 0.0);
+    assert_approx_eq(center.y, 0.0);
+    assert_approx_eq(center.z, 0.0);
+}
+
+#[test]
+fn original_placement_keeps_units_and_position() {
+    let mut scene = Scene::from_slice("triangle", &embedded_triangle_gltf(WITH_SCENE)).unwrap();
+    assert_eq!(scene.metadata().dimensions(), [1.0, 1.0, 0.0]);
+    assert_eq!(scene.placement(), Placement::Normalized);
+
+    scene.set_placement(Placement::Original);
+    let aabb = scene.aabb().unwrap();
+    assert_approx_eq(aabb.get_larger_side_size(), 1.0);
+    let center = aabb.get_center();
+    assert_approx_eq(center.x, 0.5);
+    assert_approx_eq(center.y, 0.5);
+
+    scene.set_placement(Placement::Normalized);
+    assert_approx_eq(scene.aabb().unwrap().get_larger_side_size(), 10.0);
+}
+
+#[test]
+fn bounds_contain_rotated_nodes() {
+    // Rotated by 90 degrees around z so the triangle spans [-1, 0] on x
+    let gltf = String::from_utf8(embedded_triangle_gltf(WITH_SCENE))
+        .unwrap()
+        .replace(
+            r#""nodes": [{ "mesh": 0 }]"#,
+            r#""nodes": [{ "mesh": 0, "rotation": [0.0, 0.0, 0.70710677, 0.70710677] }]"#,
+        );
+    let mut scene = Scene::from_slice("triangle", gltf.as_bytes()).unwrap();
+    scene.set_placement(Placement::Original);
+
+    let aabb = scene.aabb().unwrap();
+    let (min, max) = (aabb.min(), aabb.max());
+    assert_approx_eq(min.x, -1.0);
+    assert_approx_eq(min.y, 0.0);
+    assert_approx_eq(max.x, 0.0);
+    assert_approx_eq(max.y, 1.0);
+}
+
+#[test]
+fn scene_transform_is_applied_after_placement() {
+    let mut scene = Scene::from_slice("triangle", &embedded_triangle_gltf(WITH_SCENE)).unwrap();
+    scene.set_placement(Placement::Original);
+    scene.set_transform(
+        Matrix4::from_translation(Vector3::new(2.0, 0.0, 0.0)) * Matrix4::from_scale(2.0),
+    );
+
+    let aabb = scene.aabb().unwrap();
+    assert_approx_eq(aabb.get_larger_side_size(), 2.0);
+    let center = aabb.get_center();
+    assert_approx_eq(center.x, 3.0);
+    assert_approx_eq(center.y, 1.0);
+
+    scene.set_placement(Placement::Normalized);
+    assert_approx_eq(scene.aabb().unwrap().get_larger_side_size(), 20.0);
+}
+
+#[test]
+fn update_animates_nodes() {
+    let mut scene = Scene::from_file(CESIUM_MAN).unwrap();
+    let before = scene
+        .nodes()
+        .nodes()
+        .iter()
+        .map(|node| node.transform())
+        .collect::<Vec<_>>();
+
+    assert!(scene.get_animation_playback_state().is_some());
+    assert!(scene.update(0.5));
+
+    let after = scene
+        .nodes()
+        .nodes()
+        .iter()
+        .map(|node| node.transform())
+        .collect::<Vec<_>>();
+    assert_ne!(before, after);
+
+    scene.stop_animation();
+    assert!(!scene.update(0.5));
+}
+
+#[test]
+fn playback_state_can_be_restored_on_another_scene() {
+    let mut scene = Scene::from_file(CESIUM_MAN).unwrap();
+    scene.update(0.5);
+    scene.toggle_animation();
+    let playback_state = scene.get_animation_playback_state().unwrap();
+
+    let mut reloaded = Scene::from_file(CESIUM_MAN).unwrap();
+    reloaded.set_animation_playback_state(playback_state);
+
+    let restored = reloaded.get_animation_playback_state().unwrap();
+    assert_approx_eq(restored.time, playback_state.time);
+    assert!(restored.paused);
+    let transforms = |scene: &Scene| {
+        scene
+            .nodes()
+            .nodes()
+            .iter()
+            .map(|node| node.transform())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(transforms(&scene), transforms(&reloaded));
+}
+
+/// The triangle with a translation channel with keyframes at 1s and 2s
+/// and a scale channel with keyframes at 1s and 1.5s.
+fn animated_triangle_gltf() -> Vec<u8> {
+    let mut bin = Vec::new();
+    for v in &[1.0f32, 2.0, 1.0, 1.5] {
+        bin.extend_from_slice(&v.to_le_bytes());
+    }
+    for v in &[1.0f32, 0.0, 0.0, 3.0, 0.0, 0.0] {
+        bin.extend_from_slice(&v.to_le_bytes());
+    }
+    for v in &[1.0f32, 1.0, 1.0, 2.0, 2.0, 2.0] {
+        bin.extend_from_slice(&v.to_le_bytes());
+    }
+    let uri = |data: &[u8]| {
+        format!(
+            "data:application/octet-stream;base64,{}",
+            base64::encode(data)
+        )
+    };
+
+    format!(
+        r#"{{
+            "asset": {{ "version": "2.0" }},
+            "scene": 0,
+            "scenes": [{{ "nodes": [0] }}],
+            "nodes": [{{ "mesh": 0 }}],
+            "meshes": [{{
+                "primitives": [{{
+                    "attributes": {{ "POSITION": 0, "NORMAL": 1 }},
+                    "indices": 2
+                }}]
+            }}],
+            "buffers": [
+                {{ "uri": "{triangle}", "byteLength": 78 }},
+                {{ "uri": "{animation}", "byteLength": 64 }}
+            ],
+            "bufferViews": [
+                {{ "buffer": 0, "byteOffset": 0, "byteLength": 36 }},
+                {{ "buffer": 0, "byteOffset": 36, "byteLength": 36 }},
+                {{ "buffer": 0, "byteOffset": 72, "byteLength": 6 }},
+                {{ "buffer": 1, "byteOffset": 0, "byteLength": 16 }},
+                {{ "buffer": 1, "byteOffset": 16, "byteLength": 48 }}
+            ],
+            "accessors": [
+                {{ "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3",
+                   "min": [0.0, 0.0, 0.0], "max": [1.0, 1.0, 0.0] }},
+                {{ "bufferView": 1, "componentType": 5126, "count": 3, "type": "VEC3" }},
+                {{ "bufferView": 2, "componentType": 5123, "count": 3, "type": "SCALAR" }},
+                {{ "bufferView": 3, "byteOffset": 0, "componentType": 5126, "count": 2,
+                   "type": "SCALAR", "min": [1.0], "max": [2.0] }},
+                {{ "bufferView": 3, "byteOffset": 8, "componentType": 5126, "count": 2,
+                   "type": "SCALAR", "min": [1.0], "max": [1.5] }},
+                {{ "bufferView": 4, "byteOffset": 0, "componentType": 5126, "count": 2, "type": "VEC3" }},
+                {{ "bufferView": 4, "byteOffset": 24, "componentType": 5126, "count": 2, "type": "VEC3" }}
+            ],
+            "animations": [{{
+                "samplers": [
+                    {{ "input": 3, "output": 5, "interpolation": "LINEAR" }},
+                    {{ "input": 4, "output": 6, "interpolation": "LINEAR" }}
+                ],
+                "channels": [
+                    {{ "sampler": 0, "target": {{ "node": 0, "path": "translation" }} }},
+                    {{ "sampler": 1, "target": {{ "node": 0, "path": "scale" }} }}
+                ]
+            }}]
+        }}"#,
+        triangle = uri(&triangle_buffer()),
+        animation = uri(&bin),
+    )
+    .into_bytes()
+}
+
+#[test]
+fn animation_is_clamped_to_the_first_and_last_keyframes() {
+    let mut scene = Scene::from_slice("animated", &animated_triangle_gltf()).unwrap();
+    let playback_state = scene.get_animation_playback_state().unwrap();
+    assert_approx_eq(playback_state.total_time, 2.0);
+
+    let mut sample = |time: f32| {
+        scene.set_animation_playback_state(PlaybackState {
+            time,
+            ..playback_state
+        });
+        let node = &scene.nodes().nodes()[0];
+        (node.translation().x, node.scale().x)
+    };
+
+    // Before the first keyframe of both channels
+    let (translation, scale) = sample(0.5);
+    assert_approx_eq(translation, 1.0);
+    assert_approx_eq(scale, 1.0);
+
+    // Between keyframes of the translation, after the last keyframe of the scale
+    let (translation, scale) = sample(1.75);
+    assert_approx_eq(translation, 2.5);
+    assert_approx_eq(scale, 2.0);
+
+    // On the last keyframe of the translation
+    let (translation, scale) = sample(2.0);
+    assert_approx_eq(translation, 3.0);
+    assert_approx_eq(scale, 2.0);
+
+    // Back before the first keyframe after the cursor moved forward
+    let (translation, scale) = sample(0.0);
+    assert_approx_eq(translation, 1.0);
+    assert_approx_eq(scale, 1.0);
+}
+
+#[test]
+fn pick_returns_the_hit_in_world_space() {
+    let mut scene = Scene::from_slice("triangle", &embedded_triangle_gltf(WITH_SCENE)).unwrap();
+    scene.set_placement(Placement::Original);
+    scene.set_transform(Matrix4::from_translation(Vector3::new(0.0, 0.0, -2.0)));
+
+    let ray = Ray::new(Point3::new(0.25, 0.25, 3.0), -Vector3::unit_z());
+    let hit = scene.pick(ray).unwrap();
+    assert_eq!(hit.node_index, 0);
+    assert_eq!(hit.mesh_index, 0);
+    assert_eq!(hit.primitive_index, 0);
+    assert_eq!(hit.material_index, None);
+    assert_approx_eq(hit.distance, 5.0);
+    assert_approx_eq(hit.position.x, 0.25);
+    assert_approx_eq(hit.position.y, 0.25);
+    assert_approx_eq(hit.position.z, -2.0);
+
+    // Behind the triangle and outside of it
+    let behind = Ray::new(Point3::new(0.25, 0.25, -3.0), -Vector3::unit_z());
+    assert!(scene.pick(behind).is_none());
+    let outside = Ray::new(Point3::new(0.75, 0.75, 3.0), -Vector3::unit_z());
+    assert!(scene.pick(outside).is_none());
+}
+
+#[test]
+fn pick_skips_filtered_nodes() {
+    let scene = Scene::from_slice("triangle", &embedded_triangle_gltf(WITH_SCENE)).unwrap();
+    let ray = Ray::new(Point3::new(0.0, 0.0, 10.0), -Vector3::unit_z());
+
+    assert!(scene.pick_filtered(ray, |node| node == 0).is_some());
+    assert!(scene.pick_filtered(ray, |node| node != 0).is_none());
+}
+
+#[test]
+fn pick_uses_the_current_skinned_pose() {
+    let mut scene = Scene::from_file(CESIUM_MAN).unwrap();
+    let ray = Ray::new(Point3::new(0.0, 0.0, 50.0), -Vector3::unit_z());
+
+    let hit = scene.pick(ray).unwrap();
+    let node = &scene.nodes().nodes()[hit.node_index];
+    assert!(node.skin_index().is_some());
+    assert!(hit.material_index.is_some());
+    assert_approx_eq(hit.position.z, 50.0 - hit.distance);
+
+    scene.update(0.5);
+    let posed = scene.pick(ray).unwrap();
+    assert_ne!(hit.position, posed.position);
+}
+
+#[test]
+fn vertex_frames_are_in_world_space() {
+    let mut scene = Scene::from_slice("triangle", &embedded_triangle_gltf(WITH_SCENE)).unwrap();
+    scene.set_placement(Placement::Original);
+    scene.set_transform(
+        Matrix4::from_translation(Vector3::new(0.0, 0.0, -2.0))
+            * Matrix4::from_angle_x(Deg(90.0))
+            * Matrix4::from_nonuniform_scale(1.0, 1.0, 3.0),
+    );
+
+    let frames = scene.vertex_frames(0, 0);
+    assert_eq!(frames.len(), 3);
+    let position = frames[2].position;
+    assert_approx_eq(position.x, 0.0);
+    assert_approx_eq(position.y, 0.0);
+    assert_approx_eq(position.z, -1.0);
+    for frame in &frames {
+        assert_approx_eq(frame.normal.x, 0.0);
+        assert_approx_eq(frame.normal.y, -1.0);
+        assert_approx_eq(frame.normal.z, 0.0);
+    }
+
+    assert!(scene.vertex_frames(0, 1).is_empty());
+    assert!(scene.vertex_frames(1, 0).is_empty());
+}
+
+#[test]
+fn world_bounds_follow_the_scene_transform() {
+    let mut scene = Scene::from_slice("triangle", &embedded_triangle_gltf(WITH_SCENE)).unwrap();
+    scene.set_placement(Placement::Original);
+    scene.set_transform(
+        Matrix4::from_translation(Vector3::new(0.0, 0.0, -2.0)) * Matrix4::from_angle_x(Deg(90.0)),
+    );
+
+    let bounds = scene.world_bounds();
+    assert_eq!(bounds.len(), 1);
+    let bounds = bounds[0].as_ref().unwrap();
+    assert_eq!(bounds.primitives.len(), 1);
+    let (min, max) = (bounds.aabb.min(), bounds.aabb.max());
+    assert_approx_eq(min.x, 0.0);
+    assert_approx_eq(max.x, 1.0);
+    assert_approx_eq(min.y, 0.0);
+    assert_approx_eq(max.y, 0.0);
+    assert_approx_eq(min.z, -2.0);
+    assert_approx_eq(max.z, -1.0);
+}
+
+#[test]
+fn world_bounds_contain_the_skinned_pose() {
+    let mut scene = Scene::from_file(CESIUM_MAN).unwrap();
+    scene.update(0.5);
+
+    let bounds = scene.world_bounds();
+    let (node_index, node_bounds) = bounds
+        .iter()
+        .enumerate()
+        .find_map(|(index, bounds)| bounds.as_ref().map(|bounds| (index, bounds)))
+        .unwrap();
+    assert!(scene.nodes().nodes()[node_index].skin_index().is_some());
+
+    let aabb = node_bounds.primitives[0];
+    let (min, max) = (aabb.min(), aabb.max());
+    for frame in scene.vertex_frames(node_index, 0) {
+        let p = frame.position;
+        assert!(p.x >= min.x - 1e-3 && p.y >= min.y - 1e-3 && p.z >= min.z - 1e-3);
+        assert!(p.x <= max.x + 1e-3 && p.y <= max.y + 1e-3 && p.z <= max.z + 1e-3);
+    }
+}
+
+#[test]
+fn node_transform_can_be_edited_and_reset() {
+    let mut scene = Scene::from_slice("triangle", &embedded_triangle_gltf(WITH_SCENE)).unwrap();
+    scene.set_placement(Placement::Original);
+
+    let translation = Vector3::new(1.0, 2.0, 3.0);
+    let rotation = Quaternion::from_angle_z(Deg(90.0));
+    let scale = Vector3::new(2.0, 2.0, 2.0);
+    scene.set_node_transform(0, translation, rotation, scale);
+
+    let node = &scene.nodes().nodes()[0];
+    assert_eq!(node.translation(), translation);
+    assert_eq!(node.scale(), scale);
+    let expected =
+        Matrix4::from_translation(translation) * Matrix4::from(rotation) * Matrix4::from_scale(2.0);
+    assert_eq!(node.transform(), expected);
+    assert_eq!(scene.node_parent_transform(0), Matrix4::identity());
+
+    scene.reset_node_transform(0);
+    assert_eq!(scene.nodes().nodes()[0].transform(), Matrix4::identity());
+}
+
+#[test]
+fn editing_a_joint_updates_the_joint_matrices() {
+    let mut scene = Scene::from_file(CESIUM_MAN).unwrap();
+    let joint_matrices = |scene: &Scene| {
+        scene.skins()[0]
+            .joints()
+            .iter()
+            .map(|joint| joint.matrix())
+            .collect::<Vec<_>>()
+    };
+    let before = joint_matrices(&scene);
+
+    let joint_node = (0..scene.nodes().nodes().len()).find(|index| {
+        let node = &scene.nodes().nodes()[*index];
+        let (rotation, scale) = (node.rotation(), node.scale());
+        let translation = node.translation() + Vector3::unit_x();
+        scene.set_node_transform(*index, translation, rotation, scale);
+        let moved = joint_matrices(&scene) != before;
+        scene.reset_node_transform(*index);
+        moved
+    });
+
+    assert!(joint_node.is_some());
+    assert_eq!(joint_matrices(&scene), before);
+}
+
+#[test]
+fn primitive_data_points_into_geometry() {
+    let scene = Scene::from_slice("triangle", &embedded_triangle_gltf(WITH_SCENE)).unwrap();
+    let geometry = scene.geometry();
+
+    assert_eq!(geometry.meshes().len(), 1);
+    let primitive = &geometry.meshes()[0].primitives()[0];
+    let vertices = primitive.vertices(geometry);
+    assert_eq!(vertices.len(), 3);
+    assert_eq!(vertices[1].position, [1.0, 0.0, 0.0]);
+    assert_eq!(vertices[2].normal, [0.0, 0.0, 1.0]);
+    assert_eq!(primitive.indices(geometry), Some(&[0, 1, 2][..]));
+}
+
+#[test]
+fn first_scene_is_used_when_no_default_scene() {
+    let scene = Scene::from_slice(
+        "triangle",
+        &embedded_triangle_gltf(r#""scenes": [{ "nodes": [0] }],"#),
+    );
+    assert!(scene.is_ok());
+}
+
+#[test]
+fn missing_scene_is_an_error() {
+    let result = Scene::from_slice("triangle", &embedded_triangle_gltf(""));
+    assert!(matches!(result, Err(ModelLoadingError::NoScene)));
+}
+
+#[test]
+fn scene_without_mesh_is_an_error() {
+    let gltf = br#"{
+        "asset": { "version": "2.0" },
+        "scene": 0,
+        "scenes": [{ "nodes": [0] }],
+        "nodes": [{}]
+    }"#;
+    let result = Scene::from_slice("empty", gltf);
+    assert!(matches!(result, Err(ModelLoadingError::EmptyScene)));
+}
+
+#[test]
+fn unsupported_required_extension_is_an_error() {
+    let extra = r#"
+        "extensionsUsed": ["KHR_draco_mesh_compression"],
+        "extensionsRequired": ["KHR_draco_mesh_compression"],
+        "scene": 0, "scenes": [{ "nodes": [0] }],"#;
+    let result = Scene::from_slice("draco", &embedded_triangle_gltf(extra));
+    match result {
+        Err(ModelLoadingError::UnsupportedExtension { extension, issues }) => {
+            assert_eq!(extension, "KHR_draco_mesh_compression");
+            let issue = issues
+                .iter()
+                .find(|issue| issue.message.contains("Required extension"))
+                .expect("The required extension should be reported");
+            assert_eq!(issue.severity, Severity::Error);
+            assert_eq!(issue.subject, Subject::Document);
+        }
+        _ => panic!("Expected an unsupported extension error"),
+    }
+}
+
+#[test]
+fn malformed_document_is_a_parse_error() {
+    let result = Scene::from_slice("garbage", b"not a gltf");
+    assert!(matches!(result, Err(ModelLoadingError::Parse(_))));
+}
+
+#[test]
+fn image_out_of_its_buffer_is_an_error() {
+    let extra = r#""scene": 0, "scenes": [{ "nodes": [0] }],
+        "images": [{ "bufferView": 3, "mimeType": "image/png" }],"#;
+    let gltf = String::from_utf8(embedded_triangle_gltf(extra))
+        .unwrap()
+        .replace(
+            r#"{ "buffer": 0, "byteOffset": 72, "byteLength": 6 }"#,
+            r#"{ "buffer": 0, "byteOffset": 72, "byteLength": 6 },
+               { "buffer": 0, "byteOffset": 64, "byteLength": 1024 }"#,
+        );
+
+    let result = Scene::from_slice("triangle", gltf.as_bytes());
+    assert!(result.is_err());
+}
+
+#[test]
+fn external_resource_without_resolver_is_an_error() {
+    let result = Scene::from_slice("triangle", &triangle_gltf("triangle.bin", WITH_SCENE));
+    assert!(matches!(result, Err(ModelLoadingError::MissingResource(_))));
+}
+
+#[test]
+fn external_resource_is_loaded_with_resolver() {
+    let mut requested = Vec::new();
+    let scene = Scene::from_slice_with_resolver(
+        "triangle",
+        &triangle_gltf("triangle.bin", WITH_SCENE),
+        &mut |uri| {
+            requested.push(uri.to_owned());
+            Ok(triangle_buffer())
+        },
+    )
+    .unwrap();
+
+    assert_eq!(requested, vec!["triangle.bin"]);
+    assert_eq!(scene.geometry().vertices().len(), 3);
+    assert_eq!(scene.metadata().external_resources(), ["triangle.bin"]);
+}
+
+#[test]
+fn embedded_resources_are_not_external() {
+    let scene = Scene::from_slice("triangle", &embedded_triangle_gltf(WITH_SCENE)).unwrap();
+    assert!(scene.metadata().external_resources().is_empty());
+}
+
+#[test]
+fn external_resource_is_loaded_from_file_directory() {
+    let dir = std::env::temp_dir().join("model_tests_external_resource");
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("triangle.bin"), triangle_buffer()).unwrap();
+    let path = dir.join("triangle.gltf");
+    std::fs::write(&path, triangle_gltf("triangle.bin", WITH_SCENE)).unwrap();
+
+    let scene = Scene::from_file(&path).unwrap();
+    assert_eq!(scene.geometry().vertices().len(), 3);
+
+    std::fs::remove_file(dir.join("triangle.bin")).unwrap();
+    let result = Scene::from_file(&path);
+    assert!(matches!(result, Err(ModelLoadingError::MissingResource(_))));
+}
+
+#[test]
+fn validation_reports_out_of_range_indices() {
+    let mut buffer = triangle_buffer();
+    // Replace the last index with one past the vertex count
+    buffer[76..78].copy_from_slice(&3u16.to_le_bytes());
+    let uri = format!(
+        "data:application/octet-stream;base64,{}",
+        base64::encode(&buffer)
+    );
+    let extra = r#""scene": 0, "scenes": [{ "nodes": [0] }], "materials": [{}],"#;
+    let scene = Scene::from_slice("triangle", &triangle_gltf(&uri, extra)).unwrap();
+    let issues = scene.metadata().issues();
+
+    assert_eq!(issues[0].severity, Severity::Error);
+    assert_eq!(issues[0].subject, Subject::Mesh(0));
+    assert!(issues
+        .iter()
+        .any(|issue| issue.severity == Severity::Info && issue.subject == Subject::Material(0)));
+}
+
+#[test]
+fn triangles_with_out_of_range_indices_are_removed() {
+    let mut buffer = triangle_buffer();
+    // Replace the last index with one past the vertex count
+    buffer[76..78].copy_from_slice(&3u16.to_le_bytes());
+    let tex_coords = f32_bytes(&[0.0, 0.0, 1.0, 0.0, 0.0, 1.0]);
+    let gltf = triangle_gltf_with_attribute(buffer, "TEXCOORD_0", "VEC2", &tex_coords, WITH_SCENE);
+
+    let scene = Scene::from_slice("triangle", &gltf).unwrap();
+    let geometry = scene.geometry();
+    assert_eq!(geometry.vertices().len(), 3);
+    let primitive = &geometry.meshes()[0].primitives()[0];
+    assert_eq!(primitive.indices(geometry), Some(&[][..]));
+    assert_eq!(scene.metadata().issues()[0].severity, Severity::Error);
+}
+
+/// Find the issue of `subject` whose message contains `message`.
+fn find_issue<'a>(scene: &'a Scene, subject: Subject, message: &str) -> Option<&'a Issue> {
+    scene
+        .metadata()
+        .issues()
+        .iter()
+        .find(|issue| issue.subject == subject && issue.message.contains(message))
+}
+
+#[test]
+fn validation_reports_non_finite_attributes() {
+    let mut buffer = triangle_buffer();
+    // Replace the x of the first normal
+    buffer[36..40].copy_from_slice(&std::f32::NAN.to_le_bytes());
+    let tex_coords = f32_bytes(&[0.0, 0.0, std::f32::INFINITY, 0.0, 0.0, 1.0]);
+    let gltf = triangle_gltf_with_attribute(buffer, "TEXCOORD_1", "VEC2", &tex_coords, WITH_SCENE);
+    let scene = Scene::from_slice("triangle", &gltf).unwrap();
+
+    let issue = find_issue(&scene, Subject::Mesh(0), "NaN or infinite").unwrap();
+    assert_eq!(issue.severity, Severity::Error);
+    assert!(issue.message.contains("NORMAL"));
+    assert!(issue.message.contains("TEXCOORD_1"));
+    assert!(!issue.message.contains("POSITION"));
+}
+
+#[test]
+fn validation_reports_non_unit_quaternions() {
+    // Use the translations as two non unit rotations
+    let gltf = String::from_utf8(animated_triangle_gltf())
+        .unwrap()
+        .replace(r#""path": "translation""#, r#""path": "rotation""#)
+        .replace(
+            r#"{ "bufferView": 4, "byteOffset": 0, "componentType": 5126, "count": 2, "type": "VEC3" }"#,
+            r#"{ "bufferView": 4, "byteOffset": 0, "componentType": 5126, "count": 2, "type": "VEC4" }"#,
+        );
+    let scene = Scene::from_slice("animated", gltf.as_bytes()).unwrap();
+
+    let issue = find_issue(&scene, Subject::Animation(0), "non unit quaternions").unwrap();
+    assert_eq!(issue.severity, Severity::Warning);
+    assert!(issue.message.starts_with("Channel 0 has 2"));
+}
+
+#[test]
+fn validation_reports_unnormalized_weights() {
+    let weights = f32_bytes(&[
+        1.0, 0.0, 0.0, 0.0, //
+        0.5, 0.5, 0.0, 0.0, //
+        0.5, 0.0, 0.0, 0.0,
+    ]);
+    let gltf =
+        triangle_gltf_with_attribute(triangle_buffer(), "WEIGHTS_0", "VEC4", &weights, WITH_SCENE);
+    let scene = Scene::from_slice("triangle", &gltf).unwrap();
+
+    let issue = find_issue(&scene, Subject::Mesh(0), "joint weights").unwrap();
+    assert_eq!(issue.severity, Severity::Warning);
+    assert!(issue.message.contains("has 1 vertices"));
+}
+
+#[test]
+fn validation_reports_missing_tangents() {
+    let mut png = Vec::new();
+    image::DynamicImage::new_rgba8(1, 1)
+        .write_to(&mut png, image::ImageOutputFormat::Png)
+        .unwrap();
+    let extra = format!(
+        r#""scene": 0, "scenes": [{{ "nodes": [0] }}],
+        "materials": [{{ "normalTexture": {{ "index": 0 }} }}],
+        "textures": [{{ "source": 0 }}],
+        "images": [{{ "uri": "data:image/png;base64,{}" }}],"#,
+        base64::encode(&png)
+    );
+    let tex_coords = f32_bytes(&[0.0, 0.0, 1.0, 0.0, 0.0, 1.0]);
+    let gltf = String::from_utf8(triangle_gltf_with_attribute(
+        triangle_buffer(),
+        "TEXCOORD_0",
+        "VEC2",
+        &tex_coords,
+        &extra,
+    ))
+    .unwrap()
+    .replace(r#""indices": 2"#, r#""indices": 2, "material": 0"#);
+    let scene = Scene::from_slice("triangle", gltf.as_bytes()).unwrap();
+
+    let issue = find_issue(&scene, Subject::Mesh(0), "no tangents").unwrap();
+    assert_eq!(issue.severity, Severity::Info);
+}
+
+#[test]
+fn statistics_count_geometry_and_memory() {
+    let scene = Scene::from_slice("triangle", &embedded_triangle_gltf(WITH_SCENE)).unwrap();
+    let statistics = scene.metadata().statistics();
+
+    assert_eq!(statistics.vertex_count, 3);
+    assert_eq!(statistics.triangle_count, 1);
+    assert_eq!(statistics.draw_call_count, 1);
+    assert_eq!(
+        statistics.vertex_buffer_size,
+        3 * std::mem::size_of::<model::ModelVertex>() as u64
+    );
+    assert_eq!(statistics.index_buffer_size, 12);
+    assert_eq!(statistics.meshes[0].primitives[0].index_count, Some(3));
+    assert!(statistics.images.is_empty());
+    assert_eq!(statistics.gpu_memory(), 12 + statistics.vertex_buffer_size);
+}
+
+#[test]
+fn statistics_include_skins_and_images() {
+    let scene = Scene::from_file(CESIUM_MAN).unwrap();
+    let statistics = scene.metadata().statistics();
+
+    assert_eq!(statistics.skins.len(), scene.skins().len());
+    assert_eq!(
+        statistics.skins[0].joint_count,
+        scene.skins()[0].joints().len()
+    );
+    assert_eq!(statistics.images.len(), scene.images().len());
+    for (image, stats) in scene.images().iter().zip(&statistics.images) {
+        assert_eq!((stats.width, stats.height), (image.width, image.height));
+        assert!(stats.gpu_size >= u64::from(image.width * image.height * 4));
+    }
+}
+
+/// Check that two scenes render the same.
+fn assert_equivalent_scenes(expected: &Scene, actual: &Scene) {
+    let (expected_geometry, actual_geometry) = (expected.geometry(), actual.geometry());
+    assert_eq!(
+        expected_geometry.meshes().len(),
+        actual_geometry.meshes().len()
+    );
+    for (expected_mesh, actual_mesh) in expected_geometry
+        .meshes()
+        .iter()
+        .zip(actual_geometry.meshes())
+    {
+        assert_eq!(expected_mesh.weights(), actual_mesh.weights());
+        assert_eq!(
+            expected_mesh.primitives().len(),
+            actual_mesh.primitives().len()
+        );
+        for (e, a) in expected_mesh
+            .primitives()
+            .iter()
+            .zip(actual_mesh.primitives())
+        {
+            assert_eq!(e.indices(expected_geometry), a.indices(actual_geometry));
+            let (e_vertices, a_vertices) =
+                (e.vertices(expected_geometry), a.vertices(actual_geometry));
+            assert_eq!(e_vertices.len(), a_vertices.len());
+            for (e, a) in e_vertices.iter().zip(a_vertices) {
+                assert_eq!(e.position, a.position);
+                assert_eq!(e.normal, a.normal);
+                assert_eq!(e.tex_coords_0, a.tex_coords_0);
+                assert_eq!(e.tangent, a.tangent);
+                assert_eq!(e.weights, a.weights);
+                assert_eq!(e.joints, a.joints);
+            }
+            assert_eq!(e.targets(), a.targets());
+            let (e, a) = (e.material(), a.material());
+            assert_eq!(e.get_color(), a.get_color());
+            assert_eq!(e.get_emissive(), a.get_emissive());
+            assert_eq!(e.get_alpha_mode(), a.get_alpha_mode());
+            assert_eq!(e.get_color_texture_index(), a.get_color_texture_index());
+            assert_eq!(e.get_normal_scale(), a.get_normal_scale());
+            assert_eq!(
+                material_name(expected, e.get_index()),
+                material_name(actual, a.get_index())
+            );
+        }
+    }
+
+    assert_eq!(expected.nodes().nodes().len(), actual.nodes().nodes().len());
+    for (e, a) in expected.nodes().nodes().iter().zip(actual.nodes().nodes()) {
+        assert_eq!(e.transform(), a.transform());
+        assert_eq!(e.mesh_index(), a.mesh_index());
+        assert_eq!(e.skin_index(), a.skin_index());
+        assert_eq!(e.light_index(), a.light_index());
+    }
+
+    assert_eq!(expected.skins().len(), actual.skins().len());
+    for (e, a) in expected.skins().iter().zip(actual.skins()) {
+        let e_matrices = e.joints().iter().map(|j| j.matrix()).collect::<Vec<_>>();
+        let a_matrices = a.joints().iter().map(|j| j.matrix()).collect::<Vec<_>>();
+        assert_eq!(e_matrices, a_matrices);
+    }
+
+    assert_eq!(expected.lights().len(), actual.lights().len());
+    for (e, a) in expected.lights().iter().zip(actual.lights()) {
+        assert_eq!(e.color(), a.color());
+        assert_eq!(e.intensity(), a.intensity());
+        assert_eq!(e.light_type(), a.light_type());
+    }
+
+    assert_eq!(expected.textures().len(), actual.textures().len());
+    assert_eq!(expected.images().len(), actual.images().len());
+    for (e, a) in expected.images().iter().zip(actual.images()) {
+        assert_eq!((e.width, e.height), (a.width, a.height));
+        assert!(e.pixels == a.pixels);
+    }
+
+    assert_eq!(
+        expected.metadata().animation_count(),
+        actual.metadata().animation_count()
+    );
+
+    // Unused materials are not exported so their names are compared per primitive
+    let (e, a) = (expected.metadata().names(), actual.metadata().names());
+    assert_eq!(e.nodes, a.nodes);
+    assert_eq!(e.meshes, a.meshes);
+    assert_eq!(e.skins, a.skins);
+    assert_eq!(e.animations, a.animations);
+    assert_eq!(e.images, a.images);
+    assert_eq!(e.textures, a.textures);
+    assert_eq!(e.lights, a.lights);
+}
+
+fn material_name(scene: &Scene, index: Option<usize>) -> Option<&str> {
+    index
+        .and_then(|index| scene.metadata().names().materials.get(index))
+        .and_then(Option::as_deref)
+}
+
+#[test]
+fn exported_glb_is_equivalent() {
+    let mut scene = Scene::from_file(CESIUM_MAN).unwrap();
+    let mut exported = Scene::from_slice("exported", &scene.to_glb().unwrap()).unwrap();
+    assert_equivalent_scenes(&scene, &exported);
+
+    // Animations are exported too
+    scene.update(0.5);
+    exported.update(0.5);
+    assert_equivalent_scenes(&scene, &exported);
+}
+
+#[test]
+fn exported_glb_keeps_the_edited_transforms_without_the_animations() {
+    let translation = Vector3::new(1.0, 2.0, 3.0);
+    let rotation = Quaternion::from_angle_y(Deg(90.0));
+    let scale = Vector3::new(2.0, 2.0, 2.0);
+
+    let mut edited = Scene::from_file(CESIUM_MAN).unwrap();
+    edited.set_node_transform(0, translation, rotation, scale);
+
+    let mut scene = Scene::from_file(CESIUM_MAN).unwrap();
+    scene.update(0.5);
+    scene.set_node_transform(0, translation, rotation, scale);
+    scene.update(0.5);
+
+    let exported = Scene::from_slice("exported", &scene.to_glb().unwrap()).unwrap();
+    assert_equivalent_scenes(&edited, &exported);
+}
+
+/// A named triangle with a normal texture and a morph target displacing its vertices along z.
+fn named_morphed_triangle_gltf() -> Vec<u8> {
+    let mut png = Vec::new();
+    image::DynamicImage::new_rgba8(1, 1)
+        .write_to(&mut png, image::ImageOutputFormat::Png)
+        .unwrap();
+    let extra = format!(
+        r#""scene": 0, "scenes": [{{ "nodes": [0] }}],
+        "materials": [{{ "name": "Bumpy", "normalTexture": {{ "index": 0, "scale": 0.5 }} }}],
+        "textures": [{{ "name": "Normals", "source": 0 }}],
+        "images": [{{ "name": "Flat", "uri": "data:image/png;base64,{}" }}],"#,
+        base64::encode(&png)
+    );
+    let displacements = f32_bytes(&[0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0]);
+    String::from_utf8(triangle_gltf_with_attribute(
+        triangle_buffer(),
+        "_DISPLACEMENT",
+        "VEC3",
+        &displacements,
+        &extra,
+    ))
+    .unwrap()
+    .replace(
+        r#", "_DISPLACEMENT": 3 }"#,
+        r#" }, "targets": [{ "POSITION": 3 }]"#,
+    )
+    .replace(
+        r#""bufferView": 3, "componentType": 5126, "count": 3, "type": "VEC3" }"#,
+        r#""bufferView": 3, "componentType": 5126, "count": 3, "type": "VEC3",
+           "min": [0.0, 0.0, 1.0], "max": [0.0, 0.0, 1.0] }"#,
+    )
+    .replace(r#""indices": 2"#, r#""indices": 2, "material": 0"#)
+    .replace(
+        r#""nodes": [{ "mesh": 0 }]"#,
+        r#""nodes": [{ "name": "Node", "mesh": 0 }]"#,
+    )
+    .replace(
+        r#""meshes": [{"#,
+        r#""meshes": [{ "name": "Triangle", "weights": [0.5],"#,
+    )
+    .into_bytes()
+}
+
+#[test]
+fn exported_glb_keeps_names_and_morph_targets() {
+    let scene = Scene::from_slice("triangle", &named_morphed_triangle_gltf()).unwrap();
+    let primitive = &scene.geometry().meshes()[0].primitives()[0];
+    assert_eq!(primitive.targets()[0].positions, vec![[0.0, 0.0, 1.0]; 3]);
+    assert_eq!(primitive.material().get_normal_scale(), 0.5);
+    assert_eq!(scene.metadata().names().nodes, [Some(String::from("Node"))]);
+
+    let exported = Scene::from_slice("exported", &scene.to_glb().unwrap()).unwrap();
+    assert_equivalent_scenes(&scene, &exported);
+}
+
+#[test]
+fn hidden_nodes_are_exported_without_their_mesh() {
+    let dir = std::env::temp_dir().join("model_tests_export_hidden");
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("triangle.glb");
+
+    let scene = Scene::from_slice("triangle", &named_morphed_triangle_gltf()).unwrap();
+    scene.export_as(&path, ExportFormat::Glb, &[false]).unwrap();
+
+    let exported = Scene::from_file(&path).unwrap();
+    assert_eq!(exported.nodes().nodes().len(), 1);
+    assert_eq!(exported.nodes().nodes()[0].mesh_index(), None);
+    assert_eq!(
+        exported.metadata().names().nodes,
+        [Some(String::from("Node"))]
+    );
+}
+
+#[test]
+fn exported_gltf_is_written_with_its_buffer() {
+    let dir = std::env::temp_dir().join("model_tests_export");
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("cesium.gltf");
+
+    let scene = Scene::from_file(CESIUM_MAN).unwrap();
+    scene.export(&path).unwrap();
+    assert!(dir.join("cesium.bin").exists());
+
+    let exported = Scene::from_file(&path).unwrap();
+    assert_equivalent_scenes(&scene, &exported);
+    assert_eq!(exported.metadata().external_resources(), ["cesium.bin"]);
+}
+
+#[test]
+fn export_format_depends_on_extension() {
+    assert_eq!(ExportFormat::from_path("model.glb"), ExportFormat::Glb);
+    assert_eq!(ExportFormat::from_path("model.GLB"), ExportFormat::Glb);
+    assert_eq!(ExportFormat::from_path("model.gltf"), ExportFormat::Gltf);
+    assert_eq!(ExportFormat::from_path("model"), ExportFormat::Gltf);
+}
+
+/// A concave comb-shaped polygon without normals, which a fan can't triangulate,
+/// and a textured triangle using a material from a library in a subdirectory.
+const COMB_OBJ: &str = "mtllib materials/comb.mtl
+o Comb
+v 0 0 0
+v 3 0 0
+v 3 2 0
+v 2 2 0
+v 2 1 0
+v 1 1 0
+v 1 2 0
+v 0 2 0
+usemtl Metal
+f 1 2 3 4 5 6 7 8
+o Triangle
+v 0 0 1
+v 1 0 1
+v 0 1 1
+vt 0 0
+vt 1 0
+vt 0 1
+usemtl Phong
+f 9/1 10/2 11/3
+";
+
+const COMB_MTL: &str = "newmtl Metal
+Kd 0.8 0.2 0.1
+d 0.5
+Pr 0.25
+Pm 1.0
+Ke 0.1 0.2 0.3
+map_Pr -bm 1.0 rough.png
+
+newmtl Phong
+Kd 1 1 1
+Ns 98
+
+newmtl Unused
+Kd 0 0 0
+";
+
+fn write_comb_obj(dir: &std::path::Path) -> std::path::PathBuf {
+    std::fs::create_dir_all(dir.join("materials")).unwrap();
+    std::fs::write(dir.join("materials/comb.mtl"), COMB_MTL).unwrap();
+    image::GrayImage::from_pixel(2, 2, image::Luma([64]))
+        .save(dir.join("materials/rough.png"))
+        .unwrap();
+    let path = dir.join("comb.obj");
+    std::fs::write(&path, COMB_OBJ).unwrap();
+    path
+}
+
+#[test]
+fn obj_polygons_are_triangulated_with_generated_normals() {
+    let path = write_comb_obj(&std::env::temp_dir().join("model_tests_obj_polygons"));
+    let scene = Scene::from_obj_file(&path).unwrap();
+    let geometry = scene.geometry();
+    assert_eq!(geometry.meshes().len(), 2);
+
+    let comb = &geometry.meshes()[0].primitives()[0];
+    let vertices = comb.vertices(geometry);
+    let indices = comb.indices(geometry).unwrap();
+    assert_eq!(indices.len(), 3 * 6);
+
+    // All triangles are counter-clockwise and cover the polygon exactly
+    let mut doubled_area = 0.0;
+    for triangle in indices.chunks_exact(3) {
+        let a = vertices[triangle[0] as usize].position;
+        let b = vertices[triangle[1] as usize].position;
+        let c = vertices[triangle[2] as usize].position;
+        let area = (b[0] - a[0]) * (c[1] - a[1]) - (b[1] - a[1]) * (c[0] - a[0]);
+        assert!(area > 0.0);
+        doubled_area += area;
+    }
+    assert_approx_eq(doubled_area, 10.0);
+
+    for vertex in vertices {
+        assert_eq!(vertex.normal, [0.0, 0.0, 1.0]);
+    }
+
+    let triangle = &geometry.meshes()[1].primitives()[0];
+    for vertex in triangle.vertices(geometry) {
+        assert_eq!(vertex.normal, [0.0, 0.0, 1.0]);
+        assert_ne!(vertex.tangent, [1.0, 1.0, 1.0, 1.0]);
+    }
+    // The origin of texture coordinates is flipped to the top left corner
+    assert_eq!(triangle.vertices(geometry)[0].tex_coords_0, [0.0, 1.0]);
+}
+
+#[test]
+fn obj_materials_are_mapped_to_pbr() {
+    let path = write_comb_obj(&std::env::temp_dir().join("model_tests_obj_materials"));
+    let scene = Scene::from_obj_file(&path).unwrap();
+    let geometry = scene.geometry();
+
+    let metal = geometry.meshes()[0].primitives()[0].material();
+    assert_eq!(metal.get_color(), [0.8, 0.2, 0.1, 0.5]);
+    assert!(metal.is_transparent());
+    assert_eq!(metal.get_emissive(), [0.1, 0.2, 0.3]);
+    match metal.get_workflow() {
+        Workflow::MetallicRoughness(workflow) => {
+            assert_approx_eq(workflow.get_metallic(), 1.0);
+            assert_approx_eq(workflow.get_roughness(), 0.25);
+            assert!(workflow.get_metallic_roughness_texture().is_some());
+        }
+        _ => panic!("Expected metallic roughness workflow"),
+    }
+
+    // Roughness map in green, no metalness map so blue is white
+    assert_eq!(scene.images().len(), 1);
+    assert_eq!(&scene.images()[0].pixels[..4], &[255, 64, 255, 255]);
+
+    // Without d or Tr statement the material is opaque
+    let phong = geometry.meshes()[1].primitives()[0].material();
+    assert_eq!(phong.get_color(), [1.0, 1.0, 1.0, 1.0]);
+    assert!(!phong.is_transparent());
+    assert_eq!(phong.get_alpha_mode(), ALPHA_MODE_OPAQUE);
+    match phong.get_workflow() {
+        Workflow::MetallicRoughness(workflow) => {
+            assert_approx_eq(workflow.get_metallic(), 0.0);
+            assert_approx_eq(workflow.get_roughness(), 0.02f32.sqrt());
+        }
+        _ => panic!("Expected metallic roughness workflow"),
+    }
+
+    let metadata = scene.metadata();
+    assert_eq!(metadata.material_count(), 2);
+    assert_eq!(
+        metadata.external_resources(),
+        ["materials/comb.mtl", "materials/rough.png"]
+    );
+    assert!(metadata.issues().is_empty());
+}
+
+#[test]
+fn editing_a_material_updates_the_primitives_using_it() {
+    let path = write_comb_obj(&std::env::temp_dir().join("model_tests_material_edit"));
+    let mut scene = Scene::from_obj_file(&path).unwrap();
+
+    let mut metal = scene.geometry().meshes()[0].primitives()[0].material();
+    metal.set_color([0.0, 0.0, 1.0, 1.0]);
+    metal.set_alpha_mode(ALPHA_MODE_OPAQUE);
+    metal.set_double_sided(true);
+    metal.set_texture_enabled(TextureSlot::Material, false);
+    scene.set_material(metal);
+
+    let geometry = scene.geometry();
+    let edited = geometry.meshes()[0].primitives()[0].material();
+    assert_eq!(edited.get_color(), [0.0, 0.0, 1.0, 1.0]);
+    assert!(!edited.is_transparent());
+    assert!(edited.is_double_sided());
+    assert!(edited.get_enabled_texture(TextureSlot::Material).is_none());
+    match edited.get_workflow() {
+        Workflow::MetallicRoughness(workflow) => {
+            assert!(workflow.get_metallic_roughness_texture().is_some())
+        }
+        _ => panic!("Expected metallic roughness workflow"),
+    }
+
+    let phong = geometry.meshes()[1].primitives()[0].material();
+    assert_ne!(phong.get_color(), [0.0, 0.0, 1.0, 1.0]);
+    assert!(!phong.is_double_sided());
+}
+
+#[test]
+fn texture_references_list_the_material_slots_using_each_texture() {
+    let path = write_comb_obj(&std::env::temp_dir().join("model_tests_texture_references"));
+    let mut scene = Scene::from_obj_file(&path).unwrap();
+
+    let expected = vec![vec![TextureReference {
+        material_index: Some(0),
+        slot: TextureSlot::Material,
+    }]];
+    assert_eq!(scene.texture_references(), expected);
+
+    // Disabled textures are still referenced
+    let mut metal = scene.geometry().meshes()[0].primitives()[0].material();
+    metal.set_texture_enabled(TextureSlot::Material, false);
+    scene.set_material(metal);
+    assert_eq!(scene.texture_references(), expected);
+}
+
+const SQUARE_STL: &str = "solid square
+facet normal 0 0 0
+  outer loop
+    vertex 0 0 0
+    vertex 2 0 0
+    vertex 2 2 0
+  endloop
+endfacet
+facet normal 0 0 1
+  outer loop
+    vertex 0 0 0
+    vertex 2 2 0
+    vertex 0 2 0
+  endloop
+endfacet
+endsolid square
+";
+
+fn square_binary_stl() -> Vec<u8> {
+    let triangles = [
+        [[0.0f32, 0.0, 0.0], [2.0, 0.0, 0.0], [2.0, 2.0, 0.0]],
+        [[0.0, 0.0, 0.0], [2.0, 2.0, 0.0], [0.0, 2.0, 0.0]],
+    ];
+    let mut stl = vec![0u8; 80];
+    stl.extend_from_slice(&(triangles.len() as u32).to_le_bytes());
+    for triangle in &triangles {
+        // Normals are left to zero
+        let values = [0.0; 3].iter().chain(triangle.iter().flatten());
+        for v in values {
+            stl.extend_from_slice(&v.to_le_bytes());
+        }
+        stl.extend_from_slice(&[0, 0]);
+    }
+    stl
+}
+
+#[test]
+fn stl_facets_have_flat_normals_and_default_material() {
+    let dir = std::env::temp_dir().join("model_tests_stl");
+    std::fs::create_dir_all(&dir).unwrap();
+    let ascii = dir.join("square.stl");
+    std::fs::write(&ascii, SQUARE_STL).unwrap();
+    let binary = dir.join("square_binary.stl");
+    std::fs::write(&binary, square_binary_stl()).unwrap();
+
+    for path in &[ascii, binary] {
+        let scene = Scene::from_stl_file(path).unwrap();
+        let geometry = scene.geometry();
+        let primitive = &geometry.meshes()[0].primitives()[0];
+        assert_eq!(primitive.mode(), PrimitiveMode::Triangles);
+        assert_eq!(primitive.indices(geometry).unwrap().len(), 6);
+
+        let vertices = primitive.vertices(geometry);
+        assert_eq!(vertices.len(), 6);
+        assert_eq!(vertices[5].position, [0.0, 2.0, 0.0]);
+        for vertex in vertices {
+            assert_eq!(vertex.normal, [0.0, 0.0, 1.0]);
+            assert_eq!(vertex.colors, [1.0, 1.0, 1.0, 1.0]);
+        }
+
+        let material = primitive.material();
+        assert_eq!(material.get_color(), [1.0, 1.0, 1.0, 1.0]);
+        assert!(!material.is_unlit());
+        assert_eq!(scene.metadata().material_count(), 0);
+        assert!(scene.metadata().issues().is_empty());
+    }
+}
+
+const COLORED_PLY: &str = "ply
+format ascii 1.0
+comment quad with vertex colors and an unused edge element
+element vertex 4
+property float x
+property float y
+property float z
+property uchar red
+property uchar green
+property uchar blue
+element face 1
+property list uchar int vertex_indices
+element edge 1
+property int vertex1
+property int vertex2
+end_header
+0 0 0 255 0 0
+1 0 0 0 255 0
+1 1 0 0 0 255
+0 1 0 255 255 255
+4 0 1 2 3
+0 2
+";
+
+#[test]
+fn ply_faces_are_triangulated_with_vertex_colors() {
+    let dir = std::env::temp_dir().join("model_tests_ply_faces");
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("quad.ply");
+    std::fs::write(&path, COLORED_PLY).unwrap();
+
+    let scene = Scene::from_ply_file(&path).unwrap();
+    let geometry = scene.geometry();
+    let primitive = &geometry.meshes()[0].primitives()[0];
+    assert_eq!(primitive.mode(), PrimitiveMode::Triangles);
+    assert_eq!(primitive.indices(geometry).unwrap().len(), 6);
+
+    let vertices = primitive.vertices(geometry);
+    assert_eq!(vertices.len(), 4);
+    assert_eq!(vertices[0].colors, [1.0, 0.0, 0.0, 1.0]);
+    assert_eq!(vertices[1].colors, [0.0, 1.0, 0.0, 1.0]);
+    assert_eq!(vertices[2].colors, [0.0, 0.0, 1.0, 1.0]);
+    for vertex in vertices {
+        assert_eq!(vertex.normal, [0.0, 0.0, 1.0]);
+    }
+    assert!(!primitive.material().is_unlit());
+}
+
+#[test]
+fn ply_faces_with_nan_positions_are_triangulated() {
+    let dir = std::env::temp_dir().join("model_tests_ply_nan");
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("quad.ply");
+    std::fs::write(
+        &path,
+        COLORED_PLY.replace("1 1 0 0 0 255", "nan 1 0 0 0 255"),
+    )
+    .unwrap();
+
+    let scene = Scene::from_ply_file(&path).unwrap();
+    let geometry = scene.geometry();
+    let primitive = &geometry.meshes()[0].primitives()[0];
+    assert_eq!(primitive.indices(geometry).unwrap().len(), 6);
+}
+
+#[test]
+fn single_point_ply_is_only_centered() {
+    let dir = std::env::temp_dir().join("model_tests_ply_point");
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("point.ply");
+    std::fs::write(
+        &path,
+        "ply\nformat ascii 1.0\nelement vertex 1\nproperty float x\nproperty float y\n\
+         property float z\nend_header\n1 2 3\n",
+    )
+    .unwrap();
+
+    let scene = Scene::from_ply_file(&path).unwrap();
+    let transform = scene.global_transform();
+    let position = transform.transform_point(Point3::new(1.0, 2.0, 3.0));
+    assert_eq!(position, Point3::new(0.0, 0.0, 0.0));
+}
+
+fn point_cloud_ply(big_endian: bool) -> Vec<u8> {
+    let format = if big_endian {
+        "binary_big_endian"
+    } else {
+        "binary_little_endian"
+    };
+    let mut ply = format!(
+        "ply\nformat {} 1.0\nelement vertex 3\nproperty float x\nproperty float y\n\
+         property float z\nproperty uchar red\nproperty uchar green\nproperty uchar blue\n\
+         property uchar alpha\nend_header\n",
+        format
+    )
+    .into_bytes();
+    for (i, position) in [[0.0f32, 0.0, 0.0], [1.0, 2.0, 3.0], [-1.0, 0.5, 0.0]]
+        .iter()
+        .enumerate()
+    {
+        for v in position {
+            let bytes = if big_endian {
+                v.to_be_bytes()
+            } else {
+                v.to_le_bytes()
+            };
+            ply.extend_from_slice(&bytes);
+        }
+        ply.extend_from_slice(&[255, 0, 51 * i as u8, 255]);
+    }
+    ply
+}
+
+#[test]
+fn ply_without_faces_is_an_unlit_point_cloud() {
+    let dir = std::env::temp_dir().join("model_tests_ply_points");
+    std::fs::create_dir_all(&dir).unwrap();
+
+    for big_endian in &[false, true] {
+        let path = dir.join(format!("points_{}.ply", big_endian));
+        std::fs::write(&path, point_cloud_ply(*big_endian)).unwrap();
+
+        let scene = Scene::from_ply_file(&path).unwrap();
+        let geometry = scene.geometry();
+        let primitive = &geometry.meshes()[0].primitives()[0];
+        assert_eq!(primitive.mode(), PrimitiveMode::Points);
+        assert!(primitive.indices(geometry).is_none());
+        assert!(primitive.material().is_unlit());
+
+        let vertices = primitive.vertices(geometry);
+        assert_eq!(vertices.len(), 3);
+        assert_eq!(vertices[1].position, [1.0, 2.0, 3.0]);
+        assert_eq!(vertices[2].colors, [1.0, 0.0, 0.4, 1.0]);
+
+        let metadata = scene.metadata();
+        assert_eq!(metadata.statistics().triangle_count, 0);
+        assert!(metadata.issues().is_empty());
+    }
+}
+
+#[test]
+fn truncated_ply_is_rejected() {
+    let dir = std::env::temp_dir().join("model_tests_ply_truncated");
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("truncated.ply");
+    let mut ply = point_cloud_ply(false);
+    ply.truncate(ply.len() - 2);
+    std::fs::write(&path, ply).unwrap();
+
+    let result = Scene::from_ply_file(&path);
+    assert!(matches!(result, Err(ModelLoadingError::Parse(_))));
+}
+
+#[test]
+fn ply_with_oversized_element_count_is_rejected() {
+    let dir = std::env::temp_dir().join("model_tests_ply_oversized");
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("oversized.ply");
+    let ply = COLORED_PLY.replace("element vertex 4", "element vertex 99999999999");
+    std::fs::write(&path, ply).unwrap();
+
+    let result = Scene::from_ply_file(&path);
+    assert!(matches!(result, Err(ModelLoadingError::Parse(_))));
+}
@@ -1,9 +1,16 @@
 use crate::controls::*;
-use math::cgmath::{InnerSpace, Matrix4, Point3, Vector3};
-use math::clamp;
+use math::cgmath::{Deg, EuclideanSpace, InnerSpace, Matrix4, Point3, SquareMatrix, Vector3};
+use math::{clamp, Ray, AABB};
 
-const MIN_ORBITAL_CAMERA_DISTANCE: f32 = 0.5;
+/// Size of the scene framed by the default camera.
+const DEFAULT_SCENE_SIZE: f32 = 10.0;
+/// Minimal distance to the target relative to the scene size.
+const MIN_ORBITAL_CAMERA_DISTANCE_FACTOR: f32 = 0.05;
 const TARGET_MOVEMENT_SPEED: f32 = 0.003;
+/// Near plane distance relative to the scene size.
+const Z_NEAR_FACTOR: f32 = 0.001;
+/// Far plane distance relative to the distance to the target plus the scene size.
+const Z_FAR_FACTOR: f32 = 5.0;
 
 #[derive(Clone, Copy)]
 pub struct Camera {
@@ -11,6 +18,21 @@ pub struct Camera {
     phi: f32,
     r: f32,
     target: Point3<f32>,
+    scene_size: f32,
+}
+
+impl Camera {
+    /// Create a camera looking at the center of `aabb` from a distance
+    /// that depends on its size.
+    pub fn framing(aabb: AABB<f32>) -> Self {
+        let scene_size = aabb.get_larger_side_size().max(std::f32::EPSILON);
+        Camera {
+            r: scene_size,
+            target: Point3::from_vec(aabb.get_center()),
+            scene_size,
+            ..Default::default()
+        }
+    }
 }
 
 impl Camera {
@@ -25,6 +47,14 @@ impl Camera {
     pub fn target(&self) -> Point3<f32> {
         self.target
     }
+
+    pub fn z_near(&self) -> f32 {
+        self.scene_size * Z_NEAR_FACTOR
+    }
+
+    pub fn z_far(&self) -> f32 {
+        (self.r + self.scene_size) * Z_FAR_FACTOR
+    }
 }
 
 impl Camera {
@@ -65,7 +95,7 @@ impl Camera {
     }
 
     fn forward(&mut self, r: f32) {
-        if (self.r - r).abs() > MIN_ORBITAL_CAMERA_DISTANCE {
+        if (self.r - r).abs() > self.scene_size * MIN_ORBITAL_CAMERA_DISTANCE_FACTOR {
             self.r -= r;
         }
     }
@@ -76,8 +106,9 @@ impl Default for Camera {
         Camera {
             theta: 0.0_f32.to_radians(),
             phi: 90.0_f32.to_radians(),
-            r: 10.0,
+            r: DEFAULT_SCENE_SIZE,
             target: Point3::new(0.0, 0.0, 0.0),
+            scene_size: DEFAULT_SCENE_SIZE,
         }
     }
 }
@@ -113,4 +144,48 @@ impl CameraUBO {
             z_far,
         }
     }
+
+    /// Compute the matrices used to render the scene from `camera`.
+    ///
+    /// `aspect` is the width of the viewport divided by its height.
+    pub fn from_camera(camera: Camera, aspect: f32) -> Self {
+        let view = Matrix4::look_at(
+            camera.position(),
+            camera.target(),
+            Vector3::new(0.0, 1.0, 0.0),
+        );
+
+        let z_near = camera.z_near();
+        let z_far = camera.z_far();
+        let proj = math::perspective(Deg(45.0), aspect, z_near, z_far);
+        let inverted_proj = proj.invert().unwrap();
+
+        Self::new(view, proj, inverted_proj, camera.position(), z_near, z_far)
+    }
+
+    pub fn view(&self) -> Matrix4<f32> {
+        self.view
+    }
+
+    pub fn z_near(&self) -> f32 {
+        self.z_near
+    }
+
+    pub fn z_far(&self) -> f32 {
+        self.z_far
+    }
+
+    /// Get the matrix transforming world space positions to clip space.
+    pub fn view_proj(&self) -> Matrix4<f32> {
+        self.proj * self.view
+    }
+
+    /// Get the world space ray going through the point at `x` and `y` in
+    /// normalized device coordinates.
+    ///
+    /// Returns `None` if the view or projection matrices are not invertible.
+    pub fn ray(&self, x: f32, y: f32) -> Option<Ray<f32>> {
+        let inverse_view_proj = self.view_proj().invert()?;
+        Some(Ray::from_ndc(x, y, inverse_view_proj))
+    }
 }
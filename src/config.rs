@@ -1,4 +1,5 @@
 use crate::error::*;
+use model::Placement;
 use serde::Deserialize;
 use std::fs::File;
 
@@ -9,6 +10,7 @@ pub struct Config {
     msaa: Option<u32>,
     #[serde(default)]
     env: Environment,
+    keep_original_placement: Option<bool>,
 }
 
 impl Config {
@@ -27,6 +29,21 @@ impl Config {
     pub fn env(&self) -> &Environment {
         &self.env
     }
+
+    pub fn placement(&self) -> Placement {
+        if self.keep_original_placement.unwrap_or(false) {
+            Placement::Original
+        } else {
+            Placement::Normalized
+        }
+    }
+
+    pub fn keep_original_placement(self) -> Self {
+        Self {
+            keep_original_placement: Some(true),
+            ..self
+        }
+    }
 }
 
 impl Default for Config {
@@ -36,6 +53,7 @@ impl Default for Config {
             vsync: Some(false),
             msaa: Some(64),
             env: Default::default(),
+            keep_original_placement: Some(false),
         }
     }
 }
@@ -1,9 +1,16 @@
 use vulkan::winit::{DeviceEvent, ElementState, Event, MouseButton, MouseScrollDelta, WindowEvent};
 
+/// Maximum distance in pixels the cursor can move between the press and
+/// the release of the left button for it to count as a click.
+const CLICK_MAX_DISTANCE: f32 = 4.0;
+
 #[derive(Copy, Clone, Debug)]
 pub struct InputState {
     is_left_clicked: bool,
     is_right_clicked: bool,
+    cursor_position: [f32; 2],
+    left_press_position: Option<[f32; 2]>,
+    click_position: Option<[f32; 2]>,
     cursor_delta: [f32; 2],
     wheel_delta: f32,
 }
@@ -12,6 +19,9 @@ impl InputState {
     pub fn update(self, event: &Event) -> Self {
         let mut is_left_clicked = None;
         let mut is_right_clicked = None;
+        let mut cursor_position = self.cursor_position;
+        let mut left_press_position = self.left_press_position;
+        let mut click_position = self.click_position;
         let mut wheel_delta = self.wheel_delta;
         let mut cursor_delta = self.cursor_delta;
 
@@ -21,6 +31,7 @@ impl InputState {
                     if *state == ElementState::Pressed {
                         if *button == MouseButton::Left {
                             is_left_clicked = Some(true);
+                            left_press_position = Some(cursor_position);
                         }
                         if *button == MouseButton::Right {
                             is_right_clicked = Some(true)
@@ -28,12 +39,22 @@ impl InputState {
                     } else {
                         if *button == MouseButton::Left {
                             is_left_clicked = Some(false);
+                            if let Some(press_position) = left_press_position.take() {
+                                let x = cursor_position[0] - press_position[0];
+                                let y = cursor_position[1] - press_position[1];
+                                if x.hypot(y) <= CLICK_MAX_DISTANCE {
+                                    click_position = Some(cursor_position);
+                                }
+                            }
                         }
                         if *button == MouseButton::Right {
                             is_right_clicked = Some(false)
                         }
                     }
                 }
+                WindowEvent::CursorMoved { position, .. } => {
+                    cursor_position = [position.x as f32, position.y as f32];
+                }
                 WindowEvent::MouseWheel {
                     delta: MouseScrollDelta::LineDelta(_, v_lines),
                     ..
@@ -54,12 +75,16 @@ impl InputState {
         Self {
             is_left_clicked: is_left_clicked.unwrap_or(self.is_left_clicked),
             is_right_clicked: is_right_clicked.unwrap_or(self.is_right_clicked),
+            cursor_position,
+            left_press_position,
+            click_position,
             cursor_delta,
             wheel_delta,
         }
     }
 
     pub fn reset(&mut self) {
+        self.click_position = None;
         self.cursor_delta = [0.0, 0.0];
         self.wheel_delta = 0.0;
     }
@@ -74,6 +99,12 @@ impl InputState {
         self.is_right_clicked
     }
 
+    /// Get the position of the cursor in the window if the left button
+    /// was clicked without dragging since the last reset.
+    pub fn click_position(&self) -> Option<[f32; 2]> {
+        self.click_position
+    }
+
     pub fn cursor_delta(&self) -> [f32; 2] {
         self.cursor_delta
     }
@@ -88,6 +119,9 @@ impl Default for InputState {
         Self {
             is_left_clicked: false,
             is_right_clicked: false,
+            cursor_position: [0.0, 0.0],
+            left_press_position: None,
+            click_position: None,
             cursor_delta: [0.0, 0.0],
             wheel_delta: 0.0,
         }
@@ -0,0 +1,385 @@
+//! Transform gizmo of the selected node.
+//!
+//! The gizmo is drawn with imgui over the scene. Its handles are hovered and
+//! dragged in screen space and the drag is converted into a new local
+//! transform for the node. Translation and rotation happen along the axes of
+//! the parent of the node since its local transform is expressed in that
+//! space. Scaling happens along the axes of the node itself.
+
+use crate::camera::{Camera, CameraUBO};
+use imgui::{MouseButton, Ui};
+use math::cgmath::{
+    EuclideanSpace, InnerSpace, Matrix4, Point3, Quaternion, Rad, Rotation3, Transform, Vector2,
+    Vector3, Zero,
+};
+use std::{cmp::Ordering, f32::consts::PI};
+
+/// Length of the axes of the gizmo relative to its distance to the camera.
+const GIZMO_SIZE: f32 = 0.15;
+/// Maximum distance in pixels between the cursor and a handle to grab it.
+const HANDLE_PICK_DISTANCE: f32 = 6.0;
+const HANDLE_THICKNESS: f32 = 2.0;
+const CIRCLE_SEGMENT_COUNT: usize = 48;
+/// Scale factors are clamped so a node can't be flattened by a drag.
+const MIN_SCALE_FACTOR: f32 = 0.01;
+const AXIS_COLORS: [[f32; 4]; 3] = [
+    [0.9, 0.2, 0.2, 1.0],
+    [0.2, 0.8, 0.2, 1.0],
+    [0.2, 0.4, 0.9, 1.0],
+];
+const ACTIVE_COLOR: [f32; 4] = [1.0, 0.85, 0.1, 1.0];
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum GizmoMode {
+    Translate,
+    Rotate,
+    Scale,
+}
+
+/// Translation, rotation and scale of a node relative to its parent.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct NodeTransform {
+    pub translation: Vector3<f32>,
+    pub rotation: Quaternion<f32>,
+    pub scale: Vector3<f32>,
+}
+
+/// Transforms of the node edited with the gizmo.
+#[derive(Copy, Clone, Debug)]
+pub struct GizmoTarget {
+    pub local: NodeTransform,
+    /// Global transform of the node.
+    pub global: Matrix4<f32>,
+    /// Global transform of the parent of the node.
+    pub parent: Matrix4<f32>,
+}
+
+pub struct Gizmo {
+    mode: GizmoMode,
+    drag: Option<Drag>,
+}
+
+impl Gizmo {
+    pub fn mode(&self) -> GizmoMode {
+        self.mode
+    }
+
+    pub fn set_mode(&mut self, mode: GizmoMode) {
+        self.mode = mode;
+    }
+
+    /// Draw the gizmo of `target` and drag its handles with the left mouse button.
+    ///
+    /// Handles can't be grabbed when the cursor is `over_gui`. Returns the new
+    /// local transform of the node while a handle is dragged, and whether the
+    /// gizmo is hovered or dragged.
+    pub fn update(
+        &mut self,
+        ui: &Ui,
+        camera: Camera,
+        target: &GizmoTarget,
+        over_gui: bool,
+    ) -> (Option<NodeTransform>, bool) {
+        if !ui.is_mouse_down(MouseButton::Left) {
+            self.drag = None;
+        }
+
+        let [width, height] = ui.io().display_size;
+        if width <= 0.0 || height <= 0.0 {
+            return (None, false);
+        }
+
+        let viewport = Viewport::new(camera, width, height);
+        let handles = create_handles(self.mode, &viewport, target);
+        let cursor = Vector2::from(ui.io().mouse_pos);
+
+        let hovered_handle = if self.drag.is_none() && !over_gui {
+            find_hovered_handle(&handles, cursor)
+        } else {
+            None
+        };
+        if let Some(handle) = hovered_handle {
+            if ui.is_mouse_clicked(MouseButton::Left) {
+                self.drag = Some(Drag::new(self.mode, handle, cursor, target.local));
+            }
+        }
+
+        let active_axis = self
+            .drag
+            .as_ref()
+            .map(|drag| drag.handle.axis)
+            .or_else(|| hovered_handle.map(|handle| handle.axis));
+        draw_handles(ui, self.mode, &handles, active_axis);
+
+        let transform = self.drag.as_mut().map(|drag| drag.transform(cursor));
+        (transform, active_axis.is_some())
+    }
+}
+
+impl Default for Gizmo {
+    fn default() -> Self {
+        Self {
+            mode: GizmoMode::Translate,
+            drag: None,
+        }
+    }
+}
+
+/// Projection of world space positions in screen space.
+struct Viewport {
+    view_proj: Matrix4<f32>,
+    eye: Point3<f32>,
+    size: Vector2<f32>,
+}
+
+impl Viewport {
+    fn new(camera: Camera, width: f32, height: f32) -> Self {
+        let view_proj = CameraUBO::from_camera(camera, width / height).view_proj();
+        Self {
+            view_proj,
+            eye: camera.position(),
+            size: Vector2::new(width, height),
+        }
+    }
+
+    /// Get the screen position of `point` or `None` if it is behind the camera.
+    fn project(&self, point: Point3<f32>) -> Option<Vector2<f32>> {
+        let clip = self.view_proj * point.to_homogeneous();
+        if clip.w <= f32::EPSILON {
+            return None;
+        }
+        let x = (clip.x / clip.w + 1.0) * 0.5 * self.size.x;
+        let y = (clip.y / clip.w + 1.0) * 0.5 * self.size.y;
+        Some(Vector2::new(x, y))
+    }
+}
+
+/// Screen space geometry of one of the axes of the gizmo.
+#[derive(Clone, Debug)]
+struct Handle {
+    axis: usize,
+    /// Length of the axis of the edited space in world space.
+    axis_scale: f32,
+    /// Length of the handle in world space.
+    length: f32,
+    /// Whether the axis points towards the camera.
+    facing_camera: bool,
+    /// Screen position of the origin of the node.
+    center: Vector2<f32>,
+    /// Screen positions of the line or circle of the handle.
+    points: Vec<Vector2<f32>>,
+}
+
+impl Handle {
+    /// Get the distance in pixels between `cursor` and the handle.
+    fn distance(&self, cursor: Vector2<f32>) -> f32 {
+        self.points
+            .windows(2)
+            .map(|segment| distance_to_segment(cursor, segment[0], segment[1]))
+            .fold(f32::INFINITY, f32::min)
+    }
+}
+
+fn create_handles(mode: GizmoMode, viewport: &Viewport, target: &GizmoTarget) -> Vec<Handle> {
+    let pivot = Point3::from_vec(target.global.w.truncate());
+    let center = match viewport.project(pivot) {
+        Some(center) => center,
+        None => return Vec::new(),
+    };
+    let length = (pivot - viewport.eye).magnitude() * GIZMO_SIZE;
+    let space = match mode {
+        GizmoMode::Scale => target.global,
+        GizmoMode::Translate | GizmoMode::Rotate => target.parent,
+    };
+
+    (0..3)
+        .filter_map(|axis| {
+            let direction = space.transform_vector(unit_axis(axis));
+            let axis_scale = direction.magnitude();
+            if axis_scale <= f32::EPSILON {
+                return None;
+            }
+            let direction = direction / axis_scale;
+
+            let points = match mode {
+                GizmoMode::Translate | GizmoMode::Scale => {
+                    vec![center, viewport.project(pivot + direction * length)?]
+                }
+                GizmoMode::Rotate => {
+                    let (u, v) = perpendicular_basis(direction);
+                    (0..=CIRCLE_SEGMENT_COUNT)
+                        .map(|segment| {
+                            let angle = segment as f32 / CIRCLE_SEGMENT_COUNT as f32 * 2.0 * PI;
+                            let offset = (u * angle.cos() + v * angle.sin()) * length;
+                            viewport.project(pivot + offset)
+                        })
+                        .collect::<Option<Vec<_>>>()?
+                }
+            };
+
+            Some(Handle {
+                axis,
+                axis_scale,
+                length,
+                facing_camera: direction.dot(viewport.eye - pivot) > 0.0,
+                center,
+                points,
+            })
+        })
+        .collect()
+}
+
+fn find_hovered_handle(handles: &[Handle], cursor: Vector2<f32>) -> Option<&Handle> {
+    handles
+        .iter()
+        .map(|handle| (handle, handle.distance(cursor)))
+        .filter(|(_, distance)| *distance <= HANDLE_PICK_DISTANCE)
+        .min_by(|(_, d1), (_, d2)| d1.partial_cmp(d2).unwrap_or(Ordering::Equal))
+        .map(|(handle, _)| handle)
+}
+
+fn draw_handles(ui: &Ui, mode: GizmoMode, handles: &[Handle], active_axis: Option<usize>) {
+    let draw_list = ui.get_background_draw_list();
+    for handle in handles {
+        let color = if active_axis == Some(handle.axis) {
+            ACTIVE_COLOR
+        } else {
+            AXIS_COLORS[handle.axis]
+        };
+
+        for segment in handle.points.windows(2) {
+            draw_list
+                .add_line(segment[0].into(), segment[1].into(), color)
+                .thickness(HANDLE_THICKNESS)
+                .build();
+        }
+
+        let end = handle.points[handle.points.len() - 1];
+        match mode {
+            GizmoMode::Translate => draw_list
+                .add_circle(end.into(), 5.0, color)
+                .filled(true)
+                .build(),
+            GizmoMode::Scale => draw_list
+                .add_rect(
+                    (end - Vector2::new(4.0, 4.0)).into(),
+                    (end + Vector2::new(4.0, 4.0)).into(),
+                    color,
+                )
+                .filled(true)
+                .build(),
+            GizmoMode::Rotate => {}
+        }
+    }
+}
+
+/// A handle being dragged.
+#[derive(Clone, Debug)]
+struct Drag {
+    mode: GizmoMode,
+    handle: Handle,
+    start_cursor: Vector2<f32>,
+    start_transform: NodeTransform,
+    last_cursor: Vector2<f32>,
+    /// Angle the cursor turned around the center of the gizmo since the drag started.
+    angle: f32,
+}
+
+impl Drag {
+    fn new(
+        mode: GizmoMode,
+        handle: &Handle,
+        cursor: Vector2<f32>,
+        start_transform: NodeTransform,
+    ) -> Self {
+        Self {
+            mode,
+            handle: handle.clone(),
+            start_cursor: cursor,
+            start_transform,
+            last_cursor: cursor,
+            angle: 0.0,
+        }
+    }
+
+    /// Compute the transform of the node with the cursor at `cursor`.
+    fn transform(&mut self, cursor: Vector2<f32>) -> NodeTransform {
+        let mut transform = self.start_transform;
+        let axis = self.handle.axis;
+
+        match self.mode {
+            GizmoMode::Translate | GizmoMode::Scale => {
+                // Movement of the cursor along the handle relative to its length
+                let handle = self.handle.points[1] - self.handle.points[0];
+                let handle_length2 = handle.magnitude2();
+                let amount = if handle_length2 > 1.0 {
+                    (cursor - self.start_cursor).dot(handle) / handle_length2
+                } else {
+                    0.0
+                };
+
+                if self.mode == GizmoMode::Translate {
+                    transform.translation[axis] +=
+                        amount * self.handle.length / self.handle.axis_scale;
+                } else {
+                    transform.scale[axis] *= (1.0 + amount).max(MIN_SCALE_FACTOR);
+                }
+            }
+            GizmoMode::Rotate => {
+                let center = self.handle.center;
+                let angle = |position: Vector2<f32>| {
+                    let direction = position - center;
+                    direction.y.atan2(direction.x)
+                };
+                let mut delta = angle(cursor) - angle(self.last_cursor);
+                if delta > PI {
+                    delta -= 2.0 * PI;
+                } else if delta < -PI {
+                    delta += 2.0 * PI;
+                }
+                self.angle += delta;
+                self.last_cursor = cursor;
+
+                // The screen y axis points down so angles grow clockwise
+                let angle = if self.handle.facing_camera {
+                    -self.angle
+                } else {
+                    self.angle
+                };
+                let rotation = Quaternion::from_axis_angle(unit_axis(axis), Rad(angle));
+                transform.rotation = rotation * transform.rotation;
+            }
+        }
+
+        transform
+    }
+}
+
+fn unit_axis(axis: usize) -> Vector3<f32> {
+    let mut unit = Vector3::zero();
+    unit[axis] = 1.0;
+    unit
+}
+
+/// Get two unit vectors perpendicular to `direction` and to each other.
+fn perpendicular_basis(direction: Vector3<f32>) -> (Vector3<f32>, Vector3<f32>) {
+    let helper = if direction.x.abs() < 0.9 {
+        Vector3::unit_x()
+    } else {
+        Vector3::unit_y()
+    };
+    let u = direction.cross(helper).normalize();
+    let v = direction.cross(u);
+    (u, v)
+}
+
+fn distance_to_segment(point: Vector2<f32>, start: Vector2<f32>, end: Vector2<f32>) -> f32 {
+    let segment = end - start;
+    let length2 = segment.magnitude2();
+    let t = if length2 > 0.0 {
+        ((point - start).dot(segment) / length2).max(0.0).min(1.0)
+    } else {
+        0.0
+    };
+    (point - (start + segment * t)).magnitude()
+}
@@ -1,24 +1,62 @@
 use crate::camera::Camera;
-use crate::renderer::{OutputMode, ToneMapMode};
+use crate::gizmo::{Gizmo, GizmoMode, GizmoTarget, NodeTransform};
+use crate::loader::LoadingProgress;
+use crate::renderer::{
+    CullingStatistics, DebugOverlays, OutputMode, ShadowSettings, SortDistanceOverrides,
+    TexturePreview, ToneMapMode, TransparencyMode,
+};
 use imgui::*;
 use imgui_winit_support::{HiDpiMode, WinitPlatform};
-use model::{metadata::*, PlaybackState};
+use math::cgmath::{Deg, Euler, Matrix4, Quaternion, Vector3};
+use model::{
+    metadata::*, ExportFormat, Hit, Placement, PlaybackState, TextureData, TextureReference,
+    TextureSlot, Workflow, ALPHA_MODE_BLEND, ALPHA_MODE_MASK, ALPHA_MODE_OPAQUE,
+};
 use std::borrow::Cow;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
 use std::time::Instant;
 use vulkan::winit::{Event, Window as WinitWindow};
 
 const SSAO_KERNEL_SIZES: [u32; 4] = [16, 32, 64, 128];
+const SHADOW_RESOLUTIONS: [u32; 4] = [512, 1024, 2048, 4096];
+const EXPORT_FORMATS: [ExportFormat; 2] = [ExportFormat::Glb, ExportFormat::Gltf];
+const SAVE_PATH_CAPACITY: usize = 1024;
+const ALPHA_MODES: [u32; 3] = [ALPHA_MODE_OPAQUE, ALPHA_MODE_MASK, ALPHA_MODE_BLEND];
+const TEXTURE_SLOTS: [(TextureSlot, &str); 5] = [
+    (TextureSlot::Color, "Color texture"),
+    (TextureSlot::Material, "Material texture"),
+    (TextureSlot::Emissive, "Emissive texture"),
+    (TextureSlot::Normals, "Normal map"),
+    (TextureSlot::Occlusion, "Occlusion texture"),
+];
+const TEXTURE_CHANNELS: [&str; 4] = ["R", "G", "B", "A"];
+const TEXTURE_THUMBNAIL_SIZE: f32 = 32.0;
+const TEXTURE_MAX_ZOOM: f32 = 64.0;
 
 pub struct Gui {
     context: Context,
     winit_platform: WinitPlatform,
     last_frame_instant: Instant,
-    model_metadata: Option<Metadata>,
+    models: Vec<ModelEntry>,
+    load_error: Option<String>,
+    loading_progress: Option<LoadingProgress>,
     animation_playback_state: Option<PlaybackState>,
     camera: Option<Camera>,
+    gizmo: Gizmo,
+    gizmo_target: Option<GizmoTarget>,
+    edited_material: Option<model::Material>,
+    texture_preview_id: Option<TextureId>,
     state: State,
 }
 
+/// A texture of a model and the gui texture displaying it.
+pub struct TextureEntry {
+    pub id: TextureId,
+    pub data: TextureData,
+    pub references: Vec<TextureReference>,
+}
+
 impl Gui {
     pub fn new(window: &WinitWindow) -> Self {
         let (context, winit_platform) = init_imgui(window);
@@ -27,9 +65,15 @@ impl Gui {
             context,
             winit_platform,
             last_frame_instant: Instant::now(),
-            model_metadata: None,
+            models: Vec::new(),
+            load_error: None,
+            loading_progress: None,
             animation_playback_state: None,
             camera: None,
+            gizmo: Default::default(),
+            gizmo_target: None,
+            edited_material: None,
+            texture_preview_id: None,
             state: Default::default(),
         }
     }
@@ -54,17 +98,34 @@ impl Gui {
         {
             let ui = &ui;
 
+            self.state.remove_model = None;
+            self.state.cancel_loading = false;
+            self.state.save_model = false;
+            self.state.new_node_transform = None;
+            self.state.reset_node_transform = false;
+            self.state.new_material = None;
+            self.models.iter_mut().for_each(ModelEntry::clear_changes);
+
             build_main_menu_bar(ui, &mut self.state);
 
             if self.state.show_model_descriptor {
-                build_model_descriptor_window(ui, &mut self.state, self.model_metadata.as_ref());
+                build_model_descriptor_window(
+                    ui,
+                    &mut self.state,
+                    &mut self.models,
+                    &mut self.gizmo,
+                    self.gizmo_target.as_ref(),
+                    self.edited_material.as_ref(),
+                );
             }
+            self.state.reveal_hierarchy_node = false;
 
             if self.state.show_animation_player {
+                let selected_model = self.state.selected_model;
                 build_animation_player_window(
                     ui,
                     &mut self.state,
-                    self.model_metadata.as_ref(),
+                    self.models.get_mut(selected_model),
                     self.animation_playback_state,
                 );
             }
@@ -74,11 +135,57 @@ impl Gui {
             }
 
             if self.state.show_renderer_settings {
-                build_renderer_settings_window(ui, &mut self.state);
+                let selected_model = self.state.selected_model;
+                build_renderer_settings_window(
+                    ui,
+                    &mut self.state,
+                    self.models.get_mut(selected_model),
+                );
+            }
+
+            if self.state.show_texture_browser {
+                let selected_model = self.state.selected_model;
+                build_texture_browser_window(
+                    ui,
+                    &mut self.state,
+                    self.models.get_mut(selected_model),
+                    self.texture_preview_id,
+                );
+            }
+
+            if self.state.show_validation {
+                let selected_model = self.state.selected_model;
+                build_validation_window(ui, &mut self.state, self.models.get_mut(selected_model));
             }
 
-            self.state.hovered = ui.is_any_item_hovered()
+            if self.state.show_save_as {
+                let selected_model = self.models.get(self.state.selected_model);
+                build_save_as_window(ui, &mut self.state, selected_model);
+            }
+
+            if let Some(progress) = self.loading_progress.as_ref() {
+                build_loading_progress_window(ui, &mut self.state, progress);
+            }
+
+            if let Some(error) = self.load_error.as_ref() {
+                if !build_load_error_window(ui, error) {
+                    self.load_error = None;
+                }
+            }
+
+            let over_gui = ui.is_any_item_hovered()
                 || ui.is_window_hovered_with_flags(WindowHoveredFlags::ANY_WINDOW);
+
+            let mut gizmo_hovered = false;
+            if let (Some(camera), Some(target)) = (self.camera, self.gizmo_target.as_ref()) {
+                let (transform, hovered) = self.gizmo.update(ui, camera, target, over_gui);
+                if transform.is_some() {
+                    self.state.new_node_transform = transform;
+                }
+                gizmo_hovered = hovered;
+            }
+
+            self.state.hovered = over_gui || gizmo_hovered;
         }
 
         self.winit_platform.prepare_render(&ui, window);
@@ -89,12 +196,107 @@ impl Gui {
         &mut self.context
     }
 
+    /// Replace all the models of the scene by the model described by `metadata`.
     pub fn set_model_metadata(&mut self, metadata: Metadata) {
-        self.model_metadata.replace(metadata);
+        self.models = vec![ModelEntry::new(metadata)];
+        self.load_error = None;
         self.animation_playback_state = None;
         self.state = self.state.reset();
     }
 
+    /// Add the model described by `metadata` to the scene and select it.
+    pub fn add_model_metadata(&mut self, metadata: Metadata) {
+        self.models.push(ModelEntry::new(metadata));
+        self.load_error = None;
+        self.animation_playback_state = None;
+        self.state.selected_model = self.models.len() - 1;
+        self.state.selected_hierarchy_node = None;
+    }
+
+    /// Replace the metadata of the model at `index` after it was reloaded.
+    ///
+    /// The visibility, transform and animation settings of the model are kept.
+    /// Hidden nodes are shown again and all lights cast shadows again since
+    /// the hierarchy may have changed.
+    pub fn replace_model_metadata(&mut self, index: usize, metadata: Metadata) {
+        if let Some(model) = self.models.get_mut(index) {
+            model.lights = collect_lights(&metadata);
+            model.shadow_casting = vec![true; metadata.node_count()];
+            model.metadata = metadata;
+            model.hidden_nodes.clear();
+            self.load_error = None;
+            if self.state.selected_model == index {
+                self.state.selected_hierarchy_node = None;
+            }
+        }
+    }
+
+    /// Set the textures of the model at `index`, indexed like the textures of the model.
+    pub fn set_model_textures(&mut self, index: usize, textures: Vec<TextureEntry>) {
+        if let Some(model) = self.models.get_mut(index) {
+            model.selected_texture = model
+                .selected_texture
+                .filter(|selected| *selected < textures.len());
+            model.textures = textures;
+        }
+    }
+
+    /// Set the culling statistics of the last frame of the model at `index`.
+    pub fn set_culling_statistics(&mut self, index: usize, statistics: CullingStatistics) {
+        if let Some(model) = self.models.get_mut(index) {
+            model.culling = statistics;
+        }
+    }
+
+    /// Set the gui texture displaying the preview of the selected texture.
+    pub fn set_texture_preview_id(&mut self, id: TextureId) {
+        self.texture_preview_id = Some(id);
+    }
+
+    /// Get the texture to preview or `None` if the texture browser is closed.
+    pub fn get_texture_preview(&self) -> Option<TexturePreview> {
+        if !self.state.show_texture_browser {
+            return None;
+        }
+
+        let model_index = self.state.selected_model;
+        let texture_index = self.models.get(model_index)?.selected_texture?;
+        let uv_scale = 1.0 / self.state.texture_zoom;
+        let center = self.state.texture_center;
+        Some(TexturePreview {
+            model_index,
+            texture_index,
+            channels: self.state.texture_channels,
+            mip_level: self.state.texture_mip_level,
+            linear: self.state.texture_linear,
+            uv_offset: [center[0] - uv_scale * 0.5, center[1] - uv_scale * 0.5],
+            uv_scale: [uv_scale, uv_scale],
+        })
+    }
+
+    pub fn remove_model(&mut self, index: usize) {
+        self.models.remove(index);
+        if self.state.selected_model == index {
+            self.state.selected_hierarchy_node = None;
+            self.animation_playback_state = None;
+        }
+        if self.state.selected_model >= index {
+            self.state.selected_model = self.state.selected_model.saturating_sub(1);
+        }
+    }
+
+    pub fn set_load_error(&mut self, error: String) {
+        self.load_error.replace(error);
+    }
+
+    pub fn set_loading_progress(&mut self, progress: Option<LoadingProgress>) {
+        self.loading_progress = progress;
+    }
+
+    pub fn should_cancel_loading(&self) -> bool {
+        self.state.cancel_loading
+    }
+
     pub fn set_animation_playback_state(
         &mut self,
         animation_playback_state: Option<PlaybackState>,
@@ -106,12 +308,160 @@ impl Gui {
         self.camera = camera;
     }
 
-    pub fn get_selected_animation(&self) -> usize {
-        self.state.selected_animation
+    pub fn set_placement(&mut self, placement: Placement) {
+        self.state.original_placement = placement == Placement::Original;
+    }
+
+    /// Index of the model targeted by the animation player.
+    pub fn get_selected_model(&self) -> usize {
+        self.state.selected_model
+    }
+
+    pub fn get_model_to_remove(&self) -> Option<usize> {
+        self.state.remove_model
+    }
+
+    pub fn should_add_dropped_models(&self) -> bool {
+        self.state.add_dropped_models
+    }
+
+    pub fn is_model_visible(&self, index: usize) -> bool {
+        self.models.get(index).map_or(false, |m| m.visible)
+    }
+
+    /// Select the node of the model at `model_index` hit by a click in the scene.
+    ///
+    /// The hierarchy is expanded down to the node and the details of the hit
+    /// are shown with the node details. `None` clears the selection.
+    pub fn set_picked_node(&mut self, picked: Option<(usize, Hit)>) {
+        let (model_index, hit) = match picked {
+            Some(picked) => picked,
+            None => {
+                self.state.selected_hierarchy_node = None;
+                return;
+            }
+        };
+
+        let node = self.models.get(model_index).and_then(|model| {
+            find_issue_node(model.metadata.nodes(), Subject::Node(hit.node_index))
+        });
+        if let Some(node) = node {
+            let mut node_details = NodeDetails::from(node);
+            node_details.hit = Some(HitDetails::new(node.kind(), hit));
+            node_details.selected_primitive = Some(hit.primitive_index);
+            self.state.selected_model = model_index;
+            self.state.selected_hierarchy_node = Some(node_details);
+            self.state.show_model_descriptor = true;
+            self.state.reveal_hierarchy_node = true;
+        }
+    }
+
+    /// Get the model and node indices of the selected node if it is a node of the scene.
+    pub fn get_selected_node(&self) -> Option<(usize, usize)> {
+        self.state
+            .selected_hierarchy_node
+            .as_ref()
+            .filter(|node| matches!(node.kind, NodeKind::Node(_)))
+            .map(|node| (self.state.selected_model, node.index))
+    }
+
+    /// Set the transforms of the selected node edited by the gizmo.
+    pub fn set_gizmo_target(&mut self, target: Option<GizmoTarget>) {
+        self.gizmo_target = target;
+    }
+
+    pub fn get_new_node_transform(&self) -> Option<NodeTransform> {
+        self.state.new_node_transform
+    }
+
+    pub fn should_reset_node_transform(&self) -> bool {
+        self.state.reset_node_transform
+    }
+
+    /// Get the model, mesh and primitive indices of the primitive whose material is edited.
+    ///
+    /// The primitive index is its position in the mesh.
+    pub fn get_selected_primitive(&self) -> Option<(usize, usize, usize)> {
+        let node = self.state.selected_hierarchy_node.as_ref()?;
+        match (&node.kind, node.selected_primitive) {
+            (
+                NodeKind::Node(NodeData {
+                    mesh: Some(mesh), ..
+                }),
+                Some(primitive_index),
+            ) => Some((self.state.selected_model, mesh.index, primitive_index)),
+            _ => None,
+        }
+    }
+
+    /// Set the current material of the selected primitive.
+    pub fn set_edited_material(&mut self, material: Option<model::Material>) {
+        self.edited_material = material;
+    }
+
+    pub fn get_new_material(&self) -> Option<model::Material> {
+        self.state.new_material
+    }
+
+    pub fn get_new_model_visibility(&self, index: usize) -> Option<bool> {
+        self.models
+            .get(index)
+            .filter(|m| m.visible_changed)
+            .map(|m| m.visible)
+    }
+
+    /// Get the visibility of the nodes of the model at `index` if it changed.
+    ///
+    /// The visibility is indexed by node index.
+    pub fn get_new_node_visibility(&self, index: usize) -> Option<Vec<bool>> {
+        self.models
+            .get(index)
+            .filter(|m| m.node_visibility_changed)
+            .map(ModelEntry::node_visibility)
+    }
+
+    /// Get the sort distance overrides of the blended primitives of the model at `index`.
+    pub fn get_new_sort_distance_overrides(&self, index: usize) -> Option<SortDistanceOverrides> {
+        self.models
+            .get(index)
+            .filter(|m| m.sort_distances_changed)
+            .map(|m| m.sort_distances.clone())
+    }
+
+    /// Get whether the lights of the model at `index` cast shadows if it changed.
+    ///
+    /// The values are indexed by node index.
+    pub fn get_new_shadow_casting(&self, index: usize) -> Option<Vec<bool>> {
+        self.models
+            .get(index)
+            .filter(|m| m.shadow_casting_changed)
+            .map(|m| m.shadow_casting.clone())
+    }
+
+    /// Get the visibility of the nodes of the model at `index`.
+    ///
+    /// The visibility is indexed by node index.
+    pub fn get_node_visibility(&self, index: usize) -> Vec<bool> {
+        self.models
+            .get(index)
+            .map_or_else(Vec::new, ModelEntry::node_visibility)
     }
 
-    pub fn is_infinite_animation_checked(&self) -> bool {
-        self.state.infinite_animation
+    pub fn get_new_model_transform(&self, index: usize) -> Option<Matrix4<f32>> {
+        self.models
+            .get(index)
+            .filter(|m| m.transform_changed)
+            .map(ModelEntry::transform)
+    }
+
+    pub fn get_selected_animation(&self, index: usize) -> usize {
+        self.models.get(index).map_or(0, |m| m.selected_animation)
+    }
+
+    pub fn is_infinite_animation_checked(&self, index: usize) -> bool {
+        self.models
+            .get(index)
+            .map_or(true, |m| m.infinite_animation)
     }
 
     pub fn should_toggle_animation(&self) -> bool {
@@ -126,14 +476,46 @@ impl Gui {
         self.state.reset_animation
     }
 
-    pub fn get_animation_speed(&self) -> f32 {
-        self.state.animation_speed
+    pub fn get_animation_speed(&self, index: usize) -> f32 {
+        self.models.get(index).map_or(1.0, |m| m.animation_speed)
     }
 
     pub fn should_reset_camera(&self) -> bool {
         self.state.reset_camera
     }
 
+    pub fn placement(&self) -> Placement {
+        if self.state.original_placement {
+            Placement::Original
+        } else {
+            Placement::Normalized
+        }
+    }
+
+    pub fn get_new_placement(&self) -> Option<Placement> {
+        if self.state.original_placement_changed {
+            Some(self.placement())
+        } else {
+            None
+        }
+    }
+
+    pub fn should_export_statistics(&self) -> bool {
+        self.state.export_statistics
+    }
+
+    /// Get the path and format the selected model should be saved to.
+    pub fn get_model_save_path(&self) -> Option<(PathBuf, ExportFormat)> {
+        if self.state.save_model {
+            Some((
+                PathBuf::from(self.state.save_path.to_str()),
+                EXPORT_FORMATS[self.state.save_format_index],
+            ))
+        } else {
+            None
+        }
+    }
+
     pub fn get_new_emissive_intensity(&self) -> Option<f32> {
         if self.state.emissive_intensity_changed {
             Some(self.state.emissive_intensity)
@@ -174,6 +556,22 @@ impl Gui {
         }
     }
 
+    pub fn get_new_shadow_resolution(&self) -> Option<u32> {
+        if self.state.shadow_resolution_changed {
+            Some(SHADOW_RESOLUTIONS[self.state.shadow_resolution_index])
+        } else {
+            None
+        }
+    }
+
+    pub fn get_new_shadow_bias(&self) -> Option<f32> {
+        if self.state.shadow_bias_changed {
+            Some(self.state.shadow_bias)
+        } else {
+            None
+        }
+    }
+
     pub fn get_new_renderer_tone_map_mode(&self) -> Option<ToneMapMode> {
         if self.state.tone_map_mode_changed {
             ToneMapMode::from_value(self.state.selected_tone_map_mode)
@@ -190,6 +588,30 @@ impl Gui {
         }
     }
 
+    pub fn get_new_transparency_mode(&self) -> Option<TransparencyMode> {
+        if self.state.transparency_mode_changed {
+            TransparencyMode::from_value(self.state.selected_transparency_mode)
+        } else {
+            None
+        }
+    }
+
+    pub fn get_new_wireframe_color(&self) -> Option<[f32; 4]> {
+        if self.state.wireframe_color_changed {
+            Some(self.state.wireframe_color)
+        } else {
+            None
+        }
+    }
+
+    pub fn get_new_debug_overlays(&self) -> Option<DebugOverlays> {
+        if self.state.debug_overlays_changed {
+            Some(self.state.debug_overlays)
+        } else {
+            None
+        }
+    }
+
     pub fn is_hovered(&self) -> bool {
         self.state.hovered
     }
@@ -228,6 +650,9 @@ fn init_imgui(window: &WinitWindow) -> (Context, WinitPlatform) {
 
 fn build_main_menu_bar(ui: &Ui, state: &mut State) {
     ui.main_menu_bar(|| {
+        ui.menu(im_str!("File"), true, || {
+            MenuItem::new(im_str!("Save as...")).build_with_ref(ui, &mut state.show_save_as);
+        });
         ui.menu(im_str!("View"), true, || {
             MenuItem::new(im_str!("Model descriptor"))
                 .build_with_ref(ui, &mut state.show_model_descriptor);
@@ -237,11 +662,24 @@ fn build_main_menu_bar(ui: &Ui, state: &mut State) {
                 .build_with_ref(ui, &mut state.show_camera_details);
             MenuItem::new(im_str!("Renderer settings"))
                 .build_with_ref(ui, &mut state.show_renderer_settings);
+            MenuItem::new(im_str!("Textures")).build_with_ref(ui, &mut state.show_texture_browser);
+            MenuItem::new(im_str!("Validation")).build_with_ref(ui, &mut state.show_validation);
+        });
+        ui.menu(im_str!("Scene"), true, || {
+            MenuItem::new(im_str!("Add dropped models to scene"))
+                .build_with_ref(ui, &mut state.add_dropped_models);
         });
     });
 }
 
-fn build_model_descriptor_window(ui: &Ui, state: &mut State, model_metadata: Option<&Metadata>) {
+fn build_model_descriptor_window(
+    ui: &Ui,
+    state: &mut State,
+    models: &mut [ModelEntry],
+    gizmo: &mut Gizmo,
+    gizmo_target: Option<&GizmoTarget>,
+    edited_material: Option<&model::Material>,
+) {
     let mut opened = true;
     Window::new(im_str!("Model metadata"))
         .position([20.0, 20.0], Condition::Appearing)
@@ -254,19 +692,48 @@ fn build_model_descriptor_window(ui: &Ui, state: &mut State, model_metadata: Opt
             ChildWindow::new(0)
                 .size([parent_size[0] / 3.0, 0.0])
                 .build(ui, || {
-                    if let Some(metadata) = model_metadata {
+                    if let Some(model) = models.get(state.selected_model) {
+                        let metadata = &model.metadata;
                         build_summary_block_ui(ui, metadata);
-                        build_hierarchy_block_ui(ui, metadata, state);
-                        build_animation_block_ui(ui, metadata);
+                        build_statistics_block_ui(ui, metadata, model.culling, state);
+                    }
+                    build_hierarchy_block_ui(ui, models, state);
+                    if let Some(model) = models.get(state.selected_model) {
+                        build_animation_block_ui(ui, &model.metadata);
                     }
                 });
 
             ui.same_line(0.0);
 
-            if let Some(selected_hierarchy_node) = state.selected_hierarchy_node.as_ref() {
+            if let Some(selected_hierarchy_node) = state.selected_hierarchy_node.as_mut() {
+                let new_node_transform = &mut state.new_node_transform;
+                let reset_node_transform = &mut state.reset_node_transform;
+                let new_material = &mut state.new_material;
+                let selected_model = models.get_mut(state.selected_model);
                 ChildWindow::new(im_str!("Node details"))
                     .border(true)
-                    .build(ui, || build_node_details_ui(ui, selected_hierarchy_node));
+                    .build(ui, || {
+                        build_node_details_ui(ui, selected_hierarchy_node);
+                        if let Some(material) = edited_material {
+                            build_material_editor_ui(ui, material, new_material);
+                            let primitive_index = selected_hierarchy_node.selected_primitive;
+                            if let (true, Some(model), Some(primitive_index)) =
+                                (material.is_transparent(), selected_model, primitive_index)
+                            {
+                                let key = (selected_hierarchy_node.index, primitive_index);
+                                build_sort_distance_ui(ui, model, key);
+                            }
+                        }
+                        if let Some(target) = gizmo_target {
+                            build_node_transform_ui(
+                                ui,
+                                gizmo,
+                                target,
+                                new_node_transform,
+                                reset_node_transform,
+                            );
+                        }
+                    });
             }
         });
     state.show_model_descriptor = opened;
@@ -278,8 +745,10 @@ fn build_summary_block_ui(ui: &Ui, metadata: &Metadata) {
         .build(ui)
     {
         ui.text(im_str!("Name: {}", metadata.name()));
-        if ui.is_item_hovered() {
-            ui.tooltip_text(im_str!("{}", metadata.path()));
+        if let Some(path) = metadata.path() {
+            if ui.is_item_hovered() {
+                ui.tooltip_text(im_str!("{}", path));
+            }
         }
         ui.text(im_str!("Scene count: {}", metadata.scene_count()));
         ui.text(im_str!("Node count: {}", metadata.node_count()));
@@ -289,24 +758,225 @@ fn build_summary_block_ui(ui: &Ui, metadata: &Metadata) {
         ui.text(im_str!("Material count: {}", metadata.material_count()));
         ui.text(im_str!("Texture count: {}", metadata.texture_count()));
         ui.text(im_str!("Light count: {}", metadata.light_count()));
+        let [x, y, z] = metadata.dimensions();
+        ui.text(im_str!("Dimensions: {:.3} x {:.3} x {:.3} m", x, y, z));
+    }
+}
+
+fn build_statistics_block_ui(
+    ui: &Ui,
+    metadata: &Metadata,
+    culling: CullingStatistics,
+    state: &mut State,
+) {
+    if CollapsingHeader::new(&im_str!("Statistics")).build(ui) {
+        let statistics = metadata.statistics();
+        ui.text(im_str!("Vertex count: {}", statistics.vertex_count));
+        ui.text(im_str!("Triangle count: {}", statistics.triangle_count));
+        ui.text(im_str!(
+            "Draw calls per pass: {}",
+            statistics.draw_call_count
+        ));
+        ui.text(im_str!(
+            "Drawn nodes: {} ({} culled)",
+            culling.drawn_nodes,
+            culling.culled_nodes
+        ));
+        ui.text(im_str!(
+            "Drawn primitives: {} ({} culled)",
+            culling.drawn_primitives,
+            culling.culled_primitives
+        ));
+        ui.text(im_str!(
+            "Vertex buffer: {}",
+            format_size(statistics.vertex_buffer_size)
+        ));
+        ui.text(im_str!(
+            "Index buffer: {}",
+            format_size(statistics.index_buffer_size)
+        ));
+        ui.text(im_str!(
+            "Texture memory: {}",
+            format_size(statistics.texture_memory())
+        ));
+        ui.text(im_str!(
+            "GPU memory: {}",
+            format_size(statistics.gpu_memory())
+        ));
+
+        if !statistics.images.is_empty() {
+            TreeNode::new(im_str!("Images")).build(ui, || {
+                for image in &statistics.images {
+                    ui.text(im_str!(
+                        "{}: {}x{} - {} mips - {}",
+                        image.index,
+                        image.width,
+                        image.height,
+                        image.mip_levels,
+                        format_size(image.gpu_size)
+                    ));
+                }
+            });
+        }
+
+        if !statistics.skins.is_empty() {
+            TreeNode::new(im_str!("Skins")).build(ui, || {
+                for skin in &statistics.skins {
+                    ui.text(im_str!(
+                        "{}: {} - {} joints",
+                        skin.index,
+                        skin.name.as_deref().unwrap_or("no name"),
+                        skin.joint_count
+                    ));
+                }
+            });
+        }
+
+        state.export_statistics = ui.button(im_str!("Export"), [0.0, 0.0]);
+        if ui.is_item_hovered() {
+            ui.tooltip_text(im_str!("Export the statistics to a YAML file"));
+        }
+    }
+}
+
+fn format_size(bytes: u64) -> String {
+    const KIB: f64 = 1024.0;
+    const MIB: f64 = KIB * 1024.0;
+    let bytes = bytes as f64;
+    if bytes >= MIB {
+        format!("{:.2} MiB", bytes / MIB)
+    } else if bytes >= KIB {
+        format!("{:.2} KiB", bytes / KIB)
+    } else {
+        format!("{} B", bytes)
     }
 }
 
-fn build_hierarchy_block_ui(ui: &Ui, metadata: &Metadata, state: &mut State) {
+fn build_hierarchy_block_ui(ui: &Ui, models: &mut [ModelEntry], state: &mut State) {
     if CollapsingHeader::new(&im_str!("Hierarchy")).build(ui) {
-        for node in metadata.nodes() {
-            build_tree_node_ui(ui, node, state);
+        for (index, model) in models.iter_mut().enumerate() {
+            build_model_entry_ui(ui, index, model, state);
         }
     }
 }
 
-fn build_tree_node_ui(ui: &Ui, node: &Node, state: &mut State) {
-    let selected = state
-        .selected_hierarchy_node
-        .as_ref()
-        .map_or(false, |n| n.uid == node.uid());
+fn build_model_entry_ui(ui: &Ui, index: usize, model: &mut ModelEntry, state: &mut State) {
+    let select = |state: &mut State| {
+        if state.selected_model != index {
+            state.selected_model = index;
+            state.selected_hierarchy_node = None;
+        }
+    };
+
+    let selected = state.selected_model == index;
+    let mut opened = false;
+    TreeNode::new(&im_str!("{}: {}##model", index, model.metadata.name()))
+        .open_on_double_click(true)
+        .open_on_arrow(true)
+        .selected(selected)
+        .opened(
+            true,
+            reveal_condition(state.reveal_hierarchy_node && selected),
+        )
+        .build(ui, || {
+            opened = true;
+            if ui.is_item_clicked(MouseButton::Left) {
+                select(state);
+            }
+
+            model.visible_changed = ui.checkbox(&im_str!("Visible##{}", index), &mut model.visible);
+            let translation_changed = ui
+                .input_float3(&im_str!("Position##{}", index), &mut model.translation)
+                .build();
+            let rotation_changed = Slider::new(&im_str!("Rotation##{}", index), -180.0f32..=180.0)
+                .build(ui, &mut model.rotation);
+            let scale_changed = Slider::new(&im_str!("Scale##{}", index), 0.01f32..=10.0)
+                .build(ui, &mut model.scale);
+            model.transform_changed = translation_changed || rotation_changed || scale_changed;
+            if ui.button(&im_str!("Remove##{}", index), [0.0, 0.0]) {
+                state.remove_model = Some(index);
+            }
+
+            ui.same_line(0.0);
+            if ui.button(&im_str!("Show all##{}", index), [0.0, 0.0]) {
+                model.hidden_nodes.clear();
+                model.node_visibility_changed = true;
+            }
+
+            let isolated_node = state
+                .selected_hierarchy_node
+                .as_ref()
+                .filter(|_| state.selected_model == index)
+                .map(|n| n.uid);
+            if let Some(uid) = isolated_node {
+                ui.same_line(0.0);
+                if ui.button(&im_str!("Isolate selected##{}", index), [0.0, 0.0]) {
+                    model.hidden_nodes.clear();
+                    isolate_node(model.metadata.nodes(), uid, &mut model.hidden_nodes);
+                    model.node_visibility_changed = true;
+                }
+            }
+
+            let metadata = &model.metadata;
+            let hidden_nodes = &mut model.hidden_nodes;
+            let mut visibility_changed = false;
+            for node in metadata.nodes() {
+                build_tree_node_ui(
+                    ui,
+                    index,
+                    node,
+                    hidden_nodes,
+                    &mut visibility_changed,
+                    state,
+                );
+            }
+            model.node_visibility_changed |= visibility_changed;
+        });
+
+    if !opened && ui.is_item_clicked(MouseButton::Left) {
+        select(state);
+    }
+}
+
+fn build_tree_node_ui(
+    ui: &Ui,
+    model_index: usize,
+    node: &Node,
+    hidden_nodes: &mut HiddenNodes,
+    visibility_changed: &mut bool,
+    state: &mut State,
+) {
+    let selected = state.selected_model == model_index
+        && state
+            .selected_hierarchy_node
+            .as_ref()
+            .map_or(false, |n| n.uid == node.uid());
     let name = node.name().unwrap_or("no name");
 
+    // Expand the ancestors of the node selected by clicking on the scene
+    let revealed = state.reveal_hierarchy_node
+        && state.selected_model == model_index
+        && state
+            .selected_hierarchy_node
+            .as_ref()
+            .map_or(false, |n| contains_node(node.children(), n.uid));
+
+    // Hiding a node also hides its subtree
+    let mut visible = !hidden_nodes.is_hidden(node.uid());
+    if ui.checkbox(
+        &im_str!("##visible_{}_{}", model_index, node.uid()),
+        &mut visible,
+    ) {
+        if visible {
+            hidden_nodes.subtrees.remove(&node.uid());
+            hidden_nodes.nodes.remove(&node.uid());
+        } else {
+            hidden_nodes.subtrees.insert(node.uid());
+        }
+        *visibility_changed = true;
+    }
+    ui.same_line(0.0);
+
     // This flag is there tu make sure we attach the "is_click" to the correct node/leaf
     let mut opened = false;
     TreeNode::new(&im_str!("{}: {}", node.index(), name))
@@ -314,24 +984,60 @@ fn build_tree_node_ui(ui: &Ui, node: &Node, state: &mut State) {
         .open_on_double_click(true)
         .open_on_arrow(true)
         .selected(selected)
+        .opened(true, reveal_condition(revealed))
         .build(ui, || {
             // If the node is opened the flag will be set to true
             opened = true;
             if ui.is_item_clicked(MouseButton::Left) {
+                state.selected_model = model_index;
                 state.selected_hierarchy_node = Some(NodeDetails::from(node));
             }
-            node.children()
-                .iter()
-                .for_each(|n| build_tree_node_ui(ui, n, state))
+            node.children().iter().for_each(|n| {
+                build_tree_node_ui(ui, model_index, n, hidden_nodes, visibility_changed, state)
+            })
         });
 
     // If the was not opened then we still want to attach the "is_click"
     if !opened && ui.is_item_clicked(MouseButton::Left) {
+        state.selected_model = model_index;
         state.selected_hierarchy_node = Some(NodeDetails::from(node));
     }
 }
 
-fn build_node_details_ui(ui: &Ui, node_details: &NodeDetails) {
+/// Get the condition to force a tree node open, or leave it as it is if not `reveal`.
+fn reveal_condition(reveal: bool) -> Condition {
+    if reveal {
+        Condition::Always
+    } else {
+        Condition::Never
+    }
+}
+
+/// Hide all the nodes that are not the node with `uid` or one of its descendants.
+///
+/// The ancestors of the node are hidden without their subtree.
+fn isolate_node(nodes: &[Node], uid: usize, hidden_nodes: &mut HiddenNodes) {
+    for node in nodes {
+        if node.uid() == uid {
+            continue;
+        }
+        if contains_node(node.children(), uid) {
+            hidden_nodes.nodes.insert(node.uid());
+            isolate_node(node.children(), uid, hidden_nodes);
+        } else {
+            hidden_nodes.subtrees.insert(node.uid());
+        }
+    }
+}
+
+/// Check if the node with `uid` is one of `nodes` or one of their descendants.
+fn contains_node(nodes: &[Node], uid: usize) -> bool {
+    nodes
+        .iter()
+        .any(|node| node.uid() == uid || contains_node(node.children(), uid))
+}
+
+fn build_node_details_ui(ui: &Ui, node_details: &mut NodeDetails) {
     let title = match node_details.kind {
         NodeKind::Scene => "Scene",
         NodeKind::Node(NodeData { root: true, .. }) => "Root node",
@@ -352,7 +1058,7 @@ fn build_node_details_ui(ui: &Ui, node_details: &NodeDetails) {
         ..
     }) = node_details.kind
     {
-        build_mesh_details_ui(ui, mesh);
+        build_mesh_details_ui(ui, mesh, &mut node_details.selected_primitive);
     }
 
     if let NodeKind::Node(NodeData {
@@ -361,9 +1067,82 @@ fn build_node_details_ui(ui: &Ui, node_details: &NodeDetails) {
     {
         build_light_details_ui(ui, light);
     }
+
+    if let Some(hit) = node_details.hit.as_ref() {
+        build_hit_details_ui(ui, hit);
+    }
 }
 
-fn build_mesh_details_ui(ui: &Ui, mesh_data: &Mesh) {
+fn build_node_transform_ui(
+    ui: &Ui,
+    gizmo: &mut Gizmo,
+    target: &GizmoTarget,
+    new_transform: &mut Option<NodeTransform>,
+    reset: &mut bool,
+) {
+    ui.text("Transform");
+    ui.separator();
+
+    let mut mode = gizmo.mode();
+    ui.radio_button(im_str!("Translate"), &mut mode, GizmoMode::Translate);
+    ui.same_line(0.0);
+    ui.radio_button(im_str!("Rotate"), &mut mode, GizmoMode::Rotate);
+    ui.same_line(0.0);
+    ui.radio_button(im_str!("Scale##gizmo_mode"), &mut mode, GizmoMode::Scale);
+    gizmo.set_mode(mode);
+
+    let mut transform = target.local;
+
+    let mut translation: [f32; 3] = transform.translation.into();
+    if ui
+        .input_float3(im_str!("Translation"), &mut translation)
+        .build()
+    {
+        transform.translation = translation.into();
+        *new_transform = Some(transform);
+    }
+
+    let euler = Euler::from(transform.rotation);
+    let mut rotation = [
+        Deg::from(euler.x).0,
+        Deg::from(euler.y).0,
+        Deg::from(euler.z).0,
+    ];
+    if ui
+        .input_float3(im_str!("Rotation (deg)"), &mut rotation)
+        .build()
+    {
+        let [x, y, z] = rotation;
+        transform.rotation = Quaternion::from(Euler::new(Deg(x), Deg(y), Deg(z)));
+        *new_transform = Some(transform);
+    }
+
+    let mut scale: [f32; 3] = transform.scale.into();
+    if ui.input_float3(im_str!("Scale"), &mut scale).build() {
+        transform.scale = scale.into();
+        *new_transform = Some(transform);
+    }
+
+    *reset = ui.button(im_str!("Reset to file values"), [0.0, 0.0]);
+}
+
+fn build_hit_details_ui(ui: &Ui, hit: &HitDetails) {
+    ui.text("Picked");
+    ui.separator();
+    ui.text(im_str!("Primitive: {}", hit.primitive_index));
+    match hit.material_index {
+        Some(index) => ui.text(im_str!(
+            "Material: {} ({})",
+            index,
+            hit.material_name.as_ref().map_or("no name", |s| &s)
+        )),
+        None => ui.text("Material: default"),
+    }
+    let [x, y, z] = hit.position;
+    ui.text(im_str!("Position: {:.3}, {:.3}, {:.3}", x, y, z));
+}
+
+fn build_mesh_details_ui(ui: &Ui, mesh_data: &Mesh, selected_primitive: &mut Option<usize>) {
     ui.text("Mesh");
     ui.separator();
     ui.text(im_str!("Index: {}", mesh_data.index));
@@ -371,20 +1150,43 @@ fn build_mesh_details_ui(ui: &Ui, mesh_data: &Mesh) {
         "Name: {}",
         mesh_data.name.as_ref().map_or("no name", |s| &s)
     ));
+    let primitives = mesh_data.primitives.iter().map(|p| p.statistics);
+    ui.text(im_str!(
+        "Vertex count: {}",
+        primitives.clone().map(|p| p.vertex_count).sum::<usize>()
+    ));
+    ui.text(im_str!(
+        "Triangle count: {}",
+        primitives.map(|p| p.triangle_count).sum::<usize>()
+    ));
     if CollapsingHeader::new(&im_str!("Primitives")).build(ui) {
         mesh_data
             .primitives
             .iter()
-            .for_each(|p| build_primitive_ui(ui, p));
+            .enumerate()
+            .for_each(|(index, p)| build_primitive_ui(ui, index, p, selected_primitive));
     }
 }
 
-fn build_primitive_ui(ui: &Ui, prim: &Primitive) {
+fn build_primitive_ui(
+    ui: &Ui,
+    index: usize,
+    prim: &Primitive,
+    selected_primitive: &mut Option<usize>,
+) {
     TreeNode::new(&im_str!("{}", prim.index))
         .open_on_double_click(true)
         .open_on_arrow(true)
         .build(ui, || {
             ui.text(im_str!("Mode: {}", prim.mode));
+            ui.text(im_str!("Vertex count: {}", prim.statistics.vertex_count));
+            if let Some(index_count) = prim.statistics.index_count {
+                ui.text(im_str!("Index count: {}", index_count));
+            }
+            ui.text(im_str!(
+                "Triangle count: {}",
+                prim.statistics.triangle_count
+            ));
             ui.text("Material:");
             let material = &prim.material;
             ui.indent();
@@ -423,9 +1225,158 @@ fn build_primitive_ui(ui: &Ui, prim: &Primitive) {
                 ui.text(im_str!("Metalness: {}", material.metallic_factor));
                 ui.text(im_str!("Roughness: {}", material.roughness_factor));
             }
+            ui.unindent();
+
+            if ui.button(&im_str!("Edit material##{}", index), [0.0, 0.0]) {
+                *selected_primitive = Some(index);
+            }
         })
 }
 
+fn build_material_editor_ui(
+    ui: &Ui,
+    material: &model::Material,
+    new_material: &mut Option<model::Material>,
+) {
+    ui.text("Material editor");
+    ui.separator();
+    match material.get_index() {
+        Some(index) => ui.text(im_str!("Index: {}", index)),
+        None => ui.text("Default material"),
+    }
+
+    let mut material = *material;
+    let mut changed = false;
+
+    let mut color = material.get_color();
+    if ColorEdit::new(im_str!("Base color"), &mut color).build(ui) {
+        material.set_color(color);
+        changed = true;
+    }
+
+    let mut workflow = material.get_workflow();
+    let workflow_changed = match &mut workflow {
+        Workflow::MetallicRoughness(workflow) => {
+            let mut metallic = workflow.get_metallic();
+            let mut roughness = workflow.get_roughness();
+            let metallic_changed =
+                Slider::new(im_str!("Metallic"), 0.0f32..=1.0).build(ui, &mut metallic);
+            let roughness_changed =
+                Slider::new(im_str!("Roughness"), 0.0f32..=1.0).build(ui, &mut roughness);
+            workflow.set_metallic(metallic);
+            workflow.set_roughness(roughness);
+            metallic_changed || roughness_changed
+        }
+        Workflow::SpecularGlossiness(workflow) => {
+            let mut specular = workflow.get_specular();
+            let mut glossiness = workflow.get_glossiness();
+            let specular_changed = ColorEdit::new(im_str!("Specular"), &mut specular).build(ui);
+            let glossiness_changed =
+                Slider::new(im_str!("Glossiness"), 0.0f32..=1.0).build(ui, &mut glossiness);
+            workflow.set_specular(specular);
+            workflow.set_glossiness(glossiness);
+            specular_changed || glossiness_changed
+        }
+    };
+    if workflow_changed {
+        material.set_workflow(workflow);
+        changed = true;
+    }
+
+    let mut emissive = material.get_emissive();
+    if ColorEdit::new(im_str!("Emissive"), &mut emissive).build(ui) {
+        material.set_emissive(emissive);
+        changed = true;
+    }
+
+    fn alpha_mode_display_fn(alpha_mode: &u32) -> Cow<ImStr> {
+        let label = match *alpha_mode {
+            ALPHA_MODE_MASK => "Mask",
+            ALPHA_MODE_BLEND => "Blend",
+            _ => "Opaque",
+        };
+        Cow::Owned(im_str!("{}", label))
+    }
+
+    let mut alpha_mode_index = ALPHA_MODES
+        .iter()
+        .position(|mode| *mode == material.get_alpha_mode())
+        .unwrap_or(0);
+    if ComboBox::new(im_str!("Alpha mode")).build_simple(
+        ui,
+        &mut alpha_mode_index,
+        &ALPHA_MODES,
+        &alpha_mode_display_fn,
+    ) {
+        material.set_alpha_mode(ALPHA_MODES[alpha_mode_index]);
+        changed = true;
+    }
+
+    if material.get_alpha_mode() == ALPHA_MODE_MASK {
+        let mut alpha_cutoff = material.get_alpha_cutoff();
+        if Slider::new(im_str!("Alpha cutoff"), 0.0f32..=1.0).build(ui, &mut alpha_cutoff) {
+            material.set_alpha_cutoff(alpha_cutoff);
+            changed = true;
+        }
+    }
+
+    let mut double_sided = material.is_double_sided();
+    if ui.checkbox(im_str!("Double sided"), &mut double_sided) {
+        material.set_double_sided(double_sided);
+        changed = true;
+    }
+
+    let mut unlit = material.is_unlit();
+    if ui.checkbox(im_str!("Unlit"), &mut unlit) {
+        material.set_unlit(unlit);
+        changed = true;
+    }
+
+    for (slot, label) in TEXTURE_SLOTS.iter() {
+        if material.get_texture(*slot).is_none() {
+            continue;
+        }
+        let mut enabled = material.is_texture_enabled(*slot);
+        if ui.checkbox(&im_str!("{}", label), &mut enabled) {
+            material.set_texture_enabled(*slot, enabled);
+            changed = true;
+        }
+    }
+
+    if changed {
+        *new_material = Some(material);
+    }
+}
+
+/// Build the controls overriding the distance used to sort a blended primitive.
+///
+/// `key` is the index of the node and the index of the primitive in its mesh.
+fn build_sort_distance_ui(ui: &Ui, model: &mut ModelEntry, key: (usize, usize)) {
+    ui.text("Transparency sorting");
+    ui.separator();
+
+    let mut enabled = model.sort_distances.contains_key(&key);
+    if ui.checkbox(im_str!("Override sort distance"), &mut enabled) {
+        if enabled {
+            model.sort_distances.insert(key, 0.0);
+        } else {
+            model.sort_distances.remove(&key);
+        }
+        model.sort_distances_changed = true;
+    }
+
+    if let Some(distance) = model.sort_distances.get_mut(&key) {
+        if ui.input_float(im_str!("Distance"), distance).build() {
+            model.sort_distances_changed = true;
+        }
+        if ui.is_item_hovered() {
+            ui.tooltip_text(im_str!(
+                "Distance to the camera, further primitives are drawn first"
+            ));
+        }
+    }
+}
+
 fn build_light_details_ui(ui: &Ui, light: Light) {
     ui.text("Light");
     ui.separator();
@@ -463,7 +1414,7 @@ fn build_animation_block_ui(ui: &Ui, metadata: &Metadata) {
 fn build_animation_player_window(
     ui: &Ui,
     state: &mut State,
-    model_metadata: Option<&Metadata>,
+    model: Option<&mut ModelEntry>,
     animation_playback_state: Option<PlaybackState>,
 ) {
     let mut opened = true;
@@ -473,8 +1424,10 @@ fn build_animation_player_window(
         .collapsible(false)
         .opened(&mut opened)
         .build(ui, || {
-            if let Some(metadata) = model_metadata {
-                let animations_labels = metadata
+            if let Some(model) = model {
+                ui.text(im_str!("Model: {}", model.metadata.name()));
+                let animations_labels = model
+                    .metadata
                     .animations()
                     .iter()
                     .map(|a| {
@@ -485,7 +1438,7 @@ fn build_animation_player_window(
                 let combo_labels = animations_labels.iter().map(|l| l).collect::<Vec<_>>();
                 ComboBox::new(im_str!("Select animation")).build_simple_string(
                     ui,
-                    &mut state.selected_animation,
+                    &mut model.selected_animation,
                     &combo_labels,
                 );
 
@@ -502,13 +1455,13 @@ fn build_animation_player_window(
                     ui.same_line(0.0);
                     state.reset_animation = ui.button(im_str!("Reset"), [0.0, 0.0]);
                     ui.same_line(0.0);
-                    ui.checkbox(im_str!("Loop"), &mut state.infinite_animation);
+                    ui.checkbox(im_str!("Loop"), &mut model.infinite_animation);
 
                     Slider::new(im_str!("Speed"), 0.05f32..=3.0)
-                        .build(ui, &mut state.animation_speed);
+                        .build(ui, &mut model.animation_speed);
                     ui.same_line(0.0);
                     if ui.button(&im_str!("Default"), [0.0, 0.0]) {
-                        state.animation_speed = 1.0;
+                        model.animation_speed = 1.0;
                     }
 
                     let progress = playback_state.time / playback_state.total_time;
@@ -538,7 +1491,7 @@ fn build_camera_details_window(ui: &Ui, state: &mut State, camera: Option<Camera
     state.show_camera_details = opened;
 }
 
-fn build_renderer_settings_window(ui: &Ui, state: &mut State) {
+fn build_renderer_settings_window(ui: &Ui, state: &mut State, model: Option<&mut ModelEntry>) {
     let mut opened = true;
     Window::new(im_str!("Renderer settings"))
         .position([20.0, 20.0], Condition::Appearing)
@@ -550,11 +1503,25 @@ fn build_renderer_settings_window(ui: &Ui, state: &mut State) {
                 ui.text("Settings");
                 ui.separator();
 
+                state.original_placement_changed = ui.checkbox(
+                    im_str!("Keep original scale and position"),
+                    &mut state.original_placement,
+                );
+
                 let emissive_intensity_changed =
                     Slider::new(im_str!("Emissive intensity"), 1.0f32..=50.0)
                         .build(ui, &mut state.emissive_intensity);
                 state.emissive_intensity_changed = emissive_intensity_changed;
 
+                let combo_labels = TransparencyMode::all()
+                    .iter()
+                    .map(|mode| im_str!("{:?}", mode))
+                    .collect::<Vec<_>>();
+                let combo_labels = combo_labels.iter().map(|l| l).collect::<Vec<_>>();
+                let transparency_mode_changed = ComboBox::new(im_str!("Transparency"))
+                    .build_simple_string(ui, &mut state.selected_transparency_mode, &combo_labels);
+                state.transparency_mode_changed = transparency_mode_changed;
+
                 state.ssao_enabled_changed =
                     ui.checkbox(im_str!("Enable SSAO"), &mut state.ssao_enabled);
                 if state.ssao_enabled {
@@ -581,6 +1548,42 @@ fn build_renderer_settings_window(ui: &Ui, state: &mut State) {
                 }
             }
 
+            {
+                ui.text("Shadows");
+                ui.separator();
+
+                fn resolution_display_fn(v: &u32) -> Cow<ImStr> {
+                    Cow::Owned(im_str!("{} px", v))
+                }
+
+                let shadow_resolution_changed = ComboBox::new(im_str!("Shadow map size"))
+                    .build_simple(
+                        ui,
+                        &mut state.shadow_resolution_index,
+                        &SHADOW_RESOLUTIONS,
+                        &resolution_display_fn,
+                    );
+                state.shadow_resolution_changed = shadow_resolution_changed;
+
+                let shadow_bias_changed = Slider::new(im_str!("Shadow bias"), 0.0f32..=0.01)
+                    .display_format(im_str!("%.5f"))
+                    .build(ui, &mut state.shadow_bias);
+                state.shadow_bias_changed = shadow_bias_changed;
+
+                match model {
+                    Some(model) if !model.lights.is_empty() => {
+                        for (node_index, label) in model.lights.iter() {
+                            let casts_shadow = &mut model.shadow_casting[*node_index];
+                            if ui.checkbox(&im_str!("{}##light{}", label, node_index), casts_shadow)
+                            {
+                                model.shadow_casting_changed = true;
+                            }
+                        }
+                    }
+                    _ => ui.text_disabled(im_str!("The selected model has no light")),
+                }
+            }
+
             {
                 ui.text("Post Processing");
                 ui.separator();
@@ -607,33 +1610,626 @@ fn build_renderer_settings_window(ui: &Ui, state: &mut State) {
                 let output_mode_changed = ComboBox::new(im_str!("Output mode"))
                     .build_simple_string(ui, &mut state.selected_output_mode, &combo_labels);
                 state.output_mode_changed = output_mode_changed;
+
+                let wireframe = OutputMode::from_value(state.selected_output_mode)
+                    .map_or(false, |mode| {
+                        mode == OutputMode::Wireframe || mode == OutputMode::ShadedWireframe
+                    });
+                if wireframe {
+                    state.wireframe_color_changed =
+                        ColorEdit::new(im_str!("Wireframe color"), &mut state.wireframe_color)
+                            .build(ui);
+                }
+
+                let overlays = &mut state.debug_overlays;
+                let overlays_changed = [
+                    ui.checkbox(im_str!("Node bounds"), &mut overlays.node_bounds),
+                    ui.checkbox(im_str!("Primitive bounds"), &mut overlays.primitive_bounds),
+                    ui.checkbox(im_str!("Skeletons"), &mut overlays.skeletons),
+                    ui.checkbox(im_str!("Normals"), &mut overlays.normals),
+                    ui.checkbox(im_str!("Tangents"), &mut overlays.tangents),
+                    ui.checkbox(im_str!("Bitangents"), &mut overlays.bitangents),
+                    ui.checkbox(im_str!("Lights"), &mut overlays.lights),
+                ];
+                state.debug_overlays_changed = overlays_changed.iter().any(|changed| *changed);
+                if overlays.normals || overlays.tangents || overlays.bitangents {
+                    ui.text_disabled(im_str!(
+                        "Vertex vectors are drawn for the selected primitive"
+                    ));
+                }
             }
         });
     state.show_renderer_settings = opened;
 }
 
+fn build_texture_browser_window(
+    ui: &Ui,
+    state: &mut State,
+    model: Option<&mut ModelEntry>,
+    preview_id: Option<TextureId>,
+) {
+    let mut opened = true;
+    Window::new(im_str!("Textures"))
+        .position([20.0, 20.0], Condition::Appearing)
+        .size([700.0, 500.0], Condition::Appearing)
+        .collapsible(false)
+        .opened(&mut opened)
+        .build(ui, || {
+            let model = match model {
+                Some(model) => model,
+                None => return,
+            };
+            if model.textures.is_empty() {
+                ui.text("No texture");
+                return;
+            }
+
+            let parent_size = ui.window_size();
+            let images = &model.metadata.statistics().images;
+            let textures = &model.textures;
+            let selected_texture = &mut model.selected_texture;
+
+            ChildWindow::new(0)
+                .size([parent_size[0] / 3.0, 0.0])
+                .build(ui, || {
+                    for (index, texture) in textures.iter().enumerate() {
+                        Image::new(texture.id, [TEXTURE_THUMBNAIL_SIZE, TEXTURE_THUMBNAIL_SIZE])
+                            .build(ui);
+                        ui.same_line(0.0);
+
+                        let size = images
+                            .get(texture.data.image_index)
+                            .map_or_else(String::new, |image| {
+                                format!(" - {}x{}", image.width, image.height)
+                            });
+                        let selected = *selected_texture == Some(index);
+                        let label = im_str!("Texture {}{}##texture{}", index, size, index);
+                        if Selectable::new(&label)
+                            .selected(selected)
+                            .size([0.0, TEXTURE_THUMBNAIL_SIZE])
+                            .build(ui)
+                            && !selected
+                        {
+                            *selected_texture = Some(index);
+                            state.texture_mip_level = 0;
+                            state.texture_zoom = 1.0;
+                            state.texture_center = [0.5, 0.5];
+                        }
+                    }
+                });
+
+            ui.same_line(0.0);
+
+            let selected = selected_texture
+                .and_then(|index| textures.get(index).map(|texture| (index, texture)));
+            if let Some((index, texture)) = selected {
+                let image = images.get(texture.data.image_index);
+                ChildWindow::new(im_str!("Texture details"))
+                    .border(true)
+                    .build(ui, || {
+                        build_texture_details_ui(ui, index, texture, image);
+                        if let (Some(image), Some(preview_id)) = (image, preview_id) {
+                            build_texture_preview_ui(ui, state, image, preview_id);
+                        }
+                    });
+            }
+        });
+    state.show_texture_browser = opened;
+}
+
+fn build_texture_details_ui(
+    ui: &Ui,
+    index: usize,
+    texture: &TextureEntry,
+    image: Option<&ImageStatistics>,
+) {
+    ui.text(im_str!("Texture {}", index));
+    ui.separator();
+    ui.text(im_str!("Image: {}", texture.data.image_index));
+    if let Some(image) = image {
+        ui.text(im_str!("Resolution: {}x{}", image.width, image.height));
+        ui.text(im_str!("Mip levels: {}", image.mip_levels));
+        ui.text(im_str!("Memory: {}", format_size(image.gpu_size)));
+    }
+    // Images are converted to RGBA8 when loaded
+    ui.text("Format: R8G8B8A8_UNORM");
+    ui.text(im_str!(
+        "Filters: {:?} (min) - {:?} (mag)",
+        texture.data.min_filter,
+        texture.data.mag_filter
+    ));
+    ui.text(im_str!(
+        "Wrap: {:?} (s) - {:?} (t)",
+        texture.data.wrap_s,
+        texture.data.wrap_t
+    ));
+
+    if texture.references.is_empty() {
+        ui.text("Not referenced by any material");
+    } else {
+        TreeNode::new(im_str!("Referenced by"))
+            .default_open(true)
+            .build(ui, || {
+                for reference in &texture.references {
+                    let slot = TEXTURE_SLOTS
+                        .iter()
+                        .find(|(slot, _)| *slot == reference.slot)
+                        .map_or("", |(_, label)| label);
+                    match reference.material_index {
+                        Some(material) => ui.text(im_str!("Material {}: {}", material, slot)),
+                        None => ui.text(im_str!("Default material: {}", slot)),
+                    }
+                }
+            });
+    }
+}
+
+fn build_texture_preview_ui(
+    ui: &Ui,
+    state: &mut State,
+    image: &ImageStatistics,
+    preview_id: TextureId,
+) {
+    ui.separator();
+    ui.text("Preview");
+
+    for (index, label) in TEXTURE_CHANNELS.iter().enumerate() {
+        if index > 0 {
+            ui.same_line(0.0);
+        }
+        ui.checkbox(&im_str!("{}", label), &mut state.texture_channels[index]);
+    }
+
+    let max_mip_level = image.mip_levels.saturating_sub(1);
+    state.texture_mip_level = state.texture_mip_level.min(max_mip_level);
+    Slider::new(im_str!("Mip level"), 0..=max_mip_level).build(ui, &mut state.texture_mip_level);
+    ui.same_line(0.0);
+    ui.text(im_str!(
+        "{}x{}",
+        (image.width >> state.texture_mip_level).max(1),
+        (image.height >> state.texture_mip_level).max(1)
+    ));
+
+    ui.radio_button(im_str!("sRGB"), &mut state.texture_linear, false);
+    ui.same_line(0.0);
+    ui.radio_button(im_str!("Linear"), &mut state.texture_linear, true);
+    if ui.is_item_hovered() {
+        ui.tooltip_text(im_str!("Encode the values to sRGB before displaying them"));
+    }
+
+    Slider::new(im_str!("Zoom"), 1.0..=TEXTURE_MAX_ZOOM).build(ui, &mut state.texture_zoom);
+    ui.same_line(0.0);
+    if ui.button(im_str!("Reset view"), [0.0, 0.0]) {
+        state.texture_zoom = 1.0;
+        state.texture_center = [0.5, 0.5];
+    }
+
+    // Fit the preview in the remaining space keeping the aspect of the texture
+    let available = ui.content_region_avail();
+    let aspect = image.width as f32 / image.height.max(1) as f32;
+    let width = available[0]
+        .min(available[1] * aspect)
+        .max(TEXTURE_THUMBNAIL_SIZE);
+    let size = [width, width / aspect];
+
+    let position = ui.cursor_pos();
+    Image::new(preview_id, size).build(ui);
+    ui.set_cursor_pos(position);
+    ui.invisible_button(im_str!("##texture_preview"), size);
+
+    // Zoom with the mouse wheel and pan by dragging the preview
+    if ui.is_item_hovered() {
+        let wheel = ui.io().mouse_wheel;
+        if wheel != 0.0 {
+            state.texture_zoom *= 1.2f32.powf(wheel);
+        }
+    }
+    state.texture_zoom = state.texture_zoom.max(1.0).min(TEXTURE_MAX_ZOOM);
+    let uv_scale = 1.0 / state.texture_zoom;
+    if ui.is_item_active() {
+        let delta = ui.io().mouse_delta;
+        state.texture_center[0] -= delta[0] / size[0] * uv_scale;
+        state.texture_center[1] -= delta[1] / size[1] * uv_scale;
+    }
+    for center in state.texture_center.iter_mut() {
+        *center = center.max(uv_scale * 0.5).min(1.0 - uv_scale * 0.5);
+    }
+}
+
+fn build_validation_window(ui: &Ui, state: &mut State, model: Option<&mut ModelEntry>) {
+    let mut opened = true;
+    Window::new(im_str!("Validation"))
+        .position([20.0, 20.0], Condition::Appearing)
+        .size([500.0, 300.0], Condition::Appearing)
+        .collapsible(false)
+        .opened(&mut opened)
+        .build(ui, || {
+            if let Some(model) = model {
+                let metadata = &model.metadata;
+                ui.text(im_str!("Model: {}", metadata.name()));
+                ui.separator();
+                if metadata.issues().is_empty() {
+                    ui.text("No issue found");
+                }
+                let mut selected_animation = None;
+                for (index, issue) in metadata.issues().iter().enumerate() {
+                    build_issue_ui(ui, index, issue, metadata, state, &mut selected_animation);
+                }
+                if let Some(animation_index) = selected_animation {
+                    model.selected_animation = animation_index;
+                }
+            }
+        });
+    state.show_validation = opened;
+}
+
+fn build_issue_ui(
+    ui: &Ui,
+    index: usize,
+    issue: &Issue,
+    metadata: &Metadata,
+    state: &mut State,
+    selected_animation: &mut Option<usize>,
+) {
+    let color = match issue.severity {
+        Severity::Error => [1.0, 0.3, 0.3, 1.0],
+        Severity::Warning => [1.0, 0.8, 0.2, 1.0],
+        Severity::Info => [0.6, 0.8, 1.0, 1.0],
+    };
+    ui.text_colored(color, &im_str!("{}", issue.severity));
+    ui.same_line(0.0);
+
+    let label = im_str!("{}##{}", issue.subject, index);
+    match issue.subject {
+        Subject::Document => ui.text(im_str!("{}", issue.subject)),
+        Subject::Animation(animation_index) => {
+            if Selectable::new(&label).size([80.0, 0.0]).build(ui) {
+                *selected_animation = Some(animation_index);
+                state.show_animation_player = true;
+            }
+        }
+        subject => match find_issue_node(metadata.nodes(), subject) {
+            Some(node) => {
+                if Selectable::new(&label).size([80.0, 0.0]).build(ui) {
+                    state.selected_hierarchy_node = Some(NodeDetails::from(node));
+                    state.show_model_descriptor = true;
+                }
+            }
+            None => ui.text(im_str!("{}", issue.subject)),
+        },
+    }
+    ui.same_line(0.0);
+    ui.text_wrapped(&im_str!("{}", issue.message));
+}
+
+/// Find the first node of the hierarchy related to `subject`.
+fn find_issue_node(nodes: &[Node], subject: Subject) -> Option<&Node> {
+    for node in nodes {
+        let found = match (node.kind(), subject) {
+            (NodeKind::Node(_), Subject::Node(index)) => node.index() == index,
+            (
+                NodeKind::Node(NodeData {
+                    mesh: Some(mesh), ..
+                }),
+                Subject::Mesh(index),
+            ) => mesh.index == index,
+            (
+                NodeKind::Node(NodeData {
+                    mesh: Some(mesh), ..
+                }),
+                Subject::Material(index),
+            ) => mesh
+                .primitives
+                .iter()
+                .any(|p| p.material.index == Some(index)),
+            _ => false,
+        };
+        if found {
+            return Some(node);
+        }
+        if let Some(node) = find_issue_node(node.children(), subject) {
+            return Some(node);
+        }
+    }
+    None
+}
+
+fn build_save_as_window(ui: &Ui, state: &mut State, model: Option<&ModelEntry>) {
+    let model = match model {
+        Some(model) => model,
+        None => {
+            state.show_save_as = false;
+            return;
+        }
+    };
+
+    if state.save_path.is_empty() {
+        let metadata = &model.metadata;
+        let path = Path::new(metadata.path().unwrap_or_else(|| metadata.name())).with_extension(
+            export_format_extension(EXPORT_FORMATS[state.save_format_index]),
+        );
+        state.save_path.clear();
+        state.save_path.push_str(&path.to_string_lossy());
+    }
+
+    let mut opened = true;
+    Window::new(im_str!("Save as"))
+        .position([20.0, 40.0], Condition::Appearing)
+        .size([400.0, 0.0], Condition::Appearing)
+        .collapsible(false)
+        .opened(&mut opened)
+        .build(ui, || {
+            ui.text(im_str!("Model: {}", model.metadata.name()));
+            ui.input_text(im_str!("Path"), &mut state.save_path).build();
+
+            fn format_display_fn(format: &ExportFormat) -> Cow<ImStr> {
+                Cow::Owned(im_str!("{}", export_format_label(*format)))
+            }
+
+            let format_changed = ComboBox::new(im_str!("Format")).build_simple(
+                ui,
+                &mut state.save_format_index,
+                &EXPORT_FORMATS,
+                &format_display_fn,
+            );
+            if format_changed {
+                let format = EXPORT_FORMATS[state.save_format_index];
+                let path = Path::new(state.save_path.to_str())
+                    .with_extension(export_format_extension(format));
+                state.save_path.clear();
+                state.save_path.push_str(&path.to_string_lossy());
+            }
+            if EXPORT_FORMATS[state.save_format_index] == ExportFormat::Gltf {
+                ui.text_disabled(im_str!(
+                    "The buffer is written next to the file in a .bin file"
+                ));
+            }
+
+            state.save_model =
+                ui.button(im_str!("Save"), [0.0, 0.0]) && !state.save_path.is_empty();
+        });
+    state.show_save_as = opened && !state.save_model;
+}
+
+fn export_format_label(format: ExportFormat) -> &'static str {
+    match format {
+        ExportFormat::Glb => "Binary glTF (.glb)",
+        ExportFormat::Gltf => "glTF (.gltf + .bin)",
+    }
+}
+
+fn export_format_extension(format: ExportFormat) -> &'static str {
+    match format {
+        ExportFormat::Glb => "glb",
+        ExportFormat::Gltf => "gltf",
+    }
+}
+
+fn build_loading_progress_window(ui: &Ui, state: &mut State, progress: &LoadingProgress) {
+    let name = progress.path.file_name().map_or_else(
+        || progress.path.to_string_lossy(),
+        |name| name.to_string_lossy(),
+    );
+    Window::new(im_str!("Loading"))
+        .position([20.0, 40.0], Condition::Appearing)
+        .size([400.0, 0.0], Condition::Appearing)
+        .collapsible(false)
+        .build(ui, || {
+            ui.text(im_str!("Loading {}", name));
+            ProgressBar::new(progress.stage.progress())
+                .overlay_text(&im_str!("{}", progress.stage))
+                .build(ui);
+            state.cancel_loading = ui.button(im_str!("Cancel"), [0.0, 0.0]);
+        });
+}
+
+/// Build the load error notification.
+///
+/// Returns false when the notification is dismissed.
+fn build_load_error_window(ui: &Ui, error: &str) -> bool {
+    let mut opened = true;
+    let mut dismissed = false;
+    Window::new(im_str!("Loading error"))
+        .position([20.0, 40.0], Condition::Appearing)
+        .size([400.0, 0.0], Condition::Appearing)
+        .collapsible(false)
+        .opened(&mut opened)
+        .build(ui, || {
+            ui.text_wrapped(&im_str!("{}", error));
+            dismissed = ui.button(im_str!("Dismiss"), [0.0, 0.0]);
+        });
+    opened && !dismissed
+}
+
+/// GUI state of one of the models of the scene.
+struct ModelEntry {
+    metadata: Metadata,
+    visible: bool,
+    visible_changed: bool,
+    hidden_nodes: HiddenNodes,
+    node_visibility_changed: bool,
+    translation: [f32; 3],
+    /// Rotation around the y axis in degrees.
+    rotation: f32,
+    scale: f32,
+    transform_changed: bool,
+    selected_animation: usize,
+    infinite_animation: bool,
+    animation_speed: f32,
+    textures: Vec<TextureEntry>,
+    selected_texture: Option<usize>,
+    culling: CullingStatistics,
+    sort_distances: SortDistanceOverrides,
+    sort_distances_changed: bool,
+    /// Index and label of the nodes with a light, sorted by node index.
+    lights: Vec<(usize, String)>,
+    /// Whether the light of each node casts shadows, indexed by node index.
+    shadow_casting: Vec<bool>,
+    shadow_casting_changed: bool,
+}
+
+impl ModelEntry {
+    fn new(metadata: Metadata) -> Self {
+        let lights = collect_lights(&metadata);
+        let shadow_casting = vec![true; metadata.node_count()];
+
+        Self {
+            metadata,
+            visible: true,
+            visible_changed: false,
+            hidden_nodes: HiddenNodes::default(),
+            node_visibility_changed: false,
+            translation: [0.0; 3],
+            rotation: 0.0,
+            scale: 1.0,
+            transform_changed: false,
+            selected_animation: 0,
+            infinite_animation: true,
+            animation_speed: 1.0,
+            textures: Vec::new(),
+            selected_texture: None,
+            culling: CullingStatistics::default(),
+            sort_distances: SortDistanceOverrides::new(),
+            sort_distances_changed: false,
+            lights,
+            shadow_casting,
+            shadow_casting_changed: false,
+        }
+    }
+
+    fn clear_changes(&mut self) {
+        self.visible_changed = false;
+        self.node_visibility_changed = false;
+        self.transform_changed = false;
+        self.sort_distances_changed = false;
+        self.shadow_casting_changed = false;
+    }
+
+    /// Compute the visibility of each node from the hidden nodes of the hierarchy.
+    ///
+    /// A node instanced several times in the hierarchy is visible if any of its instances is.
+    fn node_visibility(&self) -> Vec<bool> {
+        let mut visibility = vec![None; self.metadata.node_count()];
+        collect_node_visibility(
+            self.metadata.nodes(),
+            true,
+            &self.hidden_nodes,
+            &mut visibility,
+        );
+        visibility.into_iter().map(|v| v.unwrap_or(true)).collect()
+    }
+
+    fn transform(&self) -> Matrix4<f32> {
+        Matrix4::from_translation(Vector3::from(self.translation))
+            * Matrix4::from_angle_y(Deg(self.rotation))
+            * Matrix4::from_scale(self.scale)
+    }
+}
+
+fn collect_node_visibility(
+    nodes: &[Node],
+    parent_visible: bool,
+    hidden_nodes: &HiddenNodes,
+    visibility: &mut [Option<bool>],
+) {
+    for node in nodes {
+        let subtree_visible = parent_visible && !hidden_nodes.subtrees.contains(&node.uid());
+        let visible = subtree_visible && !hidden_nodes.nodes.contains(&node.uid());
+        if let NodeKind::Node(_) = node.kind() {
+            if let Some(node_visibility) = visibility.get_mut(node.index()) {
+                *node_visibility = Some(node_visibility.unwrap_or(false) || visible);
+            }
+        }
+        collect_node_visibility(node.children(), subtree_visible, hidden_nodes, visibility);
+    }
+}
+
+/// Hierarchy nodes hidden by the user.
+#[derive(Default)]
+struct HiddenNodes {
+    /// Uids of the nodes hidden with their subtree.
+    subtrees: HashSet<usize>,
+    /// Uids of the nodes hidden without their subtree, like the ancestors of an isolated node.
+    nodes: HashSet<usize>,
+}
+
+impl HiddenNodes {
+    fn is_hidden(&self, uid: usize) -> bool {
+        self.subtrees.contains(&uid) || self.nodes.contains(&uid)
+    }
+
+    fn clear(&mut self) {
+        self.subtrees.clear();
+        self.nodes.clear();
+    }
+}
+
+/// Get the index and a label of the nodes with a light, sorted by node index.
+fn collect_lights(metadata: &Metadata) -> Vec<(usize, String)> {
+    let mut lights = Vec::new();
+    collect_light_nodes(metadata.nodes(), &mut lights);
+    lights.sort_by_key(|(index, _)| *index);
+    lights.dedup_by_key(|(index, _)| *index);
+    lights
+}
+
+fn collect_light_nodes(nodes: &[Node], lights: &mut Vec<(usize, String)>) {
+    for node in nodes {
+        if let NodeKind::Node(NodeData {
+            light: Some(light), ..
+        }) = node.kind()
+        {
+            let label = match node.name() {
+                Some(name) => format!("{} ({:?})", name, light.kind),
+                None => format!("Node {} ({:?})", node.index(), light.kind),
+            };
+            lights.push((node.index(), label));
+        }
+        collect_light_nodes(node.children(), lights);
+    }
+}
+
 struct State {
+    selected_model: usize,
+    remove_model: Option<usize>,
+    add_dropped_models: bool,
+    cancel_loading: bool,
+
+    show_save_as: bool,
+    save_path: ImString,
+    save_format_index: usize,
+    save_model: bool,
+
     show_model_descriptor: bool,
     selected_hierarchy_node: Option<NodeDetails>,
+    /// Expand the hierarchy down to the selected node during the next frame.
+    reveal_hierarchy_node: bool,
+    new_node_transform: Option<NodeTransform>,
+    reset_node_transform: bool,
+    new_material: Option<model::Material>,
+    export_statistics: bool,
 
     show_animation_player: bool,
-    selected_animation: usize,
-    infinite_animation: bool,
     reset_animation: bool,
     toggle_animation: bool,
     stop_animation: bool,
-    animation_speed: f32,
 
     show_camera_details: bool,
     reset_camera: bool,
 
     show_renderer_settings: bool,
+    original_placement: bool,
+    original_placement_changed: bool,
     selected_output_mode: usize,
     output_mode_changed: bool,
+    wireframe_color: [f32; 4],
+    wireframe_color_changed: bool,
     selected_tone_map_mode: usize,
     tone_map_mode_changed: bool,
     emissive_intensity: f32,
     emissive_intensity_changed: bool,
+    selected_transparency_mode: usize,
+    transparency_mode_changed: bool,
     ssao_enabled: bool,
     ssao_enabled_changed: bool,
     ssao_radius: f32,
@@ -642,6 +2238,23 @@ struct State {
     ssao_strength_changed: bool,
     ssao_kernel_size_index: usize,
     ssao_kernel_size_changed: bool,
+    shadow_resolution_index: usize,
+    shadow_resolution_changed: bool,
+    shadow_bias: f32,
+    shadow_bias_changed: bool,
+    debug_overlays: DebugOverlays,
+    debug_overlays_changed: bool,
+
+    show_texture_browser: bool,
+    /// Whether the red, green, blue and alpha channels of the previewed texture are displayed.
+    texture_channels: [bool; 4],
+    texture_mip_level: u32,
+    texture_linear: bool,
+    texture_zoom: f32,
+    /// Center of the displayed region of the texture in texture coordinates.
+    texture_center: [f32; 2],
+
+    show_validation: bool,
 
     hovered: bool,
 }
@@ -649,17 +2262,29 @@ struct State {
 impl State {
     fn reset(&self) -> Self {
         Self {
+            add_dropped_models: self.add_dropped_models,
             show_model_descriptor: self.show_model_descriptor,
             show_animation_player: self.show_animation_player,
             show_camera_details: self.show_camera_details,
             show_renderer_settings: self.show_renderer_settings,
+            show_texture_browser: self.show_texture_browser,
+            texture_channels: self.texture_channels,
+            texture_linear: self.texture_linear,
+            show_validation: self.show_validation,
+            save_format_index: self.save_format_index,
+            original_placement: self.original_placement,
             selected_output_mode: self.selected_output_mode,
+            wireframe_color: self.wireframe_color,
             selected_tone_map_mode: self.selected_tone_map_mode,
             emissive_intensity: self.emissive_intensity,
+            selected_transparency_mode: self.selected_transparency_mode,
             ssao_radius: self.ssao_radius,
             ssao_strength: self.ssao_strength,
             ssao_kernel_size_index: self.ssao_kernel_size_index,
             ssao_enabled: self.ssao_enabled,
+            shadow_resolution_index: self.shadow_resolution_index,
+            shadow_bias: self.shadow_bias,
+            debug_overlays: self.debug_overlays,
             ..Default::default()
         }
     }
@@ -668,27 +2293,45 @@ impl State {
 impl Default for State {
     fn default() -> Self {
         Self {
+            selected_model: 0,
+            remove_model: None,
+            add_dropped_models: false,
+            cancel_loading: false,
+
+            show_save_as: false,
+            save_path: ImString::with_capacity(SAVE_PATH_CAPACITY),
+            save_format_index: 0,
+            save_model: false,
+
             show_model_descriptor: false,
             selected_hierarchy_node: None,
+            reveal_hierarchy_node: false,
+            new_node_transform: None,
+            reset_node_transform: false,
+            new_material: None,
+            export_statistics: false,
 
             show_animation_player: false,
-            selected_animation: 0,
-            infinite_animation: true,
             reset_animation: false,
             toggle_animation: false,
             stop_animation: false,
-            animation_speed: 1.0,
 
             show_camera_details: false,
             reset_camera: false,
 
             show_renderer_settings: false,
+            original_placement: false,
+            original_placement_changed: false,
             selected_output_mode: 0,
             output_mode_changed: false,
+            wireframe_color: [1.0, 0.5, 0.0, 1.0],
+            wireframe_color_changed: false,
             selected_tone_map_mode: 0,
             tone_map_mode_changed: false,
             emissive_intensity: 1.0,
             emissive_intensity_changed: false,
+            selected_transparency_mode: 0,
+            transparency_mode_changed: false,
 
             ssao_enabled: true,
             ssao_enabled_changed: false,
@@ -698,6 +2341,24 @@ impl Default for State {
             ssao_strength_changed: false,
             ssao_kernel_size_index: 1,
             ssao_kernel_size_changed: false,
+            shadow_resolution_index: SHADOW_RESOLUTIONS
+                .iter()
+                .position(|r| *r == ShadowSettings::default().resolution)
+                .unwrap_or(0),
+            shadow_resolution_changed: false,
+            shadow_bias: ShadowSettings::default().bias,
+            shadow_bias_changed: false,
+            debug_overlays: Default::default(),
+            debug_overlays_changed: false,
+
+            show_texture_browser: false,
+            texture_channels: [true, true, true, false],
+            texture_mip_level: 0,
+            texture_linear: false,
+            texture_zoom: 1.0,
+            texture_center: [0.5, 0.5],
+
+            show_validation: false,
 
             hovered: false,
         }
@@ -710,6 +2371,10 @@ struct NodeDetails {
     name: String,
     kind: NodeKind,
     child_count: usize,
+    /// Set when the node was selected by clicking on it in the scene.
+    hit: Option<HitDetails>,
+    /// Position in the mesh of the primitive whose material is edited.
+    selected_primitive: Option<usize>,
 }
 
 impl From<&Node> for NodeDetails {
@@ -720,6 +2385,36 @@ impl From<&Node> for NodeDetails {
             name: node.name().map_or(String::from("no name"), String::from),
             kind: node.kind().clone(),
             child_count: node.children().len(),
+            hit: None,
+            selected_primitive: None,
+        }
+    }
+}
+
+struct HitDetails {
+    primitive_index: usize,
+    material_index: Option<usize>,
+    material_name: Option<String>,
+    position: [f32; 3],
+}
+
+impl HitDetails {
+    fn new(kind: &NodeKind, hit: Hit) -> Self {
+        let material_name = match kind {
+            NodeKind::Node(NodeData {
+                mesh: Some(mesh), ..
+            }) => mesh
+                .primitives
+                .get(hit.primitive_index)
+                .and_then(|p| p.material.name.clone()),
+            _ => None,
+        };
+
+        Self {
+            primitive_index: hit.primitive_index,
+            material_index: hit.material_index,
+            material_name,
+            position: hit.position.into(),
         }
     }
 }
@@ -8,59 +8,147 @@
 //! stop listening for load events. Then we wait for the thread to terminate.
 //!
 //! Users have to call `load` to load a new model and `get_model` to retrieve
-//! the loaded model.
+//! the loaded model or the error that occurred while loading it.
+//!
+//! The thread also reports the loading stages, which are available through
+//! `get_progress`. Only the latest request matters: calling `load` cancels the
+//! model being loaded as well as the ones waiting to be loaded. `cancel` does
+//! the same without requesting a new model. Models, errors and progress of
+//! cancelled loads are never returned.
+//!
+//! The file format is deduced from the extension of the model file. Wavefront
+//! OBJ, STL and PLY files are supported along with glTF and glb.
+//!
+//! Models registered with `watch` are reloaded when their file or one of their
+//! external resources changes on disk. Reloaded models are also returned by
+//! `get_model`.
 
-use model::{Model, ModelStagingResources};
+use model::{
+    LoadingStage, Model, ModelLoadingError, ModelStagingResources, ProgressCallback, Scene,
+};
+use notify::{raw_watcher, Op, RawEvent, RecommendedWatcher, RecursiveMode, Watcher};
 
-use std::error::Error;
+use std::collections::{HashMap, HashSet};
+use std::fs;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::mpsc;
 use std::sync::mpsc::{Receiver, Sender};
 use std::sync::Arc;
 use std::thread;
 use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
 use vulkan::ash::version::DeviceV1_0;
 use vulkan::ash::vk;
 use vulkan::{Context, PreLoadedResource};
 
+/// Time to wait after the last change to the files of a model before reloading it.
+///
+/// Exporters usually write several files in a row (.gltf, .bin, images)
+/// so we wait for them to be done to reload the model only once.
+const RELOAD_DELAY: Duration = Duration::from_millis(500);
+
 enum Message {
-    Load(PathBuf),
+    Load {
+        request: usize,
+        path: PathBuf,
+        reload: bool,
+    },
     Stop,
 }
 
+/// Events sent by the loader thread.
+enum Event {
+    Progress(LoadingProgress),
+    Loaded(LoadResult),
+}
+
+struct LoadResult {
+    request: usize,
+    reload: bool,
+    model: Result<PreLoadedModel, ModelLoadingError>,
+}
+
+/// Current stage of the model being loaded.
+#[derive(Clone, Debug)]
+pub struct LoadingProgress {
+    request: usize,
+    pub path: PathBuf,
+    pub stage: LoadingStage,
+}
+
+/// A model returned by the loader.
+pub struct LoadedModel {
+    pub model: Model,
+    /// True if the model was reloaded because its files changed on disk.
+    pub reloaded: bool,
+}
+
 pub struct Loader {
     message_sender: Sender<Message>,
-    model_receiver: Receiver<PreLoadedResource<Model, ModelStagingResources>>,
+    event_receiver: Receiver<Event>,
     thread_handle: Option<JoinHandle<()>>,
+    /// Id of the latest request. Loads of older requests are cancelled.
+    latest_request: Arc<AtomicUsize>,
+    progress: Option<LoadingProgress>,
+    watcher: Option<ModelWatcher>,
 }
 
 impl Loader {
     pub fn new(context: Arc<Context>) -> Self {
         let (message_sender, message_receiver) = mpsc::channel();
-        let (model_sender, model_receiver) = mpsc::channel();
+        let (event_sender, event_receiver) = mpsc::channel();
+        let latest_request = Arc::new(AtomicUsize::new(0));
+        let thread_latest_request = Arc::clone(&latest_request);
 
         let thread_handle = Some(thread::spawn(move || {
             log::info!("Starting loader");
             loop {
                 let message = message_receiver.recv().expect("Failed to receive a path");
                 match message {
-                    Message::Load(path) => {
-                        log::info!("Start loading {}", path.as_path().display());
-                        let pre_loaded_model = pre_load_model(&context, path.as_path());
+                    Message::Load {
+                        request,
+                        path,
+                        reload,
+                    } => {
+                        if request != thread_latest_request.load(Ordering::SeqCst) {
+                            log::debug!("Skipping outdated load of {}", path.display());
+                            continue;
+                        }
 
-                        match pre_loaded_model {
-                            Ok(pre_loaded_model) => {
-                                log::info!("Finish loading {}", path.as_path().display());
-                                model_sender.send(pre_loaded_model).unwrap();
+                        log::info!("Start loading {}", path.as_path().display());
+                        let mut progress = |stage| {
+                            if request != thread_latest_request.load(Ordering::SeqCst) {
+                                return false;
                             }
-                            Err(error) => {
-                                log::error!(
-                                    "Failed to load {}. Cause: {}",
-                                    path.as_path().display(),
-                                    error
-                                );
+                            let progress = LoadingProgress {
+                                request,
+                                path: path.clone(),
+                                stage,
+                            };
+                            event_sender.send(Event::Progress(progress)).is_ok()
+                        };
+                        let pre_loaded_model =
+                            pre_load_model(&context, path.as_path(), &mut progress);
+
+                        match pre_loaded_model.as_ref() {
+                            Ok(_) => log::info!("Finish loading {}", path.as_path().display()),
+                            Err(ModelLoadingError::Cancelled) => {
+                                log::info!("Cancelled loading {}", path.as_path().display())
                             }
+                            Err(error) => log::error!(
+                                "Failed to load {}. Cause: {}",
+                                path.as_path().display(),
+                                error
+                            ),
                         }
+                        event_sender
+                            .send(Event::Loaded(LoadResult {
+                                request,
+                                reload,
+                                model: pre_loaded_model,
+                            }))
+                            .unwrap();
                     }
                     Message::Stop => break,
                 }
@@ -68,37 +156,284 @@ impl Loader {
             log::info!("Stopping loader");
         }));
 
+        let watcher = ModelWatcher::new()
+            .map_err(|error| log::warn!("Models will not be hot-reloaded. Cause: {}", error))
+            .ok();
+
         Self {
             message_sender,
-            model_receiver,
+            event_receiver,
             thread_handle,
+            latest_request,
+            progress: None,
+            watcher,
         }
     }
 
     /// Start loading a new model in the background.
     ///
+    /// Any model being loaded or waiting to be loaded is cancelled.
     /// Call `get_model` to retrieve the loaded model.
     pub fn load(&self, path: PathBuf) {
+        let request = self.latest_request.fetch_add(1, Ordering::SeqCst) + 1;
+        self.send_load_message(request, path, false);
+    }
+
+    /// Cancel the model being loaded and the ones waiting to be loaded.
+    pub fn cancel(&mut self) {
+        self.latest_request.fetch_add(1, Ordering::SeqCst);
+        self.progress = None;
+    }
+
+    /// Reloads are part of the latest request so they don't cancel the
+    /// model being loaded but are cancelled by the next request.
+    fn send_load_message(&self, request: usize, path: PathBuf, reload: bool) {
         self.message_sender
-            .send(Message::Load(path))
+            .send(Message::Load {
+                request,
+                path,
+                reload,
+            })
             .expect("Failed to send load message to loader");
     }
 
-    /// Get the last loaded model.
+    /// Get the stage of the model being loaded if any.
+    pub fn get_progress(&self) -> Option<&LoadingProgress> {
+        self.progress.as_ref()
+    }
+
+    /// Get the last loaded model or the error that prevented it from loading.
     ///
+    /// Models and errors of outdated requests are dropped. It also queues the
+    /// reload of the watched models whose files changed.
     /// If no model is ready, then `None` is returned.
-    pub fn get_model(&self) -> Option<Model> {
-        match self.model_receiver.try_recv() {
-            Ok(mut pre_loaded_model) => Some(pre_loaded_model.finish()),
-            _ => None,
+    pub fn get_model(&mut self) -> Option<Result<LoadedModel, ModelLoadingError>> {
+        let changed_models = self
+            .watcher
+            .as_mut()
+            .map_or_else(Vec::new, ModelWatcher::poll_changed_models);
+        for path in changed_models {
+            log::info!("{} changed on disk, reloading it", path.display());
+            let request = self.latest_request.load(Ordering::SeqCst);
+            self.send_load_message(request, path, true);
+        }
+
+        while let Ok(event) = self.event_receiver.try_recv() {
+            match event {
+                Event::Progress(progress) => {
+                    if progress.request == self.latest_request.load(Ordering::SeqCst) {
+                        self.progress = Some(progress);
+                    }
+                }
+                Event::Loaded(LoadResult {
+                    request,
+                    reload,
+                    model,
+                }) => {
+                    if request != self.latest_request.load(Ordering::SeqCst) {
+                        // The upload is still submitted so its command buffer is freed
+                        if let Ok(mut pre_loaded_model) = model {
+                            log::debug!("Dropping outdated model");
+                            pre_loaded_model.finish();
+                        }
+                        continue;
+                    }
+                    self.progress = None;
+                    return Some(model.map(|mut pre_loaded_model| LoadedModel {
+                        model: pre_loaded_model.finish(),
+                        reloaded: reload,
+                    }));
+                }
+            }
+        }
+        None
+    }
+
+    /// Watch the file of the model at `path` and its external resources.
+    ///
+    /// `resources` are the uris of the external resources as they appear
+    /// in the glTF document.
+    pub fn watch<P: AsRef<Path>>(&mut self, path: P, resources: &[String]) {
+        if let Some(watcher) = self.watcher.as_mut() {
+            watcher.watch(path.as_ref(), resources);
+        }
+    }
+
+    /// Stop watching the model at `path`.
+    pub fn unwatch<P: AsRef<Path>>(&mut self, path: P) {
+        if let Some(watcher) = self.watcher.as_mut() {
+            watcher.unwatch(path.as_ref());
+        }
+    }
+
+    /// Stop watching all models.
+    pub fn unwatch_all(&mut self) {
+        if let Some(watcher) = self.watcher.as_mut() {
+            watcher.unwatch_all();
         }
     }
 }
 
+/// Watch the files of the loaded models and report the models to reload.
+///
+/// Parent directories are watched rather than the files themselves so files
+/// saved by writing a temporary file and renaming it are still detected.
+struct ModelWatcher {
+    watcher: RecommendedWatcher,
+    event_receiver: Receiver<RawEvent>,
+    /// Watched files by model path.
+    models: HashMap<PathBuf, Vec<PathBuf>>,
+    directories: HashSet<PathBuf>,
+    /// Instant of the last change of models waiting to be reloaded.
+    pending_reloads: HashMap<PathBuf, Instant>,
+}
+
+impl ModelWatcher {
+    fn new() -> notify::Result<Self> {
+        let (event_sender, event_receiver) = mpsc::channel();
+        let watcher = raw_watcher(event_sender)?;
+        Ok(Self {
+            watcher,
+            event_receiver,
+            models: HashMap::new(),
+            directories: HashSet::new(),
+            pending_reloads: HashMap::new(),
+        })
+    }
+
+    fn watch(&mut self, path: &Path, resources: &[String]) {
+        let model_file = match fs::canonicalize(path) {
+            Ok(model_file) => model_file,
+            Err(error) => {
+                log::warn!("Cannot watch {}. Cause: {}", path.display(), error);
+                return;
+            }
+        };
+        let base_dir = model_file
+            .parent()
+            .map(Path::to_path_buf)
+            .unwrap_or_default();
+
+        let mut files = vec![model_file];
+        for uri in resources {
+            let file = base_dir.join(uri);
+            files.push(fs::canonicalize(&file).unwrap_or(file));
+        }
+
+        for directory in files.iter().filter_map(|file| file.parent()) {
+            if self.directories.contains(directory) {
+                continue;
+            }
+            match self.watcher.watch(directory, RecursiveMode::NonRecursive) {
+                Ok(_) => {
+                    self.directories.insert(directory.to_path_buf());
+                }
+                Err(error) => log::warn!("Cannot watch {}. Cause: {}", directory.display(), error),
+            }
+        }
+
+        log::debug!("Watching {:?}", files);
+        self.models.insert(path.to_path_buf(), files);
+    }
+
+    fn unwatch(&mut self, path: &Path) {
+        self.models.remove(path);
+        self.pending_reloads.remove(path);
+
+        let used_directories = self
+            .models
+            .values()
+            .flatten()
+            .filter_map(|file| file.parent())
+            .collect::<HashSet<_>>();
+        let unused_directories = self
+            .directories
+            .iter()
+            .filter(|directory| !used_directories.contains(directory.as_path()))
+            .cloned()
+            .collect::<Vec<_>>();
+
+        for directory in unused_directories {
+            if let Err(error) = self.watcher.unwatch(&directory) {
+                log::warn!("Cannot unwatch {}. Cause: {}", directory.display(), error);
+            }
+            self.directories.remove(&directory);
+        }
+    }
+
+    fn unwatch_all(&mut self) {
+        let paths = self.models.keys().cloned().collect::<Vec<_>>();
+        paths.iter().for_each(|path| self.unwatch(path));
+    }
+
+    /// Get the models whose files did not change for `RELOAD_DELAY` since they were modified.
+    fn poll_changed_models(&mut self) -> Vec<PathBuf> {
+        let now = Instant::now();
+        for event in self.event_receiver.try_iter() {
+            let path = match event {
+                RawEvent {
+                    path: Some(path),
+                    op: Ok(op),
+                    ..
+                } if op.intersects(Op::CREATE | Op::WRITE | Op::RENAME | Op::CLOSE_WRITE) => path,
+                _ => continue,
+            };
+
+            for (model, files) in &self.models {
+                if files.contains(&path) {
+                    self.pending_reloads.insert(model.clone(), now);
+                }
+            }
+        }
+
+        let changed_models = self
+            .pending_reloads
+            .iter()
+            .filter(|(_, last_change)| now.duration_since(**last_change) >= RELOAD_DELAY)
+            .map(|(model, _)| model.clone())
+            .collect::<Vec<_>>();
+        changed_models.iter().for_each(|model| {
+            self.pending_reloads.remove(model);
+        });
+        changed_models
+    }
+}
+
+type PreLoadedModel = PreLoadedResource<Model, ModelStagingResources>;
+
+/// Read the scene of the model file at `path` according to its extension.
+///
+/// Files with an unknown extension are read as glTF.
+pub fn load_scene<P: AsRef<Path>>(
+    path: P,
+    progress: &mut ProgressCallback,
+) -> Result<Scene, ModelLoadingError> {
+    let path = path.as_ref();
+    let extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(str::to_ascii_lowercase);
+    match extension.as_deref() {
+        Some("obj") => Scene::from_obj_file_with_progress(path, progress),
+        Some("stl") => Scene::from_stl_file_with_progress(path, progress),
+        Some("ply") => Scene::from_ply_file_with_progress(path, progress),
+        _ => Scene::from_file_with_progress(path, progress),
+    }
+}
+
 fn pre_load_model<P: AsRef<Path>>(
     context: &Arc<Context>,
     path: P,
-) -> Result<PreLoadedResource<Model, ModelStagingResources>, Box<dyn Error>> {
+    progress: &mut ProgressCallback,
+) -> Result<PreLoadedModel, ModelLoadingError> {
+    // Parse the model first so no command buffer is allocated if it fails
+    let scene = load_scene(path, progress)?;
+
+    // The upload can't be cancelled once started
+    if !progress(LoadingStage::Upload) {
+        return Err(ModelLoadingError::Cancelled);
+    }
+
     let device = context.device();
 
     // Create command buffer
@@ -124,13 +459,13 @@ fn pre_load_model<P: AsRef<Path>>(
         };
     }
 
-    // Load model data and prepare command buffer
-    let model = Model::create_from_file(Arc::clone(context), command_buffer, path);
+    // Upload model data
+    let model = Model::create_from_scene(Arc::clone(context), command_buffer, scene);
 
     // End recording command buffer
     unsafe { device.end_command_buffer(command_buffer).unwrap() };
 
-    model
+    Ok(model)
 }
 
 impl Drop for Loader {
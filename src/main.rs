@@ -2,6 +2,7 @@ mod camera;
 mod config;
 mod controls;
 mod error;
+mod gizmo;
 mod gui;
 mod loader;
 mod renderer;
@@ -20,11 +21,22 @@ fn main() -> Result<(), Box<dyn Error>> {
     let config = matches
         .value_of("config")
         .map_or(Ok(Default::default()), config::load_config)?;
+    let config = if matches.is_present("original-placement") {
+        config.keep_original_placement()
+    } else {
+        config
+    };
 
     let enable_debug = matches.is_present("debug");
 
     let file_path = matches.value_of("file").map(Path::new);
 
+    if let (Some(file_path), Some(export_path)) = (file_path, matches.value_of("export")) {
+        loader::load_scene(file_path, &mut |_| true)?.export(export_path)?;
+        log::info!("Model exported to {}", export_path);
+        return Ok(());
+    }
+
     Viewer::new(config, enable_debug, file_path).run();
 
     Ok(())
@@ -48,9 +60,24 @@ fn create_app<'a, 'b>() -> App<'a, 'b> {
                 .short("f")
                 .long("file")
                 .value_name("FILE")
-                .help("Set the path to gltf model to view")
+                .help("Set the path to the gltf, glb, obj, stl or ply model to view")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("export")
+                .short("e")
+                .long("export")
+                .value_name("FILE")
+                .help("Export the model to a gltf or glb file and exit without opening the viewer")
+                .requires("file")
                 .takes_value(true),
         )
+        .arg(
+            Arg::with_name("original-placement")
+                .long("original-placement")
+                .help("Keep the original scale and position of the model")
+                .takes_value(false),
+        )
         .arg(
             Arg::with_name("debug")
                 .short("d")
@@ -0,0 +1,596 @@
+//! Line overlays drawn on top of the scene to help diagnose models.
+//!
+//! Lines are generated on the CPU each frame from the current state of the
+//! models and drawn at the end of the light pass without depth test.
+
+use super::model::{LightUniform, ModelRenderer};
+use super::{create_renderer_pipeline, LightRenderPass, RendererPipelineParameters};
+use ash::{version::DeviceV1_0, vk, Device};
+use math::cgmath::{EuclideanSpace, InnerSpace, Matrix4, Point3, Transform, Vector3, Zero};
+use math::AABB;
+use model::Model;
+use std::collections::HashSet;
+use std::f32::consts::PI;
+use std::mem::size_of;
+use std::sync::Arc;
+use vulkan::*;
+
+const NODE_BOUNDS_COLOR: [f32; 3] = [1.0, 1.0, 0.0];
+const PRIMITIVE_BOUNDS_COLOR: [f32; 3] = [0.0, 1.0, 1.0];
+const BONE_COLOR: [f32; 3] = [1.0, 0.0, 1.0];
+const NORMAL_COLOR: [f32; 3] = [0.0, 0.0, 1.0];
+const TANGENT_COLOR: [f32; 3] = [1.0, 0.0, 0.0];
+const BITANGENT_COLOR: [f32; 3] = [0.0, 1.0, 0.0];
+
+/// Length of the vertex vectors relative to the size of the primitive.
+const VERTEX_VECTOR_SCALE: f32 = 0.05;
+/// Size of the light gizmos relative to the size of the model.
+const LIGHT_GIZMO_SCALE: f32 = 0.1;
+const CIRCLE_SEGMENTS: usize = 32;
+
+/// Overlays drawn by the debug renderer.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct DebugOverlays {
+    pub node_bounds: bool,
+    pub primitive_bounds: bool,
+    pub skeletons: bool,
+    /// Normals of the vertices of the selected primitive.
+    pub normals: bool,
+    /// Tangents of the vertices of the selected primitive.
+    pub tangents: bool,
+    /// Bitangents of the vertices of the selected primitive.
+    pub bitangents: bool,
+    pub lights: bool,
+}
+
+impl DebugOverlays {
+    fn any(&self) -> bool {
+        self.node_bounds
+            || self.primitive_bounds
+            || self.skeletons
+            || self.vertex_vectors()
+            || self.lights
+    }
+
+    fn vertex_vectors(&self) -> bool {
+        self.normals || self.tangents || self.bitangents
+    }
+}
+
+/// Primitive whose vertex vectors are drawn.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct DebugSelection {
+    pub model_index: usize,
+    pub node_index: usize,
+    /// Index of the primitive in the mesh of the node.
+    pub primitive_index: usize,
+}
+
+#[derive(Copy, Clone, Debug)]
+#[repr(C)]
+struct LineVertex {
+    position: [f32; 3],
+    color: [f32; 3],
+}
+
+impl Vertex for LineVertex {
+    fn get_bindings_descriptions() -> Vec<vk::VertexInputBindingDescription> {
+        vec![vk::VertexInputBindingDescription {
+            binding: 0,
+            stride: size_of::<LineVertex>() as _,
+            input_rate: vk::VertexInputRate::VERTEX,
+        }]
+    }
+
+    fn get_attributes_descriptions() -> Vec<vk::VertexInputAttributeDescription> {
+        vec![
+            vk::VertexInputAttributeDescription {
+                location: 0,
+                binding: 0,
+                format: vk::Format::R32G32B32_SFLOAT,
+                offset: 0,
+            },
+            vk::VertexInputAttributeDescription {
+                location: 1,
+                binding: 0,
+                format: vk::Format::R32G32B32_SFLOAT,
+                offset: 12,
+            },
+        ]
+    }
+}
+
+/// Line list built each frame.
+#[derive(Default)]
+struct Lines {
+    vertices: Vec<LineVertex>,
+}
+
+impl Lines {
+    fn line(&mut self, from: Point3<f32>, to: Point3<f32>, color: [f32; 3]) {
+        self.vertices.push(LineVertex {
+            position: from.into(),
+            color,
+        });
+        self.vertices.push(LineVertex {
+            position: to.into(),
+            color,
+        });
+    }
+
+    /// Add the edges of `aabb` transformed by `transform`.
+    fn boxed(&mut self, aabb: AABB<f32>, transform: Matrix4<f32>, color: [f32; 3]) {
+        let (min, max) = (aabb.min(), aabb.max());
+        let corner = |i: usize| {
+            let x = if i & 1 == 0 { min.x } else { max.x };
+            let y = if i & 2 == 0 { min.y } else { max.y };
+            let z = if i & 4 == 0 { min.z } else { max.z };
+            transform.transform_point(Point3::new(x, y, z))
+        };
+        // Corners differing by one bit share an edge
+        for i in 0..8 {
+            for bit in &[1, 2, 4] {
+                if i & bit == 0 {
+                    self.line(corner(i), corner(i | bit), color);
+                }
+            }
+        }
+    }
+
+    fn circle(&mut self, center: Point3<f32>, axis: Vector3<f32>, radius: f32, color: [f32; 3]) {
+        let (u, v) = orthonormal_basis(axis);
+        let point = |i: usize| {
+            let angle = 2.0 * PI * i as f32 / CIRCLE_SEGMENTS as f32;
+            center + (u * angle.cos() + v * angle.sin()) * radius
+        };
+        for i in 0..CIRCLE_SEGMENTS {
+            self.line(point(i), point(i + 1), color);
+        }
+    }
+
+    fn arrow(&mut self, from: Point3<f32>, direction: Vector3<f32>, length: f32, color: [f32; 3]) {
+        let tip = from + direction * length;
+        let (u, v) = orthonormal_basis(direction);
+        let base = tip - direction * length * 0.2;
+        self.line(from, tip, color);
+        for side in &[u, -u, v, -v] {
+            self.line(tip, base + side * length * 0.1, color);
+        }
+    }
+}
+
+/// Get two unit vectors orthogonal to `axis` and to each other.
+fn orthonormal_basis(axis: Vector3<f32>) -> (Vector3<f32>, Vector3<f32>) {
+    let axis = axis.normalize();
+    let up = if axis.y.abs() < 0.99 {
+        Vector3::unit_y()
+    } else {
+        Vector3::unit_x()
+    };
+    let u = axis.cross(up).normalize();
+    let v = axis.cross(u);
+    (u, v)
+}
+
+pub struct DebugRenderer {
+    context: Arc<Context>,
+    descriptors: Descriptors,
+    pipeline_layout: vk::PipelineLayout,
+    pipeline: vk::Pipeline,
+    /// Line vertices of each swapchain image, created when needed.
+    vertex_buffers: Vec<Option<Buffer>>,
+    vertex_counts: Vec<u32>,
+    overlays: DebugOverlays,
+    selection: Option<DebugSelection>,
+}
+
+impl DebugRenderer {
+    pub fn create(
+        context: Arc<Context>,
+        camera_buffers: &[Buffer],
+        swapchain_props: SwapchainProperties,
+        msaa_samples: vk::SampleCountFlags,
+        render_pass: &LightRenderPass,
+    ) -> Self {
+        let descriptors = create_descriptors(&context, camera_buffers);
+        let pipeline_layout = create_pipeline_layout(context.device(), descriptors.layout());
+        let pipeline = create_debug_pipeline(
+            &context,
+            swapchain_props,
+            msaa_samples,
+            render_pass.get_render_pass(),
+            pipeline_layout,
+        );
+
+        Self {
+            context,
+            descriptors,
+            pipeline_layout,
+            pipeline,
+            vertex_buffers: camera_buffers.iter().map(|_| None).collect(),
+            vertex_counts: vec![0; camera_buffers.len()],
+            overlays: Default::default(),
+            selection: None,
+        }
+    }
+
+    pub fn rebuild_pipeline(
+        &mut self,
+        swapchain_props: SwapchainProperties,
+        msaa_samples: vk::SampleCountFlags,
+        render_pass: &LightRenderPass,
+    ) {
+        let device = self.context.device();
+        unsafe {
+            device.destroy_pipeline(self.pipeline, None);
+        }
+
+        self.pipeline = create_debug_pipeline(
+            &self.context,
+            swapchain_props,
+            msaa_samples,
+            render_pass.get_render_pass(),
+            self.pipeline_layout,
+        );
+    }
+
+    pub fn set_overlays(&mut self, overlays: DebugOverlays) {
+        self.overlays = overlays;
+    }
+
+    pub fn set_selection(&mut self, selection: Option<DebugSelection>) {
+        self.selection = selection;
+    }
+}
+
+impl DebugRenderer {
+    /// Build the lines of the visible models for the swapchain image at `frame_index`.
+    pub fn update(&mut self, frame_index: usize, model_renderers: &[ModelRenderer]) {
+        self.vertex_counts[frame_index] = 0;
+        if !self.overlays.any() {
+            return;
+        }
+
+        let mut lines = Lines::default();
+        for (model_index, renderer) in model_renderers.iter().enumerate() {
+            let model = match renderer.data.model() {
+                Some(model) if renderer.visible => model,
+                _ => continue,
+            };
+            let model = model.borrow();
+            let node_visibility = renderer.data.node_visibility();
+            let is_visible = |node: usize| node_visibility.get(node).copied().unwrap_or(true);
+
+            self.add_model_lines(&mut lines, &model, is_visible);
+
+            if let Some(selection) = self
+                .selection
+                .filter(|s| s.model_index == model_index && is_visible(s.node_index))
+            {
+                self.add_vertex_lines(&mut lines, &model, selection);
+            }
+        }
+
+        if lines.vertices.is_empty() {
+            return;
+        }
+
+        let size = (lines.vertices.len() * size_of::<LineVertex>()) as vk::DeviceSize;
+        let buffer = &mut self.vertex_buffers[frame_index];
+        if buffer.as_ref().map_or(true, |buffer| buffer.size < size) {
+            let mut new_buffer = Buffer::create(
+                Arc::clone(&self.context),
+                size.next_power_of_two(),
+                vk::BufferUsageFlags::VERTEX_BUFFER,
+                vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+            );
+            new_buffer.map_memory();
+            *buffer = Some(new_buffer);
+        }
+
+        let buffer = buffer.as_mut().unwrap();
+        unsafe {
+            let data_ptr = buffer.map_memory();
+            mem_copy(data_ptr, &lines.vertices);
+        }
+        self.vertex_counts[frame_index] = lines.vertices.len() as _;
+    }
+
+    fn add_model_lines<F>(&self, lines: &mut Lines, model: &Model, is_visible: F)
+    where
+        F: Fn(usize) -> bool,
+    {
+        let nodes = model.nodes();
+        let visible_nodes = nodes
+            .nodes()
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| is_visible(*index))
+            .map(|(_, node)| node);
+
+        let mut drawn_skins = HashSet::new();
+        for node in visible_nodes {
+            let transform = node.transform();
+
+            if let Some(mesh) = node.mesh_index().map(|index| model.mesh(index)) {
+                if self.overlays.node_bounds {
+                    if let Some(aabb) = mesh.aabb() {
+                        lines.boxed(aabb, transform, NODE_BOUNDS_COLOR);
+                    }
+                }
+                if self.overlays.primitive_bounds {
+                    for primitive in mesh.primitives() {
+                        lines.boxed(primitive.aabb(), transform, PRIMITIVE_BOUNDS_COLOR);
+                    }
+                }
+            }
+
+            if let Some(skin_index) = node.skin_index() {
+                if self.overlays.skeletons && drawn_skins.insert(skin_index) {
+                    let joints = model.skins()[skin_index].joints();
+                    let joint_nodes = joints.iter().map(|j| j.node_id()).collect::<HashSet<_>>();
+                    for joint in joints {
+                        let parent = nodes
+                            .parent_index(joint.node_id())
+                            .filter(|parent| joint_nodes.contains(parent));
+                        if let Some(parent) = parent {
+                            let from = node_position(&nodes.nodes()[parent].transform());
+                            let to = node_position(&nodes.nodes()[joint.node_id()].transform());
+                            lines.line(from, to, BONE_COLOR);
+                        }
+                    }
+                }
+            }
+
+            if let Some(light_index) = node.light_index() {
+                if self.overlays.lights {
+                    let size = model
+                        .scene()
+                        .aabb()
+                        .map_or(1.0, |aabb| aabb.get_size().magnitude())
+                        * LIGHT_GIZMO_SCALE;
+                    let light = LightUniform::from((transform, model.lights()[light_index]));
+                    add_light_lines(lines, light, size);
+                }
+            }
+        }
+    }
+
+    fn add_vertex_lines(&self, lines: &mut Lines, model: &Model, selection: DebugSelection) {
+        if !self.overlays.vertex_vectors() {
+            return;
+        }
+        let frames = model
+            .scene()
+            .vertex_frames(selection.node_index, selection.primitive_index);
+        let positions = frames.iter().map(|frame| frame.position.to_vec());
+        let length = match AABB::from_points(positions) {
+            Some(aabb) => aabb.get_size().magnitude() * VERTEX_VECTOR_SCALE,
+            None => return,
+        };
+
+        for frame in frames {
+            let vectors = [
+                (self.overlays.normals, frame.normal, NORMAL_COLOR),
+                (self.overlays.tangents, frame.tangent, TANGENT_COLOR),
+                (self.overlays.bitangents, frame.bitangent, BITANGENT_COLOR),
+            ];
+            for (enabled, vector, color) in vectors.iter().copied() {
+                if enabled && !vector.is_zero() {
+                    lines.line(frame.position, frame.position + vector * length, color);
+                }
+            }
+        }
+    }
+
+    pub fn cmd_draw(&self, command_buffer: vk::CommandBuffer, frame_index: usize) {
+        let vertex_count = self.vertex_counts[frame_index];
+        let buffer = match &self.vertex_buffers[frame_index] {
+            Some(buffer) if vertex_count > 0 => buffer,
+            _ => return,
+        };
+
+        let device = self.context.device();
+        unsafe {
+            device.cmd_bind_pipeline(
+                command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                self.pipeline,
+            );
+            device.cmd_bind_descriptor_sets(
+                command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                self.pipeline_layout,
+                0,
+                &self.descriptors.sets()[frame_index..=frame_index],
+                &[],
+            );
+            device.cmd_bind_vertex_buffers(command_buffer, 0, &[buffer.buffer], &[0]);
+            device.cmd_draw(command_buffer, vertex_count, 1, 0, 0);
+        }
+    }
+}
+
+impl Drop for DebugRenderer {
+    fn drop(&mut self) {
+        let device = self.context.device();
+        unsafe {
+            device.destroy_pipeline(self.pipeline, None);
+            device.destroy_pipeline_layout(self.pipeline_layout, None);
+        }
+    }
+}
+
+fn node_position(transform: &Matrix4<f32>) -> Point3<f32> {
+    Point3::new(transform.w.x, transform.w.y, transform.w.z)
+}
+
+/// Add the gizmo of a light.
+///
+/// Directional lights are drawn as an arrow, point lights as their range
+/// and spot lights as their outer cone. `size` is used when a light has no range.
+fn add_light_lines(lines: &mut Lines, light: LightUniform, size: f32) {
+    let position = light.position();
+    let direction = light.direction();
+    let color = light.color();
+
+    if light.is_directional() {
+        lines.arrow(position, direction, size, color);
+    } else if let Some(outer_cos) = light.outer_cone_cos() {
+        let length = light.range().unwrap_or(size);
+        let outer_cos = outer_cos.max(-1.0).min(1.0);
+        let radius = length * (1.0 - outer_cos * outer_cos).sqrt() / outer_cos.max(0.001);
+        let center = position + direction * length;
+        let (u, v) = orthonormal_basis(direction);
+        lines.circle(center, direction, radius, color);
+        for side in &[u, -u, v, -v] {
+            lines.line(position, center + side * radius, color);
+        }
+        lines.arrow(position, direction, size, color);
+    } else {
+        let radius = light.range().unwrap_or(size);
+        for axis in &[Vector3::unit_x(), Vector3::unit_y(), Vector3::unit_z()] {
+            lines.circle(position, *axis, radius, color);
+        }
+    }
+}
+
+fn create_descriptors(context: &Arc<Context>, uniform_buffers: &[Buffer]) -> Descriptors {
+    let layout = create_descriptor_set_layout(context.device());
+    let pool = create_descriptor_pool(context.device(), uniform_buffers.len() as _);
+    let sets = create_descriptor_sets(context, pool, layout, uniform_buffers);
+    Descriptors::new(Arc::clone(context), layout, pool, sets)
+}
+
+fn create_descriptor_set_layout(device: &Device) -> vk::DescriptorSetLayout {
+    let bindings = [vk::DescriptorSetLayoutBinding::builder()
+        .binding(0)
+        .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
+        .descriptor_count(1)
+        .stage_flags(vk::ShaderStageFlags::VERTEX)
+        .build()];
+
+    let layout_info = vk::DescriptorSetLayoutCreateInfo::builder().bindings(&bindings);
+
+    unsafe {
+        device
+            .create_descriptor_set_layout(&layout_info, None)
+            .unwrap()
+    }
+}
+
+fn create_descriptor_pool(device: &Device, descriptor_count: u32) -> vk::DescriptorPool {
+    let pool_sizes = [vk::DescriptorPoolSize {
+        ty: vk::DescriptorType::UNIFORM_BUFFER,
+        descriptor_count,
+    }];
+
+    let create_info = vk::DescriptorPoolCreateInfo::builder()
+        .pool_sizes(&pool_sizes)
+        .max_sets(descriptor_count);
+
+    unsafe { device.create_descriptor_pool(&create_info, None).unwrap() }
+}
+
+fn create_descriptor_sets(
+    context: &Arc<Context>,
+    pool: vk::DescriptorPool,
+    layout: vk::DescriptorSetLayout,
+    buffers: &[Buffer],
+) -> Vec<vk::DescriptorSet> {
+    let layouts = (0..buffers.len()).map(|_| layout).collect::<Vec<_>>();
+
+    let allocate_info = vk::DescriptorSetAllocateInfo::builder()
+        .descriptor_pool(pool)
+        .set_layouts(&layouts);
+    let sets = unsafe {
+        context
+            .device()
+            .allocate_descriptor_sets(&allocate_info)
+            .unwrap()
+    };
+
+    sets.iter().zip(buffers.iter()).for_each(|(set, buffer)| {
+        let buffer_info = [vk::DescriptorBufferInfo::builder()
+            .buffer(buffer.buffer)
+            .offset(0)
+            .range(vk::WHOLE_SIZE)
+            .build()];
+
+        let descriptor_writes = [vk::WriteDescriptorSet::builder()
+            .dst_set(*set)
+            .dst_binding(0)
+            .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
+            .buffer_info(&buffer_info)
+            .build()];
+
+        unsafe {
+            context
+                .device()
+                .update_descriptor_sets(&descriptor_writes, &[])
+        }
+    });
+
+    sets
+}
+
+fn create_pipeline_layout(
+    device: &Device,
+    descriptor_set_layout: vk::DescriptorSetLayout,
+) -> vk::PipelineLayout {
+    let layouts = [descriptor_set_layout];
+    let layout_info = vk::PipelineLayoutCreateInfo::builder().set_layouts(&layouts);
+    unsafe { device.create_pipeline_layout(&layout_info, None).unwrap() }
+}
+
+fn create_debug_pipeline(
+    context: &Arc<Context>,
+    swapchain_properties: SwapchainProperties,
+    msaa_samples: vk::SampleCountFlags,
+    render_pass: vk::RenderPass,
+    layout: vk::PipelineLayout,
+) -> vk::Pipeline {
+    // Overlays are drawn on top of the scene
+    let depth_stencil_info = vk::PipelineDepthStencilStateCreateInfo::builder()
+        .depth_test_enable(false)
+        .depth_write_enable(false)
+        .depth_compare_op(vk::CompareOp::LESS_OR_EQUAL)
+        .depth_bounds_test_enable(false)
+        .min_depth_bounds(0.0)
+        .max_depth_bounds(1.0)
+        .stencil_test_enable(false)
+        .front(Default::default())
+        .back(Default::default());
+
+    let color_blend_attachments = [vk::PipelineColorBlendAttachmentState::builder()
+        .color_write_mask(vk::ColorComponentFlags::all())
+        .blend_enable(false)
+        .src_color_blend_factor(vk::BlendFactor::ONE)
+        .dst_color_blend_factor(vk::BlendFactor::ZERO)
+        .color_blend_op(vk::BlendOp::ADD)
+        .src_alpha_blend_factor(vk::BlendFactor::ONE)
+        .dst_alpha_blend_factor(vk::BlendFactor::ZERO)
+        .alpha_blend_op(vk::BlendOp::ADD)
+        .build()];
+
+    create_renderer_pipeline::<LineVertex>(
+        context,
+        RendererPipelineParameters {
+            vertex_shader_name: "debug",
+            fragment_shader_name: "debug",
+            vertex_shader_specialization: None,
+            fragment_shader_specialization: None,
+            swapchain_properties,
+            msaa_samples,
+            render_pass,
+            subpass: 0,
+            layout,
+            depth_stencil_info: &depth_stencil_info,
+            color_blend_attachments: &color_blend_attachments,
+            topology: vk::PrimitiveTopology::LINE_LIST,
+            polygon_mode: vk::PolygonMode::FILL,
+            enable_face_culling: false,
+            parent: None,
+        },
+    )
+}
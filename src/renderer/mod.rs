@@ -1,24 +1,32 @@
+mod debug;
 mod fullscreen;
 mod model;
 mod postprocess;
 mod skybox;
 mod ssao;
+mod texturepreview;
 
 extern crate model as model_crate;
 
+use self::debug::DebugRenderer;
+pub use self::debug::{DebugOverlays, DebugSelection};
 use self::fullscreen::QuadModel;
 use self::model::gbufferpass::{GBufferPass, GBufferRenderPass};
-pub use self::model::lightpass::{LightPass, LightRenderPass, OutputMode};
-use self::model::{ModelData, ModelRenderer};
+pub use self::model::lightpass::{LightPass, LightRenderPass, OutputMode, TransparencyMode};
+use self::model::shadowpass::ShadowPass;
+use self::model::{merge_blended_draws, ModelData, ModelRenderer};
+pub use self::model::{CullingStatistics, ShadowSettings, SortDistanceOverrides};
 use self::ssao::*;
+pub use self::texturepreview::TexturePreview;
+use self::texturepreview::TexturePreviewPass;
 pub use self::{postprocess::*, skybox::*};
 
 use super::camera::{Camera, CameraUBO};
 use ash::{version::DeviceV1_0, vk};
 use environment::Environment;
-use imgui::{Context as GuiContext, DrawData};
+use imgui::{Context as GuiContext, DrawData, TextureId};
 use imgui_rs_vulkan_renderer::Renderer as GuiRenderer;
-use math::cgmath::{Deg, Matrix4, SquareMatrix, Vector3};
+use math::Frustum;
 use model_crate::Model;
 use std::borrow::Borrow;
 use std::cell::RefCell;
@@ -39,18 +47,25 @@ pub struct Renderer {
     light_render_pass: LightRenderPass,
     lightpass_framebuffer: vk::Framebuffer,
     skybox_renderer: SkyboxRenderer,
+    debug_renderer: DebugRenderer,
     gbuffer_render_pass: GBufferRenderPass,
     gbuffer_framebuffer: vk::Framebuffer,
-    model_renderer: Option<ModelRenderer>,
+    model_renderers: Vec<ModelRenderer>,
     ssao_pass: SSAOPass,
     ssao_blur_pass: BlurPass,
     quad_model: QuadModel,
+    oit_composite_pass: OitCompositePass,
     final_pass: FinalPass,
     gui_renderer: GuiRenderer,
+    texture_preview_pass: TexturePreviewPass,
+    texture_preview: Option<TexturePreview>,
     output_mode: OutputMode,
+    transparency_mode: TransparencyMode,
+    wireframe_color: [f32; 4],
     emissive_intensity: f32,
     tone_map_mode: ToneMapMode,
     ssao_enabled: bool,
+    shadow_settings: ShadowSettings,
 }
 
 impl Renderer {
@@ -84,6 +99,14 @@ impl Renderer {
             &light_render_pass,
         );
 
+        let debug_renderer = DebugRenderer::create(
+            Arc::clone(&context),
+            &camera_uniform_buffers,
+            swapchain_properties,
+            msaa_samples,
+            &light_render_pass,
+        );
+
         let gbuffer_render_pass = GBufferRenderPass::create(
             Arc::clone(&context),
             swapchain_properties.extent,
@@ -108,6 +131,12 @@ impl Renderer {
 
         let quad_model = QuadModel::new(&context);
 
+        let oit_composite_pass = OitCompositePass::create(
+            Arc::clone(&context),
+            swapchain_properties,
+            oit_composite_inputs(&light_render_pass),
+        );
+
         let tone_map_mode = ToneMapMode::Default;
         let final_pass = FinalPass::create(
             Arc::clone(&context),
@@ -117,7 +146,7 @@ impl Renderer {
             tone_map_mode,
         );
 
-        let gui_renderer = GuiRenderer::new::<Context>(
+        let mut gui_renderer = GuiRenderer::new::<Context>(
             context.borrow(),
             crate::viewer::MAX_FRAMES_IN_FLIGHT as _,
             simple_render_pass.get_render_pass(),
@@ -125,6 +154,12 @@ impl Renderer {
         )
         .expect("Failed to create gui renderer");
 
+        let texture_preview_pass = TexturePreviewPass::create(
+            Arc::clone(&context),
+            swapchain_properties,
+            gui_renderer.textures(),
+        );
+
         let output_mode = OutputMode::Final;
 
         Self {
@@ -137,22 +172,37 @@ impl Renderer {
             light_render_pass,
             lightpass_framebuffer,
             skybox_renderer,
+            debug_renderer,
             gbuffer_render_pass,
             gbuffer_framebuffer,
-            model_renderer: None,
+            model_renderers: Vec::new(),
             ssao_pass,
             ssao_blur_pass,
             quad_model,
+            oit_composite_pass,
             final_pass,
             gui_renderer,
+            texture_preview_pass,
+            texture_preview: None,
             output_mode,
+            transparency_mode: TransparencyMode::Sorted,
+            wireframe_color: [1.0, 0.5, 0.0, 1.0],
             emissive_intensity: 1.0,
             tone_map_mode,
             ssao_enabled: true,
+            shadow_settings: ShadowSettings::default(),
         }
     }
 }
 
+fn oit_composite_inputs(light_render_pass: &LightRenderPass) -> OitCompositeInputs {
+    OitCompositeInputs {
+        color: light_render_pass.get_color_attachment(),
+        accumulation: light_render_pass.get_accumulation_attachment(),
+        revealage: light_render_pass.get_revealage_attachment(),
+    }
+}
+
 fn create_camera_uniform_buffers(context: &Arc<Context>, count: u32) -> Vec<Buffer> {
     (0..count)
         .map(|_| {
@@ -180,6 +230,13 @@ impl Renderer {
     ) {
         let device = self.context.device();
 
+        // Shadow passes
+        for renderer in self.model_renderers.iter().filter(|r| r.visible) {
+            renderer
+                .shadow_pass
+                .cmd_draw(command_buffer, frame_index, &renderer.data);
+        }
+
         if self.ssao_enabled {
             // GBuffer pass
             {
@@ -215,7 +272,7 @@ impl Renderer {
                     };
                 }
 
-                if let Some(renderer) = self.model_renderer.as_ref() {
+                for renderer in self.model_renderers.iter().filter(|r| r.visible) {
                     renderer
                         .gbuffer_pass
                         .cmd_draw(command_buffer, frame_index, &renderer.data);
@@ -248,6 +305,18 @@ impl Renderer {
                             stencil: 0,
                         },
                     },
+                    // OIT accumulation
+                    vk::ClearValue {
+                        color: vk::ClearColorValue {
+                            float32: [0.0, 0.0, 0.0, 0.0],
+                        },
+                    },
+                    // OIT revealage
+                    vk::ClearValue {
+                        color: vk::ClearColorValue {
+                            float32: [1.0, 0.0, 0.0, 0.0],
+                        },
+                    },
                 ];
                 let render_pass_begin_info = vk::RenderPassBeginInfo::builder()
                     .render_pass(self.light_render_pass.get_render_pass())
@@ -269,15 +338,76 @@ impl Renderer {
 
             self.skybox_renderer.cmd_draw(command_buffer, frame_index);
 
-            if let Some(renderer) = self.model_renderer.as_ref() {
+            let renderers = self
+                .model_renderers
+                .iter()
+                .filter(|r| r.visible)
+                .collect::<Vec<_>>();
+
+            for renderer in &renderers {
                 renderer
                     .light_pass
                     .cmd_draw(command_buffer, frame_index, &renderer.data);
             }
 
+            // Blended primitives of all models are sorted together and drawn after
+            // the opaque ones so a model can't cover the blended primitives of another
+            if self.transparency_mode == TransparencyMode::Sorted {
+                let draws = merge_blended_draws(renderers.iter().map(|r| r.data.blended_draws()));
+                let mut draws = draws.as_slice();
+                while let Some(&(index, _)) = draws.first() {
+                    let count = draws.iter().take_while(|(i, _)| *i == index).count();
+                    let model_draws = draws[..count]
+                        .iter()
+                        .map(|(_, draw)| *draw)
+                        .collect::<Vec<_>>();
+                    let renderer = renderers[index];
+                    renderer.light_pass.cmd_draw_blended(
+                        command_buffer,
+                        frame_index,
+                        &renderer.data,
+                        &model_draws,
+                    );
+                    draws = &draws[count..];
+                }
+            }
+
+            for renderer in &renderers {
+                renderer.light_pass.cmd_draw_wireframe(
+                    command_buffer,
+                    frame_index,
+                    &renderer.data,
+                    self.wireframe_color,
+                );
+            }
+
+            self.debug_renderer.cmd_draw(command_buffer, frame_index);
+
+            unsafe { device.cmd_next_subpass(command_buffer, vk::SubpassContents::INLINE) };
+
+            if self.transparency_mode == TransparencyMode::WeightedBlended {
+                for renderer in &renderers {
+                    renderer
+                        .light_pass
+                        .cmd_draw_oit(command_buffer, frame_index, &renderer.data);
+                }
+            }
+
             unsafe { device.cmd_end_render_pass(command_buffer) };
         }
 
+        // OIT composite pass
+        if self.transparency_mode == TransparencyMode::WeightedBlended {
+            self.oit_composite_pass
+                .cmd_draw(command_buffer, &self.quad_model);
+        }
+
+        // Texture preview
+        if let Some(preview) = self.texture_preview {
+            self.texture_preview_pass
+                .cmd_draw(command_buffer, &self.quad_model, preview);
+        }
+
         // Final pass and UI
         {
             {
@@ -316,15 +446,50 @@ impl Renderer {
         }
     }
 
+    /// Replace all the rendered models by `model`.
     pub fn set_model(&mut self, model: &Rc<RefCell<Model>>) {
-        self.model_renderer.take();
+        self.model_renderers.clear();
+        self.texture_preview_pass
+            .clear(self.gui_renderer.textures());
+        self.add_model(model);
+    }
+
+    /// Render `model` along with the models already rendered.
+    pub fn add_model(&mut self, model: &Rc<RefCell<Model>>) {
+        let renderer = self.create_model_renderer(model);
+        self.model_renderers.push(renderer);
+        self.texture_preview_pass
+            .add_model(&RefCell::borrow(model), self.gui_renderer.textures());
+    }
+
+    /// Render `model` in place of the model at `index`.
+    ///
+    /// The visibility of the replaced model is kept.
+    /// The caller must make sure the GPU is not using the replaced model's resources anymore.
+    pub fn replace_model(&mut self, index: usize, model: &Rc<RefCell<Model>>) {
+        let mut renderer = self.create_model_renderer(model);
+        renderer.visible = self.model_renderers[index].visible;
+        self.model_renderers[index] = renderer;
+        self.texture_preview_pass.replace_model(
+            index,
+            &RefCell::borrow(model),
+            self.gui_renderer.textures(),
+        );
+    }
 
+    fn create_model_renderer(&self, model: &Rc<RefCell<Model>>) -> ModelRenderer {
         let model_data = ModelData::create(
             Arc::clone(&self.context),
             Rc::downgrade(model),
             self.swapchain_properties,
         );
 
+        let shadow_pass = ShadowPass::create(
+            Arc::clone(&self.context),
+            &model_data,
+            self.shadow_settings.resolution,
+        );
+
         let gbuffer_pass = GBufferPass::create(
             Arc::clone(&self.context),
             &model_data,
@@ -345,17 +510,75 @@ impl Renderer {
             self.swapchain_properties,
             &self.environment,
             ao_map,
+            shadow_pass.get_shadow_maps(),
             self.msaa_samples,
             &self.light_render_pass,
             self.output_mode,
             self.emissive_intensity,
         );
 
-        self.model_renderer = Some(ModelRenderer {
+        ModelRenderer {
             data: model_data,
+            shadow_pass,
             gbuffer_pass,
             light_pass,
-        });
+            visible: true,
+        }
+    }
+
+    /// Stop rendering the model at `index`.
+    ///
+    /// The caller must make sure the GPU is not using the model's resources anymore.
+    pub fn remove_model(&mut self, index: usize) {
+        self.model_renderers.remove(index);
+        self.texture_preview_pass
+            .remove_model(index, self.gui_renderer.textures());
+    }
+
+    /// Get the gui texture ids of the textures of the model at `index`.
+    ///
+    /// The ids are indexed like the textures of the model.
+    pub fn get_texture_ids(&self, index: usize) -> &[TextureId] {
+        self.texture_preview_pass.get_texture_ids(index)
+    }
+
+    /// Get the gui texture id of the texture preview.
+    pub fn get_texture_preview_id(&self) -> TextureId {
+        self.texture_preview_pass.get_output_texture_id()
+    }
+
+    /// Set the texture to preview or `None` to stop rendering the preview.
+    pub fn set_texture_preview(&mut self, preview: Option<TexturePreview>) {
+        self.texture_preview = preview;
+    }
+
+    pub fn set_model_visible(&mut self, index: usize, visible: bool) {
+        if let Some(renderer) = self.model_renderers.get_mut(index) {
+            renderer.visible = visible;
+        }
+    }
+
+    /// Get the culling statistics of the last frame of the model at `index`.
+    pub fn get_culling_statistics(&self, index: usize) -> Option<CullingStatistics> {
+        self.model_renderers
+            .get(index)
+            .map(|renderer| renderer.data.culling().statistics())
+    }
+
+    /// Set the distances used to sort the blended primitives of the model at `index`.
+    pub fn set_sort_distance_overrides(&mut self, index: usize, overrides: SortDistanceOverrides) {
+        if let Some(renderer) = self.model_renderers.get_mut(index) {
+            renderer.data.set_sort_distance_overrides(overrides);
+        }
+    }
+
+    /// Set which nodes of the model at `index` are rendered.
+    ///
+    /// `node_visibility` is indexed like the nodes of the model.
+    pub fn set_node_visibility(&mut self, index: usize, node_visibility: Vec<bool>) {
+        if let Some(renderer) = self.model_renderers.get_mut(index) {
+            renderer.data.set_node_visibility(node_visibility);
+        }
     }
 
     pub fn on_new_swapchain(
@@ -410,8 +633,15 @@ impl Renderer {
             &light_render_pass,
         );
 
+        // Debug overlays
+        self.debug_renderer.rebuild_pipeline(
+            swapchain_properties,
+            self.msaa_samples,
+            &light_render_pass,
+        );
+
         // Model
-        if let Some(renderer) = self.model_renderer.as_mut() {
+        for renderer in self.model_renderers.iter_mut() {
             renderer
                 .gbuffer_pass
                 .rebuild_pipelines(swapchain_properties, &gbuffer_render_pass);
@@ -421,7 +651,9 @@ impl Renderer {
             } else {
                 None
             };
-            renderer.light_pass.set_ao_map(ao_map);
+            renderer
+                .light_pass
+                .set_inputs(ao_map, renderer.shadow_pass.get_shadow_maps());
 
             renderer.light_pass.rebuild_pipelines(
                 &renderer.data,
@@ -433,9 +665,20 @@ impl Renderer {
             )
         }
 
+        // OIT composite
+        self.oit_composite_pass
+            .set_extent(swapchain_properties.extent);
+        self.oit_composite_pass
+            .set_inputs(oit_composite_inputs(&light_render_pass));
+        self.oit_composite_pass
+            .rebuild_pipelines(swapchain_properties);
+
         // Final
-        self.final_pass
-            .set_input_image(light_render_pass.get_color_attachment());
+        let final_input = match self.transparency_mode {
+            TransparencyMode::Sorted => light_render_pass.get_color_attachment(),
+            TransparencyMode::WeightedBlended => self.oit_composite_pass.get_output(),
+        };
+        self.final_pass.set_input_image(final_input);
         self.final_pass.rebuild_pipelines(
             swapchain_properties,
             simple_render_pass,
@@ -451,7 +694,7 @@ impl Renderer {
 
     pub fn set_emissive_intensity(&mut self, emissive_intensity: f32) {
         self.emissive_intensity = emissive_intensity;
-        if let Some(renderer) = self.model_renderer.as_mut() {
+        for renderer in self.model_renderers.iter_mut() {
             renderer.light_pass.rebuild_pipelines(
                 &renderer.data,
                 self.swapchain_properties,
@@ -478,7 +721,7 @@ impl Renderer {
 
     pub fn set_output_mode(&mut self, output_mode: OutputMode) {
         self.output_mode = output_mode;
-        if let Some(renderer) = self.model_renderer.as_mut() {
+        for renderer in self.model_renderers.iter_mut() {
             renderer.light_pass.rebuild_pipelines(
                 &renderer.data,
                 self.swapchain_properties,
@@ -490,16 +733,35 @@ impl Renderer {
         }
     }
 
+    /// Set how blended primitives are composited.
+    ///
+    /// The caller must make sure the GPU is not using the final pass' input anymore.
+    pub fn set_transparency_mode(&mut self, transparency_mode: TransparencyMode) {
+        self.transparency_mode = transparency_mode;
+        let final_input = match transparency_mode {
+            TransparencyMode::Sorted => self.light_render_pass.get_color_attachment(),
+            TransparencyMode::WeightedBlended => self.oit_composite_pass.get_output(),
+        };
+        self.final_pass.set_input_image(final_input);
+    }
+
+    /// Set the color of the edges drawn by the wireframe output modes.
+    pub fn set_wireframe_color(&mut self, color: [f32; 4]) {
+        self.wireframe_color = color;
+    }
+
     pub fn enabled_ssao(&mut self, enable: bool) {
         if self.ssao_enabled != enable {
             self.ssao_enabled = enable;
-            if let Some(renderer) = self.model_renderer.as_mut() {
-                let ao_map = if enable {
-                    Some(self.ssao_blur_pass.get_output())
-                } else {
-                    None
-                };
-                renderer.light_pass.set_ao_map(ao_map);
+            let ao_map = if enable {
+                Some(self.ssao_blur_pass.get_output())
+            } else {
+                None
+            };
+            for renderer in self.model_renderers.iter_mut() {
+                renderer
+                    .light_pass
+                    .set_inputs(ao_map, renderer.shadow_pass.get_shadow_maps());
             }
         }
     }
@@ -519,35 +781,82 @@ impl Renderer {
         self.ssao_pass.rebuild_pipelines(self.swapchain_properties);
     }
 
+    /// Set the width and height of the shadow maps.
+    ///
+    /// The caller must make sure the GPU is not using the shadow maps anymore.
+    pub fn set_shadow_resolution(&mut self, resolution: u32) {
+        self.shadow_settings.resolution = resolution;
+        let ao_map = if self.ssao_enabled {
+            Some(self.ssao_blur_pass.get_output())
+        } else {
+            None
+        };
+        for renderer in self.model_renderers.iter_mut() {
+            renderer
+                .shadow_pass
+                .set_resolution(resolution, renderer.data.shadow_layer_count());
+            renderer
+                .light_pass
+                .set_inputs(ao_map, renderer.shadow_pass.get_shadow_maps());
+        }
+    }
+
+    pub fn set_shadow_bias(&mut self, bias: f32) {
+        self.shadow_settings.bias = bias;
+    }
+
+    /// Set which lights of the model at `index` cast shadows.
+    ///
+    /// `shadow_casting` is indexed like the nodes of the model.
+    pub fn set_shadow_casting(&mut self, index: usize, shadow_casting: Vec<bool>) {
+        if let Some(renderer) = self.model_renderers.get_mut(index) {
+            renderer.data.set_shadow_casting(shadow_casting);
+        }
+    }
+
+    pub fn set_debug_overlays(&mut self, overlays: DebugOverlays) {
+        self.debug_renderer.set_overlays(overlays);
+    }
+
+    /// Set the primitive whose vertex normals, tangents and bitangents are drawn.
+    pub fn set_debug_selection(&mut self, selection: Option<DebugSelection>) {
+        self.debug_renderer.set_selection(selection);
+    }
+
+    /// Update the uniform buffers of the frame, cull the models, sort their blended
+    /// primitives and fit the shadow maps of their lights.
+    ///
+    /// It must be called before recording the draw commands of the frame.
     pub fn update_ubos(&mut self, frame_index: usize, camera: Camera) {
+        let (ubo, frustum, view);
+
         // Camera
         {
             let aspect = self.swapchain_properties.extent.width as f32
                 / self.swapchain_properties.extent.height as f32;
 
-            let view = Matrix4::look_at(
-                camera.position(),
-                camera.target(),
-                Vector3::new(0.0, 1.0, 0.0),
-            );
-
-            const Z_NEAR: f32 = 0.01;
-            const Z_FAR: f32 = 100.0;
-            let proj = math::perspective(Deg(45.0), aspect, Z_NEAR, Z_FAR);
-            let inverted_proj = proj.invert().unwrap();
-
-            let ubo = CameraUBO::new(view, proj, inverted_proj, camera.position(), Z_NEAR, Z_FAR);
+            ubo = CameraUBO::from_camera(camera, aspect);
             let buffer = &mut self.camera_uniform_buffers[frame_index];
             unsafe {
                 let data_ptr = buffer.map_memory();
                 mem_copy(data_ptr, &[ubo]);
             }
+            frustum = Frustum::from_matrix(ubo.view_proj());
+            view = ubo.view();
         }
 
-        // model
-        if let Some(renderer) = self.model_renderer.as_mut() {
+        // models
+        for renderer in self.model_renderers.iter_mut() {
             renderer.data.update_buffers(frame_index);
+            renderer.data.update_draws(&frustum, view);
+            renderer
+                .data
+                .update_shadows(frame_index, &ubo, self.shadow_settings);
         }
+
+        // Debug overlays
+        self.debug_renderer
+            .update(frame_index, &self.model_renderers);
     }
 }
 
@@ -580,6 +889,8 @@ struct RendererPipelineParameters<'a> {
     layout: vk::PipelineLayout,
     depth_stencil_info: &'a vk::PipelineDepthStencilStateCreateInfo,
     color_blend_attachments: &'a [vk::PipelineColorBlendAttachmentState],
+    topology: vk::PrimitiveTopology,
+    polygon_mode: vk::PolygonMode,
     enable_face_culling: bool,
     parent: Option<vk::Pipeline>,
 }
@@ -631,7 +942,7 @@ fn create_renderer_pipeline<V: Vertex>(
     let rasterizer_info = vk::PipelineRasterizationStateCreateInfo::builder()
         .depth_clamp_enable(false)
         .rasterizer_discard_enable(false)
-        .polygon_mode(vk::PolygonMode::FILL)
+        .polygon_mode(params.polygon_mode)
         .line_width(1.0)
         .cull_mode(cull_mode)
         .front_face(vk::FrontFace::COUNTER_CLOCKWISE)
@@ -647,6 +958,7 @@ fn create_renderer_pipeline<V: Vertex>(
             fragment_shader_params,
             multisampling_info: &multisampling_info,
             viewport_info: &viewport_info,
+            topology: params.topology,
             rasterizer_info: &rasterizer_info,
             dynamic_state_info: None,
             depth_stencil_info: Some(params.depth_stencil_info),
@@ -0,0 +1,99 @@
+//! Frustum culling of the nodes and primitives of a model.
+//!
+//! Nodes and primitives are tested in their current pose against the camera
+//! frustum each frame before the draw commands are recorded.
+
+use math::Frustum;
+use model::NodeBounds;
+
+/// Number of nodes and primitives drawn and culled in a frame.
+///
+/// Hidden nodes are neither drawn nor culled.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct CullingStatistics {
+    pub drawn_nodes: usize,
+    pub culled_nodes: usize,
+    pub drawn_primitives: usize,
+    pub culled_primitives: usize,
+}
+
+/// Result of the frustum culling of a model.
+///
+/// The default value culls nothing.
+#[derive(Clone, Debug, Default)]
+pub struct Culling {
+    /// Whether each node is outside of the frustum, indexed like the nodes.
+    nodes: Vec<bool>,
+    /// Whether each primitive of the mesh of each node is outside of the frustum.
+    primitives: Vec<Vec<bool>>,
+    statistics: CullingStatistics,
+}
+
+impl Culling {
+    /// Test the world space bounds of the nodes of a model against `frustum`.
+    ///
+    /// `node_visibility` is only used to compute the statistics.
+    pub fn compute(
+        bounds: &[Option<NodeBounds>],
+        node_visibility: &[bool],
+        frustum: &Frustum<f32>,
+    ) -> Self {
+        let mut statistics = CullingStatistics::default();
+        let mut nodes = Vec::with_capacity(bounds.len());
+        let mut primitives = Vec::with_capacity(bounds.len());
+
+        for (node_index, bounds) in bounds.iter().enumerate() {
+            let (node_culled, primitives_culled) = match bounds {
+                Some(bounds) if frustum.intersects_aabb(&bounds.aabb) => (
+                    false,
+                    bounds
+                        .primitives
+                        .iter()
+                        .map(|aabb| !frustum.intersects_aabb(aabb))
+                        .collect::<Vec<_>>(),
+                ),
+                Some(bounds) => (true, vec![true; bounds.primitives.len()]),
+                None => (false, Vec::new()),
+            };
+
+            if bounds.is_some() && node_visibility.get(node_index).copied().unwrap_or(true) {
+                let culled = primitives_culled.iter().filter(|culled| **culled).count();
+                if node_culled {
+                    statistics.culled_nodes += 1;
+                } else {
+                    statistics.drawn_nodes += 1;
+                }
+                statistics.culled_primitives += culled;
+                statistics.drawn_primitives += primitives_culled.len() - culled;
+            }
+
+            nodes.push(node_culled);
+            primitives.push(primitives_culled);
+        }
+
+        Self {
+            nodes,
+            primitives,
+            statistics,
+        }
+    }
+
+    pub fn is_node_culled(&self, node_index: usize) -> bool {
+        self.nodes.get(node_index).copied().unwrap_or(false)
+    }
+
+    /// Check whether a primitive of the mesh of a node is culled.
+    ///
+    /// `primitive_index` is the index of the primitive in the mesh.
+    pub fn is_primitive_culled(&self, node_index: usize, primitive_index: usize) -> bool {
+        self.primitives
+            .get(node_index)
+            .and_then(|primitives| primitives.get(primitive_index))
+            .copied()
+            .unwrap_or(false)
+    }
+
+    pub fn statistics(&self) -> CullingStatistics {
+        self.statistics
+    }
+}
@@ -2,10 +2,12 @@ mod renderpass;
 
 pub use renderpass::RenderPass as GBufferRenderPass;
 
-use super::{JointsBuffer, ModelData};
+use super::{Culling, JointsBuffer, ModelData};
 use crate::renderer::{create_renderer_pipeline, RendererPipelineParameters};
 use math::cgmath::Matrix4;
-use model::{Material, Model, ModelVertex, Primitive, Texture};
+use model::{
+    metadata::PrimitiveMode, Material, Model, ModelVertex, Primitive, Texture, TextureSlot,
+};
 use std::{mem::size_of, sync::Arc};
 use util::any_as_u8_slice;
 use vulkan::ash::{version::DeviceV1_0, vk, Device};
@@ -26,6 +28,7 @@ pub struct GBufferPass {
     pipeline_layout: vk::PipelineLayout,
     culled_pipeline: vk::Pipeline,
     unculled_pipeline: vk::Pipeline,
+    points_pipeline: vk::Pipeline,
 }
 
 impl GBufferPass {
@@ -60,6 +63,7 @@ impl GBufferPass {
             swapchain_props,
             render_pass.get_render_pass(),
             pipeline_layout,
+            vk::PrimitiveTopology::TRIANGLE_LIST,
             true,
         );
         let unculled_pipeline = create_pipeline(
@@ -67,6 +71,15 @@ impl GBufferPass {
             swapchain_props,
             render_pass.get_render_pass(),
             pipeline_layout,
+            vk::PrimitiveTopology::TRIANGLE_LIST,
+            false,
+        );
+        let points_pipeline = create_pipeline(
+            &context,
+            swapchain_props,
+            render_pass.get_render_pass(),
+            pipeline_layout,
+            vk::PrimitiveTopology::POINT_LIST,
             false,
         );
 
@@ -77,6 +90,7 @@ impl GBufferPass {
             pipeline_layout,
             culled_pipeline,
             unculled_pipeline,
+            points_pipeline,
         }
     }
 
@@ -88,6 +102,7 @@ impl GBufferPass {
         let device = self.context.device();
 
         unsafe {
+            device.destroy_pipeline(self.points_pipeline, None);
             device.destroy_pipeline(self.unculled_pipeline, None);
             device.destroy_pipeline(self.culled_pipeline, None);
         }
@@ -97,6 +112,7 @@ impl GBufferPass {
             swapchain_props,
             render_pass.get_render_pass(),
             self.pipeline_layout,
+            vk::PrimitiveTopology::TRIANGLE_LIST,
             true,
         );
 
@@ -105,6 +121,16 @@ impl GBufferPass {
             swapchain_props,
             render_pass.get_render_pass(),
             self.pipeline_layout,
+            vk::PrimitiveTopology::TRIANGLE_LIST,
+            false,
+        );
+
+        self.points_pipeline = create_pipeline(
+            &self.context,
+            swapchain_props,
+            render_pass.get_render_pass(),
+            self.pipeline_layout,
+            vk::PrimitiveTopology::POINT_LIST,
             false,
         );
     }
@@ -138,9 +164,15 @@ impl GBufferPass {
             self.pipeline_layout,
             command_buffer,
             &model,
+            model_data.node_visibility(),
+            model_data.culling(),
             &self.descriptors.dynamic_data_sets[frame_index..=frame_index],
             &self.descriptors.per_primitive_sets,
-            |p| !p.material().is_transparent() && !p.material().is_double_sided(),
+            |p| {
+                !p.material().is_transparent()
+                    && !p.material().is_double_sided()
+                    && p.mode() != PrimitiveMode::Points
+            },
         );
 
         // Bind opaque without culling pipeline
@@ -158,9 +190,37 @@ impl GBufferPass {
             self.pipeline_layout,
             command_buffer,
             &model,
+            model_data.node_visibility(),
+            model_data.culling(),
+            &self.descriptors.dynamic_data_sets[frame_index..=frame_index],
+            &self.descriptors.per_primitive_sets,
+            |p| {
+                !p.material().is_transparent()
+                    && p.material().is_double_sided()
+                    && p.mode() != PrimitiveMode::Points
+            },
+        );
+
+        // Bind points pipeline
+        unsafe {
+            device.cmd_bind_pipeline(
+                command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                self.points_pipeline,
+            )
+        };
+
+        // Draw opaque point clouds
+        register_model_draw_commands(
+            &self.context,
+            self.pipeline_layout,
+            command_buffer,
+            &model,
+            model_data.node_visibility(),
+            model_data.culling(),
             &self.descriptors.dynamic_data_sets[frame_index..=frame_index],
             &self.descriptors.per_primitive_sets,
-            |p| !p.material().is_transparent() && p.material().is_double_sided(),
+            |p| !p.material().is_transparent() && p.mode() == PrimitiveMode::Points,
         );
     }
 }
@@ -170,6 +230,7 @@ impl Drop for GBufferPass {
         self.context.graphics_queue_wait_idle();
         let device = self.context.device();
         unsafe {
+            device.destroy_pipeline(self.points_pipeline, None);
             device.destroy_pipeline(self.unculled_pipeline, None);
             device.destroy_pipeline(self.culled_pipeline, None);
             device.destroy_pipeline_layout(self.pipeline_layout, None);
@@ -473,6 +534,7 @@ fn create_pipeline(
     swapchain_properties: SwapchainProperties,
     render_pass: vk::RenderPass,
     layout: vk::PipelineLayout,
+    topology: vk::PrimitiveTopology,
     enable_face_culling: bool,
 ) -> vk::Pipeline {
     let depth_stencil_info = vk::PipelineDepthStencilStateCreateInfo::builder()
@@ -511,6 +573,8 @@ fn create_pipeline(
             layout,
             depth_stencil_info: &depth_stencil_info,
             color_blend_attachments: &color_blend_attachments,
+            topology,
+            polygon_mode: vk::PolygonMode::FILL,
             enable_face_culling,
             parent: None,
         },
@@ -522,6 +586,8 @@ fn register_model_draw_commands<F>(
     pipeline_layout: vk::PipelineLayout,
     command_buffer: vk::CommandBuffer,
     model: &Model,
+    node_visibility: &[bool],
+    culling: &Culling,
     dynamic_descriptors: &[vk::DescriptorSet],
     per_primitive_descriptors: &[vk::DescriptorSet],
     primitive_filter: F,
@@ -532,13 +598,21 @@ fn register_model_draw_commands<F>(
     let model_transform_ubo_offset = context.get_ubo_alignment::<Matrix4<f32>>();
     let model_skin_ubo_offset = context.get_ubo_alignment::<JointsBuffer>();
 
-    for (index, node) in model
+    let mesh_nodes = model
         .nodes()
         .nodes()
         .iter()
-        .filter(|n| n.mesh_index().is_some())
         .enumerate()
-    {
+        .filter(|(_, n)| n.mesh_index().is_some());
+
+    // The transform buffer holds the transforms of all mesh nodes, hidden or not
+    for (index, (node_index, node)) in mesh_nodes.enumerate() {
+        if !node_visibility.get(node_index).copied().unwrap_or(true)
+            || culling.is_node_culled(node_index)
+        {
+            continue;
+        }
+
         let mesh = model.mesh(node.mesh_index().unwrap());
         let skin_index = node.skin_index().unwrap_or(0);
 
@@ -557,7 +631,15 @@ fn register_model_draw_commands<F>(
             )
         };
 
-        for primitive in mesh.primitives().iter().filter(primitive_filter) {
+        let primitives = mesh
+            .primitives()
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| !culling.is_primitive_culled(node_index, *index))
+            .map(|(_, primitive)| primitive)
+            .filter(primitive_filter);
+
+        for primitive in primitives {
             let primitive_index = primitive.index();
 
             unsafe {
@@ -651,7 +733,7 @@ impl<'a> From<Material> for MaterialUniform {
     fn from(material: Material) -> MaterialUniform {
         let alpha = material.get_color()[3];
         let color_texture_channel = material
-            .get_color_texture()
+            .get_enabled_texture(TextureSlot::Color)
             .map_or(Self::NO_TEXTURE_ID, |info| info.get_channel());
         let alpha_mode = material.get_alpha_mode();
         let alpha_cutoff = material.get_alpha_cutoff();
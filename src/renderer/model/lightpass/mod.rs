@@ -1,16 +1,19 @@
 mod renderpass;
 
-pub use renderpass::RenderPass as LightRenderPass;
+pub use renderpass::{RenderPass as LightRenderPass, OIT_SUBPASS};
 
-use super::{uniform::*, JointsBuffer, ModelData};
+use super::{shadows, sorting::BlendedDraw, uniform::*, Culling, JointsBuffer, ModelData};
 use crate::renderer::{create_renderer_pipeline, RendererPipelineParameters};
 use environment::*;
 use math::cgmath::Matrix4;
-use model::{Model, ModelVertex, Primitive, Texture, Workflow};
+use model::{metadata::PrimitiveMode, Model, ModelVertex, Primitive, Scene, Texture, Workflow};
 use std::{mem::size_of, sync::Arc};
 use util::*;
 use vulkan::ash::{version::DeviceV1_0, vk, Device};
-use vulkan::{Buffer, Context, SwapchainProperties, Texture as VulkanTexture};
+use vulkan::{
+    create_device_local_buffer_with_data, Buffer, Context, SwapchainProperties,
+    Texture as VulkanTexture,
+};
 
 const DYNAMIC_DATA_SET_INDEX: u32 = 0;
 const STATIC_DATA_SET_INDEX: u32 = 1;
@@ -30,6 +33,8 @@ const MATERIAL_SAMPLER_BINDING: u32 = 9;
 const OCCLUSION_SAMPLER_BINDING: u32 = 10;
 const EMISSIVE_SAMPLER_BINDING: u32 = 11;
 const AO_MAP_SAMPLER_BINDING: u32 = 12;
+const SHADOW_MAPS_SAMPLER_BINDING: u32 = 13;
+const SHADOWS_UBO_BINDING: u32 = 14;
 
 pub struct LightPass {
     context: Arc<Context>,
@@ -39,9 +44,16 @@ pub struct LightPass {
     opaque_pipeline: vk::Pipeline,
     opaque_unculled_pipeline: vk::Pipeline,
     transparent_pipeline: vk::Pipeline,
+    points_pipeline: vk::Pipeline,
+    wireframe_pipeline: vk::Pipeline,
+    oit_pipeline: vk::Pipeline,
+    /// Unindexed vertices of each primitive used to draw wireframes when the
+    /// device cannot rasterize polygons as lines. Indexed like the primitives.
+    wireframe_vertices: Option<Vec<Option<Buffer>>>,
+    output_mode: OutputMode,
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum OutputMode {
     Final = 0,
     Color,
@@ -55,14 +67,28 @@ pub enum OutputMode {
     TexCoord0,
     TexCoord1,
     Ssao,
+    Wireframe,
+    ShadedWireframe,
 }
 
 impl OutputMode {
-    pub fn all() -> [OutputMode; 12] {
+    pub fn all() -> [OutputMode; 14] {
         use OutputMode::*;
         [
-            Final, Color, Emissive, Metallic, Specular, Roughness, Occlusion, Normal, Alpha,
-            TexCoord0, TexCoord1, Ssao,
+            Final,
+            Color,
+            Emissive,
+            Metallic,
+            Specular,
+            Roughness,
+            Occlusion,
+            Normal,
+            Alpha,
+            TexCoord0,
+            TexCoord1,
+            Ssao,
+            Wireframe,
+            ShadedWireframe,
         ]
     }
 
@@ -81,6 +107,50 @@ impl OutputMode {
             9 => Some(TexCoord0),
             10 => Some(TexCoord1),
             11 => Some(Ssao),
+            12 => Some(Wireframe),
+            13 => Some(ShadedWireframe),
+            _ => None,
+        }
+    }
+
+    /// Get the output mode of model.frag.
+    ///
+    /// Wireframes are drawn by their own pipeline over the final shading.
+    fn shader_value(self) -> u32 {
+        match self {
+            OutputMode::Wireframe | OutputMode::ShadedWireframe => OutputMode::Final as _,
+            mode => mode as _,
+        }
+    }
+
+    fn draws_surfaces(self) -> bool {
+        self != OutputMode::Wireframe
+    }
+
+    fn draws_wireframe(self) -> bool {
+        self == OutputMode::Wireframe || self == OutputMode::ShadedWireframe
+    }
+}
+
+/// How `AlphaMode::Blend` primitives are composited over the rest of the scene.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TransparencyMode {
+    /// Primitives are sorted from back to front and alpha blended.
+    Sorted = 0,
+    /// Weighted blended order-independent transparency. Primitives are accumulated
+    /// in the OIT subpass and resolved by the OIT composite pass.
+    WeightedBlended,
+}
+
+impl TransparencyMode {
+    pub fn all() -> [TransparencyMode; 2] {
+        [TransparencyMode::Sorted, TransparencyMode::WeightedBlended]
+    }
+
+    pub fn from_value(value: usize) -> Option<Self> {
+        match value {
+            0 => Some(TransparencyMode::Sorted),
+            1 => Some(TransparencyMode::WeightedBlended),
             _ => None,
         }
     }
@@ -94,6 +164,7 @@ impl LightPass {
         swapchain_props: SwapchainProperties,
         environment: &Environment,
         ao_map: Option<&VulkanTexture>,
+        shadow_maps: &VulkanTexture,
         msaa_samples: vk::SampleCountFlags,
         render_pass: &LightRenderPass,
         output_mode: OutputMode,
@@ -113,12 +184,14 @@ impl LightPass {
                 model_transform_buffers: &model_data.transform_ubos,
                 model_skin_buffers: &model_data.skin_ubos,
                 light_buffers: &model_data.light_buffers,
+                shadow_buffers: &model_data.shadow_buffers,
                 dummy_texture: &dummy_texture,
                 environment,
 
                 model: &model_rc.borrow(),
             },
             ao_map.unwrap_or(&dummy_texture),
+            shadow_maps,
         );
 
         let pipeline_layout = create_pipeline_layout(context.device(), &descriptors);
@@ -126,6 +199,7 @@ impl LightPass {
             &context,
             swapchain_props,
             msaa_samples,
+            vk::PrimitiveTopology::TRIANGLE_LIST,
             true,
             render_pass.get_render_pass(),
             pipeline_layout,
@@ -138,6 +212,7 @@ impl LightPass {
             &context,
             swapchain_props,
             msaa_samples,
+            vk::PrimitiveTopology::TRIANGLE_LIST,
             false,
             render_pass.get_render_pass(),
             pipeline_layout,
@@ -158,6 +233,48 @@ impl LightPass {
             emissive_intensity,
         );
 
+        let points_pipeline = create_opaque_pipeline(
+            &context,
+            swapchain_props,
+            msaa_samples,
+            vk::PrimitiveTopology::POINT_LIST,
+            false,
+            render_pass.get_render_pass(),
+            pipeline_layout,
+            &model_rc.borrow(),
+            output_mode,
+            emissive_intensity,
+        );
+
+        let oit_pipeline = create_oit_pipeline(
+            &context,
+            swapchain_props,
+            msaa_samples,
+            render_pass.get_render_pass(),
+            pipeline_layout,
+            &model_rc.borrow(),
+            output_mode,
+            emissive_intensity,
+        );
+
+        let wireframe_vertices = if context.features().fill_mode_non_solid == vk::TRUE {
+            None
+        } else {
+            Some(create_wireframe_vertices(
+                &context,
+                model_rc.borrow().scene(),
+            ))
+        };
+
+        let wireframe_pipeline = create_wireframe_pipeline(
+            &context,
+            swapchain_props,
+            msaa_samples,
+            render_pass.get_render_pass(),
+            pipeline_layout,
+            wireframe_vertices.is_some(),
+        );
+
         LightPass {
             context,
             dummy_texture,
@@ -166,10 +283,15 @@ impl LightPass {
             opaque_pipeline,
             opaque_unculled_pipeline,
             transparent_pipeline,
+            points_pipeline,
+            wireframe_pipeline,
+            oit_pipeline,
+            wireframe_vertices,
+            output_mode,
         }
     }
 
-    pub fn set_ao_map(&mut self, ao_map: Option<&VulkanTexture>) {
+    pub fn set_inputs(&mut self, ao_map: Option<&VulkanTexture>, shadow_maps: &VulkanTexture) {
         unsafe {
             self.context
                 .device()
@@ -180,6 +302,7 @@ impl LightPass {
             self.descriptors.pool,
             self.descriptors.input_layout,
             ao_map.unwrap_or(&self.dummy_texture),
+            shadow_maps,
         );
     }
 
@@ -202,12 +325,18 @@ impl LightPass {
             device.destroy_pipeline(self.opaque_pipeline, None);
             device.destroy_pipeline(self.opaque_unculled_pipeline, None);
             device.destroy_pipeline(self.transparent_pipeline, None);
+            device.destroy_pipeline(self.points_pipeline, None);
+            device.destroy_pipeline(self.wireframe_pipeline, None);
+            device.destroy_pipeline(self.oit_pipeline, None);
         }
 
+        self.output_mode = output_mode;
+
         self.opaque_pipeline = create_opaque_pipeline(
             &self.context,
             swapchain_props,
             msaa_samples,
+            vk::PrimitiveTopology::TRIANGLE_LIST,
             true,
             render_pass.get_render_pass(),
             self.pipeline_layout,
@@ -220,6 +349,7 @@ impl LightPass {
             &self.context,
             swapchain_props,
             msaa_samples,
+            vk::PrimitiveTopology::TRIANGLE_LIST,
             false,
             render_pass.get_render_pass(),
             self.pipeline_layout,
@@ -239,10 +369,48 @@ impl LightPass {
             output_mode,
             emissive_intensity,
         );
+
+        self.points_pipeline = create_opaque_pipeline(
+            &self.context,
+            swapchain_props,
+            msaa_samples,
+            vk::PrimitiveTopology::POINT_LIST,
+            false,
+            render_pass.get_render_pass(),
+            self.pipeline_layout,
+            &model.borrow(),
+            output_mode,
+            emissive_intensity,
+        );
+
+        self.wireframe_pipeline = create_wireframe_pipeline(
+            &self.context,
+            swapchain_props,
+            msaa_samples,
+            render_pass.get_render_pass(),
+            self.pipeline_layout,
+            self.wireframe_vertices.is_some(),
+        );
+
+        self.oit_pipeline = create_oit_pipeline(
+            &self.context,
+            swapchain_props,
+            msaa_samples,
+            render_pass.get_render_pass(),
+            self.pipeline_layout,
+            &model.borrow(),
+            output_mode,
+            emissive_intensity,
+        );
     }
 }
 
 impl LightPass {
+    /// Register the draw commands of the opaque primitives and point clouds of the model
+    /// in the first subpass.
+    ///
+    /// Blended primitives are drawn by `cmd_draw_blended` or `cmd_draw_oit` once the
+    /// opaque primitives of all models are drawn, and edges by `cmd_draw_wireframe`.
     pub fn cmd_draw(
         &self,
         command_buffer: vk::CommandBuffer,
@@ -256,89 +424,249 @@ impl LightPass {
             .expect("Cannot register draw commands because model was dropped");
         let model = model.borrow();
 
-        // Bind opaque pipeline
-        unsafe {
-            device.cmd_bind_pipeline(
+        self.bind_static_descriptor_sets(command_buffer);
+
+        let draw_primitives = |filter: fn(&&Primitive) -> bool| {
+            self.register_model_draw_commands(
                 command_buffer,
-                vk::PipelineBindPoint::GRAPHICS,
-                self.opaque_pipeline,
+                frame_index,
+                model_data,
+                &model,
+                filter,
+                PrimitiveDraw::Shaded,
             )
         };
 
-        // Bind static data
+        if self.output_mode.draws_surfaces() {
+            // Bind opaque pipeline
+            unsafe {
+                device.cmd_bind_pipeline(
+                    command_buffer,
+                    vk::PipelineBindPoint::GRAPHICS,
+                    self.opaque_pipeline,
+                )
+            };
+
+            // Draw opaque primitives
+            draw_primitives(|p| {
+                !p.material().is_transparent()
+                    && !p.material().is_double_sided()
+                    && p.mode() != PrimitiveMode::Points
+            });
+
+            // Bind opaque without culling pipeline
+            unsafe {
+                device.cmd_bind_pipeline(
+                    command_buffer,
+                    vk::PipelineBindPoint::GRAPHICS,
+                    self.opaque_unculled_pipeline,
+                )
+            };
+
+            // Draw opaque, double sided primitives
+            draw_primitives(|p| {
+                !p.material().is_transparent()
+                    && p.material().is_double_sided()
+                    && p.mode() != PrimitiveMode::Points
+            });
+        }
+
+        // Bind points pipeline
         unsafe {
-            device.cmd_bind_descriptor_sets(
+            device.cmd_bind_pipeline(
                 command_buffer,
                 vk::PipelineBindPoint::GRAPHICS,
-                self.pipeline_layout,
-                STATIC_DATA_SET_INDEX,
-                &[self.descriptors.static_data_set],
-                &[],
+                self.points_pipeline,
             )
         };
+        // Draw point clouds, blended ones are drawn as opaque
+        // They have no edges so they are also drawn in wireframe mode
+        draw_primitives(|p| p.mode() == PrimitiveMode::Points);
+    }
 
-        // Bind input data
+    /// Register the draw commands of blended primitives of the model in the first subpass.
+    ///
+    /// `draws` are a run of the blended primitives of all models sorted from back
+    /// to front that belong to this model. They are drawn in that order.
+    pub fn cmd_draw_blended(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        frame_index: usize,
+        model_data: &ModelData,
+        draws: &[BlendedDraw],
+    ) {
+        if !self.output_mode.draws_surfaces() {
+            return;
+        }
+
+        let device = self.context.device();
+        let model = model_data
+            .model
+            .upgrade()
+            .expect("Cannot register draw commands because model was dropped");
+        let model = model.borrow();
+
+        self.bind_static_descriptor_sets(command_buffer);
+
+        // Bind transparent pipeline
         unsafe {
-            device.cmd_bind_descriptor_sets(
+            device.cmd_bind_pipeline(
                 command_buffer,
                 vk::PipelineBindPoint::GRAPHICS,
-                self.pipeline_layout,
-                INPUT_SET_INDEX,
-                &[self.descriptors.input_set],
-                &[],
+                self.transparent_pipeline,
             )
         };
-
-        // Draw opaque primitives
-        register_model_draw_commands(
+        // Draw transparent primitives from back to front
+        register_blended_draw_commands(
             &self.context,
             self.pipeline_layout,
             command_buffer,
             &model,
+            draws,
             &self.descriptors.dynamic_data_sets[frame_index..=frame_index],
             &self.descriptors.per_primitive_sets,
-            |p| !p.material().is_transparent() && !p.material().is_double_sided(),
         );
+    }
+
+    /// Register the draw commands of the edges of the model in the first subpass.
+    ///
+    /// `wireframe_color` is the color of the edges. Nothing is drawn unless
+    /// the output mode draws wireframes.
+    pub fn cmd_draw_wireframe(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        frame_index: usize,
+        model_data: &ModelData,
+        wireframe_color: [f32; 4],
+    ) {
+        if !self.output_mode.draws_wireframe() {
+            return;
+        }
 
-        // Bind opaque without culling pipeline
+        let device = self.context.device();
+        let model = model_data
+            .model
+            .upgrade()
+            .expect("Cannot register draw commands because model was dropped");
+        let model = model.borrow();
+
+        self.bind_static_descriptor_sets(command_buffer);
+
+        // Bind wireframe pipeline
         unsafe {
             device.cmd_bind_pipeline(
                 command_buffer,
                 vk::PipelineBindPoint::GRAPHICS,
-                self.opaque_unculled_pipeline,
+                self.wireframe_pipeline,
             )
         };
-
-        // Draw opaque, double sided primitives
-        register_model_draw_commands(
-            &self.context,
-            self.pipeline_layout,
+        // Draw the edges of all primitives but point clouds
+        self.register_model_draw_commands(
             command_buffer,
+            frame_index,
+            model_data,
             &model,
-            &self.descriptors.dynamic_data_sets[frame_index..=frame_index],
-            &self.descriptors.per_primitive_sets,
-            |p| !p.material().is_transparent() && p.material().is_double_sided(),
+            |p| p.mode() != PrimitiveMode::Points,
+            PrimitiveDraw::Wireframe {
+                color: wireframe_color,
+                vertices: self.wireframe_vertices.as_deref(),
+            },
         );
+    }
 
-        // Bind transparent pipeline
+    /// Register the draw commands of the blended primitives of the model in the OIT subpass.
+    ///
+    /// They are accumulated in any order so `ModelData::blended_draws` is only used
+    /// for its culled list of primitives.
+    pub fn cmd_draw_oit(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        frame_index: usize,
+        model_data: &ModelData,
+    ) {
+        if !self.output_mode.draws_surfaces() {
+            return;
+        }
+
+        let device = self.context.device();
+        let model = model_data
+            .model
+            .upgrade()
+            .expect("Cannot register draw commands because model was dropped");
+        let model = model.borrow();
+
+        self.bind_static_descriptor_sets(command_buffer);
+
+        // Bind OIT pipeline
         unsafe {
             device.cmd_bind_pipeline(
                 command_buffer,
                 vk::PipelineBindPoint::GRAPHICS,
-                self.transparent_pipeline,
+                self.oit_pipeline,
             )
         };
-        // Draw transparent primitives
-        register_model_draw_commands(
+        // Accumulate transparent primitives
+        register_blended_draw_commands(
             &self.context,
             self.pipeline_layout,
             command_buffer,
             &model,
+            model_data.blended_draws(),
             &self.descriptors.dynamic_data_sets[frame_index..=frame_index],
             &self.descriptors.per_primitive_sets,
-            |p| p.material().is_transparent(),
         );
     }
+
+    fn register_model_draw_commands(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        frame_index: usize,
+        model_data: &ModelData,
+        model: &Model,
+        filter: fn(&&Primitive) -> bool,
+        draw: PrimitiveDraw,
+    ) {
+        register_model_draw_commands(
+            &self.context,
+            self.pipeline_layout,
+            command_buffer,
+            model,
+            model_data.node_visibility(),
+            model_data.culling(),
+            &self.descriptors.dynamic_data_sets[frame_index..=frame_index],
+            &self.descriptors.per_primitive_sets,
+            filter,
+            draw,
+        )
+    }
+
+    fn bind_static_descriptor_sets(&self, command_buffer: vk::CommandBuffer) {
+        let device = self.context.device();
+
+        // Bind static data
+        unsafe {
+            device.cmd_bind_descriptor_sets(
+                command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                self.pipeline_layout,
+                STATIC_DATA_SET_INDEX,
+                &[self.descriptors.static_data_set],
+                &[],
+            )
+        };
+
+        // Bind input data
+        unsafe {
+            device.cmd_bind_descriptor_sets(
+                command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                self.pipeline_layout,
+                INPUT_SET_INDEX,
+                &[self.descriptors.input_set],
+                &[],
+            )
+        };
+    }
 }
 
 impl Drop for LightPass {
@@ -349,6 +677,9 @@ impl Drop for LightPass {
             device.destroy_pipeline(self.opaque_pipeline, None);
             device.destroy_pipeline(self.opaque_unculled_pipeline, None);
             device.destroy_pipeline(self.transparent_pipeline, None);
+            device.destroy_pipeline(self.points_pipeline, None);
+            device.destroy_pipeline(self.wireframe_pipeline, None);
+            device.destroy_pipeline(self.oit_pipeline, None);
             device.destroy_pipeline_layout(self.pipeline_layout, None);
         }
     }
@@ -360,6 +691,7 @@ struct DescriptorsResources<'a> {
     model_transform_buffers: &'a [Buffer],
     model_skin_buffers: &'a [Buffer],
     light_buffers: &'a [Buffer],
+    shadow_buffers: &'a [Buffer],
     dummy_texture: &'a VulkanTexture,
     environment: &'a Environment,
     model: &'a Model,
@@ -395,6 +727,7 @@ fn create_descriptors(
     context: &Arc<Context>,
     resources: DescriptorsResources,
     ao_map: &VulkanTexture,
+    shadow_maps: &VulkanTexture,
 ) -> Descriptors {
     let pool = create_descriptor_pool(context.device(), resources);
 
@@ -411,7 +744,7 @@ fn create_descriptors(
         create_per_primitive_descriptor_sets(context, pool, per_primitive_layout, resources);
 
     let input_layout = create_input_descriptor_set_layout(context.device());
-    let input_set = create_input_descriptor_set(context, pool, input_layout, ao_map);
+    let input_set = create_input_descriptor_set(context, pool, input_layout, ao_map, shadow_maps);
 
     Descriptors {
         context: Arc::clone(context),
@@ -431,7 +764,7 @@ fn create_descriptor_pool(
     device: &Device,
     descriptors_resources: DescriptorsResources,
 ) -> vk::DescriptorPool {
-    const GLOBAL_TEXTURES_COUNT: u32 = 4; // irradiance, prefiltered, brdf lut, ao
+    const GLOBAL_TEXTURES_COUNT: u32 = 5; // irradiance, prefiltered, brdf lut, ao, shadow maps
     const STATIC_SETS_COUNT: u32 = 1;
     const INPUT_SETS_COUNT: u32 = 1;
 
@@ -442,7 +775,7 @@ fn create_descriptor_pool(
     let pool_sizes = [
         vk::DescriptorPoolSize {
             ty: vk::DescriptorType::UNIFORM_BUFFER,
-            descriptor_count: descriptor_count * 2,
+            descriptor_count: descriptor_count * 3,
         },
         vk::DescriptorPoolSize {
             ty: vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC,
@@ -488,6 +821,12 @@ fn create_dynamic_data_descriptor_set_layout(device: &Device) -> vk::DescriptorS
             .descriptor_count(1)
             .stage_flags(vk::ShaderStageFlags::VERTEX)
             .build(),
+        vk::DescriptorSetLayoutBinding::builder()
+            .binding(SHADOWS_UBO_BINDING)
+            .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
+            .descriptor_count(1)
+            .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+            .build(),
     ];
 
     let layout_info = vk::DescriptorSetLayoutCreateInfo::builder().bindings(&bindings);
@@ -522,6 +861,7 @@ fn create_dynamic_data_descriptor_sets(
     sets.iter().enumerate().for_each(|(i, set)| {
         let camera_ubo = &resources.camera_buffers[i];
         let light_buffer = &resources.light_buffers[i];
+        let shadow_buffer = &resources.shadow_buffers[i];
         let model_transform_ubo = &resources.model_transform_buffers[i];
         let model_skin_ubo = &resources.model_skin_buffers[i];
 
@@ -537,6 +877,12 @@ fn create_dynamic_data_descriptor_sets(
             .range(vk::WHOLE_SIZE)
             .build()];
 
+        let shadow_buffer_info = [vk::DescriptorBufferInfo::builder()
+            .buffer(shadow_buffer.buffer)
+            .offset(0)
+            .range(vk::WHOLE_SIZE)
+            .build()];
+
         let model_transform_buffer_info = [vk::DescriptorBufferInfo::builder()
             .buffer(model_transform_ubo.buffer)
             .offset(0)
@@ -562,6 +908,12 @@ fn create_dynamic_data_descriptor_sets(
                 .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
                 .buffer_info(&light_buffer_info)
                 .build(),
+            vk::WriteDescriptorSet::builder()
+                .dst_set(*set)
+                .dst_binding(SHADOWS_UBO_BINDING)
+                .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
+                .buffer_info(&shadow_buffer_info)
+                .build(),
             vk::WriteDescriptorSet::builder()
                 .dst_set(*set)
                 .dst_binding(TRANSFORMS_UBO_BINDING)
@@ -832,12 +1184,20 @@ fn create_per_primitive_descriptor_sets(
 }
 
 fn create_input_descriptor_set_layout(device: &Device) -> vk::DescriptorSetLayout {
-    let bindings = [vk::DescriptorSetLayoutBinding::builder()
-        .binding(AO_MAP_SAMPLER_BINDING)
-        .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
-        .descriptor_count(1)
-        .stage_flags(vk::ShaderStageFlags::FRAGMENT)
-        .build()];
+    let bindings = [
+        vk::DescriptorSetLayoutBinding::builder()
+            .binding(AO_MAP_SAMPLER_BINDING)
+            .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+            .descriptor_count(1)
+            .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+            .build(),
+        vk::DescriptorSetLayoutBinding::builder()
+            .binding(SHADOW_MAPS_SAMPLER_BINDING)
+            .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+            .descriptor_count(1)
+            .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+            .build(),
+    ];
 
     let layout_info = vk::DescriptorSetLayoutCreateInfo::builder().bindings(&bindings);
 
@@ -853,6 +1213,7 @@ fn create_input_descriptor_set(
     pool: vk::DescriptorPool,
     layout: vk::DescriptorSetLayout,
     ao_map: &VulkanTexture,
+    shadow_maps: &VulkanTexture,
 ) -> vk::DescriptorSet {
     let layouts = [layout];
     let allocate_info = vk::DescriptorSetAllocateInfo::builder()
@@ -871,13 +1232,27 @@ fn create_input_descriptor_set(
         .sampler(ao_map.sampler.unwrap())
         .build()];
 
-    let descriptor_writes = [vk::WriteDescriptorSet::builder()
-        .dst_set(set)
-        .dst_binding(AO_MAP_SAMPLER_BINDING)
-        .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
-        .image_info(&ao_map_info)
+    let shadow_maps_info = [vk::DescriptorImageInfo::builder()
+        .image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+        .image_view(shadow_maps.view)
+        .sampler(shadow_maps.sampler.unwrap())
         .build()];
 
+    let descriptor_writes = [
+        vk::WriteDescriptorSet::builder()
+            .dst_set(set)
+            .dst_binding(AO_MAP_SAMPLER_BINDING)
+            .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+            .image_info(&ao_map_info)
+            .build(),
+        vk::WriteDescriptorSet::builder()
+            .dst_set(set)
+            .dst_binding(SHADOW_MAPS_SAMPLER_BINDING)
+            .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+            .image_info(&shadow_maps_info)
+            .build(),
+    ];
+
     unsafe {
         context
             .device()
@@ -928,6 +1303,7 @@ fn create_opaque_pipeline(
     context: &Arc<Context>,
     swapchain_properties: SwapchainProperties,
     msaa_samples: vk::SampleCountFlags,
+    topology: vk::PrimitiveTopology,
     enable_face_culling: bool,
     render_pass: vk::RenderPass,
     layout: vk::PipelineLayout,
@@ -936,7 +1312,7 @@ fn create_opaque_pipeline(
     emissive_intensity: f32,
 ) -> vk::Pipeline {
     let (specialization_info, _map_entries, _data) =
-        create_model_frag_shader_specialization(model, output_mode, emissive_intensity);
+        create_model_frag_shader_specialization(model, output_mode, emissive_intensity, false);
 
     let depth_stencil_info = vk::PipelineDepthStencilStateCreateInfo::builder()
         .depth_test_enable(true)
@@ -974,6 +1350,8 @@ fn create_opaque_pipeline(
             layout,
             depth_stencil_info: &depth_stencil_info,
             color_blend_attachments: &color_blend_attachments,
+            topology,
+            polygon_mode: vk::PolygonMode::FILL,
             enable_face_culling,
             parent: None,
         },
@@ -992,7 +1370,7 @@ fn create_transparent_pipeline(
     emissive_intensity: f32,
 ) -> vk::Pipeline {
     let (specialization_info, _map_entries, _data) =
-        create_model_frag_shader_specialization(model, output_mode, emissive_intensity);
+        create_model_frag_shader_specialization(model, output_mode, emissive_intensity, false);
 
     let depth_stencil_info = vk::PipelineDepthStencilStateCreateInfo::builder()
         .depth_test_enable(true)
@@ -1030,16 +1408,213 @@ fn create_transparent_pipeline(
             layout,
             depth_stencil_info: &depth_stencil_info,
             color_blend_attachments: &color_blend_attachments,
+            topology: vk::PrimitiveTopology::TRIANGLE_LIST,
+            polygon_mode: vk::PolygonMode::FILL,
             enable_face_culling: false,
             parent: Some(parent),
         },
     )
 }
 
+/// Create the pipeline accumulating blended primitives in the OIT subpass.
+///
+/// Weighted colors are summed in the accumulation attachment and transparencies
+/// are multiplied in the revealage attachment so the draw order does not matter.
+fn create_oit_pipeline(
+    context: &Arc<Context>,
+    swapchain_properties: SwapchainProperties,
+    msaa_samples: vk::SampleCountFlags,
+    render_pass: vk::RenderPass,
+    layout: vk::PipelineLayout,
+    model: &Model,
+    output_mode: OutputMode,
+    emissive_intensity: f32,
+) -> vk::Pipeline {
+    let (specialization_info, _map_entries, _data) =
+        create_model_frag_shader_specialization(model, output_mode, emissive_intensity, true);
+
+    let depth_stencil_info = vk::PipelineDepthStencilStateCreateInfo::builder()
+        .depth_test_enable(true)
+        .depth_write_enable(false)
+        .depth_compare_op(vk::CompareOp::LESS_OR_EQUAL)
+        .depth_bounds_test_enable(false)
+        .min_depth_bounds(0.0)
+        .max_depth_bounds(1.0)
+        .stencil_test_enable(false)
+        .front(Default::default())
+        .back(Default::default());
+
+    let color_blend_attachments = [
+        // Accumulation
+        vk::PipelineColorBlendAttachmentState::builder()
+            .color_write_mask(vk::ColorComponentFlags::all())
+            .blend_enable(true)
+            .src_color_blend_factor(vk::BlendFactor::ONE)
+            .dst_color_blend_factor(vk::BlendFactor::ONE)
+            .color_blend_op(vk::BlendOp::ADD)
+            .src_alpha_blend_factor(vk::BlendFactor::ONE)
+            .dst_alpha_blend_factor(vk::BlendFactor::ONE)
+            .alpha_blend_op(vk::BlendOp::ADD)
+            .build(),
+        // Revealage
+        vk::PipelineColorBlendAttachmentState::builder()
+            .color_write_mask(vk::ColorComponentFlags::R)
+            .blend_enable(true)
+            .src_color_blend_factor(vk::BlendFactor::ZERO)
+            .dst_color_blend_factor(vk::BlendFactor::ONE_MINUS_SRC_COLOR)
+            .color_blend_op(vk::BlendOp::ADD)
+            .src_alpha_blend_factor(vk::BlendFactor::ZERO)
+            .dst_alpha_blend_factor(vk::BlendFactor::ONE)
+            .alpha_blend_op(vk::BlendOp::ADD)
+            .build(),
+    ];
+
+    create_renderer_pipeline::<ModelVertex>(
+        context,
+        RendererPipelineParameters {
+            vertex_shader_name: "model",
+            fragment_shader_name: "model",
+            vertex_shader_specialization: None,
+            fragment_shader_specialization: Some(&specialization_info),
+            swapchain_properties,
+            msaa_samples,
+            render_pass,
+            subpass: OIT_SUBPASS,
+            layout,
+            depth_stencil_info: &depth_stencil_info,
+            color_blend_attachments: &color_blend_attachments,
+            topology: vk::PrimitiveTopology::TRIANGLE_LIST,
+            polygon_mode: vk::PolygonMode::FILL,
+            enable_face_culling: false,
+            parent: None,
+        },
+    )
+}
+
+/// Create the pipeline drawing the edges of the primitives.
+///
+/// When `barycentric` is true, triangles are filled and the fragment shader only
+/// keeps the fragments close to the edges. Otherwise they are rasterized as lines
+/// which requires the `fillModeNonSolid` feature.
+fn create_wireframe_pipeline(
+    context: &Arc<Context>,
+    swapchain_properties: SwapchainProperties,
+    msaa_samples: vk::SampleCountFlags,
+    render_pass: vk::RenderPass,
+    layout: vk::PipelineLayout,
+    barycentric: bool,
+) -> vk::Pipeline {
+    let map_entries = [vk::SpecializationMapEntry {
+        constant_id: 0,
+        offset: 0,
+        size: size_of::<u32>(),
+    }];
+    let data = [barycentric as u32];
+    let specialization_info = vk::SpecializationInfo::builder()
+        .map_entries(&map_entries)
+        .data(unsafe { any_as_u8_slice(&data) })
+        .build();
+
+    // Edges are drawn over the surfaces without hiding each other
+    let depth_stencil_info = vk::PipelineDepthStencilStateCreateInfo::builder()
+        .depth_test_enable(true)
+        .depth_write_enable(false)
+        .depth_compare_op(vk::CompareOp::LESS_OR_EQUAL)
+        .depth_bounds_test_enable(false)
+        .min_depth_bounds(0.0)
+        .max_depth_bounds(1.0)
+        .stencil_test_enable(false)
+        .front(Default::default())
+        .back(Default::default());
+
+    let color_blend_attachments = [vk::PipelineColorBlendAttachmentState::builder()
+        .color_write_mask(vk::ColorComponentFlags::all())
+        .blend_enable(true)
+        .src_color_blend_factor(vk::BlendFactor::SRC_ALPHA)
+        .dst_color_blend_factor(vk::BlendFactor::ONE_MINUS_SRC_ALPHA)
+        .color_blend_op(vk::BlendOp::ADD)
+        .src_alpha_blend_factor(vk::BlendFactor::ONE)
+        .dst_alpha_blend_factor(vk::BlendFactor::ZERO)
+        .alpha_blend_op(vk::BlendOp::ADD)
+        .build()];
+
+    let polygon_mode = if barycentric {
+        vk::PolygonMode::FILL
+    } else {
+        vk::PolygonMode::LINE
+    };
+
+    create_renderer_pipeline::<ModelVertex>(
+        context,
+        RendererPipelineParameters {
+            vertex_shader_name: "wireframe",
+            fragment_shader_name: "wireframe",
+            vertex_shader_specialization: None,
+            fragment_shader_specialization: Some(&specialization_info),
+            swapchain_properties,
+            msaa_samples,
+            render_pass,
+            subpass: 0,
+            layout,
+            depth_stencil_info: &depth_stencil_info,
+            color_blend_attachments: &color_blend_attachments,
+            topology: vk::PrimitiveTopology::TRIANGLE_LIST,
+            polygon_mode,
+            enable_face_culling: false,
+            parent: None,
+        },
+    )
+}
+
+/// Create the unindexed vertex buffers used by the barycentric wireframe fallback.
+///
+/// Each triangle gets its own three vertices so the vertex shader can assign them
+/// distinct barycentric coordinates. Point clouds have no buffer.
+fn create_wireframe_vertices(context: &Arc<Context>, scene: &Scene) -> Vec<Option<Buffer>> {
+    let geometry = scene.geometry();
+    let mut buffers = (0..scene.primitive_count())
+        .map(|_| None)
+        .collect::<Vec<_>>();
+
+    for primitive in geometry.meshes().iter().flat_map(|m| m.primitives()) {
+        if primitive.mode() == PrimitiveMode::Points {
+            continue;
+        }
+
+        let vertices = primitive.vertices(geometry);
+        let triangles = match primitive.indices(geometry) {
+            Some(indices) => {
+                let mut triangles = Vec::with_capacity(indices.len());
+                for triangle in indices.chunks_exact(3) {
+                    // Triangles with out of range indices are reported by the validation
+                    let vertex = |i: usize| vertices.get(triangle[i] as usize).copied();
+                    if let (Some(v0), Some(v1), Some(v2)) = (vertex(0), vertex(1), vertex(2)) {
+                        triangles.extend_from_slice(&[v0, v1, v2]);
+                    }
+                }
+                triangles
+            }
+            None => vertices.to_vec(),
+        };
+        if triangles.is_empty() {
+            continue;
+        }
+
+        buffers[primitive.index()] = Some(create_device_local_buffer_with_data::<u8, _>(
+            context,
+            vk::BufferUsageFlags::VERTEX_BUFFER,
+            &triangles,
+        ));
+    }
+
+    buffers
+}
+
 fn create_model_frag_shader_specialization(
     model: &Model,
     output_mode: OutputMode,
     emissive_intensity: f32,
+    oit: bool,
 ) -> (
     vk::SpecializationInfo,
     Vec<vk::SpecializationMapEntry>,
@@ -1061,6 +1636,16 @@ fn create_model_frag_shader_specialization(
             offset: (2 * size_of::<u32>()) as _,
             size: size_of::<f32>(),
         },
+        vk::SpecializationMapEntry {
+            constant_id: 3,
+            offset: (2 * size_of::<u32>() + size_of::<f32>()) as _,
+            size: size_of::<u32>(),
+        },
+        vk::SpecializationMapEntry {
+            constant_id: 4,
+            offset: (3 * size_of::<u32>() + size_of::<f32>()) as _,
+            size: size_of::<u32>(),
+        },
     ];
 
     let light_count = model
@@ -1070,9 +1655,11 @@ fn create_model_frag_shader_specialization(
         .filter(|n| n.light_index().is_some())
         .count() as u32;
 
-    let data = [light_count, output_mode as _];
+    let data = [light_count, output_mode.shader_value()];
     let mut data = Vec::from(unsafe { any_as_u8_slice(&data) });
     data.extend_from_slice(unsafe { any_as_u8_slice(&[emissive_intensity]) });
+    let (_, shadow_layer_count) = shadows::shadow_layers(model);
+    data.extend_from_slice(unsafe { any_as_u8_slice(&[oit as u32, shadow_layer_count]) });
 
     let specialization_info = vk::SpecializationInfo::builder()
         .map_entries(&map_entries)
@@ -1082,119 +1669,243 @@ fn create_model_frag_shader_specialization(
     (specialization_info, map_entries, data)
 }
 
+/// How the primitives are drawn by `register_model_draw_commands`.
+#[derive(Copy, Clone)]
+enum PrimitiveDraw<'a> {
+    /// Draw the primitives with their material.
+    Shaded,
+    /// Draw the edges of the primitives with `color`.
+    ///
+    /// `vertices` are the unindexed vertices of the primitives when
+    /// the edges are found from barycentric coordinates.
+    Wireframe {
+        color: [f32; 4],
+        vertices: Option<&'a [Option<Buffer>]>,
+    },
+}
+
 fn register_model_draw_commands<F>(
     context: &Context,
     pipeline_layout: vk::PipelineLayout,
     command_buffer: vk::CommandBuffer,
     model: &Model,
+    node_visibility: &[bool],
+    culling: &Culling,
     dynamic_descriptors: &[vk::DescriptorSet],
     per_primitive_descriptors: &[vk::DescriptorSet],
     primitive_filter: F,
+    draw: PrimitiveDraw,
 ) where
     F: FnMut(&&Primitive) -> bool + Copy,
 {
-    let device = context.device();
-    let model_transform_ubo_offset = context.get_ubo_alignment::<Matrix4<f32>>();
-    let model_skin_ubo_offset = context.get_ubo_alignment::<JointsBuffer>();
-
-    for (index, node) in model
+    let mesh_nodes = model
         .nodes()
         .nodes()
         .iter()
-        .filter(|n| n.mesh_index().is_some())
         .enumerate()
-    {
+        .filter(|(_, n)| n.mesh_index().is_some());
+
+    // The transform buffer holds the transforms of all mesh nodes, hidden or not
+    for (index, (node_index, node)) in mesh_nodes.enumerate() {
+        if !node_visibility.get(node_index).copied().unwrap_or(true)
+            || culling.is_node_culled(node_index)
+        {
+            continue;
+        }
+
         let mesh = model.mesh(node.mesh_index().unwrap());
-        let skin_index = node.skin_index().unwrap_or(0);
 
-        // Bind descriptor sets
-        unsafe {
-            device.cmd_bind_descriptor_sets(
+        bind_node_descriptor_sets(
+            context,
+            pipeline_layout,
+            command_buffer,
+            dynamic_descriptors,
+            index,
+            node.skin_index().unwrap_or(0),
+        );
+
+        let primitives = mesh
+            .primitives()
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| !culling.is_primitive_culled(node_index, *index))
+            .map(|(_, primitive)| primitive)
+            .filter(primitive_filter);
+
+        for primitive in primitives {
+            register_primitive_draw_commands(
+                context,
+                pipeline_layout,
                 command_buffer,
-                vk::PipelineBindPoint::GRAPHICS,
+                primitive,
+                per_primitive_descriptors,
+                draw,
+            );
+        }
+    }
+}
+
+/// Register the draw commands of blended primitives in the order of `draws`.
+fn register_blended_draw_commands(
+    context: &Context,
+    pipeline_layout: vk::PipelineLayout,
+    command_buffer: vk::CommandBuffer,
+    model: &Model,
+    draws: &[BlendedDraw],
+    dynamic_descriptors: &[vk::DescriptorSet],
+    per_primitive_descriptors: &[vk::DescriptorSet],
+) {
+    let mut bound_node = None;
+    for draw in draws {
+        let node = &model.nodes().nodes()[draw.node_index];
+        let mesh = model.mesh(node.mesh_index().unwrap());
+
+        // Consecutive primitives of the same node share its descriptor sets
+        if bound_node != Some(draw.node_index) {
+            bind_node_descriptor_sets(
+                context,
                 pipeline_layout,
-                DYNAMIC_DATA_SET_INDEX,
-                &dynamic_descriptors,
-                &[
-                    model_transform_ubo_offset * index as u32,
-                    model_skin_ubo_offset * skin_index as u32,
-                ],
-            )
-        };
+                command_buffer,
+                dynamic_descriptors,
+                draw.transform_index,
+                node.skin_index().unwrap_or(0),
+            );
+            bound_node = Some(draw.node_index);
+        }
 
-        for primitive in mesh.primitives().iter().filter(primitive_filter) {
-            let primitive_index = primitive.index();
+        register_primitive_draw_commands(
+            context,
+            pipeline_layout,
+            command_buffer,
+            &mesh.primitives()[draw.primitive_index],
+            per_primitive_descriptors,
+            PrimitiveDraw::Shaded,
+        );
+    }
+}
 
-            // Bind descriptor sets
-            unsafe {
-                device.cmd_bind_descriptor_sets(
-                    command_buffer,
-                    vk::PipelineBindPoint::GRAPHICS,
-                    pipeline_layout,
-                    PER_PRIMITIVE_DATA_SET_INDEX,
-                    &per_primitive_descriptors[primitive_index..=primitive_index],
-                    &[],
-                )
-            };
+/// Bind the transform and skin of a mesh node.
+///
+/// `transform_index` is the index of the node among the nodes with a mesh.
+fn bind_node_descriptor_sets(
+    context: &Context,
+    pipeline_layout: vk::PipelineLayout,
+    command_buffer: vk::CommandBuffer,
+    dynamic_descriptors: &[vk::DescriptorSet],
+    transform_index: usize,
+    skin_index: usize,
+) {
+    let model_transform_ubo_offset = context.get_ubo_alignment::<Matrix4<f32>>();
+    let model_skin_ubo_offset = context.get_ubo_alignment::<JointsBuffer>();
 
-            unsafe {
-                device.cmd_bind_vertex_buffers(
-                    command_buffer,
-                    0,
-                    &[primitive.vertices().buffer().buffer],
-                    &[primitive.vertices().offset()],
-                );
+    unsafe {
+        context.device().cmd_bind_descriptor_sets(
+            command_buffer,
+            vk::PipelineBindPoint::GRAPHICS,
+            pipeline_layout,
+            DYNAMIC_DATA_SET_INDEX,
+            &dynamic_descriptors,
+            &[
+                model_transform_ubo_offset * transform_index as u32,
+                model_skin_ubo_offset * skin_index as u32,
+            ],
+        )
+    };
+}
+
+fn register_primitive_draw_commands(
+    context: &Context,
+    pipeline_layout: vk::PipelineLayout,
+    command_buffer: vk::CommandBuffer,
+    primitive: &Primitive,
+    per_primitive_descriptors: &[vk::DescriptorSet],
+    draw: PrimitiveDraw,
+) {
+    let device = context.device();
+    let primitive_index = primitive.index();
+
+    // Bind descriptor sets
+    unsafe {
+        device.cmd_bind_descriptor_sets(
+            command_buffer,
+            vk::PipelineBindPoint::GRAPHICS,
+            pipeline_layout,
+            PER_PRIMITIVE_DATA_SET_INDEX,
+            &per_primitive_descriptors[primitive_index..=primitive_index],
+            &[],
+        )
+    };
+
+    // Push constants
+    unsafe {
+        let constants = match draw {
+            PrimitiveDraw::Shaded => {
+                let material: MaterialUniform = primitive.material().into();
+                any_as_u8_slice(&material).to_vec()
             }
+            PrimitiveDraw::Wireframe { color, .. } => any_as_u8_slice(&color).to_vec(),
+        };
+        device.cmd_push_constants(
+            command_buffer,
+            pipeline_layout,
+            vk::ShaderStageFlags::FRAGMENT,
+            0,
+            &constants,
+        );
+    };
 
-            if let Some(index_buffer) = primitive.indices() {
-                unsafe {
-                    device.cmd_bind_index_buffer(
-                        command_buffer,
-                        index_buffer.buffer().buffer,
-                        index_buffer.offset(),
-                        index_buffer.index_type(),
-                    );
-                }
+    if let PrimitiveDraw::Wireframe {
+        vertices: Some(vertices),
+        ..
+    } = draw
+    {
+        // Draw unindexed triangles
+        if let Some(buffer) = &vertices[primitive_index] {
+            let vertex_count = buffer.size / size_of::<ModelVertex>() as vk::DeviceSize;
+            unsafe {
+                device.cmd_bind_vertex_buffers(command_buffer, 0, &[buffer.buffer], &[0]);
+                device.cmd_draw(command_buffer, vertex_count as _, 1, 0, 0);
             }
-            // Push material constants
+        }
+        return;
+    }
+
+    unsafe {
+        device.cmd_bind_vertex_buffers(
+            command_buffer,
+            0,
+            &[primitive.vertices().buffer().buffer],
+            &[primitive.vertices().offset()],
+        );
+    }
+
+    if let Some(index_buffer) = primitive.indices() {
+        unsafe {
+            device.cmd_bind_index_buffer(
+                command_buffer,
+                index_buffer.buffer().buffer,
+                index_buffer.offset(),
+                index_buffer.index_type(),
+            );
+        }
+    }
+    // Draw geometry
+    match primitive.indices() {
+        Some(index_buffer) => {
             unsafe {
-                let material: MaterialUniform = primitive.material().into();
-                let material_contants = any_as_u8_slice(&material);
-                device.cmd_push_constants(
+                device.cmd_draw_indexed(command_buffer, index_buffer.element_count(), 1, 0, 0, 0)
+            };
+        }
+        None => {
+            unsafe {
+                device.cmd_draw(
                     command_buffer,
-                    pipeline_layout,
-                    vk::ShaderStageFlags::FRAGMENT,
+                    primitive.vertices().element_count(),
+                    1,
+                    0,
                     0,
-                    &material_contants,
-                );
+                )
             };
-
-            // Draw geometry
-            match primitive.indices() {
-                Some(index_buffer) => {
-                    unsafe {
-                        device.cmd_draw_indexed(
-                            command_buffer,
-                            index_buffer.element_count(),
-                            1,
-                            0,
-                            0,
-                            0,
-                        )
-                    };
-                }
-                None => {
-                    unsafe {
-                        device.cmd_draw(
-                            command_buffer,
-                            primitive.vertices().element_count(),
-                            1,
-                            0,
-                            0,
-                        )
-                    };
-                }
-            }
         }
     }
 }
@@ -3,13 +3,23 @@ use vulkan::ash::{version::DeviceV1_0, vk, Device};
 use vulkan::{Context, Image, ImageParameters, Texture};
 
 const COLOR_FORMAT: vk::Format = vk::Format::R32G32B32A32_SFLOAT;
+const ACCUMULATION_FORMAT: vk::Format = vk::Format::R16G16B16A16_SFLOAT;
+const REVEALAGE_FORMAT: vk::Format = vk::Format::R16_SFLOAT;
+
+/// Index of the subpass accumulating blended primitives for weighted blended
+/// order-independent transparency. Everything else is drawn in the first subpass.
+pub const OIT_SUBPASS: u32 = 1;
 
 pub struct RenderPass {
     context: Arc<Context>,
     extent: vk::Extent2D,
     color_attachment: Texture,
     depth_attachment: Texture,
+    accumulation_attachment: Texture,
+    revealage_attachment: Texture,
     color_resolve_attachment: Option<Texture>,
+    accumulation_resolve_attachment: Option<Texture>,
+    revealage_resolve_attachment: Option<Texture>,
     render_pass: vk::RenderPass,
 }
 
@@ -22,10 +32,17 @@ impl RenderPass {
     ) -> Self {
         let color_attachment = create_color_texture(&context, COLOR_FORMAT, extent, msaa_samples);
         let depth_attachment = create_depth_texture(&context, depth_format, extent, msaa_samples);
-        let color_resolve_attachment = match msaa_samples {
+        let accumulation_attachment =
+            create_color_texture(&context, ACCUMULATION_FORMAT, extent, msaa_samples);
+        let revealage_attachment =
+            create_color_texture(&context, REVEALAGE_FORMAT, extent, msaa_samples);
+        let create_resolve_texture = |format| match msaa_samples {
             vk::SampleCountFlags::TYPE_1 => None,
-            _ => Some(create_color_resolve_texture(&context, COLOR_FORMAT, extent)),
+            _ => Some(create_color_resolve_texture(&context, format, extent)),
         };
+        let color_resolve_attachment = create_resolve_texture(COLOR_FORMAT);
+        let accumulation_resolve_attachment = create_resolve_texture(ACCUMULATION_FORMAT);
+        let revealage_resolve_attachment = create_resolve_texture(REVEALAGE_FORMAT);
         let render_pass = create_render_pass(context.device(), depth_format, msaa_samples);
 
         Self {
@@ -33,7 +50,11 @@ impl RenderPass {
             extent,
             color_attachment,
             depth_attachment,
+            accumulation_attachment,
+            revealage_attachment,
             color_resolve_attachment,
+            accumulation_resolve_attachment,
+            revealage_resolve_attachment,
             render_pass,
         }
     }
@@ -46,6 +67,20 @@ impl RenderPass {
             .map_or(&self.color_attachment, |a| &a)
     }
 
+    /// Get the sum of the weighted premultiplied colors and alphas of the blended fragments.
+    pub fn get_accumulation_attachment(&self) -> &Texture {
+        self.accumulation_resolve_attachment
+            .as_ref()
+            .map_or(&self.accumulation_attachment, |a| &a)
+    }
+
+    /// Get the product of the transparencies of the blended fragments.
+    pub fn get_revealage_attachment(&self) -> &Texture {
+        self.revealage_resolve_attachment
+            .as_ref()
+            .map_or(&self.revealage_attachment, |a| &a)
+    }
+
     pub fn get_render_pass(&self) -> vk::RenderPass {
         self.render_pass
     }
@@ -53,14 +88,22 @@ impl RenderPass {
 
 impl RenderPass {
     pub fn create_framebuffer(&self) -> vk::Framebuffer {
-        let attachments = {
-            let color = self.color_attachment.view;
-            let depth = self.depth_attachment.view;
-            match self.color_resolve_attachment.as_ref() {
-                Some(color_resolve) => vec![color, depth, color_resolve.view],
-                _ => vec![color, depth],
-            }
-        };
+        let mut attachments = vec![
+            self.color_attachment.view,
+            self.depth_attachment.view,
+            self.accumulation_attachment.view,
+            self.revealage_attachment.view,
+        ];
+        let resolve_attachments = [
+            &self.color_resolve_attachment,
+            &self.accumulation_resolve_attachment,
+            &self.revealage_resolve_attachment,
+        ];
+        attachments.extend(
+            resolve_attachments
+                .iter()
+                .filter_map(|a| a.as_ref().map(|a| a.view)),
+        );
 
         let framebuffer_info = vk::FramebufferCreateInfo::builder()
             .render_pass(self.render_pass)
@@ -104,16 +147,33 @@ fn create_render_pass(
         vk::AttachmentStoreOp::DONT_CARE
     };
 
-    let mut attachment_descs = vec![
-        // Color attachment
+    let color_attachment_desc = |format| {
         vk::AttachmentDescription::builder()
-            .format(COLOR_FORMAT)
+            .format(format)
             .samples(msaa_samples)
             .load_op(vk::AttachmentLoadOp::CLEAR)
             .store_op(color_store_op)
             .initial_layout(vk::ImageLayout::UNDEFINED)
             .final_layout(color_final_layout)
-            .build(),
+            .build()
+    };
+
+    let resolve_attachment_desc = |format| {
+        vk::AttachmentDescription::builder()
+            .format(format)
+            .samples(vk::SampleCountFlags::TYPE_1)
+            .load_op(vk::AttachmentLoadOp::DONT_CARE)
+            .store_op(vk::AttachmentStoreOp::STORE)
+            .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
+            .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
+            .initial_layout(vk::ImageLayout::UNDEFINED)
+            .final_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+            .build()
+    };
+
+    let mut attachment_descs = vec![
+        // Color attachment
+        color_attachment_desc(COLOR_FORMAT),
         // Depth attachment
         vk::AttachmentDescription::builder()
             .format(depth_format)
@@ -125,36 +185,34 @@ fn create_render_pass(
             .initial_layout(vk::ImageLayout::UNDEFINED)
             .final_layout(vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL)
             .build(),
+        // Accumulation attachment
+        color_attachment_desc(ACCUMULATION_FORMAT),
+        // Revealage attachment
+        color_attachment_desc(REVEALAGE_FORMAT),
     ];
     if msaa_samples != vk::SampleCountFlags::TYPE_1 {
-        // Resolve attachment
-        attachment_descs.push(
-            vk::AttachmentDescription::builder()
-                .format(COLOR_FORMAT)
-                .samples(vk::SampleCountFlags::TYPE_1)
-                .load_op(vk::AttachmentLoadOp::DONT_CARE)
-                .store_op(vk::AttachmentStoreOp::STORE)
-                .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
-                .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
-                .initial_layout(vk::ImageLayout::UNDEFINED)
-                .final_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
-                .build(),
-        );
+        // Resolve attachments
+        attachment_descs.push(resolve_attachment_desc(COLOR_FORMAT));
+        attachment_descs.push(resolve_attachment_desc(ACCUMULATION_FORMAT));
+        attachment_descs.push(resolve_attachment_desc(REVEALAGE_FORMAT));
     }
 
-    let render_color_attachment_refs = [vk::AttachmentReference::builder()
-        .attachment(0)
-        .layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
-        .build()];
+    let color_attachment_ref = |attachment| {
+        vk::AttachmentReference::builder()
+            .attachment(attachment)
+            .layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
+            .build()
+    };
+
+    let render_color_attachment_refs = [color_attachment_ref(0)];
+    let oit_color_attachment_refs = [color_attachment_ref(2), color_attachment_ref(3)];
 
     let depth_attachment_ref = vk::AttachmentReference::builder()
         .attachment(1)
         .layout(vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL);
 
-    let resolve_attachment_refs = [vk::AttachmentReference::builder()
-        .attachment(2)
-        .layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
-        .build()];
+    let resolve_attachment_refs = [color_attachment_ref(4)];
+    let oit_resolve_attachment_refs = [color_attachment_ref(5), color_attachment_ref(6)];
 
     // Subpasses
     let subpasses = {
@@ -162,10 +220,15 @@ fn create_render_pass(
             .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
             .color_attachments(&render_color_attachment_refs)
             .depth_stencil_attachment(&depth_attachment_ref);
+        let mut oit_subpass_desc = vk::SubpassDescription::builder()
+            .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
+            .color_attachments(&oit_color_attachment_refs)
+            .depth_stencil_attachment(&depth_attachment_ref);
         if msaa_samples != vk::SampleCountFlags::TYPE_1 {
-            subpass_desc = subpass_desc.resolve_attachments(&resolve_attachment_refs)
+            subpass_desc = subpass_desc.resolve_attachments(&resolve_attachment_refs);
+            oit_subpass_desc = oit_subpass_desc.resolve_attachments(&oit_resolve_attachment_refs);
         }
-        [subpass_desc.build()]
+        [subpass_desc.build(), oit_subpass_desc.build()]
     };
 
     // Dependencies
@@ -180,8 +243,39 @@ fn create_render_pass(
                 vk::AccessFlags::COLOR_ATTACHMENT_READ | vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
             )
             .build(),
+        vk::SubpassDependency::builder()
+            .src_subpass(vk::SUBPASS_EXTERNAL)
+            .dst_subpass(OIT_SUBPASS)
+            .src_stage_mask(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)
+            .src_access_mask(vk::AccessFlags::empty())
+            .dst_stage_mask(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)
+            .dst_access_mask(
+                vk::AccessFlags::COLOR_ATTACHMENT_READ | vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+            )
+            .build(),
+        // Blended primitives are tested against the depth of the opaque ones
         vk::SubpassDependency::builder()
             .src_subpass(0)
+            .dst_subpass(OIT_SUBPASS)
+            .src_stage_mask(vk::PipelineStageFlags::LATE_FRAGMENT_TESTS)
+            .src_access_mask(vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE)
+            .dst_stage_mask(
+                vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS
+                    | vk::PipelineStageFlags::LATE_FRAGMENT_TESTS,
+            )
+            .dst_access_mask(vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_READ)
+            .dependency_flags(vk::DependencyFlags::BY_REGION)
+            .build(),
+        vk::SubpassDependency::builder()
+            .src_subpass(0)
+            .dst_subpass(vk::SUBPASS_EXTERNAL)
+            .src_stage_mask(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)
+            .src_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE)
+            .dst_stage_mask(vk::PipelineStageFlags::FRAGMENT_SHADER)
+            .dst_access_mask(vk::AccessFlags::SHADER_READ)
+            .build(),
+        vk::SubpassDependency::builder()
+            .src_subpass(OIT_SUBPASS)
             .dst_subpass(vk::SUBPASS_EXTERNAL)
             .src_stage_mask(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)
             .src_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE)
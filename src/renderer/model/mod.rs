@@ -1,16 +1,31 @@
 pub mod gbufferpass;
 pub mod lightpass;
+pub mod shadowpass;
 
+mod culling;
+mod shadows;
+mod sorting;
 mod uniform;
 
+use crate::camera::CameraUBO;
+pub use culling::{Culling, CullingStatistics};
 use gbufferpass::GBufferPass;
 use lightpass::LightPass;
-use math::cgmath::Matrix4;
+use math::{
+    cgmath::{Matrix4, SquareMatrix},
+    Frustum, AABB,
+};
 use model::Model;
 use model::MAX_JOINTS_PER_MESH;
+use shadowpass::ShadowPass;
+pub use shadows::ShadowSettings;
+use sorting::BlendedDraw;
+pub use sorting::{merge_blended_draws, SortDistanceOverrides};
 use std::cell::RefCell;
-use std::rc::Weak;
+use std::mem::size_of;
+use std::rc::{Rc, Weak};
 use std::sync::Arc;
+pub use uniform::LightUniform;
 use uniform::*;
 use vulkan::{mem_copy, mem_copy_aligned, Buffer, Context, SwapchainProperties};
 
@@ -23,12 +38,36 @@ pub struct ModelData {
     skin_ubos: Vec<Buffer>,
     skin_matrices: Vec<Vec<JointsBuffer>>,
     light_buffers: Vec<Buffer>,
+    /// Visibility of each node of the model, indexed like the nodes of the model.
+    node_visibility: Vec<bool>,
+    culling: Culling,
+    sort_distance_overrides: SortDistanceOverrides,
+    /// Blended primitives to draw this frame, from the furthest to the closest.
+    blended_draws: Vec<BlendedDraw>,
+    shadow_buffers: Vec<Buffer>,
+    /// First shadow map layer of each light, indexed like the lights of the model.
+    /// Lights without layers don't cast shadows.
+    shadow_layers: Vec<Option<u32>>,
+    shadow_layer_count: u32,
+    /// Index of the node of each light.
+    light_nodes: Vec<usize>,
+    /// Whether the light of each node casts shadows, indexed like the nodes of the model.
+    /// Lights of missing nodes cast shadows.
+    shadow_casting: Vec<bool>,
+    /// Light space matrix of each shadow map layer this frame.
+    shadow_view_projs: Vec<Matrix4<f32>>,
+    /// Shadow map layers to render this frame.
+    shadow_draws: Vec<u32>,
+    /// World space bounds of the model in its current pose.
+    scene_aabb: Option<AABB<f32>>,
 }
 
 pub struct ModelRenderer {
     pub data: ModelData,
+    pub shadow_pass: ShadowPass,
     pub gbuffer_pass: GBufferPass,
     pub light_pass: LightPass,
+    pub visible: bool,
 }
 
 impl ModelData {
@@ -48,6 +87,19 @@ impl ModelData {
             create_skin_ubos(&context, &model_rc.borrow(), swapchain_props.image_count);
         let light_buffers =
             create_lights_ubos(&context, &model_rc.borrow(), swapchain_props.image_count);
+        let node_visibility = vec![true; model_rc.borrow().nodes().nodes().len()];
+        let (shadow_layers, shadow_layer_count) = shadows::shadow_layers(&model_rc.borrow());
+        let light_nodes = model_rc
+            .borrow()
+            .nodes()
+            .nodes()
+            .iter()
+            .enumerate()
+            .filter(|(_, n)| n.light_index().is_some())
+            .map(|(index, _)| index)
+            .collect();
+        let shadow_buffers =
+            create_shadow_ubos(&context, shadow_layer_count, swapchain_props.image_count);
 
         Self {
             context,
@@ -56,9 +108,107 @@ impl ModelData {
             skin_ubos,
             skin_matrices,
             light_buffers,
+            node_visibility,
+            culling: Culling::default(),
+            sort_distance_overrides: SortDistanceOverrides::new(),
+            blended_draws: Vec::new(),
+            shadow_buffers,
+            shadow_layers,
+            shadow_layer_count,
+            light_nodes,
+            shadow_casting: Vec::new(),
+            shadow_view_projs: Vec::new(),
+            shadow_draws: Vec::new(),
+            scene_aabb: None,
         }
     }
 
+    /// Get the rendered model or `None` if it was dropped.
+    pub fn model(&self) -> Option<Rc<RefCell<Model>>> {
+        self.model.upgrade()
+    }
+
+    pub fn node_visibility(&self) -> &[bool] {
+        &self.node_visibility
+    }
+
+    /// Set which nodes are rendered.
+    ///
+    /// Missing nodes are visible.
+    pub fn set_node_visibility(&mut self, node_visibility: Vec<bool>) {
+        self.node_visibility = node_visibility;
+    }
+
+    pub fn culling(&self) -> &Culling {
+        &self.culling
+    }
+
+    pub fn blended_draws(&self) -> &[BlendedDraw] {
+        &self.blended_draws
+    }
+
+    pub fn shadow_layer_count(&self) -> u32 {
+        self.shadow_layer_count
+    }
+
+    pub fn shadow_draws(&self) -> &[u32] {
+        &self.shadow_draws
+    }
+
+    /// Get the light space matrix of the shadow map layer at `layer`.
+    pub fn shadow_view_proj(&self, layer: u32) -> Matrix4<f32> {
+        self.shadow_view_projs[layer as usize]
+    }
+
+    /// Set which lights cast shadows.
+    ///
+    /// `shadow_casting` is indexed like the nodes of the model.
+    /// Lights of missing nodes cast shadows.
+    pub fn set_shadow_casting(&mut self, shadow_casting: Vec<bool>) {
+        self.shadow_casting = shadow_casting;
+    }
+
+    fn casts_shadow(&self, light_index: usize) -> bool {
+        self.shadow_casting
+            .get(self.light_nodes[light_index])
+            .copied()
+            .unwrap_or(true)
+    }
+
+    /// Set the distances used to sort blended primitives instead of their computed depth.
+    pub fn set_sort_distance_overrides(&mut self, overrides: SortDistanceOverrides) {
+        self.sort_distance_overrides = overrides;
+    }
+
+    /// Cull the nodes and primitives of the model outside of `frustum` and
+    /// sort its blended primitives from back to front.
+    ///
+    /// `view` is the view matrix of the camera.
+    pub fn update_draws(&mut self, frustum: &Frustum<f32>, view: Matrix4<f32>) {
+        let model = &self
+            .model
+            .upgrade()
+            .expect("Cannot update draws because model was dropped");
+        let model = model.borrow();
+
+        let bounds = model.scene().world_bounds();
+        let aabbs = bounds
+            .iter()
+            .flatten()
+            .map(|bounds| bounds.aabb)
+            .collect::<Vec<_>>();
+        self.scene_aabb = AABB::union(&aabbs);
+        self.culling = Culling::compute(&bounds, &self.node_visibility, frustum);
+        self.blended_draws = sorting::sort_blended_primitives(
+            &model,
+            &bounds,
+            &self.node_visibility,
+            &self.culling,
+            view,
+            &self.sort_distance_overrides,
+        );
+    }
+
     pub fn update_buffers(&mut self, frame_index: usize) {
         let model = &self
             .model
@@ -107,14 +257,15 @@ impl ModelData {
 
         // Update light buffers
         {
-            let uniforms = model
-                .nodes()
-                .nodes()
-                .iter()
-                .filter(|n| n.light_index().is_some())
-                .map(|n| (n.transform(), n.light_index().unwrap()))
-                .map(|(t, i)| (t, model.lights()[i]).into())
-                .collect::<Vec<LightUniform>>();
+            let uniforms = light_uniforms(&model)
+                .into_iter()
+                .enumerate()
+                .map(|(index, light)| {
+                    let shadow_layer =
+                        self.shadow_layers[index].filter(|_| self.casts_shadow(index));
+                    light.with_shadow_layer(shadow_layer)
+                })
+                .collect::<Vec<_>>();
 
             if !uniforms.is_empty() {
                 let buffer = &mut self.light_buffers[frame_index];
@@ -123,4 +274,65 @@ impl ModelData {
             }
         }
     }
+
+    /// Compute the light space matrices of the shadow maps of the lights
+    /// seen from `camera` and list the layers to render.
+    ///
+    /// It must be called after `update_draws` which computes the bounds of the model.
+    pub fn update_shadows(
+        &mut self,
+        frame_index: usize,
+        camera: &CameraUBO,
+        settings: ShadowSettings,
+    ) {
+        let model = &self
+            .model
+            .upgrade()
+            .expect("Cannot update shadows because model was dropped");
+        let model = model.borrow();
+
+        let splits = shadows::cascade_splits(camera.z_near(), camera.z_far());
+        self.shadow_view_projs = vec![Matrix4::identity(); self.shadow_layer_count as usize];
+        self.shadow_draws.clear();
+        for (index, light) in light_uniforms(&model).iter().enumerate() {
+            let first_layer = match self.shadow_layers[index] {
+                Some(layer) if self.casts_shadow(index) => layer as usize,
+                _ => continue,
+            };
+            let matrices = shadows::light_view_projs(
+                light,
+                camera,
+                &splits,
+                self.scene_aabb,
+                settings.resolution,
+            );
+            for (offset, matrix) in matrices.into_iter().enumerate() {
+                self.shadow_view_projs[first_layer + offset] = matrix;
+                self.shadow_draws.push((first_layer + offset) as u32);
+            }
+        }
+
+        let uniform = ShadowUniform::new(splits, settings.bias);
+        let buffer = &mut self.shadow_buffers[frame_index];
+        unsafe {
+            let data_ptr = buffer.map_memory();
+            mem_copy(data_ptr, &[uniform]);
+            let matrices_ptr = (data_ptr as *mut u8).add(size_of::<ShadowUniform>());
+            mem_copy(matrices_ptr as _, &self.shadow_view_projs);
+        }
+    }
+}
+
+/// Get the uniforms of the lights of `model` in their current pose.
+///
+/// The lights are ordered like the nodes they are attached to.
+fn light_uniforms(model: &Model) -> Vec<LightUniform> {
+    model
+        .nodes()
+        .nodes()
+        .iter()
+        .filter(|n| n.light_index().is_some())
+        .map(|n| (n.transform(), n.light_index().unwrap()))
+        .map(|(t, i)| (t, model.lights()[i]).into())
+        .collect()
 }
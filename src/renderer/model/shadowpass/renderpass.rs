@@ -0,0 +1,251 @@
+use std::sync::Arc;
+use vulkan::ash::{version::DeviceV1_0, vk, Device};
+use vulkan::{Context, Image, ImageParameters, Texture};
+
+const DEPTH_FORMAT: vk::Format = vk::Format::D32_SFLOAT;
+
+/// Depth only render pass rendering the layers of a shadow map array.
+pub struct RenderPass {
+    context: Arc<Context>,
+    resolution: u32,
+    shadow_maps: Texture,
+    layer_views: Vec<vk::ImageView>,
+    framebuffers: Vec<vk::Framebuffer>,
+    render_pass: vk::RenderPass,
+}
+
+impl RenderPass {
+    pub fn create(context: Arc<Context>, resolution: u32, layer_count: u32) -> Self {
+        let shadow_maps = create_shadow_maps_texture(&context, resolution, layer_count);
+        let render_pass = create_render_pass(context.device());
+        let layer_views = create_layer_views(context.device(), &shadow_maps, layer_count);
+        let framebuffers = layer_views
+            .iter()
+            .map(|view| create_framebuffer(context.device(), render_pass, *view, resolution))
+            .collect();
+
+        Self {
+            context,
+            resolution,
+            shadow_maps,
+            layer_views,
+            framebuffers,
+            render_pass,
+        }
+    }
+}
+
+impl RenderPass {
+    pub fn get_resolution(&self) -> u32 {
+        self.resolution
+    }
+
+    /// Get the shadow maps as a 2D array texture with a depth comparison sampler.
+    pub fn get_shadow_maps(&self) -> &Texture {
+        &self.shadow_maps
+    }
+
+    pub fn get_render_pass(&self) -> vk::RenderPass {
+        self.render_pass
+    }
+
+    /// Get the framebuffer rendering to the shadow map layer at `layer`.
+    pub fn get_framebuffer(&self, layer: u32) -> vk::Framebuffer {
+        self.framebuffers[layer as usize]
+    }
+}
+
+impl Drop for RenderPass {
+    fn drop(&mut self) {
+        let device = self.context.device();
+        unsafe {
+            self.framebuffers
+                .iter()
+                .for_each(|f| device.destroy_framebuffer(*f, None));
+            self.layer_views
+                .iter()
+                .for_each(|v| device.destroy_image_view(*v, None));
+            device.destroy_render_pass(self.render_pass, None);
+        }
+    }
+}
+
+fn create_render_pass(device: &Device) -> vk::RenderPass {
+    let attachment_descs = [vk::AttachmentDescription::builder()
+        .format(DEPTH_FORMAT)
+        .samples(vk::SampleCountFlags::TYPE_1)
+        .load_op(vk::AttachmentLoadOp::CLEAR)
+        .store_op(vk::AttachmentStoreOp::STORE)
+        .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
+        .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
+        .initial_layout(vk::ImageLayout::UNDEFINED)
+        .final_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+        .build()];
+
+    let depth_attachment_ref = vk::AttachmentReference::builder()
+        .attachment(0)
+        .layout(vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL);
+
+    let subpasses = [vk::SubpassDescription::builder()
+        .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
+        .depth_stencil_attachment(&depth_attachment_ref)
+        .build()];
+
+    let subpass_deps = [
+        vk::SubpassDependency::builder()
+            .src_subpass(vk::SUBPASS_EXTERNAL)
+            .dst_subpass(0)
+            .src_stage_mask(vk::PipelineStageFlags::FRAGMENT_SHADER)
+            .src_access_mask(vk::AccessFlags::SHADER_READ)
+            .dst_stage_mask(vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS)
+            .dst_access_mask(vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE)
+            .build(),
+        vk::SubpassDependency::builder()
+            .src_subpass(0)
+            .dst_subpass(vk::SUBPASS_EXTERNAL)
+            .src_stage_mask(vk::PipelineStageFlags::LATE_FRAGMENT_TESTS)
+            .src_access_mask(vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE)
+            .dst_stage_mask(vk::PipelineStageFlags::FRAGMENT_SHADER)
+            .dst_access_mask(vk::AccessFlags::SHADER_READ)
+            .build(),
+    ];
+
+    let render_pass_info = vk::RenderPassCreateInfo::builder()
+        .attachments(&attachment_descs)
+        .subpasses(&subpasses)
+        .dependencies(&subpass_deps);
+
+    unsafe {
+        device
+            .create_render_pass(&render_pass_info, None)
+            .expect("Failed to create render pass")
+    }
+}
+
+/// Create the shadow map array texture.
+///
+/// The image is transitioned to be readable by shaders so layers of lights
+/// that do not cast shadows can still be bound.
+fn create_shadow_maps_texture(context: &Arc<Context>, resolution: u32, layers: u32) -> Texture {
+    let image = Image::create(
+        Arc::clone(context),
+        ImageParameters {
+            mem_properties: vk::MemoryPropertyFlags::DEVICE_LOCAL,
+            extent: vk::Extent2D {
+                width: resolution,
+                height: resolution,
+            },
+            layers,
+            format: DEPTH_FORMAT,
+            usage: vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT | vk::ImageUsageFlags::SAMPLED,
+            ..Default::default()
+        },
+    );
+
+    context.execute_one_time_commands(|command_buffer| {
+        let barrier = vk::ImageMemoryBarrier::builder()
+            .old_layout(vk::ImageLayout::UNDEFINED)
+            .new_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+            .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .image(image.image)
+            .subresource_range(vk::ImageSubresourceRange {
+                aspect_mask: vk::ImageAspectFlags::DEPTH,
+                base_mip_level: 0,
+                level_count: 1,
+                base_array_layer: 0,
+                layer_count: layers,
+            })
+            .src_access_mask(vk::AccessFlags::empty())
+            .dst_access_mask(vk::AccessFlags::SHADER_READ)
+            .build();
+
+        unsafe {
+            context.device().cmd_pipeline_barrier(
+                command_buffer,
+                vk::PipelineStageFlags::TOP_OF_PIPE,
+                vk::PipelineStageFlags::FRAGMENT_SHADER,
+                vk::DependencyFlags::empty(),
+                &[],
+                &[],
+                &[barrier],
+            )
+        };
+    });
+
+    let view = image.create_view(
+        vk::ImageViewType::TYPE_2D_ARRAY,
+        vk::ImageAspectFlags::DEPTH,
+    );
+    let sampler = Some(create_sampler(context));
+
+    Texture::new(Arc::clone(context), image, view, sampler)
+}
+
+fn create_layer_views(device: &Device, texture: &Texture, layers: u32) -> Vec<vk::ImageView> {
+    (0..layers)
+        .map(|layer| {
+            let create_info = vk::ImageViewCreateInfo::builder()
+                .image(texture.image.image)
+                .view_type(vk::ImageViewType::TYPE_2D)
+                .format(DEPTH_FORMAT)
+                .subresource_range(vk::ImageSubresourceRange {
+                    aspect_mask: vk::ImageAspectFlags::DEPTH,
+                    base_mip_level: 0,
+                    level_count: 1,
+                    base_array_layer: layer,
+                    layer_count: 1,
+                });
+
+            unsafe { device.create_image_view(&create_info, None).unwrap() }
+        })
+        .collect()
+}
+
+fn create_framebuffer(
+    device: &Device,
+    render_pass: vk::RenderPass,
+    view: vk::ImageView,
+    resolution: u32,
+) -> vk::Framebuffer {
+    let attachments = [view];
+    let framebuffer_info = vk::FramebufferCreateInfo::builder()
+        .render_pass(render_pass)
+        .attachments(&attachments)
+        .width(resolution)
+        .height(resolution)
+        .layers(1);
+    unsafe {
+        device
+            .create_framebuffer(&framebuffer_info, None)
+            .expect("Failed to create framebuffer")
+    }
+}
+
+/// Create a sampler comparing the depth of the shadow maps with linear
+/// filtering so each fetch returns a bilinear percentage of lit texels.
+fn create_sampler(context: &Arc<Context>) -> vk::Sampler {
+    let sampler_info = vk::SamplerCreateInfo::builder()
+        .mag_filter(vk::Filter::LINEAR)
+        .min_filter(vk::Filter::LINEAR)
+        .address_mode_u(vk::SamplerAddressMode::CLAMP_TO_BORDER)
+        .address_mode_v(vk::SamplerAddressMode::CLAMP_TO_BORDER)
+        .address_mode_w(vk::SamplerAddressMode::CLAMP_TO_BORDER)
+        .anisotropy_enable(false)
+        .max_anisotropy(0.0)
+        .border_color(vk::BorderColor::FLOAT_OPAQUE_WHITE)
+        .unnormalized_coordinates(false)
+        .compare_enable(true)
+        .compare_op(vk::CompareOp::LESS_OR_EQUAL)
+        .mipmap_mode(vk::SamplerMipmapMode::NEAREST)
+        .mip_lod_bias(0.0)
+        .min_lod(0.0)
+        .max_lod(1.0);
+
+    unsafe {
+        context
+            .device()
+            .create_sampler(&sampler_info, None)
+            .expect("Failed to create sampler")
+    }
+}
@@ -0,0 +1,247 @@
+//! Layout and light space matrices of the shadow maps of a model.
+//!
+//! Each shadow casting light renders into one or more layers of a depth
+//! texture array. Directional lights use cascades splitting the camera
+//! frustum, spot lights a single perspective layer and point lights the
+//! six faces of a cube. The number of layers is capped so lights that
+//! don't fit in the remaining layers don't cast shadows.
+
+use super::LightUniform;
+use crate::camera::CameraUBO;
+use math::cgmath::{
+    EuclideanSpace, InnerSpace, Matrix4, Point3, Rad, SquareMatrix, Transform, Vector3, Vector4,
+};
+use math::AABB;
+use model::{Model, Type};
+
+/// Number of cascades of the shadow maps of directional lights.
+const CASCADE_COUNT: usize = 4;
+const CUBE_FACE_COUNT: usize = 6;
+/// Maximum number of shadow map layers of a model.
+///
+/// It bounds the memory of the shadow maps and keeps the light space
+/// matrices of all layers in a single uniform buffer.
+const MAX_SHADOW_LAYER_COUNT: usize = 16;
+/// Blend between logarithmic (1.0) and uniform (0.0) cascade splits.
+const CASCADE_SPLIT_LAMBDA: f32 = 0.75;
+/// Near plane distance of perspective shadow maps relative to their far plane.
+const NEAR_FAR_RATIO: f32 = 0.001;
+
+const DEFAULT_SHADOW_RESOLUTION: u32 = 2048;
+const DEFAULT_SHADOW_BIAS: f32 = 0.0005;
+
+/// Global shadow settings of the renderer.
+#[derive(Copy, Clone, Debug)]
+pub struct ShadowSettings {
+    /// Width and height of each shadow map layer in pixels.
+    pub resolution: u32,
+    /// Depth bias applied when comparing depths, scaled by the slope of the surface.
+    pub bias: f32,
+}
+
+impl Default for ShadowSettings {
+    fn default() -> Self {
+        Self {
+            resolution: DEFAULT_SHADOW_RESOLUTION,
+            bias: DEFAULT_SHADOW_BIAS,
+        }
+    }
+}
+
+/// Number of shadow map layers used by a light of type `light_type`.
+fn layer_count(light_type: Type) -> usize {
+    match light_type {
+        Type::Directional => CASCADE_COUNT,
+        Type::Spot { .. } => 1,
+        Type::Point => CUBE_FACE_COUNT,
+    }
+}
+
+/// Get the first shadow map layer of each light of `model` and the total number of layers.
+///
+/// Lights are ordered like the nodes they are attached to. Lights whose layers
+/// don't fit in `MAX_SHADOW_LAYER_COUNT` have no layer. The total is at least 1
+/// so the shadow maps can always be created.
+pub fn shadow_layers(model: &Model) -> (Vec<Option<u32>>, u32) {
+    let mut first_layers = Vec::new();
+    let mut total = 0;
+    for light_index in model.nodes().nodes().iter().filter_map(|n| n.light_index()) {
+        let count = layer_count(model.lights()[light_index].light_type());
+        if total + count <= MAX_SHADOW_LAYER_COUNT {
+            first_layers.push(Some(total as u32));
+            total += count;
+        } else {
+            first_layers.push(None);
+        }
+    }
+    (first_layers, total.max(1) as u32)
+}
+
+/// Compute the view space depth at which each cascade ends.
+///
+/// Splits blend a logarithmic and a uniform distribution of the depth
+/// range of the camera.
+pub fn cascade_splits(z_near: f32, z_far: f32) -> [f32; CASCADE_COUNT] {
+    let mut splits = [0.0; CASCADE_COUNT];
+    for (index, split) in splits.iter_mut().enumerate() {
+        let p = (index + 1) as f32 / CASCADE_COUNT as f32;
+        let log = z_near * (z_far / z_near).powf(p);
+        let uniform = z_near + (z_far - z_near) * p;
+        *split = CASCADE_SPLIT_LAMBDA * (log - uniform) + uniform;
+    }
+    splits
+}
+
+/// Compute the light space matrix of each shadow map layer of `light`.
+///
+/// `splits` are the cascade splits of the camera and `scene_aabb` the
+/// world space bounds of the model, used to fit the depth range of the
+/// shadow maps so casters outside of the camera frustum are kept.
+pub fn light_view_projs(
+    light: &LightUniform,
+    camera: &CameraUBO,
+    splits: &[f32; CASCADE_COUNT],
+    scene_aabb: Option<AABB<f32>>,
+    resolution: u32,
+) -> Vec<Matrix4<f32>> {
+    let corners = scene_aabb.map(aabb_corners).unwrap_or_default();
+    if light.is_directional() {
+        cascade_view_projs(light.direction(), camera, splits, &corners, resolution)
+    } else if let Some(outer_cone_cos) = light.outer_cone_cos() {
+        let position = light.position();
+        let far = perspective_far(light, position, &corners);
+        let fovy = 2.0 * math::clamp(outer_cone_cos, -1.0, 1.0).acos();
+        let fovy = Rad(math::min(fovy, std::f32::consts::PI * 0.99));
+        let view = Matrix4::look_at_dir(position, light.direction(), up_vector(light.direction()));
+        vec![math::perspective(fovy, 1.0, far * NEAR_FAR_RATIO, far) * view]
+    } else {
+        let position = light.position();
+        let far = perspective_far(light, position, &corners);
+        let proj = math::perspective(
+            Rad(std::f32::consts::FRAC_PI_2),
+            1.0,
+            far * NEAR_FAR_RATIO,
+            far,
+        );
+        [
+            Vector3::unit_x(),
+            -Vector3::unit_x(),
+            Vector3::unit_y(),
+            -Vector3::unit_y(),
+            Vector3::unit_z(),
+            -Vector3::unit_z(),
+        ]
+        .iter()
+        .map(|dir| proj * Matrix4::look_at_dir(position, *dir, up_vector(*dir)))
+        .collect()
+    }
+}
+
+fn cascade_view_projs(
+    direction: Vector3<f32>,
+    camera: &CameraUBO,
+    splits: &[f32; CASCADE_COUNT],
+    scene_corners: &[Point3<f32>],
+    resolution: u32,
+) -> Vec<Matrix4<f32>> {
+    let inverse_view_proj = match camera.view_proj().invert() {
+        Some(matrix) => matrix,
+        None => return vec![Matrix4::identity(); CASCADE_COUNT],
+    };
+
+    // Corners of the camera frustum on the near and far planes
+    let unproject = |x: f32, y: f32, z: f32| {
+        let point = inverse_view_proj * Vector4::new(x, y, z, 1.0);
+        Point3::from_homogeneous(point)
+    };
+    let frustum_corners = [(-1.0, -1.0), (1.0, -1.0), (-1.0, 1.0), (1.0, 1.0)]
+        .iter()
+        .map(|(x, y)| (unproject(*x, *y, 0.0), unproject(*x, *y, 1.0)))
+        .collect::<Vec<_>>();
+
+    let direction = direction.normalize();
+    let light_view = Matrix4::look_at_dir(Point3::origin(), direction, up_vector(direction));
+
+    let (z_near, z_far) = (camera.z_near(), camera.z_far());
+    let mut previous_split = z_near;
+    splits
+        .iter()
+        .map(|split| {
+            // Bounding sphere of the slice of the frustum covered by the cascade
+            let slice_corners = frustum_corners
+                .iter()
+                .flat_map(|(near, far)| {
+                    let lerp = |depth: f32| {
+                        let t = (depth - z_near) / (z_far - z_near);
+                        *near + (*far - *near) * t
+                    };
+                    vec![lerp(previous_split), lerp(*split)]
+                })
+                .collect::<Vec<_>>();
+            previous_split = *split;
+
+            let center = Point3::centroid(&slice_corners);
+            let radius = slice_corners
+                .iter()
+                .map(|corner| (*corner - center).magnitude())
+                .fold(0.0, f32::max);
+            // Quantize the radius to avoid shimmering when the camera rotates
+            let radius = (radius * 16.0).ceil() / 16.0;
+
+            // Snap the center to the texels of the shadow map to avoid shimmering when the camera moves
+            let center = light_view.transform_point(center);
+            let texel_size = 2.0 * radius / resolution as f32;
+            let x = (center.x / texel_size).floor() * texel_size;
+            let y = (center.y / texel_size).floor() * texel_size;
+
+            // Extend the near plane toward the light to keep casters outside of the slice
+            let near = scene_corners
+                .iter()
+                .map(|corner| -light_view.transform_point(*corner).z)
+                .fold(-center.z - radius, f32::min);
+            let far = -center.z + radius;
+
+            math::orthographic(x - radius, x + radius, y - radius, y + radius, near, far)
+                * light_view
+        })
+        .collect()
+}
+
+/// Get the far plane distance of the perspective shadow maps of a light.
+///
+/// It is the range of the light if it has one or the distance to the
+/// furthest corner of the scene otherwise.
+fn perspective_far(
+    light: &LightUniform,
+    position: Point3<f32>,
+    scene_corners: &[Point3<f32>],
+) -> f32 {
+    let far = light.range().unwrap_or_else(|| {
+        scene_corners
+            .iter()
+            .map(|corner| (*corner - position).magnitude())
+            .fold(0.0, f32::max)
+    });
+    far.max(std::f32::EPSILON.sqrt())
+}
+
+fn up_vector(direction: Vector3<f32>) -> Vector3<f32> {
+    if direction.normalize().y.abs() > 0.99 {
+        Vector3::unit_z()
+    } else {
+        Vector3::unit_y()
+    }
+}
+
+fn aabb_corners(aabb: AABB<f32>) -> Vec<Point3<f32>> {
+    let (min, max) = (aabb.min(), aabb.max());
+    (0..8)
+        .map(|i| {
+            Point3::new(
+                if i & 1 == 0 { min.x } else { max.x },
+                if i & 2 == 0 { min.y } else { max.y },
+                if i & 4 == 0 { min.z } else { max.z },
+            )
+        })
+        .collect()
+}
@@ -0,0 +1,191 @@
+//! Back to front ordering of the blended primitives of a model.
+//!
+//! Blended primitives are sorted each frame by the view space depth of the
+//! center of their bounds so overlapping transparent surfaces blend correctly.
+//! Primitives are sorted as a whole so intersecting ones can still blend wrong.
+//! The sorted primitives of all the models are then merged so they are drawn
+//! after the opaque primitives of every model.
+
+use super::Culling;
+use math::cgmath::{Matrix4, Vector4};
+use model::{metadata::PrimitiveMode, Model, NodeBounds};
+use std::{cmp::Ordering, collections::HashMap};
+
+/// Distances overriding the computed sort distance of primitives.
+///
+/// Keys are node indices and indices of primitives in the mesh of the node.
+pub type SortDistanceOverrides = HashMap<(usize, usize), f32>;
+
+/// Blended primitive of a mesh node to draw.
+#[derive(Copy, Clone, Debug)]
+pub struct BlendedDraw {
+    pub node_index: usize,
+    /// Index of the node among the nodes with a mesh, which is its index in the transform buffer.
+    pub transform_index: usize,
+    /// Index of the primitive in the mesh of the node.
+    pub primitive_index: usize,
+    /// Distance to the camera the primitive is sorted by.
+    pub distance: f32,
+}
+
+/// List the drawn blended primitives of `model` from the furthest to the closest.
+///
+/// `bounds` are the world space bounds of the nodes. Hidden or culled primitives
+/// are skipped. The distance to the camera is the opposite of the view space depth
+/// of the center of the bounds, unless an override is set for the primitive.
+pub fn sort_blended_primitives(
+    model: &Model,
+    bounds: &[Option<NodeBounds>],
+    node_visibility: &[bool],
+    culling: &Culling,
+    view: Matrix4<f32>,
+    overrides: &SortDistanceOverrides,
+) -> Vec<BlendedDraw> {
+    let mesh_nodes = model
+        .nodes()
+        .nodes()
+        .iter()
+        .enumerate()
+        .filter(|(_, n)| n.mesh_index().is_some());
+
+    let mut draws = Vec::new();
+    for (transform_index, (node_index, node)) in mesh_nodes.enumerate() {
+        if !node_visibility.get(node_index).copied().unwrap_or(true)
+            || culling.is_node_culled(node_index)
+        {
+            continue;
+        }
+
+        let mesh = model.mesh(node.mesh_index().unwrap());
+        for (primitive_index, primitive) in mesh.primitives().iter().enumerate() {
+            if !primitive.material().is_transparent()
+                || primitive.mode() == PrimitiveMode::Points
+                || culling.is_primitive_culled(node_index, primitive_index)
+            {
+                continue;
+            }
+
+            draws.push(BlendedDraw {
+                node_index,
+                transform_index,
+                primitive_index,
+                distance: sort_distance(bounds, view, overrides, node_index, primitive_index),
+            });
+        }
+    }
+
+    // Stable so primitives at the same distance keep their index order
+    draws.sort_by(back_to_front);
+    draws
+}
+
+/// Get the distance to the camera a primitive of a node is sorted by.
+///
+/// Primitives without override nor bounds are sorted at the camera position.
+fn sort_distance(
+    bounds: &[Option<NodeBounds>],
+    view: Matrix4<f32>,
+    overrides: &SortDistanceOverrides,
+    node_index: usize,
+    primitive_index: usize,
+) -> f32 {
+    overrides
+        .get(&(node_index, primitive_index))
+        .copied()
+        .or_else(|| {
+            let aabb = bounds.get(node_index)?.as_ref()?.primitives[primitive_index];
+            let center = aabb.get_center();
+            Some(-(view * Vector4::new(center.x, center.y, center.z, 1.0)).z)
+        })
+        .unwrap_or(0.0)
+}
+
+/// Order the furthest draws first.
+fn back_to_front(d0: &BlendedDraw, d1: &BlendedDraw) -> Ordering {
+    d1.distance
+        .partial_cmp(&d0.distance)
+        .unwrap_or(Ordering::Equal)
+}
+
+/// Merge the sorted blended draws of several models from the furthest to the closest.
+///
+/// Each draw is returned with the index of the list of `draws` it comes from.
+pub fn merge_blended_draws<'a, I>(draws: I) -> Vec<(usize, BlendedDraw)>
+where
+    I: IntoIterator<Item = &'a [BlendedDraw]>,
+{
+    let mut merged = draws
+        .into_iter()
+        .enumerate()
+        .flat_map(|(index, draws)| draws.iter().map(move |draw| (index, *draw)))
+        .collect::<Vec<_>>();
+
+    // Stable so primitives at the same distance keep the order of their model
+    merged.sort_by(|(_, d0), (_, d1)| back_to_front(d0, d1));
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use math::{cgmath::Vector3, AABB};
+
+    fn draw(node_index: usize, distance: f32) -> BlendedDraw {
+        BlendedDraw {
+            node_index,
+            transform_index: node_index,
+            primitive_index: 0,
+            distance,
+        }
+    }
+
+    fn node_indices<'a, I: IntoIterator<Item = &'a BlendedDraw>>(draws: I) -> Vec<usize> {
+        draws.into_iter().map(|d| d.node_index).collect()
+    }
+
+    #[test]
+    fn draws_are_sorted_back_to_front() {
+        let mut draws = vec![draw(0, 1.0), draw(1, 5.0), draw(2, 1.0), draw(3, 3.0)];
+        draws.sort_by(back_to_front);
+
+        // Draws at the same distance keep their order
+        assert_eq!(node_indices(&draws), [1, 3, 0, 2]);
+    }
+
+    #[test]
+    fn sort_distance_is_the_depth_of_the_bounds_unless_overridden() {
+        let aabb = AABB::new(Vector3::new(-1.0, -1.0, -5.0), Vector3::new(1.0, 1.0, -3.0));
+        let bounds = vec![
+            Some(NodeBounds {
+                aabb,
+                primitives: vec![aabb, aabb],
+            }),
+            None,
+        ];
+        let view = Matrix4::from_translation(Vector3::new(0.0, 0.0, -1.0));
+        let mut overrides = SortDistanceOverrides::new();
+        overrides.insert((0, 1), 2.0);
+
+        assert_eq!(sort_distance(&bounds, view, &overrides, 0, 0), 5.0);
+        assert_eq!(sort_distance(&bounds, view, &overrides, 0, 1), 2.0);
+        assert_eq!(sort_distance(&bounds, view, &overrides, 1, 0), 0.0);
+
+        // Overrides take priority over missing bounds too
+        overrides.insert((1, 0), 7.0);
+        assert_eq!(sort_distance(&bounds, view, &overrides, 1, 0), 7.0);
+    }
+
+    #[test]
+    fn merged_draws_are_sorted_back_to_front() {
+        let first = [draw(0, 4.0), draw(1, 2.0)];
+        let second = [draw(2, 3.0), draw(3, 2.0), draw(4, 1.0)];
+
+        let merged = merge_blended_draws(vec![&first[..], &second[..]]);
+        let draws = merged.iter().map(|(_, d)| d);
+        assert_eq!(node_indices(draws), [0, 2, 1, 3, 4]);
+
+        // Draws at the same distance keep the order of their model
+        let models = merged.iter().map(|(i, _)| *i).collect::<Vec<_>>();
+        assert_eq!(models, [0, 1, 0, 1, 1]);
+    }
+}
@@ -1,6 +1,6 @@
 use super::JointsBuffer;
-use math::cgmath::{InnerSpace, Matrix4, SquareMatrix, Vector4};
-use model::{Light, Material, Model, Type, Workflow, MAX_JOINTS_PER_MESH};
+use math::cgmath::{InnerSpace, Matrix4, Point3, SquareMatrix, Vector3, Vector4};
+use model::{Light, Material, Model, TextureSlot, Type, Workflow, MAX_JOINTS_PER_MESH};
 use std::{mem::size_of, sync::Arc};
 use vulkan::{ash::vk, Buffer, Context};
 
@@ -25,7 +25,9 @@ pub struct LightUniform {
     angle_scale: f32,
     angle_offset: f32,
     light_type: u32,
-    pad: [u32; 3],
+    /// First shadow map layer of the light or -1 if it casts no shadow.
+    shadow_layer: i32,
+    pad: [u32; 2],
 }
 
 impl From<(Matrix4<f32>, Light)> for LightUniform {
@@ -71,7 +73,74 @@ impl From<(Matrix4<f32>, Light)> for LightUniform {
             angle_scale,
             angle_offset,
             light_type,
-            pad: [0, 0, 0],
+            shadow_layer: -1,
+            pad: [0, 0],
+        }
+    }
+}
+
+impl LightUniform {
+    /// Set the first shadow map layer of the light or `None` if it casts no shadow.
+    pub fn with_shadow_layer(self, shadow_layer: Option<u32>) -> Self {
+        Self {
+            shadow_layer: shadow_layer.map_or(-1, |layer| layer as _),
+            ..self
+        }
+    }
+
+    pub fn position(&self) -> Point3<f32> {
+        Point3::new(self.position[0], self.position[1], self.position[2])
+    }
+
+    pub fn direction(&self) -> Vector3<f32> {
+        Vector3::new(self.direction[0], self.direction[1], self.direction[2])
+    }
+
+    pub fn color(&self) -> [f32; 3] {
+        [self.color[0], self.color[1], self.color[2]]
+    }
+
+    /// Get the range of the light or `None` if it is infinite.
+    pub fn range(&self) -> Option<f32> {
+        if self.range < 0.0 {
+            None
+        } else {
+            Some(self.range)
+        }
+    }
+
+    pub fn is_directional(&self) -> bool {
+        self.light_type == DIRECTIONAL_LIGHT_TYPE
+    }
+
+    /// Get the cosine of the outer cone angle or `None` if the light is not a spot light.
+    pub fn outer_cone_cos(&self) -> Option<f32> {
+        if self.light_type == SPOT_LIGHT_TYPE {
+            Some(-self.angle_offset / self.angle_scale)
+        } else {
+            None
+        }
+    }
+}
+
+/// Header of the shadow uniform buffer.
+///
+/// It is followed by the light space matrix of each shadow map layer.
+#[derive(Copy, Clone, Debug)]
+#[repr(C)]
+pub struct ShadowUniform {
+    /// View space depth at which each cascade of directional lights ends.
+    cascade_splits: [f32; 4],
+    bias: f32,
+    pad: [f32; 3],
+}
+
+impl ShadowUniform {
+    pub fn new(cascade_splits: [f32; 4], bias: f32) -> Self {
+        Self {
+            cascade_splits,
+            bias,
+            pad: [0.0; 3],
         }
     }
 }
@@ -137,29 +206,23 @@ impl<'a> From<Material> for MaterialUniform {
             occlusion,
         ];
 
-        let color_texture_id = material
-            .get_color_texture()
-            .map_or(NO_TEXTURE_ID, |info| info.get_channel());
+        // Disabled textures are still bound but flagged as missing
+        let texture_id = |slot| {
+            material
+                .get_enabled_texture(slot)
+                .map_or(NO_TEXTURE_ID, |info| info.get_channel())
+        };
 
-        let metallic_roughness_texture_id = match material.get_workflow() {
-            Workflow::MetallicRoughness(workflow) => workflow.get_metallic_roughness_texture(),
-            Workflow::SpecularGlossiness(workflow) => workflow.get_specular_glossiness_texture(),
-        }
-        .map_or(NO_TEXTURE_ID, |t| t.get_channel());
-        let emissive_texture_id = material
-            .get_emissive_texture()
-            .map_or(NO_TEXTURE_ID, |info| info.get_channel());
-        let normal_texture_id = material
-            .get_normals_texture()
-            .map_or(NO_TEXTURE_ID, |info| info.get_channel());
+        let color_texture_id = texture_id(TextureSlot::Color);
+        let metallic_roughness_texture_id = texture_id(TextureSlot::Material);
+        let emissive_texture_id = texture_id(TextureSlot::Emissive);
+        let normal_texture_id = texture_id(TextureSlot::Normals);
         let color_material_emissive_normal_texture_channels = (color_texture_id << 24)
             | (metallic_roughness_texture_id << 16)
             | (emissive_texture_id << 8)
             | normal_texture_id;
 
-        let occlusion_texture_id = material
-            .get_occlusion_texture()
-            .map_or(NO_TEXTURE_ID, |info| info.get_channel());
+        let occlusion_texture_id = texture_id(TextureSlot::Occlusion);
         let alpha_mode = material.get_alpha_mode();
         let unlit_flag = if material.is_unlit() {
             UNLIT_FLAG_UNLIT
@@ -267,3 +330,18 @@ pub fn create_lights_ubos(context: &Arc<Context>, model: &Model, count: u32) ->
         })
         .collect::<Vec<_>>()
 }
+
+pub fn create_shadow_ubos(context: &Arc<Context>, layer_count: u32, count: u32) -> Vec<Buffer> {
+    let buffer_size = size_of::<ShadowUniform>() + layer_count as usize * size_of::<Matrix4<f32>>();
+
+    (0..count)
+        .map(|_| {
+            Buffer::create(
+                Arc::clone(context),
+                buffer_size as vk::DeviceSize,
+                vk::BufferUsageFlags::UNIFORM_BUFFER,
+                vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+            )
+        })
+        .collect::<Vec<_>>()
+}
@@ -305,6 +305,8 @@ fn create_pipeline(
             layout,
             depth_stencil_info: &depth_stencil_info,
             color_blend_attachments: &color_blend_attachments,
+            topology: vk::PrimitiveTopology::TRIANGLE_LIST,
+            polygon_mode: vk::PolygonMode::FILL,
             enable_face_culling: true,
             parent: None,
         },
@@ -1,4 +1,5 @@
 mod blurpass;
 mod finalpass;
+mod oitcompositepass;
 
-pub use self::{blurpass::*, finalpass::*};
+pub use self::{blurpass::*, finalpass::*, oitcompositepass::*};
@@ -0,0 +1,529 @@
+mod renderpass;
+
+use crate::renderer::{create_renderer_pipeline, fullscreen::*, RendererPipelineParameters};
+use imgui::{TextureId, Textures};
+use model::Model;
+use renderpass::RenderPass;
+use std::{mem::size_of, sync::Arc};
+use util::*;
+use vulkan::ash::{version::DeviceV1_0, vk, Device};
+use vulkan::{Context, SwapchainProperties};
+
+const PREVIEW_EXTENT: vk::Extent2D = vk::Extent2D {
+    width: 1024,
+    height: 1024,
+};
+
+/// Texture displayed by the texture preview pass.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct TexturePreview {
+    pub model_index: usize,
+    pub texture_index: usize,
+    /// Whether the red, green, blue and alpha channels are displayed.
+    pub channels: [bool; 4],
+    pub mip_level: u32,
+    /// Whether the texture stores linear values rather than sRGB values.
+    pub linear: bool,
+    /// Top left corner of the displayed region in texture coordinates.
+    pub uv_offset: [f32; 2],
+    /// Size of the displayed region in texture coordinates.
+    pub uv_scale: [f32; 2],
+}
+
+#[derive(Copy, Clone)]
+#[allow(dead_code)]
+#[repr(C)]
+struct PreviewUniform {
+    channels: [f32; 4],
+    uv_offset: [f32; 2],
+    uv_scale: [f32; 2],
+    mip_level: f32,
+    is_linear: u32,
+}
+
+impl From<TexturePreview> for PreviewUniform {
+    fn from(preview: TexturePreview) -> Self {
+        let channel = |enabled| if enabled { 1.0 } else { 0.0 };
+        Self {
+            channels: [
+                channel(preview.channels[0]),
+                channel(preview.channels[1]),
+                channel(preview.channels[2]),
+                channel(preview.channels[3]),
+            ],
+            uv_offset: preview.uv_offset,
+            uv_scale: preview.uv_scale,
+            mip_level: preview.mip_level as _,
+            is_linear: preview.linear as _,
+        }
+    }
+}
+
+/// Renders one channel combination and mip level of a model texture so the gui can display it.
+///
+/// The textures of the rendered models are registered as gui textures so the
+/// gui can display their thumbnails. Their descriptor sets are also the input
+/// of the pass so nothing is updated while frames are in flight.
+pub struct TexturePreviewPass {
+    context: Arc<Context>,
+    render_pass: RenderPass,
+    framebuffer: vk::Framebuffer,
+    descriptor_set_layout: vk::DescriptorSetLayout,
+    output_descriptor_pool: vk::DescriptorPool,
+    output_texture_id: TextureId,
+    /// Sampler of the model textures, which gives access to all their mip levels.
+    sampler: vk::Sampler,
+    models: Vec<ModelTextures>,
+    pipeline_layout: vk::PipelineLayout,
+    pipeline: vk::Pipeline,
+}
+
+/// Descriptor sets and gui texture ids of the textures of a model.
+struct ModelTextures {
+    context: Arc<Context>,
+    pool: Option<vk::DescriptorPool>,
+    sets: Vec<vk::DescriptorSet>,
+    ids: Vec<TextureId>,
+}
+
+impl TexturePreviewPass {
+    pub fn create(
+        context: Arc<Context>,
+        swapchain_props: SwapchainProperties,
+        gui_textures: &mut Textures<vk::DescriptorSet>,
+    ) -> Self {
+        let render_pass = RenderPass::create(Arc::clone(&context), PREVIEW_EXTENT);
+        let framebuffer = render_pass.create_framebuffer();
+
+        let descriptor_set_layout = create_descriptor_set_layout(context.device());
+        let output_descriptor_pool = create_descriptor_pool(context.device(), 1);
+        let output = render_pass.get_output_attachment();
+        let output_set = create_descriptor_sets(
+            &context,
+            output_descriptor_pool,
+            descriptor_set_layout,
+            &[(
+                output.view,
+                output
+                    .sampler
+                    .expect("Preview output image must have a sampler"),
+            )],
+        )[0];
+        let output_texture_id = gui_textures.insert(output_set);
+
+        let sampler = create_sampler(&context);
+        let pipeline_layout = create_pipeline_layout(context.device(), descriptor_set_layout);
+        let pipeline = create_pipeline(
+            &context,
+            swapchain_props,
+            render_pass.get_render_pass(),
+            pipeline_layout,
+        );
+
+        TexturePreviewPass {
+            context,
+            render_pass,
+            framebuffer,
+            descriptor_set_layout,
+            output_descriptor_pool,
+            output_texture_id,
+            sampler,
+            models: Vec::new(),
+            pipeline_layout,
+            pipeline,
+        }
+    }
+}
+
+impl TexturePreviewPass {
+    /// Register the textures of `model` after the ones of the models already registered.
+    pub fn add_model(&mut self, model: &Model, gui_textures: &mut Textures<vk::DescriptorSet>) {
+        let textures = self.create_model_textures(model, gui_textures);
+        self.models.push(textures);
+    }
+
+    /// Register the textures of `model` in place of the ones of the model at `index`.
+    ///
+    /// The caller must make sure the GPU is not using the replaced textures anymore.
+    pub fn replace_model(
+        &mut self,
+        index: usize,
+        model: &Model,
+        gui_textures: &mut Textures<vk::DescriptorSet>,
+    ) {
+        let textures = self.create_model_textures(model, gui_textures);
+        let replaced = std::mem::replace(&mut self.models[index], textures);
+        replaced.unregister(gui_textures);
+    }
+
+    /// Unregister the textures of the model at `index`.
+    ///
+    /// The caller must make sure the GPU is not using the textures anymore.
+    pub fn remove_model(&mut self, index: usize, gui_textures: &mut Textures<vk::DescriptorSet>) {
+        self.models.remove(index).unregister(gui_textures);
+    }
+
+    /// Unregister the textures of all the models.
+    ///
+    /// The caller must make sure the GPU is not using the textures anymore.
+    pub fn clear(&mut self, gui_textures: &mut Textures<vk::DescriptorSet>) {
+        self.models
+            .drain(..)
+            .for_each(|textures| textures.unregister(gui_textures));
+    }
+
+    fn create_model_textures(
+        &self,
+        model: &Model,
+        gui_textures: &mut Textures<vk::DescriptorSet>,
+    ) -> ModelTextures {
+        let textures = model
+            .textures()
+            .iter()
+            .map(|texture| (texture.get_view(), self.sampler))
+            .collect::<Vec<_>>();
+
+        if textures.is_empty() {
+            return ModelTextures {
+                context: Arc::clone(&self.context),
+                pool: None,
+                sets: Vec::new(),
+                ids: Vec::new(),
+            };
+        }
+
+        let pool = create_descriptor_pool(self.context.device(), textures.len() as _);
+        let sets =
+            create_descriptor_sets(&self.context, pool, self.descriptor_set_layout, &textures);
+        let ids = sets.iter().map(|set| gui_textures.insert(*set)).collect();
+
+        ModelTextures {
+            context: Arc::clone(&self.context),
+            pool: Some(pool),
+            sets,
+            ids,
+        }
+    }
+
+    pub fn cmd_draw(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        quad_model: &QuadModel,
+        preview: TexturePreview,
+    ) {
+        let input_set = match self
+            .models
+            .get(preview.model_index)
+            .and_then(|textures| textures.sets.get(preview.texture_index))
+        {
+            Some(set) => *set,
+            None => return,
+        };
+
+        let device = self.context.device();
+
+        {
+            let clear_values = [vk::ClearValue {
+                color: vk::ClearColorValue {
+                    float32: [0.0, 0.0, 0.0, 1.0],
+                },
+            }];
+            let render_pass_begin_info = vk::RenderPassBeginInfo::builder()
+                .render_pass(self.render_pass.get_render_pass())
+                .framebuffer(self.framebuffer)
+                .render_area(vk::Rect2D {
+                    offset: vk::Offset2D { x: 0, y: 0 },
+                    extent: PREVIEW_EXTENT,
+                })
+                .clear_values(&clear_values);
+
+            unsafe {
+                device.cmd_begin_render_pass(
+                    command_buffer,
+                    &render_pass_begin_info,
+                    vk::SubpassContents::INLINE,
+                )
+            };
+        }
+
+        // Bind pipeline
+        unsafe {
+            device.cmd_bind_pipeline(
+                command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                self.pipeline,
+            )
+        };
+
+        // Bind buffers
+        unsafe {
+            device.cmd_bind_vertex_buffers(command_buffer, 0, &[quad_model.vertices.buffer], &[0]);
+            device.cmd_bind_index_buffer(
+                command_buffer,
+                quad_model.indices.buffer,
+                0,
+                vk::IndexType::UINT16,
+            );
+        }
+
+        // Bind descriptor sets
+        unsafe {
+            device.cmd_bind_descriptor_sets(
+                command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                self.pipeline_layout,
+                0,
+                &[input_set],
+                &[],
+            )
+        };
+
+        // Push preview constants
+        unsafe {
+            let uniform = PreviewUniform::from(preview);
+            device.cmd_push_constants(
+                command_buffer,
+                self.pipeline_layout,
+                vk::ShaderStageFlags::FRAGMENT,
+                0,
+                any_as_u8_slice(&uniform),
+            );
+        }
+
+        // Draw
+        unsafe { device.cmd_draw_indexed(command_buffer, 6, 1, 0, 0, 1) };
+
+        unsafe { device.cmd_end_render_pass(command_buffer) };
+    }
+}
+
+/// Getters
+impl TexturePreviewPass {
+    /// Get the gui texture ids of the textures of the model at `index`.
+    ///
+    /// The ids are indexed like the textures of the model.
+    pub fn get_texture_ids(&self, index: usize) -> &[TextureId] {
+        self.models
+            .get(index)
+            .map(|textures| textures.ids.as_slice())
+            .unwrap_or(&[])
+    }
+
+    /// Get the gui texture id of the output of the pass.
+    pub fn get_output_texture_id(&self) -> TextureId {
+        self.output_texture_id
+    }
+}
+
+impl Drop for TexturePreviewPass {
+    fn drop(&mut self) {
+        self.models.clear();
+        let device = self.context.device();
+        unsafe {
+            device.destroy_framebuffer(self.framebuffer, None);
+            device.destroy_pipeline(self.pipeline, None);
+            device.destroy_pipeline_layout(self.pipeline_layout, None);
+            device.destroy_sampler(self.sampler, None);
+            device.destroy_descriptor_pool(self.output_descriptor_pool, None);
+            device.destroy_descriptor_set_layout(self.descriptor_set_layout, None);
+        }
+    }
+}
+
+impl ModelTextures {
+    fn unregister(self, gui_textures: &mut Textures<vk::DescriptorSet>) {
+        self.ids.iter().for_each(|id| {
+            gui_textures.remove(*id);
+        });
+    }
+}
+
+impl Drop for ModelTextures {
+    fn drop(&mut self) {
+        if let Some(pool) = self.pool {
+            unsafe { self.context.device().destroy_descriptor_pool(pool, None) };
+        }
+    }
+}
+
+/// Same layout as the one of the textures of the gui renderer.
+fn create_descriptor_set_layout(device: &Device) -> vk::DescriptorSetLayout {
+    let bindings = [vk::DescriptorSetLayoutBinding::builder()
+        .binding(0)
+        .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+        .descriptor_count(1)
+        .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+        .build()];
+
+    let layout_info = vk::DescriptorSetLayoutCreateInfo::builder().bindings(&bindings);
+
+    unsafe {
+        device
+            .create_descriptor_set_layout(&layout_info, None)
+            .unwrap()
+    }
+}
+
+fn create_descriptor_pool(device: &Device, descriptor_count: u32) -> vk::DescriptorPool {
+    let pool_sizes = [vk::DescriptorPoolSize {
+        ty: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+        descriptor_count,
+    }];
+
+    let create_info = vk::DescriptorPoolCreateInfo::builder()
+        .pool_sizes(&pool_sizes)
+        .max_sets(descriptor_count);
+
+    unsafe { device.create_descriptor_pool(&create_info, None).unwrap() }
+}
+
+/// Create one descriptor set for each image view and sampler pair of `images`.
+fn create_descriptor_sets(
+    context: &Arc<Context>,
+    pool: vk::DescriptorPool,
+    layout: vk::DescriptorSetLayout,
+    images: &[(vk::ImageView, vk::Sampler)],
+) -> Vec<vk::DescriptorSet> {
+    let layouts = (0..images.len()).map(|_| layout).collect::<Vec<_>>();
+    let allocate_info = vk::DescriptorSetAllocateInfo::builder()
+        .descriptor_pool(pool)
+        .set_layouts(&layouts);
+    let sets = unsafe {
+        context
+            .device()
+            .allocate_descriptor_sets(&allocate_info)
+            .unwrap()
+    };
+
+    let image_infos = images
+        .iter()
+        .map(|(view, sampler)| {
+            [vk::DescriptorImageInfo::builder()
+                .image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+                .image_view(*view)
+                .sampler(*sampler)
+                .build()]
+        })
+        .collect::<Vec<_>>();
+
+    let descriptor_writes = sets
+        .iter()
+        .zip(image_infos.iter())
+        .map(|(set, image_info)| {
+            vk::WriteDescriptorSet::builder()
+                .dst_set(*set)
+                .dst_binding(0)
+                .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+                .image_info(image_info)
+                .build()
+        })
+        .collect::<Vec<_>>();
+
+    unsafe {
+        context
+            .device()
+            .update_descriptor_sets(&descriptor_writes, &[])
+    }
+
+    sets
+}
+
+/// Unfiltered sampler giving access to all the mip levels of the textures.
+fn create_sampler(context: &Arc<Context>) -> vk::Sampler {
+    let sampler_info = vk::SamplerCreateInfo::builder()
+        .mag_filter(vk::Filter::NEAREST)
+        .min_filter(vk::Filter::NEAREST)
+        .address_mode_u(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+        .address_mode_v(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+        .address_mode_w(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+        .anisotropy_enable(false)
+        .max_anisotropy(0.0)
+        .border_color(vk::BorderColor::INT_OPAQUE_BLACK)
+        .unnormalized_coordinates(false)
+        .compare_enable(false)
+        .compare_op(vk::CompareOp::ALWAYS)
+        .mipmap_mode(vk::SamplerMipmapMode::NEAREST)
+        .mip_lod_bias(0.0)
+        .min_lod(0.0)
+        .max_lod(vk::LOD_CLAMP_NONE);
+
+    unsafe {
+        context
+            .device()
+            .create_sampler(&sampler_info, None)
+            .expect("Failed to create sampler")
+    }
+}
+
+fn create_pipeline_layout(
+    device: &Device,
+    descriptor_set_layout: vk::DescriptorSetLayout,
+) -> vk::PipelineLayout {
+    let layouts = [descriptor_set_layout];
+    let push_constant_range = [vk::PushConstantRange {
+        stage_flags: vk::ShaderStageFlags::FRAGMENT,
+        offset: 0,
+        size: size_of::<PreviewUniform>() as _,
+    }];
+    let layout_info = vk::PipelineLayoutCreateInfo::builder()
+        .set_layouts(&layouts)
+        .push_constant_ranges(&push_constant_range);
+
+    unsafe { device.create_pipeline_layout(&layout_info, None).unwrap() }
+}
+
+fn create_pipeline(
+    context: &Arc<Context>,
+    swapchain_properties: SwapchainProperties,
+    render_pass: vk::RenderPass,
+    layout: vk::PipelineLayout,
+) -> vk::Pipeline {
+    let depth_stencil_info = vk::PipelineDepthStencilStateCreateInfo::builder()
+        .depth_test_enable(false)
+        .depth_write_enable(false)
+        .depth_compare_op(vk::CompareOp::LESS_OR_EQUAL)
+        .depth_bounds_test_enable(false)
+        .min_depth_bounds(0.0)
+        .max_depth_bounds(1.0)
+        .stencil_test_enable(false)
+        .front(Default::default())
+        .back(Default::default());
+
+    let color_blend_attachments = [vk::PipelineColorBlendAttachmentState::builder()
+        .color_write_mask(vk::ColorComponentFlags::all())
+        .blend_enable(false)
+        .src_color_blend_factor(vk::BlendFactor::ONE)
+        .dst_color_blend_factor(vk::BlendFactor::ZERO)
+        .color_blend_op(vk::BlendOp::ADD)
+        .src_alpha_blend_factor(vk::BlendFactor::ONE)
+        .dst_alpha_blend_factor(vk::BlendFactor::ZERO)
+        .alpha_blend_op(vk::BlendOp::ADD)
+        .build()];
+
+    // The viewport covers the preview and not the swapchain
+    let swapchain_properties = SwapchainProperties {
+        extent: PREVIEW_EXTENT,
+        ..swapchain_properties
+    };
+
+    create_renderer_pipeline::<QuadVertex>(
+        context,
+        RendererPipelineParameters {
+            vertex_shader_name: "fullscreen",
+            fragment_shader_name: "texture_preview",
+            vertex_shader_specialization: None,
+            fragment_shader_specialization: None,
+            swapchain_properties,
+            msaa_samples: vk::SampleCountFlags::TYPE_1,
+            render_pass,
+            subpass: 0,
+            layout,
+            depth_stencil_info: &depth_stencil_info,
+            color_blend_attachments: &color_blend_attachments,
+            topology: vk::PrimitiveTopology::TRIANGLE_LIST,
+            polygon_mode: vk::PolygonMode::FILL,
+            enable_face_culling: true,
+            parent: None,
+        },
+    )
+}
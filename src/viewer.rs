@@ -1,8 +1,17 @@
-use crate::{camera::*, config::Config, controls::*, gui::Gui, loader::*, renderer::*};
+use crate::{
+    camera::*,
+    config::Config,
+    controls::*,
+    gizmo::{GizmoTarget, NodeTransform},
+    gui::{Gui, TextureEntry},
+    loader::*,
+    renderer::*,
+};
 use ash::{version::DeviceV1_0, vk, Device};
 use environment::*;
-use model::{Model, PlaybackMode};
-use std::{cell::RefCell, path::Path, rc::Rc, sync::Arc, time::Instant};
+use math::AABB;
+use model::{Model, ModelLoadingError, PlaybackMode};
+use std::{cell::RefCell, cmp::Ordering, fs::File, path::Path, rc::Rc, sync::Arc, time::Instant};
 use vulkan::*;
 use winit::{dpi::LogicalSize, Event, EventsLoop, Window, WindowBuilder, WindowEvent};
 
@@ -17,7 +26,7 @@ pub struct Viewer {
 
     camera: Camera,
     input_state: InputState,
-    model: Option<Rc<RefCell<Model>>>,
+    models: Vec<Rc<RefCell<Model>>>,
 
     gui: Gui,
 
@@ -50,6 +59,7 @@ impl Viewer {
             .unwrap();
 
         let mut gui = Gui::new(&window);
+        gui.set_placement(config.placement());
 
         let context = Arc::new(Context::new(&window, enable_debug));
 
@@ -87,6 +97,7 @@ impl Viewer {
             environment,
             gui.get_context(),
         );
+        gui.set_texture_preview_id(renderer.get_texture_preview_id());
 
         let command_buffers = Self::allocate_command_buffers(&context, swapchain.image_count());
 
@@ -105,7 +116,7 @@ impl Viewer {
             run: true,
             camera: Default::default(),
             input_state: Default::default(),
-            model: None,
+            models: Vec::new(),
             gui,
             context,
             swapchain_properties,
@@ -191,8 +202,14 @@ impl Viewer {
             }
 
             self.load_new_model();
+            self.export_model_statistics();
+            self.save_model();
             self.update_model(delta_s as f32);
             self.update_camera();
+            self.pick_node();
+            self.update_selected_node();
+            self.update_selected_material();
+            self.update_texture_preview();
             self.update_renderer_settings();
             self.draw_frame();
         }
@@ -244,48 +261,259 @@ impl Viewer {
     }
 
     fn load_new_model(&mut self) {
-        if let Some(model) = self.loader.get_model() {
-            self.gui.set_model_metadata(model.metadata().clone());
-            self.model.take();
+        if self.gui.should_cancel_loading() {
+            self.loader.cancel();
+        }
 
-            self.context.graphics_queue_wait_idle();
-            let model = Rc::new(RefCell::new(model));
-            self.renderer.set_model(&model);
-            self.model = Some(model);
+        let loaded_model = self.loader.get_model();
+        self.gui
+            .set_loading_progress(self.loader.get_progress().cloned());
+
+        match loaded_model {
+            Some(Ok(LoadedModel { model, reloaded })) => {
+                if reloaded {
+                    self.reload_model(model)
+                } else if self.gui.should_add_dropped_models() && !self.models.is_empty() {
+                    self.add_model(model)
+                } else {
+                    self.set_model(model)
+                }
+            }
+            Some(Err(ModelLoadingError::Cancelled)) => {}
+            Some(Err(error)) => self.gui.set_load_error(error.to_string()),
+            None => {}
+        }
+    }
+
+    /// Replace all the models of the scene by `model`.
+    fn set_model(&mut self, mut model: Model) {
+        self.gui.set_model_metadata(model.metadata().clone());
+        self.models.clear();
+        self.loader.unwatch_all();
+        self.watch_model(&model);
+
+        model.set_placement(self.gui.placement());
+
+        self.context.graphics_queue_wait_idle();
+        let model = Rc::new(RefCell::new(model));
+        self.renderer.set_model(&model);
+        self.models.push(model);
+        self.update_model_textures(0);
+        self.fit_camera();
+    }
+
+    /// Add `model` to the models of the scene.
+    fn add_model(&mut self, mut model: Model) {
+        self.gui.add_model_metadata(model.metadata().clone());
+        self.watch_model(&model);
+
+        model.set_placement(self.gui.placement());
+
+        self.context.graphics_queue_wait_idle();
+        let model = Rc::new(RefCell::new(model));
+        self.renderer.add_model(&model);
+        self.models.push(model);
+        self.update_model_textures(self.models.len() - 1);
+        self.fit_camera();
+    }
+
+    /// Replace the model loaded from the same file as `model`.
+    ///
+    /// The transform, visibility and animation state of the replaced model
+    /// are kept as well as the camera.
+    fn reload_model(&mut self, mut model: Model) {
+        let index = match self.find_model(model.metadata().path()) {
+            Some(index) => index,
+            None => {
+                log::debug!("Reloaded model is not in the scene anymore");
+                return;
+            }
+        };
+
+        {
+            let previous = self.models[index].borrow();
+            model.set_placement(self.gui.placement());
+            model.set_transform(previous.scene().transform());
+            if let Some(playback_state) = previous.get_animation_playback_state() {
+                model.set_animation_playback_state(playback_state);
+            }
+        }
+
+        self.gui
+            .replace_model_metadata(index, model.metadata().clone());
+        self.watch_model(&model);
+
+        self.context.graphics_queue_wait_idle();
+        let model = Rc::new(RefCell::new(model));
+        self.renderer.replace_model(index, &model);
+        self.models[index] = model;
+        self.update_model_textures(index);
+    }
+
+    /// Pass the textures of the model at `index` to the gui with their gui texture ids.
+    fn update_model_textures(&mut self, index: usize) {
+        let model = self.models[index].borrow();
+        let scene = model.scene();
+        let textures = self
+            .renderer
+            .get_texture_ids(index)
+            .iter()
+            .zip(scene.textures())
+            .zip(scene.texture_references())
+            .map(|((id, data), references)| TextureEntry {
+                id: *id,
+                data: *data,
+                references,
+            })
+            .collect();
+        self.gui.set_model_textures(index, textures);
+    }
+
+    fn remove_model(&mut self, index: usize) {
+        self.context.graphics_queue_wait_idle();
+        self.renderer.remove_model(index);
+        self.gui.remove_model(index);
+        let model = self.models.remove(index);
+
+        let path = model.borrow().metadata().path().map(String::from);
+        if let Some(path) = path {
+            if self.find_model(Some(&path)).is_none() {
+                self.loader.unwatch(path);
+            }
+        }
+    }
+
+    /// Find the index of the first model loaded from `path`.
+    fn find_model(&self, path: Option<&str>) -> Option<usize> {
+        path.and_then(|path| {
+            self.models
+                .iter()
+                .position(|model| model.borrow().metadata().path() == Some(path))
+        })
+    }
+
+    fn watch_model(&mut self, model: &Model) {
+        let metadata = model.metadata();
+        if let Some(path) = metadata.path() {
+            self.loader.watch(path, metadata.external_resources());
+        }
+    }
+
+    /// Write the statistics of the selected model next to the model file
+    /// or in the working directory if it was not loaded from a file.
+    fn export_model_statistics(&self) {
+        if !self.gui.should_export_statistics() {
+            return;
+        }
+
+        if let Some(model) = self.models.get(self.gui.get_selected_model()) {
+            let model = model.borrow();
+            let metadata = model.metadata();
+            let path = Path::new(metadata.path().unwrap_or_else(|| metadata.name()))
+                .with_extension("stats.yml");
+
+            let result = File::create(&path)
+                .map_err(|e| e.to_string())
+                .and_then(|file| {
+                    serde_yaml::to_writer(file, metadata.statistics()).map_err(|e| e.to_string())
+                });
+            match result {
+                Ok(_) => log::info!("Statistics exported to {}", path.display()),
+                Err(error) => log::error!(
+                    "Failed to export statistics to {}. Cause: {}",
+                    path.display(),
+                    error
+                ),
+            }
+        }
+    }
+
+    /// Export the scene of the selected model to the path chosen in the gui.
+    fn save_model(&self) {
+        let (path, format) = match self.gui.get_model_save_path() {
+            Some(save_path) => save_path,
+            None => return,
+        };
+
+        let index = self.gui.get_selected_model();
+        if let Some(model) = self.models.get(index) {
+            let node_visibility = self.gui.get_node_visibility(index);
+            match model
+                .borrow()
+                .scene()
+                .export_as(&path, format, &node_visibility)
+            {
+                Ok(_) => log::info!("Model exported to {}", path.display()),
+                Err(error) => log::error!("{}", error),
+            }
         }
     }
 
     fn update_model(&mut self, delta_s: f32) {
-        if let Some(model) = self.model.as_ref() {
+        if let Some(index) = self.gui.get_model_to_remove() {
+            self.remove_model(index);
+        }
+
+        let selected_model = self.gui.get_selected_model();
+        for (index, model) in self.models.iter().enumerate() {
             let mut model = model.borrow_mut();
 
-            if self.gui.should_toggle_animation() {
+            if let Some(visible) = self.gui.get_new_model_visibility(index) {
+                self.renderer.set_model_visible(index, visible);
+            }
+            if let Some(node_visibility) = self.gui.get_new_node_visibility(index) {
+                self.renderer.set_node_visibility(index, node_visibility);
+            }
+            if let Some(overrides) = self.gui.get_new_sort_distance_overrides(index) {
+                self.renderer.set_sort_distance_overrides(index, overrides);
+            }
+            if let Some(shadow_casting) = self.gui.get_new_shadow_casting(index) {
+                self.renderer.set_shadow_casting(index, shadow_casting);
+            }
+            if let Some(statistics) = self.renderer.get_culling_statistics(index) {
+                self.gui.set_culling_statistics(index, statistics);
+            }
+            if let Some(transform) = self.gui.get_new_model_transform(index) {
+                model.set_transform(transform);
+            }
+
+            let selected = index == selected_model;
+            if selected && self.gui.should_toggle_animation() {
                 model.toggle_animation();
-            } else if self.gui.should_stop_animation() {
+            } else if selected && self.gui.should_stop_animation() {
                 model.stop_animation();
-            } else if self.gui.should_reset_animation() {
+            } else if selected && self.gui.should_reset_animation() {
                 model.reset_animation();
             } else {
-                let playback_mode = if self.gui.is_infinite_animation_checked() {
+                let playback_mode = if self.gui.is_infinite_animation_checked(index) {
                     PlaybackMode::LOOP
                 } else {
                     PlaybackMode::ONCE
                 };
 
                 model.set_animation_playback_mode(playback_mode);
-                model.set_current_animation(self.gui.get_selected_animation());
+                model.set_current_animation(self.gui.get_selected_animation(index));
+            }
+            if selected {
+                self.gui
+                    .set_animation_playback_state(model.get_animation_playback_state());
             }
-            self.gui
-                .set_animation_playback_state(model.get_animation_playback_state());
 
-            let delta_s = delta_s * self.gui.get_animation_speed();
+            let delta_s = delta_s * self.gui.get_animation_speed(index);
             model.update(delta_s);
         }
+
+        if let Some(placement) = self.gui.get_new_placement() {
+            for model in &self.models {
+                model.borrow_mut().set_placement(placement);
+            }
+            self.fit_camera();
+        }
     }
 
     fn update_camera(&mut self) {
         if self.gui.should_reset_camera() {
-            self.camera = Default::default();
+            self.fit_camera();
         }
 
         if self.gui.is_hovered() {
@@ -296,11 +524,137 @@ impl Viewer {
         self.gui.set_camera(Some(self.camera));
     }
 
+    /// Select the node under the cursor when the scene is clicked.
+    ///
+    /// Hidden models and nodes are ignored. Clicking where nothing is hit clears the selection.
+    fn pick_node(&mut self) {
+        let click_position = match self.input_state.click_position() {
+            Some(position) => position,
+            None => return,
+        };
+        if self.gui.is_hovered() {
+            return;
+        }
+        let window_size = match self.window.get_inner_size() {
+            Some(size) => size,
+            None => return,
+        };
+
+        let x = click_position[0] / window_size.width as f32 * 2.0 - 1.0;
+        let y = click_position[1] / window_size.height as f32 * 2.0 - 1.0;
+        let extent = self.swapchain_properties.extent;
+        let aspect = extent.width as f32 / extent.height as f32;
+        let ray = match CameraUBO::from_camera(self.camera, aspect).ray(x, y) {
+            Some(ray) => ray,
+            None => return,
+        };
+
+        let gui = &self.gui;
+        let picked = self
+            .models
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| gui.is_model_visible(*index))
+            .filter_map(|(index, model)| {
+                let node_visibility = gui.get_node_visibility(index);
+                let is_visible = |node: usize| node_visibility.get(node).copied().unwrap_or(true);
+                let hit = model.borrow().scene().pick_filtered(ray, is_visible);
+                hit.map(|hit| (index, hit))
+            })
+            .min_by(|(_, h1), (_, h2)| {
+                h1.distance
+                    .partial_cmp(&h2.distance)
+                    .unwrap_or(Ordering::Equal)
+            });
+
+        if let Some((index, hit)) = picked {
+            log::debug!(
+                "Picked node {} of model {} at {:?}",
+                hit.node_index,
+                index,
+                hit.position
+            );
+        }
+        self.gui.set_picked_node(picked);
+    }
+
+    /// Apply the transform edits of the selected node and place the gizmo on it.
+    fn update_selected_node(&mut self) {
+        let gui = &self.gui;
+        let models = &self.models;
+        let target = gui
+            .get_selected_node()
+            .and_then(|(model_index, node_index)| {
+                let mut model = models.get(model_index)?.borrow_mut();
+                if let Some(transform) = gui.get_new_node_transform() {
+                    model.set_node_transform(
+                        node_index,
+                        transform.translation,
+                        transform.rotation,
+                        transform.scale,
+                    );
+                } else if gui.should_reset_node_transform() {
+                    model.reset_node_transform(node_index);
+                }
+
+                let node = model.nodes().nodes().get(node_index)?;
+                Some(GizmoTarget {
+                    local: NodeTransform {
+                        translation: node.translation(),
+                        rotation: node.rotation(),
+                        scale: node.scale(),
+                    },
+                    global: node.transform(),
+                    parent: model.scene().node_parent_transform(node_index),
+                })
+            });
+        self.gui.set_gizmo_target(target);
+    }
+
+    /// Apply the edits of the material of the selected primitive and show its current values.
+    fn update_selected_material(&mut self) {
+        let gui = &self.gui;
+        let models = &self.models;
+        let material =
+            gui.get_selected_primitive()
+                .and_then(|(model_index, mesh_index, primitive_index)| {
+                    let mut model = models.get(model_index)?.borrow_mut();
+                    if let Some(material) = gui.get_new_material() {
+                        model.set_material(material);
+                    }
+
+                    let mesh = model.meshes().get(mesh_index)?;
+                    mesh.primitives()
+                        .get(primitive_index)
+                        .map(|primitive| primitive.material())
+                });
+        self.gui.set_edited_material(material);
+    }
+
+    fn update_texture_preview(&mut self) {
+        self.renderer
+            .set_texture_preview(self.gui.get_texture_preview());
+    }
+
+    /// Reset the camera so it frames all the models of the scene.
+    fn fit_camera(&mut self) {
+        let aabbs = self
+            .models
+            .iter()
+            .filter_map(|model| model.borrow().scene().aabb())
+            .collect::<Vec<_>>();
+        self.camera = AABB::union(&aabbs).map_or_else(Default::default, Camera::framing);
+    }
+
     fn update_renderer_settings(&mut self) {
         if let Some(emissive_intensity) = self.gui.get_new_emissive_intensity() {
             self.context.graphics_queue_wait_idle();
             self.renderer.set_emissive_intensity(emissive_intensity);
         }
+        if let Some(transparency_mode) = self.gui.get_new_transparency_mode() {
+            self.context.graphics_queue_wait_idle();
+            self.renderer.set_transparency_mode(transparency_mode);
+        }
         if let Some(ssao_enabled) = self.gui.get_new_ssao_enabled() {
             self.context.graphics_queue_wait_idle();
             self.renderer.enabled_ssao(ssao_enabled);
@@ -317,6 +671,13 @@ impl Viewer {
             self.context.graphics_queue_wait_idle();
             self.renderer.set_ssao_strength(ssao_strength);
         }
+        if let Some(shadow_resolution) = self.gui.get_new_shadow_resolution() {
+            self.context.graphics_queue_wait_idle();
+            self.renderer.set_shadow_resolution(shadow_resolution);
+        }
+        if let Some(shadow_bias) = self.gui.get_new_shadow_bias() {
+            self.renderer.set_shadow_bias(shadow_bias);
+        }
         if let Some(tone_map_mode) = self.gui.get_new_renderer_tone_map_mode() {
             self.context.graphics_queue_wait_idle();
             self.renderer
@@ -326,6 +687,24 @@ impl Viewer {
             self.context.graphics_queue_wait_idle();
             self.renderer.set_output_mode(output_mode);
         }
+        if let Some(color) = self.gui.get_new_wireframe_color() {
+            self.renderer.set_wireframe_color(color);
+        }
+        if let Some(overlays) = self.gui.get_new_debug_overlays() {
+            self.renderer.set_debug_overlays(overlays);
+        }
+
+        let selection = self
+            .gui
+            .get_selected_node()
+            .zip(self.gui.get_selected_primitive());
+        self.renderer.set_debug_selection(selection.map(
+            |((model_index, node_index), (_, _, primitive_index))| DebugSelection {
+                model_index,
+                node_index,
+                primitive_index,
+            },
+        ));
     }
 
     fn draw_frame(&mut self) {
@@ -357,8 +736,9 @@ impl Viewer {
 
         unsafe { self.context.device().reset_fences(&wait_fences).unwrap() };
 
-        self.record_command_buffer(self.command_buffers[image_index as usize], image_index as _);
+        // Models are culled when updating the buffers so it must be done before recording
         self.renderer.update_ubos(image_index as _, self.camera);
+        self.record_command_buffer(self.command_buffers[image_index as usize], image_index as _);
 
         let device = self.context.device();
         let wait_semaphores = [image_available_semaphore];
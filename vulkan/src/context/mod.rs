@@ -95,6 +95,11 @@ impl Context {
         self.shared_context.queue_families_indices()
     }
 
+    /// Get the features enabled on the logical device.
+    pub fn features(&self) -> vk::PhysicalDeviceFeatures {
+        self.shared_context.features()
+    }
+
     pub fn graphics_queue(&self) -> vk::Queue {
         self.shared_context.graphics_queue()
     }
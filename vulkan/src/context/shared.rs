@@ -23,6 +23,7 @@ pub struct SharedContext {
     surface_khr: vk::SurfaceKHR,
     physical_device: vk::PhysicalDevice,
     device: Device,
+    features: vk::PhysicalDeviceFeatures,
     pub queue_families_indices: QueueFamiliesIndices,
     graphics_queue: vk::Queue,
     present_queue: vk::Queue,
@@ -47,11 +48,12 @@ impl SharedContext {
         let (physical_device, queue_families_indices) =
             pick_physical_device(&instance, &surface, surface_khr);
 
-        let (device, graphics_queue, present_queue) = create_logical_device_with_graphics_queue(
-            &instance,
-            physical_device,
-            queue_families_indices,
-        );
+        let (device, features, graphics_queue, present_queue) =
+            create_logical_device_with_graphics_queue(
+                &instance,
+                physical_device,
+                queue_families_indices,
+            );
 
         Self {
             _entry: entry,
@@ -61,6 +63,7 @@ impl SharedContext {
             surface_khr,
             physical_device,
             device,
+            features,
             queue_families_indices,
             graphics_queue,
             present_queue,
@@ -227,14 +230,17 @@ fn find_queue_families(
 /// Create the logical device to interact with `device`, a graphics queue
 /// and a presentation queue.
 ///
+/// Optional features are enabled when `device` supports them.
+///
 /// # Returns
 ///
-/// Return a tuple containing the logical device, the graphics queue and the presentation queue.
+/// Return a tuple containing the logical device, its enabled features,
+/// the graphics queue and the presentation queue.
 fn create_logical_device_with_graphics_queue(
     instance: &Instance,
     device: vk::PhysicalDevice,
     queue_families_indices: QueueFamiliesIndices,
-) -> (Device, vk::Queue, vk::Queue) {
+) -> (Device, vk::PhysicalDeviceFeatures, vk::Queue, vk::Queue) {
     let graphics_family_index = queue_families_indices.graphics_index;
     let present_family_index = queue_families_indices.present_index;
     let queue_priorities = [1.0f32];
@@ -265,7 +271,11 @@ fn create_logical_device_with_graphics_queue(
         .map(|ext| ext.as_ptr())
         .collect::<Vec<_>>();
 
-    let device_features = vk::PhysicalDeviceFeatures::builder().sampler_anisotropy(true);
+    let supported_features = unsafe { instance.get_physical_device_features(device) };
+    let device_features = vk::PhysicalDeviceFeatures::builder()
+        .sampler_anisotropy(true)
+        .fill_mode_non_solid(supported_features.fill_mode_non_solid == vk::TRUE)
+        .build();
 
     let device_create_info = vk::DeviceCreateInfo::builder()
         .queue_create_infos(&queue_create_infos)
@@ -281,7 +291,7 @@ fn create_logical_device_with_graphics_queue(
     let graphics_queue = unsafe { device.get_device_queue(graphics_family_index, 0) };
     let present_queue = unsafe { device.get_device_queue(present_family_index, 0) };
 
-    (device, graphics_queue, present_queue)
+    (device, device_features, graphics_queue, present_queue)
 }
 
 impl SharedContext {
@@ -309,6 +319,10 @@ impl SharedContext {
         self.queue_families_indices
     }
 
+    pub fn features(&self) -> vk::PhysicalDeviceFeatures {
+        self.features
+    }
+
     pub fn graphics_queue(&self) -> vk::Queue {
         self.graphics_queue
     }
@@ -40,6 +40,7 @@ pub use winit;
 pub struct PreLoadedResource<R, T> {
     context: Arc<Context>,
     command_buffer: vk::CommandBuffer,
+    thread_command_buffers: Vec<vk::CommandBuffer>,
     resource: Option<R>,
     tmp_data: Option<T>,
 }
@@ -54,10 +55,20 @@ impl<R, T> PreLoadedResource<R, T> {
         Self {
             context,
             command_buffer,
+            thread_command_buffers: Vec::new(),
             resource: Some(resource),
             tmp_data: Some(tmp_data),
         }
     }
+
+    /// Execute `command_buffers` after the main command buffer when finishing loading.
+    ///
+    /// The command buffers are not freed by this struct. They are usually
+    /// [ThreadCommandBuffer]s stored in the temporary data.
+    pub fn with_thread_command_buffers(mut self, command_buffers: Vec<vk::CommandBuffer>) -> Self {
+        self.thread_command_buffers = command_buffers;
+        self
+    }
 }
 
 impl<R, T> PreLoadedResource<R, T> {
@@ -85,10 +96,12 @@ impl<R, T> PreLoadedResource<R, T> {
     fn execute_commands(&self) {
         self.context
             .execute_one_time_commands(|primary_command_buffer| unsafe {
-                let secondary_command_buffer = [self.command_buffer];
+                let secondary_command_buffers = std::iter::once(self.command_buffer)
+                    .chain(self.thread_command_buffers.iter().copied())
+                    .collect::<Vec<_>>();
                 self.context
                     .device()
-                    .cmd_execute_commands(primary_command_buffer, &secondary_command_buffer);
+                    .cmd_execute_commands(primary_command_buffer, &secondary_command_buffers);
             });
     }
 
@@ -102,3 +115,70 @@ impl<R, T> PreLoadedResource<R, T> {
         }
     }
 }
+
+/// Secondary command buffer that can be recorded from any thread.
+///
+/// Command pools must not be used by several threads at the same time so
+/// each instance allocates its command buffer from its own pools. The command
+/// buffer is freed on drop so it must outlive its submission.
+pub struct ThreadCommandBuffer {
+    context: Context,
+    command_buffer: vk::CommandBuffer,
+}
+
+impl ThreadCommandBuffer {
+    /// Allocate a secondary command buffer and begin recording it.
+    pub fn begin(context: &Context) -> Self {
+        let context = context.new_thread();
+        let device = context.device();
+
+        let command_buffer = {
+            let allocate_info = vk::CommandBufferAllocateInfo::builder()
+                .command_pool(context.general_command_pool())
+                .level(vk::CommandBufferLevel::SECONDARY)
+                .command_buffer_count(1);
+
+            unsafe { device.allocate_command_buffers(&allocate_info).unwrap()[0] }
+        };
+
+        let inheritance_info = vk::CommandBufferInheritanceInfo::builder().build();
+        let command_buffer_begin_info = vk::CommandBufferBeginInfo::builder()
+            .inheritance_info(&inheritance_info)
+            .flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT);
+        unsafe {
+            device
+                .begin_command_buffer(command_buffer, &command_buffer_begin_info)
+                .unwrap()
+        };
+
+        Self {
+            context,
+            command_buffer,
+        }
+    }
+
+    pub fn command_buffer(&self) -> vk::CommandBuffer {
+        self.command_buffer
+    }
+
+    /// End recording the command buffer.
+    pub fn end(&self) {
+        unsafe {
+            self.context
+                .device()
+                .end_command_buffer(self.command_buffer)
+                .unwrap()
+        };
+    }
+}
+
+impl Drop for ThreadCommandBuffer {
+    fn drop(&mut self) {
+        let command_buffers = [self.command_buffer];
+        unsafe {
+            self.context
+                .device()
+                .free_command_buffers(self.context.general_command_pool(), &command_buffers)
+        }
+    }
+}
@@ -8,6 +8,7 @@ pub struct PipelineParameters<'a> {
     pub fragment_shader_params: ShaderParameters<'a>,
     pub multisampling_info: &'a vk::PipelineMultisampleStateCreateInfo,
     pub viewport_info: &'a vk::PipelineViewportStateCreateInfo,
+    pub topology: vk::PrimitiveTopology,
     pub rasterizer_info: &'a vk::PipelineRasterizationStateCreateInfo,
     pub dynamic_state_info: Option<&'a vk::PipelineDynamicStateCreateInfo>,
     pub depth_stencil_info: Option<&'a vk::PipelineDepthStencilStateCreateInfo>,
@@ -48,7 +49,7 @@ pub fn create_pipeline<V: Vertex>(
         .vertex_attribute_descriptions(&attributes_descs);
 
     let input_assembly_info = vk::PipelineInputAssemblyStateCreateInfo::builder()
-        .topology(vk::PrimitiveTopology::TRIANGLE_LIST)
+        .topology(params.topology)
         .primitive_restart_enable(false);
 
     let color_blending_info = vk::PipelineColorBlendStateCreateInfo::builder()